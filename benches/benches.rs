@@ -1 +1,249 @@
-
+#![feature(test)]
+
+extern crate lix_dds;
+extern crate test;
+
+use lix_dds::dds::core::policy::*;
+use lix_dds::rtps::behavior::stateful_reader::{ReaderTimes, StatefulReader};
+use lix_dds::rtps::behavior::stateful_writer::{StatefulWriter, WriterTimes};
+use lix_dds::rtps::common::change_kind_t::ChangeKind_t;
+use lix_dds::rtps::common::entity_id_t::EntityId_t;
+use lix_dds::rtps::common::guid::GUID_t;
+use lix_dds::rtps::common::guid_prefix_t::GuidPrefix_t;
+use lix_dds::rtps::common::instance_handle::InstanceHandle_t;
+use lix_dds::rtps::common::locator::Locator_t;
+use lix_dds::rtps::common::payload_pool::*;
+use lix_dds::rtps::common::protocol_version::c_ProtocolVersion;
+use lix_dds::rtps::common::serialized_payload::*;
+use lix_dds::rtps::common::time::Time_t;
+use lix_dds::rtps::common::vendor_id::c_VendorId_Unknown;
+use lix_dds::rtps::messages::message_header::Header_t;
+use lix_dds::rtps::messages::message_receiver::*;
+use lix_dds::rtps::structure::endpoint::*;
+use lix_dds::rtps::structure::intraprocess;
+use lix_dds::rtps::structure::reader_history::ReaderHistory;
+use lix_dds::rtps::structure::rtps_reader::RTPSReader;
+use lix_dds::rtps::structure::rtps_writer::RTPSWriter;
+use lix_dds::rtps::structure::writer_history::WriterHistory;
+use lix_dds::rtps::transport::udp_transport::*;
+use lix_dds::rtps::utils::ip_locator::setIPv4;
+use std::sync::Arc;
+use test::Bencher;
+
+const SAMPLE_SIZE: usize = 4096;
+const SAMPLES: usize = 64;
+
+fn write_sample(payload: &mut SerializedPayload_t) {
+    payload.begin_write(RepresentationId_t::CDR_LE, 0);
+    payload.write_bytes(&[0x5a; SAMPLE_SIZE - SerializedPayload_t::representation_header_size]);
+}
+
+#[bench]
+fn payload_without_pool(b: &mut Bencher) {
+    b.iter(|| {
+        for _ in 0..SAMPLES {
+            let mut payload = SerializedPayload_t::new(SAMPLE_SIZE);
+            write_sample(&mut payload);
+            test::black_box(&payload);
+        }
+    });
+}
+
+#[bench]
+fn payload_preallocated_pool(b: &mut Bencher) {
+    let pool = PayloadPool::new(PoolConfig {
+        mode: PayloadPoolMode::PREALLOCATED,
+        payload_initial_size: SAMPLE_SIZE,
+        initial_size: 1,
+        maximum_size: 0,
+    });
+    let mut payload = SerializedPayload_t::default();
+    b.iter(|| {
+        for _ in 0..SAMPLES {
+            pool.get_payload(SAMPLE_SIZE, &mut payload);
+            write_sample(&mut payload);
+            test::black_box(&payload);
+            pool.release_payload(&mut payload);
+        }
+    });
+    assert_eq!(1, pool.allocations());
+}
+
+#[bench]
+fn payload_size_class_pool(b: &mut Bencher) {
+    let pool = PayloadPool::new(PoolConfig {
+        mode: PayloadPoolMode::SIZE_CLASS,
+        ..PoolConfig::default()
+    });
+    let mut payload = SerializedPayload_t::default();
+    b.iter(|| {
+        for _ in 0..SAMPLES {
+            pool.get_payload(SAMPLE_SIZE, &mut payload);
+            write_sample(&mut payload);
+            test::black_box(&payload);
+            pool.release_payload(&mut payload);
+        }
+    });
+    assert_eq!(1, pool.allocations());
+}
+
+#[bench]
+fn payload_copy_to_readers(b: &mut Bencher) {
+    let mut received = SerializedPayload_t::new(SAMPLE_SIZE);
+    write_sample(&mut received);
+    b.iter(|| {
+        for _ in 0..8 {
+            let mut reader = SerializedPayload_t::default();
+            reader.copy(&received, false);
+            test::black_box(&reader);
+        }
+    });
+}
+
+#[bench]
+fn payload_share_to_readers(b: &mut Bencher) {
+    let pool = PayloadPool::new(PoolConfig::default());
+    let mut received = SerializedPayload_t::default();
+    pool.get_payload(SAMPLE_SIZE, &mut received);
+    write_sample(&mut received);
+    let mut readers: Vec<SerializedPayload_t> =
+        (0..8).map(|_| SerializedPayload_t::default()).collect();
+    b.iter(|| {
+        for reader in readers.iter_mut() {
+            pool.get_payload_from(&received, reader);
+        }
+        test::black_box(&readers);
+        for reader in readers.iter_mut() {
+            pool.release_payload(reader);
+        }
+    });
+}
+
+fn bench_guid(participant: u8, entity_kind: u8) -> GUID_t {
+    let mut guid_prefix = GuidPrefix_t::unknown();
+    guid_prefix.value[..8].copy_from_slice(&[0xbe; 8]);
+    guid_prefix.value[11] = participant;
+    GUID_t {
+        guidPrefix: guid_prefix,
+        entityId: EntityId_t::new_from_key_kind(1, entity_kind),
+    }
+}
+
+// Best-effort writer keeping the last sample
+fn latency_writer(guid: GUID_t) -> Arc<StatefulWriter> {
+    let history = WriterHistory::new(
+        guid,
+        HistoryQosPolicy {
+            kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+            depth: 1,
+        },
+        ResourceLimitsQosPolicy {
+            max_samples: 1,
+            max_instances: -1,
+            max_samples_per_instance: -1,
+        },
+        &ReliabilityQosPolicy {
+            kind: ReliabilityQosPolicyKind::BEST_EFFORT_RELIABILITY_QOS,
+            max_blocking_time: Time_t::c_TimeZero,
+        },
+    );
+    let writer = StatefulWriter::new(
+        guid,
+        EndpointAttributes::new(
+            EndpointKind_t::WRITER,
+            TopicKind_t::NO_KEY,
+            ReliabilityKind_t::BEST_EFFORT,
+        ),
+        history,
+        Arc::new(UdpSender::new().unwrap()),
+        Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, guid.guidPrefix),
+        WriterTimes::default(),
+    )
+    .unwrap();
+    Arc::new(writer)
+}
+
+// Best-effort reader keeping the last sample, matched with a writer
+fn latency_reader(guid: GUID_t, writer_guid: GUID_t) -> Arc<StatefulReader> {
+    let history = ReaderHistory::new(
+        HistoryQosPolicy {
+            kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+            depth: 1,
+        },
+        ResourceLimitsQosPolicy {
+            max_samples: -1,
+            max_instances: -1,
+            max_samples_per_instance: -1,
+        },
+    );
+    let reader = StatefulReader::new(
+        guid,
+        EndpointAttributes::new(
+            EndpointKind_t::READER,
+            TopicKind_t::NO_KEY,
+            ReliabilityKind_t::BEST_EFFORT,
+        ),
+        history,
+        Arc::new(UdpSender::new().unwrap()),
+        Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, guid.guidPrefix),
+        ReaderTimes::default(),
+    )
+    .unwrap();
+    reader.matched_writer_add(&RemoteEndpointAttributes::new(
+        writer_guid,
+        ReliabilityKind_t::BEST_EFFORT,
+    ));
+    Arc::new(reader)
+}
+
+fn write_latency_sample(writer: &StatefulWriter) {
+    let mut change = writer.new_change(ChangeKind_t::ALIVE, InstanceHandle_t::new());
+    write_sample(&mut change.serializedPayload);
+    writer.add_change(change).unwrap();
+}
+
+// Time from writing a sample to its delivery to a reader of another participant of the process
+#[bench]
+fn latency_intraprocess(b: &mut Bencher) {
+    let writer_guid = bench_guid(1, TopicKind_t::NO_KEY.writer_entity_kind());
+    let reader_guid = bench_guid(2, TopicKind_t::NO_KEY.reader_entity_kind());
+    let writer = latency_writer(writer_guid);
+    let reader = latency_reader(reader_guid, writer_guid);
+    let endpoints = Arc::new(EndpointRegistry::new());
+    endpoints.add_reader(reader_guid.entityId, reader.clone());
+    intraprocess::register_participant(reader_guid.guidPrefix, &endpoints);
+    writer.matched_reader_add(&RemoteEndpointAttributes::new(
+        reader_guid,
+        ReliabilityKind_t::BEST_EFFORT,
+    ));
+    b.iter(|| write_latency_sample(&writer));
+    assert_eq!(1, reader.history().lock().unwrap().len());
+    intraprocess::unregister_participant(&reader_guid.guidPrefix, &endpoints);
+}
+
+// Same as latency_intraprocess, through a loopback socket
+#[bench]
+fn latency_udp_loopback(b: &mut Bencher) {
+    let writer_guid = bench_guid(3, TopicKind_t::NO_KEY.writer_entity_kind());
+    let reader_guid = bench_guid(4, TopicKind_t::NO_KEY.reader_entity_kind());
+    let writer = latency_writer(writer_guid);
+    let reader = latency_reader(reader_guid, writer_guid);
+    let endpoints = Arc::new(EndpointRegistry::new());
+    endpoints.add_reader(reader_guid.entityId, reader.clone());
+    let receiver = MessageReceiver::new(reader_guid.guidPrefix, endpoints);
+    let mut locator = Locator_t::new_from_port(0);
+    setIPv4(&mut locator, "127.0.0.1").unwrap();
+    let (socket, locator) = open_input_channel(&locator).unwrap();
+    let mut attributes = RemoteEndpointAttributes::new(reader_guid, ReliabilityKind_t::BEST_EFFORT);
+    attributes.unicastLocatorList.push_back(locator);
+    writer.matched_reader_add(&attributes);
+    let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE];
+    b.iter(|| {
+        write_latency_sample(&writer);
+        let (length, _) = socket.recv_from(&mut buffer).unwrap();
+        receiver
+            .process_message(&buffer[..length], &locator)
+            .unwrap();
+    });
+    assert_eq!(1, reader.history().lock().unwrap().len());
+}
@@ -0,0 +1,318 @@
+/*
+ * #[derive(DdsType)] generates the type support of a topic data type: CdrSerialize,
+ * CdrDeserialize and DdsType implementations.
+ *
+ * Attributes:
+ *  #[dds(final)], #[dds(appendable)], #[dds(mutable)]  Extensibility of the type, final by default
+ *  #[dds(name = "...")]                                 Type name, the type identifier by default
+ *  #[key]                                               Key member
+ *  #[id(n)]                                             Member id, by default the id of the previous
+ *                                                       member plus one, starting at 0
+ *
+ * Members of type Option<T> are optional members. The members of mutable types absent from the
+ * data, such as the ones added by a newer version of the type, take their default value.
+ */
+
+extern crate proc_macro;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident, LitInt, LitStr, Type};
+
+#[proc_macro_derive(DdsType, attributes(dds, key, id))]
+pub fn derive_dds_type(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Extensibility {
+    Final,
+    Appendable,
+    Mutable,
+}
+
+struct Member {
+    // Field name, or index for tuple structs
+    member: syn::Member,
+    // Local variable holding the decoded value
+    var: Ident,
+    ty: Type,
+    id: u32,
+    key: bool,
+    optional: bool,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream, Error> {
+    let mut extensibility = Extensibility::Final;
+    let mut type_name = input.ident.to_string();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("dds")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("final") {
+                extensibility = Extensibility::Final;
+            } else if meta.path.is_ident("appendable") {
+                extensibility = Extensibility::Appendable;
+            } else if meta.path.is_ident("mutable") {
+                extensibility = Extensibility::Mutable;
+            } else if meta.path.is_ident("name") {
+                type_name = meta.value()?.parse::<LitStr>()?.value();
+            } else {
+                return Err(meta.error("expected final, appendable, mutable or name"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "DdsType can only be derived for structs",
+            ))
+        }
+    };
+    let members = parse_members(fields)?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let ext = match extensibility {
+        Extensibility::Final => quote!(::lix_dds::cdr::encoding::Extensibility::FINAL),
+        Extensibility::Appendable => quote!(::lix_dds::cdr::encoding::Extensibility::APPENDABLE),
+        Extensibility::Mutable => quote!(::lix_dds::cdr::encoding::Extensibility::MUTABLE),
+    };
+    let is_key_defined = members.iter().any(|m| m.key);
+
+    let serialize = serialize_members(&members, extensibility);
+    let max_size = max_serialized_size(&members, extensibility);
+    let deserialize = deserialize_members(&members, extensibility, fields);
+
+    let mut keys: Vec<&Member> = members.iter().filter(|m| m.key).collect();
+    keys.sort_by_key(|m| m.id);
+    let key_members = keys.iter().map(|m| &m.member);
+    let key_types = keys.iter().map(|m| &m.ty);
+
+    Ok(quote! {
+        impl #impl_generics ::lix_dds::cdr::cdr_serializer::CdrSerialize for #name #ty_generics #where_clause {
+            fn serialize(
+                &self,
+                ser: &mut ::lix_dds::cdr::cdr_serializer::CdrSerializer,
+            ) -> Result<(), ::lix_dds::rtps::common::error::RtpsError> {
+                ser.serialize_struct(#ext, |ser| {
+                    #serialize
+                    Ok(())
+                })
+            }
+
+            fn max_serialized_size(
+                version: ::lix_dds::cdr::encoding::CdrVersion,
+                position: usize,
+            ) -> Option<usize> {
+                #max_size
+            }
+        }
+
+        impl #impl_generics ::lix_dds::cdr::cdr_deserializer::CdrDeserialize for #name #ty_generics #where_clause {
+            fn deserialize(
+                de: &mut ::lix_dds::cdr::cdr_deserializer::CdrDeserializer,
+            ) -> Result<Self, ::lix_dds::rtps::common::error::RtpsError> {
+                de.deserialize_struct(#ext, |de| {
+                    #deserialize
+                })
+            }
+        }
+
+        impl #impl_generics ::lix_dds::dds::topic::dds_type::DdsType for #name #ty_generics #where_clause {
+            const TYPE_NAME: &'static str = #type_name;
+            const EXTENSIBILITY: ::lix_dds::cdr::encoding::Extensibility = #ext;
+            const IS_KEY_DEFINED: bool = #is_key_defined;
+
+            fn serialize_key(
+                &self,
+                ser: &mut ::lix_dds::cdr::cdr_serializer::CdrSerializer,
+            ) -> Result<(), ::lix_dds::rtps::common::error::RtpsError> {
+                #( ser.serialize(&self.#key_members)?; )*
+                Ok(())
+            }
+
+            fn key_max_serialized_size() -> Option<usize> {
+                let version = ::lix_dds::cdr::encoding::CdrVersion::XCDRv2;
+                let position = 0;
+                #( let position = <#key_types as ::lix_dds::cdr::cdr_serializer::CdrSerialize>::max_serialized_size(version, position)?; )*
+                Some(position)
+            }
+        }
+    })
+}
+
+fn parse_members(fields: &Fields) -> Result<Vec<Member>, Error> {
+    let mut members: Vec<Member> = Vec::new();
+    let mut next_id = 0;
+    for (index, field) in fields.iter().enumerate() {
+        let mut id = next_id;
+        let mut key = false;
+        for attr in &field.attrs {
+            if attr.path().is_ident("key") {
+                attr.meta.require_path_only()?;
+                key = true;
+            } else if attr.path().is_ident("id") {
+                id = attr.parse_args::<LitInt>()?.base10_parse()?;
+            }
+        }
+        if id > 0x0fff_ffff {
+            return Err(Error::new(
+                field.span(),
+                "member id does not fit in 28 bits",
+            ));
+        }
+        if members.iter().any(|m| m.id == id) {
+            return Err(Error::new(
+                field.span(),
+                format!("duplicated member id {}", id),
+            ));
+        }
+        let optional = is_option(&field.ty);
+        if key && optional {
+            return Err(Error::new(field.span(), "key members can not be optional"));
+        }
+        next_id = id + 1;
+
+        let member = match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(index.into()),
+        };
+        members.push(Member {
+            member,
+            var: format_ident!("__field{}", index),
+            ty: field.ty.clone(),
+            id,
+            key,
+            optional,
+        });
+    }
+    Ok(members)
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+fn serialize_members(members: &[Member], extensibility: Extensibility) -> TokenStream {
+    let members = members.iter().map(|m| {
+        let Member {
+            member, id, key, ..
+        } = m;
+        match (extensibility, m.optional) {
+            // Absent optional members of mutable structures are not written
+            (Extensibility::Mutable, true) => quote! {
+                if let Some(value) = &self.#member {
+                    ser.serialize_member(#id, false, value)?;
+                }
+            },
+            (Extensibility::Mutable, false) => quote! {
+                ser.serialize_member(#id, #key, &self.#member)?;
+            },
+            (_, true) => quote! {
+                ser.serialize_optional(#id, false, self.#member.as_ref())?;
+            },
+            (_, false) => quote! {
+                ser.serialize(&self.#member)?;
+            },
+        }
+    });
+    quote!( #(#members)* )
+}
+
+// Only final and appendable structures without optional members are bounded
+fn max_serialized_size(members: &[Member], extensibility: Extensibility) -> TokenStream {
+    if extensibility == Extensibility::Mutable || members.iter().any(|m| m.optional) {
+        return quote!(None);
+    }
+    let dheader = match extensibility {
+        Extensibility::Appendable => quote! {
+            let position = match version {
+                ::lix_dds::cdr::encoding::CdrVersion::XCDRv1 => position,
+                ::lix_dds::cdr::encoding::CdrVersion::XCDRv2 => version.align(position, 4) + 4,
+            };
+        },
+        _ => quote!(),
+    };
+    let types = members.iter().map(|m| &m.ty);
+    quote! {
+        #dheader
+        #( let position = <#types as ::lix_dds::cdr::cdr_serializer::CdrSerialize>::max_serialized_size(version, position)?; )*
+        Some(position)
+    }
+}
+
+fn deserialize_members(
+    members: &[Member],
+    extensibility: Extensibility,
+    fields: &Fields,
+) -> TokenStream {
+    let vars: Vec<&Ident> = members.iter().map(|m| &m.var).collect();
+    let construct = match fields {
+        Fields::Named(_) => {
+            let names = members.iter().map(|m| &m.member);
+            quote!(Self { #(#names: #vars),* })
+        }
+        Fields::Unnamed(_) => quote!(Self( #(#vars),* )),
+        Fields::Unit => quote!(Self),
+    };
+
+    if extensibility != Extensibility::Mutable {
+        let values = members.iter().map(|m| {
+            let Member { var, ty, id, .. } = m;
+            if m.optional {
+                quote!(let #var: #ty = de.deserialize_optional(#id)?;)
+            } else {
+                quote!(let #var: #ty = de.deserialize()?;)
+            }
+        });
+        return quote! {
+            #(#values)*
+            Ok(#construct)
+        };
+    }
+
+    // Members of mutable structures may come in any order
+    let declarations = members.iter().map(|m| {
+        let Member { var, ty, .. } = m;
+        if m.optional {
+            quote!(let mut #var: #ty = None;)
+        } else {
+            quote!(let mut #var: Option<#ty> = None;)
+        }
+    });
+    let arms = members.iter().map(|m| {
+        let Member { var, id, .. } = m;
+        quote!(#id => #var = Some(de.deserialize()?),)
+    });
+    let defaults = members.iter().filter(|m| !m.optional).map(|m| {
+        let var = &m.var;
+        quote!(let #var = #var.unwrap_or_default();)
+    });
+    quote! {
+        #(#declarations)*
+        de.deserialize_members(|de, member_id| {
+            match member_id {
+                #(#arms)*
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        #(#defaults)*
+        Ok(#construct)
+    }
+}
@@ -1 +1,3 @@
+pub mod host;
+pub mod interface;
 pub mod lookup;
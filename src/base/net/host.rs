@@ -0,0 +1,59 @@
+#[cfg(unix)]
+use std::ffi::CStr;
+
+/**
+ * @brief Get the name of the local host.
+ *
+ * @return Host name, or None if it could not be retrieved.
+ */
+#[cfg(unix)]
+pub fn host_name() -> Option<String> {
+    let mut buffer = [0 as libc::c_char; 256];
+    let ret = unsafe { libc::gethostname(buffer.as_mut_ptr(), buffer.len()) };
+    if ret != 0 {
+        return None;
+    }
+    // Make sure the name is terminated even if it was truncated
+    buffer[buffer.len() - 1] = 0;
+    let c_name = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+    c_name.to_str().ok().map(|s| s.to_string())
+}
+
+#[cfg(not(unix))]
+pub fn host_name() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+/**
+ * @brief Get a 16 bits identifier of the local host.
+ *        The identifier is a hash of the host name, so it is the same for every
+ *        process running on the host.
+ *
+ * @return Host identifier.
+ */
+pub fn host_id() -> u16 {
+    lazy_static! {
+        static ref HOST_ID: u16 = {
+            // FNV-1a folded to 16 bits
+            let name = host_name().unwrap_or_default();
+            let mut hash: u32 = 0x811c_9dc5;
+            for b in name.bytes() {
+                hash ^= b as u32;
+                hash = hash.wrapping_mul(0x0100_0193);
+            }
+            ((hash >> 16) ^ (hash & 0xffff)) as u16
+        };
+    }
+    *HOST_ID
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_id_test() {
+        assert!(host_name().is_some());
+        assert_eq!(host_id(), host_id());
+    }
+}
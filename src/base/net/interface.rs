@@ -0,0 +1,64 @@
+#[cfg(unix)]
+use std::ffi::{CStr, CString};
+
+/**
+ * @brief Get the index of a network interface from its name.
+ *
+ * @param name Interface name, i.e. "eth0".
+ * @return Interface index, or None if there is no interface with that name.
+ */
+#[cfg(unix)]
+pub fn interface_index(name: &str) -> Option<u32> {
+    let c_name = CString::new(name).ok()?;
+    let index = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+    if index == 0 {
+        None
+    } else {
+        Some(index)
+    }
+}
+
+#[cfg(not(unix))]
+pub fn interface_index(_name: &str) -> Option<u32> {
+    None
+}
+
+/**
+ * @brief Get the name of a network interface from its index.
+ *
+ * @param index Interface index.
+ * @return Interface name, or None if there is no interface with that index.
+ */
+#[cfg(unix)]
+pub fn interface_name(index: u32) -> Option<String> {
+    let mut buffer = [0 as libc::c_char; libc::IF_NAMESIZE];
+    let name = unsafe { libc::if_indextoname(index, buffer.as_mut_ptr()) };
+    if name.is_null() {
+        return None;
+    }
+    let c_name = unsafe { CStr::from_ptr(name) };
+    c_name.to_str().ok().map(|s| s.to_string())
+}
+
+#[cfg(not(unix))]
+pub fn interface_name(_index: u32) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn loopback_test() {
+        let index = interface_index("lo").unwrap();
+        assert_eq!(Some("lo".to_string()), interface_name(index));
+    }
+
+    #[test]
+    fn unknown_interface_test() {
+        assert_eq!(None, interface_index("no-such-interface0"));
+        assert_eq!(None, interface_index("bad\0name"));
+    }
+}
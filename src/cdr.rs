@@ -0,0 +1,5 @@
+pub mod cdr_deserializer;
+#[cfg(feature = "serde")]
+pub mod cdr_serde;
+pub mod cdr_serializer;
+pub mod encoding;
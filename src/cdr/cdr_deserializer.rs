@@ -0,0 +1,916 @@
+use crate::cdr::encoding::*;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::serialized_payload::*;
+use std::convert::TryInto;
+
+/**
+ * @brief Types that can be decoded with a CDR deserializer.
+ */
+pub trait CdrDeserialize: Sized {
+    /// Whether the type is a primitive type, see CdrSerialize::IS_PRIMITIVE
+    const IS_PRIMITIVE: bool = false;
+
+    fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError>;
+}
+
+/**
+ * @brief CDR decoder reading from a serialized payload.
+ *        The representation header is read when the deserializer is created, and
+ *        alignment is computed from the first byte after it.
+ */
+pub struct CdrDeserializer<'a> {
+    data: &'a [u8],
+    pos: usize,
+    representation: RepresentationId_t,
+    endianness: Endianness,
+    version: CdrVersion,
+    // Position from which alignment is computed
+    origin: usize,
+    // Position where the data being decoded ends
+    end: usize,
+}
+
+/**
+ * @brief Header of a member of a mutable structure, see CdrDeserializer::next_member.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemberHeader {
+    pub member_id: u32,
+    pub must_understand: bool,
+    // Size of the member value
+    size: usize,
+}
+
+macro_rules! deserialize_primitive {
+    ($name:ident, $t:ty) => {
+        pub fn $name(&mut self) -> Result<$t, RtpsError> {
+            const SIZE: usize = std::mem::size_of::<$t>();
+            self.align(SIZE)?;
+            let bytes: [u8; SIZE] = self.read_bytes(SIZE)?.try_into().unwrap();
+            match self.endianness {
+                Endianness::BIG_ENDIANNESS => Ok(<$t>::from_be_bytes(bytes)),
+                Endianness::LITTLE_ENDIANNESS => Ok(<$t>::from_le_bytes(bytes)),
+            }
+        }
+    };
+}
+
+impl<'a> CdrDeserializer<'a> {
+    /**
+     * @brief Create a deserializer, reading the representation header of the payload.
+     *
+     * @param payload Payload where the data is read from.
+     * @return The deserializer, or an error if the representation is not supported.
+     */
+    pub fn new(payload: &'a SerializedPayload_t) -> Result<Self, RtpsError> {
+        let data = payload.data();
+        if data.len() < SerializedPayload_t::representation_header_size {
+            return Err(RtpsError::new("Missing representation header"));
+        }
+
+        let id = u16::from_be_bytes([data[0], data[1]]);
+        let representation = match RepresentationId_t::from_u16(id) {
+            Some(representation) => representation,
+            None => return Err(RtpsError::new("Unknown representation")),
+        };
+        let (endianness, version) = match cdr_encoding(representation) {
+            Some(encoding) => encoding,
+            None => return Err(RtpsError::new("Not a CDR representation")),
+        };
+        // Padding added at the end of the payload is not part of the data
+        let padding =
+            (u16::from_be_bytes([data[2], data[3]]) & REPRESENTATION_OPTIONS_PADDING_MASK) as usize;
+        let end = std::cmp::max(
+            data.len().saturating_sub(padding),
+            SerializedPayload_t::representation_header_size,
+        );
+
+        Ok(CdrDeserializer {
+            data,
+            pos: SerializedPayload_t::representation_header_size,
+            representation,
+            endianness,
+            version,
+            origin: SerializedPayload_t::representation_header_size,
+            end,
+        })
+    }
+
+    /**
+     * @brief Create a deserializer reading data without representation header, such as the
+     *        value of a parameter.
+     *
+     * @param data Data to decode.
+     * @param representation Representation of the data.
+     * @return The deserializer, or an error if the representation is not supported.
+     */
+    pub fn from_bytes(
+        data: &'a [u8],
+        representation: RepresentationId_t,
+    ) -> Result<Self, RtpsError> {
+        let (endianness, version) = match cdr_encoding(representation) {
+            Some(encoding) => encoding,
+            None => return Err(RtpsError::new("Not a CDR representation")),
+        };
+        Ok(CdrDeserializer {
+            data,
+            pos: 0,
+            representation,
+            endianness,
+            version,
+            origin: 0,
+            end: data.len(),
+        })
+    }
+
+    pub fn representation(&self) -> RepresentationId_t {
+        self.representation
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn version(&self) -> CdrVersion {
+        self.version
+    }
+
+    // Bytes left to decode
+    pub fn remaining(&self) -> usize {
+        self.end - self.pos
+    }
+
+    /**
+     * @brief Skip the padding placed before a value of size bytes.
+     *
+     * @param size Size of the next value.
+     */
+    pub fn align(&mut self, size: usize) -> Result<(), RtpsError> {
+        let size = std::cmp::min(size, self.version.max_alignment());
+        if size <= 1 {
+            return Ok(());
+        }
+        let offset = (self.pos - self.origin) % size;
+        if offset != 0 {
+            self.read_bytes(size - offset)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * @brief Decode a value.
+     *
+     * @return The decoded value.
+     */
+    pub fn deserialize<T: CdrDeserialize>(&mut self) -> Result<T, RtpsError> {
+        T::deserialize(self)
+    }
+
+    deserialize_primitive!(deserialize_u8, u8);
+    deserialize_primitive!(deserialize_i8, i8);
+    deserialize_primitive!(deserialize_u16, u16);
+    deserialize_primitive!(deserialize_i16, i16);
+    deserialize_primitive!(deserialize_u32, u32);
+    deserialize_primitive!(deserialize_i32, i32);
+    deserialize_primitive!(deserialize_u64, u64);
+    deserialize_primitive!(deserialize_i64, i64);
+    deserialize_primitive!(deserialize_f32, f32);
+    deserialize_primitive!(deserialize_f64, f64);
+
+    pub fn deserialize_bool(&mut self) -> Result<bool, RtpsError> {
+        match self.deserialize_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(RtpsError::new("Invalid boolean value")),
+        }
+    }
+
+    /**
+     * @brief Decode a string: length including the NUL terminator, characters and terminator.
+     *
+     * @return The decoded string.
+     */
+    pub fn deserialize_string(&mut self) -> Result<String, RtpsError> {
+        self.deserialize_str().map(|s| s.to_string())
+    }
+
+    /**
+     * @brief Decode a string without copying it.
+     *
+     * @return The decoded string, borrowed from the payload.
+     */
+    pub fn deserialize_str(&mut self) -> Result<&'a str, RtpsError> {
+        let length = self.deserialize_u32()? as usize;
+        if length == 0 {
+            // Some implementations send empty strings without terminator
+            return Ok("");
+        }
+        let bytes = self.read_bytes(length)?;
+        if bytes[length - 1] != 0 {
+            return Err(RtpsError::new("String without NUL terminator"));
+        }
+        match std::str::from_utf8(&bytes[..length - 1]) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(RtpsError::new("String is not valid UTF-8")),
+        }
+    }
+
+    /**
+     * @brief Decode a sequence: number of elements followed by the elements.
+     *
+     * @return The decoded elements.
+     */
+    pub fn deserialize_sequence<T: CdrDeserialize>(&mut self) -> Result<Vec<T>, RtpsError> {
+        if self.needs_dheader::<T>() {
+            return self.deserialize_delimited(|de| de.deserialize_sequence_elements());
+        }
+        self.deserialize_sequence_elements()
+    }
+
+    /**
+     * @brief Decode an array: the elements, without length.
+     *
+     * @param length Number of elements of the array.
+     * @return The decoded elements.
+     */
+    pub fn deserialize_array<T: CdrDeserialize>(
+        &mut self,
+        length: usize,
+    ) -> Result<Vec<T>, RtpsError> {
+        if self.needs_dheader::<T>() {
+            return self.deserialize_delimited(|de| de.deserialize_elements(length));
+        }
+        self.deserialize_elements(length)
+    }
+
+    fn deserialize_sequence_elements<T: CdrDeserialize>(&mut self) -> Result<Vec<T>, RtpsError> {
+        let length = self.deserialize_u32()? as usize;
+        // Every element uses at least one byte, do not trust bigger lengths
+        if length > self.remaining() {
+            return Err(RtpsError::new("Sequence length exceeds the payload"));
+        }
+        self.deserialize_elements(length)
+    }
+
+    fn deserialize_elements<T: CdrDeserialize>(
+        &mut self,
+        length: usize,
+    ) -> Result<Vec<T>, RtpsError> {
+        let mut values = Vec::with_capacity(std::cmp::min(length, self.remaining()));
+        for _ in 0..length {
+            values.push(T::deserialize(self)?);
+        }
+        Ok(values)
+    }
+
+    /**
+     * @brief Decode a sequence of octets, copying it at once.
+     *
+     * @return The decoded octets.
+     */
+    pub fn deserialize_bytes(&mut self) -> Result<Vec<u8>, RtpsError> {
+        let length = self.deserialize_u32()? as usize;
+        Ok(self.read_bytes(length)?.to_vec())
+    }
+
+    /**
+     * @brief Decode the value of an enumeration, which uses 32 bits.
+     *
+     * @return Enumerator value.
+     */
+    pub fn deserialize_enum(&mut self) -> Result<u32, RtpsError> {
+        self.deserialize_u32()
+    }
+
+    /**
+     * @brief Decode a structure, written with serialize_struct.
+     *        Data added at the end of an appendable or mutable structure by newer versions
+     *        of the type is skipped. Members of mutable structures must be decoded with
+     *        deserialize_members.
+     *
+     * @param extensibility Extensibility kind of the structure.
+     * @param members Function decoding the members.
+     * @return The decoded structure.
+     */
+    pub fn deserialize_struct<T, F>(
+        &mut self,
+        extensibility: Extensibility,
+        members: F,
+    ) -> Result<T, RtpsError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, RtpsError>,
+    {
+        match (self.version, extensibility) {
+            (CdrVersion::XCDRv2, Extensibility::APPENDABLE)
+            | (CdrVersion::XCDRv2, Extensibility::MUTABLE) => self.deserialize_delimited(members),
+            _ => members(self),
+        }
+    }
+
+    /**
+     * @brief Decode the members of a mutable structure, written with serialize_member.
+     *        Members may come in any order. The function is called with the id of each
+     *        member and returns whether it knows and decoded the member. Unknown members
+     *        are skipped, unless they are flagged as must understand.
+     *
+     * @param member Function decoding a member.
+     */
+    pub fn deserialize_members<F>(&mut self, mut member: F) -> Result<(), RtpsError>
+    where
+        F: FnMut(&mut Self, u32) -> Result<bool, RtpsError>,
+    {
+        while let Some(header) = self.next_member()? {
+            let known = self.deserialize_member(&header, |de| member(de, header.member_id))?;
+            if !known && header.must_understand {
+                return Err(RtpsError::new("Unknown must understand member"));
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * @brief Read the header of the next member of a mutable structure.
+     *        The member value must be decoded or skipped with deserialize_member.
+     *
+     * @return The member header, None after the last member.
+     */
+    pub fn next_member(&mut self) -> Result<Option<MemberHeader>, RtpsError> {
+        match self.version {
+            CdrVersion::XCDRv1 => {
+                self.align(4)?;
+                let pid = self.peek_u16()?;
+                if (pid & PID_ID_MASK) == PID_LIST_END {
+                    self.read_bytes(4)?;
+                    return Ok(None);
+                }
+                self.read_member_header().map(Some)
+            }
+            CdrVersion::XCDRv2 => {
+                // The structure size does not include padding after the last member
+                if self.remaining() == 0 {
+                    return Ok(None);
+                }
+                self.align(4)?;
+                self.read_emheader().map(Some)
+            }
+        }
+    }
+
+    /**
+     * @brief Decode the value of a member whose header was read with next_member.
+     *        Bytes of the member not read by the function are skipped.
+     *
+     * @param header Member header.
+     * @param value Function decoding the value.
+     * @return The decoded value.
+     */
+    pub fn deserialize_member<T, F>(
+        &mut self,
+        header: &MemberHeader,
+        value: F,
+    ) -> Result<T, RtpsError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, RtpsError>,
+    {
+        let reset_origin = self.version == CdrVersion::XCDRv1;
+        self.deserialize_bounded(header.size, reset_origin, value)
+    }
+
+    /**
+     * @brief Decode an optional member, written with serialize_optional.
+     *
+     * @param member_id Expected member id.
+     * @return The member value, None when it is not present.
+     */
+    pub fn deserialize_optional<T: CdrDeserialize>(
+        &mut self,
+        member_id: u32,
+    ) -> Result<Option<T>, RtpsError> {
+        self.deserialize_optional_with(member_id, T::deserialize)
+    }
+
+    /**
+     * @brief Decode an optional member, written with serialize_optional or begin_optional.
+     *
+     * @param member_id Expected member id.
+     * @param value Function decoding the value, only called when the member is present.
+     * @return The member value, None when it is not present.
+     */
+    pub fn deserialize_optional_with<T, F>(
+        &mut self,
+        member_id: u32,
+        value: F,
+    ) -> Result<Option<T>, RtpsError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, RtpsError>,
+    {
+        if self.version == CdrVersion::XCDRv2 {
+            return match self.deserialize_bool()? {
+                true => value(self).map(Some),
+                false => Ok(None),
+            };
+        }
+
+        self.align(4)?;
+        let header = self.read_member_header()?;
+        if header.member_id != member_id {
+            return Err(RtpsError::new("Unexpected member id"));
+        }
+        if header.size == 0 {
+            return Ok(None);
+        }
+        self.deserialize_member(&header, value).map(Some)
+    }
+
+    /**
+     * @brief Decode a value preceded by a DHEADER holding its size.
+     *        Bytes of the value not read by the function are skipped.
+     *
+     * @param value Function decoding the value.
+     * @return The decoded value.
+     */
+    pub fn deserialize_delimited<T, F>(&mut self, value: F) -> Result<T, RtpsError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, RtpsError>,
+    {
+        let size = self.deserialize_u32()? as usize;
+        self.deserialize_bounded(size, false, value)
+    }
+
+    /**
+     * @brief Read raw bytes.
+     *
+     * @param len Number of bytes.
+     * @return The bytes, or an error if there are not enough bytes left.
+     */
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], RtpsError> {
+        if len > self.remaining() {
+            return Err(RtpsError::new("Not enough data"));
+        }
+        let start = self.pos;
+        self.pos += len;
+        Ok(&self.data[start..self.pos])
+    }
+
+    fn needs_dheader<T: CdrDeserialize>(&self) -> bool {
+        self.version == CdrVersion::XCDRv2 && !T::IS_PRIMITIVE
+    }
+
+    // Run a function that can only read the next size bytes, which are consumed at the end
+    fn deserialize_bounded<T, F>(
+        &mut self,
+        size: usize,
+        reset_origin: bool,
+        value: F,
+    ) -> Result<T, RtpsError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, RtpsError>,
+    {
+        if size > self.remaining() {
+            return Err(RtpsError::new("Member size exceeds the payload"));
+        }
+
+        let data_pos = self.pos;
+        let origin = self.origin;
+        let end = self.end;
+        if reset_origin {
+            self.origin = data_pos;
+        }
+        self.end = data_pos + size;
+        let ret = value(self);
+        self.origin = origin;
+        self.end = end;
+        // The size is authoritative, skip anything the function did not read
+        self.pos = data_pos + size;
+        ret
+    }
+
+    fn peek_u16(&mut self) -> Result<u16, RtpsError> {
+        let pos = self.pos;
+        let ret = self.deserialize_u16();
+        self.pos = pos;
+        ret
+    }
+
+    fn read_emheader(&mut self) -> Result<MemberHeader, RtpsError> {
+        let header = self.deserialize_u32()?;
+        let must_understand = (header & EMHEADER_MUST_UNDERSTAND_FLAG) != 0;
+        let id = header & EMHEADER_ID_MASK;
+        let size = match (header >> EMHEADER_LC_SHIFT) & 0x7 {
+            lc @ 0..=3 => 1 << lc,
+            EMHEADER_LC_NEXTINT => self.deserialize_u32()? as usize,
+            lc => {
+                // NEXTINT is the first word of the member, holding the number of elements
+                let pos = self.pos;
+                let count = self.deserialize_u32()? as usize;
+                self.pos = pos;
+                let element = match lc {
+                    5 => 1,
+                    6 => 4,
+                    _ => 8,
+                };
+                count
+                    .checked_mul(element)
+                    .and_then(|s| s.checked_add(4))
+                    .ok_or_else(|| RtpsError::new("Invalid member size"))?
+            }
+        };
+        Ok(MemberHeader {
+            member_id: id,
+            must_understand,
+            size,
+        })
+    }
+
+    fn read_member_header(&mut self) -> Result<MemberHeader, RtpsError> {
+        let pid = self.deserialize_u16()?;
+        let length = self.deserialize_u16()? as usize;
+        let must_understand = (pid & PID_MUST_UNDERSTAND_FLAG) != 0;
+        if (pid & PID_ID_MASK) == PID_EXTENDED {
+            if length != 8 {
+                return Err(RtpsError::new("Invalid extended member header"));
+            }
+            let member_id = self.deserialize_u32()?;
+            let size = self.deserialize_u32()? as usize;
+            Ok(MemberHeader {
+                member_id,
+                must_understand,
+                size,
+            })
+        } else {
+            Ok(MemberHeader {
+                member_id: (pid & PID_ID_MASK) as u32,
+                must_understand,
+                size: length,
+            })
+        }
+    }
+}
+
+macro_rules! impl_deserialize_primitive {
+    ($t:ty, $name:ident) => {
+        impl CdrDeserialize for $t {
+            const IS_PRIMITIVE: bool = true;
+
+            fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+                de.$name()
+            }
+        }
+    };
+}
+
+impl_deserialize_primitive!(u8, deserialize_u8);
+impl_deserialize_primitive!(i8, deserialize_i8);
+impl_deserialize_primitive!(u16, deserialize_u16);
+impl_deserialize_primitive!(i16, deserialize_i16);
+impl_deserialize_primitive!(u32, deserialize_u32);
+impl_deserialize_primitive!(i32, deserialize_i32);
+impl_deserialize_primitive!(u64, deserialize_u64);
+impl_deserialize_primitive!(i64, deserialize_i64);
+impl_deserialize_primitive!(f32, deserialize_f32);
+impl_deserialize_primitive!(f64, deserialize_f64);
+impl_deserialize_primitive!(bool, deserialize_bool);
+
+impl CdrDeserialize for String {
+    fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+        de.deserialize_string()
+    }
+}
+
+impl<T: CdrDeserialize> CdrDeserialize for Vec<T> {
+    fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+        de.deserialize_sequence()
+    }
+}
+
+impl<T: CdrDeserialize, const N: usize> CdrDeserialize for [T; N] {
+    fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+        let values = de.deserialize_array::<T>(N)?;
+        match values.try_into() {
+            Ok(array) => Ok(array),
+            Err(_) => Err(RtpsError::new("Array length mismatch")),
+        }
+    }
+}
+
+impl<T: CdrDeserialize> CdrDeserialize for Box<T> {
+    fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+        T::deserialize(de).map(Box::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdr::cdr_serializer::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Sample {
+        a: u8,
+        b: u32,
+        c: i16,
+        d: f64,
+        e: String,
+        f: Vec<u16>,
+        g: [i64; 2],
+        h: Option<u64>,
+        i: Option<String>,
+    }
+
+    impl CdrSerialize for Sample {
+        fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+            ser.serialize(&self.a)?;
+            ser.serialize(&self.b)?;
+            ser.serialize(&self.c)?;
+            ser.serialize(&self.d)?;
+            ser.serialize(&self.e)?;
+            ser.serialize(&self.f)?;
+            ser.serialize(&self.g)?;
+            ser.serialize_optional(1, false, self.h.as_ref())?;
+            ser.serialize_optional(2, false, self.i.as_ref())
+        }
+    }
+
+    impl CdrDeserialize for Sample {
+        fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+            Ok(Sample {
+                a: de.deserialize()?,
+                b: de.deserialize()?,
+                c: de.deserialize()?,
+                d: de.deserialize()?,
+                e: de.deserialize()?,
+                f: de.deserialize()?,
+                g: de.deserialize()?,
+                h: de.deserialize_optional(1)?,
+                i: de.deserialize_optional(2)?,
+            })
+        }
+    }
+
+    fn round_trip(representation: RepresentationId_t, sample: &Sample) {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, representation).unwrap();
+        ser.serialize(sample).unwrap();
+        ser.finish().unwrap();
+
+        let mut de = CdrDeserializer::new(&payload).unwrap();
+        let decoded: Sample = de.deserialize().unwrap();
+        assert_eq!(sample, &decoded);
+        assert_eq!(0, de.remaining());
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let mut sample = Sample {
+            a: 1,
+            b: 0xdeadbeef,
+            c: -3,
+            d: 2.5,
+            e: "hello".to_string(),
+            f: vec![1, 2, 3],
+            g: [i64::MIN, i64::MAX],
+            h: Some(7),
+            i: None,
+        };
+        round_trip(RepresentationId_t::CDR_LE, &sample);
+        round_trip(RepresentationId_t::CDR_BE, &sample);
+        round_trip(RepresentationId_t::CDR2_LE, &sample);
+        round_trip(RepresentationId_t::CDR2_BE, &sample);
+
+        sample.h = None;
+        sample.i = Some("opt".to_string());
+        sample.e = String::new();
+        sample.f = vec![];
+        round_trip(RepresentationId_t::CDR_LE, &sample);
+        round_trip(RepresentationId_t::CDR_BE, &sample);
+        round_trip(RepresentationId_t::CDR2_LE, &sample);
+        round_trip(RepresentationId_t::CDR2_BE, &sample);
+    }
+
+    // Version 2 of a type adds members to version 1
+    #[derive(Debug, Default, PartialEq)]
+    struct Shape {
+        x: i32,
+        name: String,
+        size: Option<u32>,
+        tags: Vec<String>,
+    }
+
+    fn write_shape(
+        representation: RepresentationId_t,
+        extensibility: Extensibility,
+        shape: &Shape,
+        tags_must_understand: bool,
+    ) -> SerializedPayload_t {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, representation).unwrap();
+        ser.serialize_struct(extensibility, |ser| {
+            if extensibility != Extensibility::MUTABLE {
+                ser.serialize(&shape.x)?;
+                ser.serialize(&shape.name)?;
+                ser.serialize_optional(3, false, shape.size.as_ref())?;
+                return ser.serialize(&shape.tags);
+            }
+            // Members out of order
+            ser.serialize_member(4, tags_must_understand, &shape.tags)?;
+            ser.serialize_member(2, false, &shape.name)?;
+            if let Some(size) = &shape.size {
+                ser.serialize_member(3, false, size)?;
+            }
+            ser.serialize_member(1, true, &shape.x)
+        })
+        .unwrap();
+        ser.serialize_u8(0xaa).unwrap();
+        ser.finish().unwrap();
+        payload
+    }
+
+    // Read with version 1, which only knows x and name
+    fn read_shape(payload: &SerializedPayload_t, extensibility: Extensibility) -> Shape {
+        let mut de = CdrDeserializer::new(payload).unwrap();
+        let shape = de
+            .deserialize_struct(extensibility, |de| {
+                let mut shape = Shape::default();
+                if extensibility != Extensibility::MUTABLE {
+                    shape.x = de.deserialize()?;
+                    shape.name = de.deserialize()?;
+                    return Ok(shape);
+                }
+                de.deserialize_members(|de, id| {
+                    match id {
+                        1 => shape.x = de.deserialize()?,
+                        2 => shape.name = de.deserialize()?,
+                        _ => return Ok(false),
+                    }
+                    Ok(true)
+                })?;
+                Ok(shape)
+            })
+            .unwrap();
+        assert_eq!(0xaa, de.deserialize_u8().unwrap());
+        assert_eq!(0, de.remaining());
+        shape
+    }
+
+    #[test]
+    fn type_evolution_test() {
+        let shape = Shape {
+            x: -5,
+            name: "square".to_string(),
+            size: Some(12),
+            tags: vec!["red".to_string(), "big".to_string()],
+        };
+        let expected = Shape {
+            x: -5,
+            name: "square".to_string(),
+            ..Default::default()
+        };
+
+        for (representation, extensibility) in [
+            (RepresentationId_t::D_CDR2_LE, Extensibility::APPENDABLE),
+            (RepresentationId_t::D_CDR2_BE, Extensibility::APPENDABLE),
+            (RepresentationId_t::PL_CDR2_LE, Extensibility::MUTABLE),
+            (RepresentationId_t::PL_CDR2_BE, Extensibility::MUTABLE),
+            (RepresentationId_t::PL_CDR_LE, Extensibility::MUTABLE),
+            (RepresentationId_t::PL_CDR_BE, Extensibility::MUTABLE),
+        ] {
+            let payload = write_shape(representation, extensibility, &shape, false);
+            assert_eq!(expected, read_shape(&payload, extensibility));
+        }
+    }
+
+    #[test]
+    fn must_understand_test() {
+        let shape = Shape {
+            tags: vec!["red".to_string()],
+            ..Default::default()
+        };
+        for representation in [
+            RepresentationId_t::PL_CDR2_LE,
+            RepresentationId_t::PL_CDR_LE,
+        ] {
+            let payload = write_shape(representation, Extensibility::MUTABLE, &shape, true);
+            let mut de = CdrDeserializer::new(&payload).unwrap();
+            let ret = de.deserialize_struct(Extensibility::MUTABLE, |de| {
+                de.deserialize_members(|de, id| match id {
+                    1 => de.deserialize::<i32>().map(|_| true),
+                    _ => Ok(false),
+                })
+            });
+            assert!(ret.is_err());
+        }
+    }
+
+    #[test]
+    fn length_code_test() {
+        #[rustfmt::skip]
+        let data = [
+            0x00, 0x0b, 0x00, 0x02,
+            0x26, 0x00, 0x00, 0x00,                         // DHEADER
+            0x01, 0x00, 0x00, 0x50,                         // LC 5, octets
+            0x02, 0x00, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00,
+            0x02, 0x00, 0x00, 0x60,                         // LC 6, 4 byte elements
+            0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+            0x03, 0x00, 0x00, 0x70,                         // LC 7, 8 byte elements
+            0x00, 0x00, 0x00, 0x00,
+            0x04, 0x00, 0x00, 0x10,                         // LC 1
+            0x04, 0x00, 0x00, 0x00,
+        ];
+        let mut payload = SerializedPayload_t::new(data.len());
+        payload.write_bytes(&data);
+
+        let mut de = CdrDeserializer::new(&payload).unwrap();
+        let mut members = vec![];
+        de.deserialize_struct(Extensibility::MUTABLE, |de| {
+            de.deserialize_members(|de, id| {
+                let value = match id {
+                    1 => de.deserialize_bytes()?.len() as u32,
+                    2 => de.deserialize::<Vec<u32>>()?[0],
+                    3 => de.deserialize::<Vec<u64>>()?.len() as u32,
+                    _ => de.deserialize::<u16>()? as u32,
+                };
+                members.push((id, value));
+                Ok(true)
+            })
+        })
+        .unwrap();
+        assert_eq!(vec![(1, 2), (2, 3), (3, 0), (4, 4)], members);
+        assert_eq!(0, de.remaining());
+    }
+
+    #[test]
+    fn golden_bytes_test() {
+        #[rustfmt::skip]
+        let data = [
+            0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03,
+            0x00, 0x00, 0x00, 0x04, b'a', b'b', b'c', 0x00,
+        ];
+        let mut payload = SerializedPayload_t::new(data.len());
+        payload.write_bytes(&data);
+
+        let mut de = CdrDeserializer::new(&payload).unwrap();
+        assert_eq!(Endianness::BIG_ENDIANNESS, de.endianness());
+        assert!(de.deserialize_bool().unwrap());
+        assert_eq!(2, de.deserialize_enum().unwrap());
+        assert_eq!(3, de.deserialize_u64().unwrap());
+        assert_eq!("abc", de.deserialize_string().unwrap());
+        assert!(de.deserialize_u8().is_err());
+    }
+
+    #[test]
+    fn padding_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        ser.serialize_u8(5).unwrap();
+        ser.finish().unwrap();
+        assert_eq!(8, payload.length());
+
+        let mut de = CdrDeserializer::new(&payload).unwrap();
+        assert_eq!(5, de.deserialize_u8().unwrap());
+        assert_eq!(0, de.remaining());
+    }
+
+    #[test]
+    fn from_bytes_test() {
+        let data = [0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02];
+        let mut de = CdrDeserializer::from_bytes(&data, RepresentationId_t::CDR_BE).unwrap();
+        assert_eq!(1, de.deserialize_u16().unwrap());
+        assert_eq!(2, de.deserialize_u32().unwrap());
+        assert_eq!(0, de.remaining());
+        assert!(CdrDeserializer::from_bytes(&data, RepresentationId_t::XML).is_err());
+    }
+
+    #[test]
+    fn error_test() {
+        let mut payload = SerializedPayload_t::default();
+        assert!(CdrDeserializer::new(&payload).is_err());
+
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        ser.serialize_u32(0xffff_ffff).unwrap();
+        ser.serialize_u8(2).unwrap();
+        ser.finish().unwrap();
+
+        // Huge sequence length
+        let mut de = CdrDeserializer::new(&payload).unwrap();
+        assert!(de.deserialize::<Vec<u8>>().is_err());
+
+        // String without terminator
+        let mut de = CdrDeserializer::new(&payload).unwrap();
+        assert!(de.deserialize_string().is_err());
+
+        // Invalid boolean
+        let mut de = CdrDeserializer::new(&payload).unwrap();
+        de.deserialize_u32().unwrap();
+        assert!(de.deserialize_bool().is_err());
+
+        // Unexpected optional member
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        ser.serialize_optional(3, false, Some(&1_u8)).unwrap();
+        ser.finish().unwrap();
+        let mut de = CdrDeserializer::new(&payload).unwrap();
+        assert!(de.deserialize_optional::<u8>(4).is_err());
+    }
+}
@@ -0,0 +1,1326 @@
+/*
+ * serde data format for CDR payloads, enabled with the "serde" feature.
+ *
+ * The serde data model is mapped to CDR as follows:
+ *
+ *  bool, integers, floats  Primitive types. i128 and u128 are not supported.
+ *  char                    CDR char (ISO-8859-1), characters above U+00FF are refused
+ *  str, String             string
+ *  bytes                   sequence<octet>
+ *  Option                  Optional member
+ *  unit, unit struct       Empty structure
+ *  newtype struct          The wrapped value
+ *  struct                  Structure, whose member ids are the field indexes
+ *  tuple struct            Final structure
+ *  seq                     sequence
+ *  tuple, array            array
+ *  map                     sequence of key and value pairs
+ *  enum                    enum when the variant has no data, union otherwise. The variant
+ *                          index is the enumerator value or the union discriminator.
+ *
+ * The extensibility of structures is given by the representation of the payload: final for
+ * CDR/CDR2, appendable for D_CDR2 and mutable for PL_CDR/PL_CDR2.
+ *
+ * XCDR2 adds a DHEADER to collections of non primitive types. As serde does not tell the
+ * element type of a collection, it is taken from its first element. Empty collections are
+ * written without DHEADER, and read either way. Enumerations and unions count as primitive.
+ */
+
+use crate::cdr::cdr_deserializer::*;
+use crate::cdr::cdr_serializer::*;
+use crate::cdr::encoding::*;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::serialized_payload::*;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use std::convert::TryFrom;
+use std::fmt::Display;
+
+impl ser::Error for RtpsError {
+    fn custom<T: Display>(msg: T) -> Self {
+        RtpsError::new(&msg.to_string())
+    }
+}
+
+impl de::Error for RtpsError {
+    fn custom<T: Display>(msg: T) -> Self {
+        RtpsError::new(&msg.to_string())
+    }
+}
+
+/**
+ * @brief Encode a value into a payload.
+ *
+ * @param value Value to be encoded.
+ * @param representation Representation of the payload.
+ * @param payload Payload where the data is written.
+ */
+pub fn to_payload<T: Serialize + ?Sized>(
+    value: &T,
+    representation: RepresentationId_t,
+    payload: &mut SerializedPayload_t,
+) -> Result<(), RtpsError> {
+    let mut ser = CdrSerializer::new(payload, representation)?;
+    value.serialize(Serializer::new(&mut ser))?;
+    ser.finish()
+}
+
+/**
+ * @brief Decode a value from a payload.
+ *
+ * @param payload Payload where the data is read from.
+ * @return The decoded value.
+ */
+pub fn from_payload<'de, T: de::Deserialize<'de>>(
+    payload: &'de SerializedPayload_t,
+) -> Result<T, RtpsError> {
+    let mut de = CdrDeserializer::new(payload)?;
+    T::deserialize(Deserializer::new(&mut de))
+}
+
+// Extensibility of the structures of a payload
+fn struct_extensibility(representation: RepresentationId_t) -> Extensibility {
+    match representation {
+        RepresentationId_t::D_CDR2_BE | RepresentationId_t::D_CDR2_LE => Extensibility::APPENDABLE,
+        r if r.is_parameter_list() => Extensibility::MUTABLE,
+        _ => Extensibility::FINAL,
+    }
+}
+
+// Kind of a value, which decides its member header and whether collections need a DHEADER
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Primitive,
+    Union,
+    Other,
+}
+
+fn to_length(length: usize) -> Result<u32, RtpsError> {
+    u32::try_from(length).map_err(|_| RtpsError::new("Length does not fit in 32 bits"))
+}
+
+// Collection being encoded, whose header is written with the first element
+struct Collection {
+    // Number of elements, None for arrays
+    length: Option<usize>,
+    delimiter: Option<Delimiter>,
+    started: bool,
+}
+
+impl Collection {
+    fn new(ser: &mut CdrSerializer, length: Option<usize>) -> Result<Self, RtpsError> {
+        let mut collection = Collection {
+            length,
+            delimiter: None,
+            started: false,
+        };
+        if ser.version() == CdrVersion::XCDRv1 {
+            // No DHEADER, the first element does not matter
+            collection.start(ser, Kind::Primitive)?;
+        }
+        Ok(collection)
+    }
+
+    fn start(&mut self, ser: &mut CdrSerializer, kind: Kind) -> Result<(), RtpsError> {
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        if ser.version() == CdrVersion::XCDRv2 && kind == Kind::Other {
+            self.delimiter = Some(ser.begin_delimited()?);
+        }
+        match self.length {
+            Some(length) => ser.serialize_u32(to_length(length)?),
+            None => Ok(()),
+        }
+    }
+
+    fn end(mut self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        self.start(ser, Kind::Primitive)?;
+        match self.delimiter {
+            Some(delimiter) => ser.end_delimited(delimiter),
+            None => Ok(()),
+        }
+    }
+}
+
+// Place of the value being encoded
+enum Context<'s> {
+    Value,
+    // Member of a final or appendable structure
+    Field(u32),
+    // Member of a mutable structure
+    Member(u32),
+    FirstElement(&'s mut Collection),
+}
+
+/**
+ * @brief serde serializer writing on a CDR serializer.
+ */
+pub struct Serializer<'s, 'a> {
+    ser: &'s mut CdrSerializer<'a>,
+    extensibility: Extensibility,
+    context: Context<'s>,
+}
+
+impl<'s, 'a> Serializer<'s, 'a> {
+    pub fn new(ser: &'s mut CdrSerializer<'a>) -> Self {
+        let extensibility = struct_extensibility(ser.representation());
+        Serializer {
+            ser,
+            extensibility,
+            context: Context::Value,
+        }
+    }
+
+    fn nested<'n>(
+        ser: &'n mut CdrSerializer<'a>,
+        extensibility: Extensibility,
+        context: Context<'n>,
+    ) -> Serializer<'n, 'a> {
+        Serializer {
+            ser,
+            extensibility,
+            context,
+        }
+    }
+
+    // Write what goes before the value: member header or collection header
+    fn begin(&mut self, kind: Kind) -> Result<Option<MemberScope>, RtpsError> {
+        match &mut self.context {
+            Context::Member(id) => self
+                .ser
+                .begin_member(*id, false, kind == Kind::Primitive)
+                .map(Some),
+            Context::FirstElement(collection) => {
+                collection.start(self.ser, kind)?;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn value<F>(mut self, kind: Kind, value: F) -> Result<(), RtpsError>
+    where
+        F: FnOnce(&mut CdrSerializer<'a>) -> Result<(), RtpsError>,
+    {
+        let member = self.begin(kind)?;
+        value(self.ser)?;
+        match member {
+            Some(member) => self.ser.end_member(member),
+            None => Ok(()),
+        }
+    }
+
+    fn compound(mut self, kind: Kind, body: Body) -> Result<Compound<'s, 'a>, RtpsError> {
+        let member = self.begin(kind)?;
+        Ok(Compound {
+            ser: self.ser,
+            extensibility: self.extensibility,
+            member,
+            body,
+        })
+    }
+
+    // The collection header goes after the member header
+    fn collection(mut self, length: Option<usize>) -> Result<Compound<'s, 'a>, RtpsError> {
+        let member = self.begin(Kind::Other)?;
+        let body = Body::Collection(Collection::new(self.ser, length)?);
+        Ok(Compound {
+            ser: self.ser,
+            extensibility: self.extensibility,
+            member,
+            body,
+        })
+    }
+
+    fn optional<T: Serialize + ?Sized>(mut self, value: Option<&T>) -> Result<(), RtpsError> {
+        let member_id = match self.context {
+            Context::Member(_) => {
+                // Absent members of mutable structures are not written
+                return match value {
+                    Some(value) => value.serialize(self),
+                    None => Ok(()),
+                };
+            }
+            Context::Field(id) => id,
+            _ => 0,
+        };
+
+        self.begin(Kind::Other)?;
+        let member = self.ser.begin_optional(member_id, false, value.is_some())?;
+        if let Some(value) = value {
+            value.serialize(Serializer::nested(
+                self.ser,
+                self.extensibility,
+                Context::Value,
+            ))?;
+        }
+        match member {
+            Some(member) => self.ser.end_member(member),
+            None => Ok(()),
+        }
+    }
+}
+
+macro_rules! serialize_primitive {
+    ($name:ident, $t:ty, $method:ident) => {
+        fn $name(self, v: $t) -> Result<(), RtpsError> {
+            self.value(Kind::Primitive, |ser| ser.$method(v))
+        }
+    };
+}
+
+impl<'s, 'a> ser::Serializer for Serializer<'s, 'a> {
+    type Ok = ();
+    type Error = RtpsError;
+    type SerializeSeq = Compound<'s, 'a>;
+    type SerializeTuple = Compound<'s, 'a>;
+    type SerializeTupleStruct = Compound<'s, 'a>;
+    type SerializeTupleVariant = Compound<'s, 'a>;
+    type SerializeMap = Compound<'s, 'a>;
+    type SerializeStruct = Compound<'s, 'a>;
+    type SerializeStructVariant = Compound<'s, 'a>;
+
+    serialize_primitive!(serialize_bool, bool, serialize_bool);
+    serialize_primitive!(serialize_i8, i8, serialize_i8);
+    serialize_primitive!(serialize_i16, i16, serialize_i16);
+    serialize_primitive!(serialize_i32, i32, serialize_i32);
+    serialize_primitive!(serialize_i64, i64, serialize_i64);
+    serialize_primitive!(serialize_u8, u8, serialize_u8);
+    serialize_primitive!(serialize_u16, u16, serialize_u16);
+    serialize_primitive!(serialize_u32, u32, serialize_u32);
+    serialize_primitive!(serialize_u64, u64, serialize_u64);
+    serialize_primitive!(serialize_f32, f32, serialize_f32);
+    serialize_primitive!(serialize_f64, f64, serialize_f64);
+
+    fn serialize_char(self, v: char) -> Result<(), RtpsError> {
+        let c = u8::try_from(v as u32)
+            .map_err(|_| RtpsError::new("Character does not fit in a CDR char"))?;
+        self.serialize_u8(c)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), RtpsError> {
+        self.value(Kind::Other, |ser| ser.serialize_string(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), RtpsError> {
+        self.value(Kind::Other, |ser| ser.serialize_bytes(v))
+    }
+
+    fn serialize_none(self) -> Result<(), RtpsError> {
+        self.optional::<()>(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), RtpsError> {
+        self.optional(Some(value))
+    }
+
+    fn serialize_unit(self) -> Result<(), RtpsError> {
+        self.value(Kind::Other, |_| Ok(()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), RtpsError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), RtpsError> {
+        self.value(Kind::Primitive, |ser| ser.serialize_enum(variant_index))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), RtpsError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), RtpsError> {
+        let extensibility = self.extensibility;
+        self.value(Kind::Union, |ser| {
+            ser.serialize_enum(variant_index)?;
+            value.serialize(Serializer::nested(ser, extensibility, Context::Value))
+        })
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'s, 'a>, RtpsError> {
+        let len = len.ok_or_else(|| RtpsError::new("Sequence length is required"))?;
+        self.collection(Some(len))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'s, 'a>, RtpsError> {
+        self.collection(None)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a>, RtpsError> {
+        self.compound(Kind::Other, Body::Fields { next_id: 0 })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a>, RtpsError> {
+        let compound = self.compound(Kind::Union, Body::Fields { next_id: 0 })?;
+        compound.ser.serialize_enum(variant_index)?;
+        Ok(compound)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'s, 'a>, RtpsError> {
+        let len = len.ok_or_else(|| RtpsError::new("Map length is required"))?;
+        self.collection(Some(len))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a>, RtpsError> {
+        let extensibility = self.extensibility;
+        let mut compound = self.compound(Kind::Other, Body::Fields { next_id: 0 })?;
+        let scope = compound.ser.begin_struct(extensibility)?;
+        compound.body = Body::Struct { scope, next_id: 0 };
+        Ok(compound)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a>, RtpsError> {
+        let compound = self.compound(Kind::Union, Body::Fields { next_id: 0 })?;
+        compound.ser.serialize_enum(variant_index)?;
+        Ok(compound)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+enum Body {
+    // Structure with the extensibility of the payload
+    Struct { scope: StructScope, next_id: u32 },
+    // Members of a final structure or union
+    Fields { next_id: u32 },
+    Collection(Collection),
+}
+
+/**
+ * @brief Compound value being encoded by the serde serializer.
+ */
+pub struct Compound<'s, 'a> {
+    ser: &'s mut CdrSerializer<'a>,
+    extensibility: Extensibility,
+    // Header of the value, if it is a member of a mutable structure
+    member: Option<MemberScope>,
+    body: Body,
+}
+
+impl<'s, 'a> Compound<'s, 'a> {
+    fn field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RtpsError> {
+        let context = match &mut self.body {
+            Body::Struct { next_id, .. } if self.extensibility == Extensibility::MUTABLE => {
+                *next_id += 1;
+                Context::Member(*next_id - 1)
+            }
+            Body::Struct { next_id, .. } | Body::Fields { next_id } => {
+                *next_id += 1;
+                Context::Field(*next_id - 1)
+            }
+            Body::Collection(collection) if !collection.started => {
+                Context::FirstElement(collection)
+            }
+            Body::Collection(_) => Context::Value,
+        };
+        value.serialize(Serializer::nested(self.ser, self.extensibility, context))
+    }
+
+    fn skip(&mut self) {
+        if let Body::Struct { next_id, .. } | Body::Fields { next_id } = &mut self.body {
+            *next_id += 1;
+        }
+    }
+
+    fn end(self) -> Result<(), RtpsError> {
+        match self.body {
+            Body::Struct { scope, .. } => self.ser.end_struct(scope)?,
+            Body::Fields { .. } => {}
+            Body::Collection(collection) => collection.end(self.ser)?,
+        }
+        match self.member {
+            Some(member) => self.ser.end_member(member),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'s, 'a> ser::SerializeSeq for Compound<'s, 'a> {
+    type Ok = ();
+    type Error = RtpsError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RtpsError> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<(), RtpsError> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a> ser::SerializeTuple for Compound<'s, 'a> {
+    type Ok = ();
+    type Error = RtpsError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RtpsError> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<(), RtpsError> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a> ser::SerializeTupleStruct for Compound<'s, 'a> {
+    type Ok = ();
+    type Error = RtpsError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RtpsError> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<(), RtpsError> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a> ser::SerializeTupleVariant for Compound<'s, 'a> {
+    type Ok = ();
+    type Error = RtpsError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RtpsError> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<(), RtpsError> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a> ser::SerializeMap for Compound<'s, 'a> {
+    type Ok = ();
+    type Error = RtpsError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), RtpsError> {
+        self.field(key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RtpsError> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<(), RtpsError> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a> ser::SerializeStruct for Compound<'s, 'a> {
+    type Ok = ();
+    type Error = RtpsError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), RtpsError> {
+        self.field(value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), RtpsError> {
+        self.skip();
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), RtpsError> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a> ser::SerializeStructVariant for Compound<'s, 'a> {
+    type Ok = ();
+    type Error = RtpsError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), RtpsError> {
+        self.field(value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), RtpsError> {
+        self.skip();
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), RtpsError> {
+        Compound::end(self)
+    }
+}
+
+// Collection being decoded, whose header is read with the first element
+struct Elements {
+    // Number of elements, read from the collection header when counted
+    length: usize,
+    counted: bool,
+    started: bool,
+    next: usize,
+}
+
+impl Elements {
+    fn new(de: &mut CdrDeserializer, length: Option<usize>) -> Result<Self, RtpsError> {
+        let mut elements = Elements {
+            length: length.unwrap_or(0),
+            counted: length.is_none(),
+            started: false,
+            next: 0,
+        };
+        if de.version() == CdrVersion::XCDRv1 || (length == Some(0)) {
+            // No DHEADER, the first element does not matter
+            elements.start(de, Kind::Primitive)?;
+        }
+        Ok(elements)
+    }
+
+    fn start(&mut self, de: &mut CdrDeserializer, kind: Kind) -> Result<(), RtpsError> {
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        if de.version() == CdrVersion::XCDRv2 && kind == Kind::Other {
+            // Empty collections may come without DHEADER, which is never 0 otherwise
+            if de.deserialize_u32()? == 0 && self.counted {
+                self.length = 0;
+                return Ok(());
+            }
+        }
+        if self.counted {
+            self.length = de.deserialize_u32()? as usize;
+            // Every element uses at least one byte, do not trust bigger lengths
+            if self.length > de.remaining() {
+                return Err(RtpsError::new("Sequence length exceeds the payload"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Place of the value being decoded
+enum DeContext<'s> {
+    Value,
+    // Member of a final or appendable structure
+    Field(u32),
+    // Member of a mutable structure
+    Member,
+    FirstElement(&'s mut Elements),
+}
+
+/**
+ * @brief serde deserializer reading from a CDR deserializer.
+ */
+pub struct Deserializer<'s, 'de> {
+    de: &'s mut CdrDeserializer<'de>,
+    extensibility: Extensibility,
+    context: DeContext<'s>,
+}
+
+impl<'s, 'de> Deserializer<'s, 'de> {
+    pub fn new(de: &'s mut CdrDeserializer<'de>) -> Self {
+        let extensibility = struct_extensibility(de.representation());
+        Deserializer {
+            de,
+            extensibility,
+            context: DeContext::Value,
+        }
+    }
+
+    fn nested<'n>(
+        de: &'n mut CdrDeserializer<'de>,
+        extensibility: Extensibility,
+        context: DeContext<'n>,
+    ) -> Deserializer<'n, 'de> {
+        Deserializer {
+            de,
+            extensibility,
+            context,
+        }
+    }
+
+    // Read what goes before the value, member headers are read by the enclosing structure
+    fn begin(&mut self, kind: Kind) -> Result<(), RtpsError> {
+        if let DeContext::FirstElement(elements) = &mut self.context {
+            elements.start(self.de, kind)?;
+            if elements.length == 0 {
+                // Caught by the collection, which has no elements
+                return Err(RtpsError::new("Empty collection"));
+            }
+        }
+        Ok(())
+    }
+
+    fn collection<V: Visitor<'de>>(
+        mut self,
+        length: Option<usize>,
+        map: bool,
+        visitor: V,
+    ) -> Result<V::Value, RtpsError> {
+        self.begin(Kind::Other)?;
+        let elements = Elements::new(self.de, length)?;
+        let access = ElementAccess {
+            de: self.de,
+            extensibility: self.extensibility,
+            elements,
+        };
+        if map {
+            visitor.visit_map(access)
+        } else {
+            visitor.visit_seq(access)
+        }
+    }
+}
+
+macro_rules! deserialize_primitive {
+    ($name:ident, $visit:ident, $method:ident) => {
+        fn $name<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, RtpsError> {
+            self.begin(Kind::Primitive)?;
+            visitor.$visit(self.de.$method()?)
+        }
+    };
+}
+
+impl<'s, 'de> de::Deserializer<'de> for Deserializer<'s, 'de> {
+    type Error = RtpsError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, RtpsError> {
+        Err(RtpsError::new("CDR is not self-describing"))
+    }
+
+    deserialize_primitive!(deserialize_bool, visit_bool, deserialize_bool);
+    deserialize_primitive!(deserialize_i8, visit_i8, deserialize_i8);
+    deserialize_primitive!(deserialize_i16, visit_i16, deserialize_i16);
+    deserialize_primitive!(deserialize_i32, visit_i32, deserialize_i32);
+    deserialize_primitive!(deserialize_i64, visit_i64, deserialize_i64);
+    deserialize_primitive!(deserialize_u8, visit_u8, deserialize_u8);
+    deserialize_primitive!(deserialize_u16, visit_u16, deserialize_u16);
+    deserialize_primitive!(deserialize_u32, visit_u32, deserialize_u32);
+    deserialize_primitive!(deserialize_u64, visit_u64, deserialize_u64);
+    deserialize_primitive!(deserialize_f32, visit_f32, deserialize_f32);
+    deserialize_primitive!(deserialize_f64, visit_f64, deserialize_f64);
+
+    fn deserialize_char<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, RtpsError> {
+        self.begin(Kind::Primitive)?;
+        visitor.visit_char(char::from(self.de.deserialize_u8()?))
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, RtpsError> {
+        self.begin(Kind::Other)?;
+        visitor.visit_borrowed_str(self.de.deserialize_str()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RtpsError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, RtpsError> {
+        self.begin(Kind::Other)?;
+        visitor.visit_byte_buf(self.de.deserialize_bytes()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RtpsError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, RtpsError> {
+        let member_id = match self.context {
+            // Absent members of mutable structures are not written
+            DeContext::Member => return visitor.visit_some(self),
+            DeContext::Field(id) => id,
+            _ => 0,
+        };
+
+        self.begin(Kind::Other)?;
+        let extensibility = self.extensibility;
+        let mut visitor = Some(visitor);
+        let value = self.de.deserialize_optional_with(member_id, |de| {
+            let visitor = visitor.take().unwrap();
+            visitor.visit_some(Deserializer::nested(de, extensibility, DeContext::Value))
+        })?;
+        match (value, visitor) {
+            (Some(value), _) => Ok(value),
+            (None, Some(visitor)) => visitor.visit_none(),
+            (None, None) => Err(RtpsError::new("Optional member without value")),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, RtpsError> {
+        self.begin(Kind::Other)?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, RtpsError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, RtpsError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RtpsError> {
+        self.collection(None, false, visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, RtpsError> {
+        self.collection(Some(len), false, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, RtpsError> {
+        self.begin(Kind::Other)?;
+        visitor.visit_seq(FieldAccess {
+            de: self.de,
+            extensibility: self.extensibility,
+            next_id: 0,
+            count: len as u32,
+        })
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RtpsError> {
+        self.collection(None, true, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RtpsError> {
+        self.begin(Kind::Other)?;
+        let extensibility = self.extensibility;
+        self.de.deserialize_struct(extensibility, |de| {
+            if extensibility == Extensibility::MUTABLE {
+                visitor.visit_map(MemberAccess {
+                    de,
+                    extensibility,
+                    fields,
+                    header: None,
+                })
+            } else {
+                visitor.visit_seq(FieldAccess {
+                    de,
+                    extensibility,
+                    next_id: 0,
+                    count: fields.len() as u32,
+                })
+            }
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RtpsError> {
+        self.begin(Kind::Union)?;
+        visitor.visit_enum(EnumAccess {
+            de: self.de,
+            extensibility: self.extensibility,
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, RtpsError> {
+        Err(RtpsError::new("CDR has no identifiers"))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RtpsError> {
+        match self.context {
+            // The member size is known, the enclosing structure skips it
+            DeContext::Member => visitor.visit_unit(),
+            _ => Err(RtpsError::new("CDR values can not be skipped")),
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+// Elements of a collection, or keys and values of a map
+struct ElementAccess<'s, 'de> {
+    de: &'s mut CdrDeserializer<'de>,
+    extensibility: Extensibility,
+    elements: Elements,
+}
+
+impl<'s, 'de> ElementAccess<'s, 'de> {
+    fn next<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, RtpsError> {
+        if !self.elements.started {
+            let context = DeContext::FirstElement(&mut self.elements);
+            let ret = seed.deserialize(Deserializer::nested(self.de, self.extensibility, context));
+            if self.elements.started && self.elements.length == 0 {
+                return Ok(None);
+            }
+            self.elements.next = 1;
+            return ret.map(Some);
+        }
+
+        if self.elements.next >= self.elements.length {
+            return Ok(None);
+        }
+        self.elements.next += 1;
+        let context = DeContext::Value;
+        seed.deserialize(Deserializer::nested(self.de, self.extensibility, context))
+            .map(Some)
+    }
+}
+
+impl<'s, 'de> de::SeqAccess<'de> for ElementAccess<'s, 'de> {
+    type Error = RtpsError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, RtpsError> {
+        self.next(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.elements.started {
+            true => Some(self.elements.length - self.elements.next),
+            false => None,
+        }
+    }
+}
+
+impl<'s, 'de> de::MapAccess<'de> for ElementAccess<'s, 'de> {
+    type Error = RtpsError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, RtpsError> {
+        self.next(seed)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, RtpsError> {
+        let context = DeContext::Value;
+        seed.deserialize(Deserializer::nested(self.de, self.extensibility, context))
+    }
+}
+
+// Members of a final or appendable structure, in order
+struct FieldAccess<'s, 'de> {
+    de: &'s mut CdrDeserializer<'de>,
+    extensibility: Extensibility,
+    next_id: u32,
+    count: u32,
+}
+
+impl<'s, 'de> de::SeqAccess<'de> for FieldAccess<'s, 'de> {
+    type Error = RtpsError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, RtpsError> {
+        if self.next_id >= self.count {
+            return Ok(None);
+        }
+        let context = DeContext::Field(self.next_id);
+        self.next_id += 1;
+        seed.deserialize(Deserializer::nested(self.de, self.extensibility, context))
+            .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.count - self.next_id) as usize)
+    }
+}
+
+// Members of a mutable structure, in any order
+struct MemberAccess<'s, 'de> {
+    de: &'s mut CdrDeserializer<'de>,
+    extensibility: Extensibility,
+    fields: &'static [&'static str],
+    header: Option<MemberHeader>,
+}
+
+impl<'s, 'de> de::MapAccess<'de> for MemberAccess<'s, 'de> {
+    type Error = RtpsError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, RtpsError> {
+        while let Some(header) = self.de.next_member()? {
+            if let Some(field) = self.fields.get(header.member_id as usize) {
+                self.header = Some(header);
+                let key: de::value::StrDeserializer<RtpsError> = field.into_deserializer();
+                return seed.deserialize(key).map(Some);
+            }
+            if header.must_understand {
+                return Err(RtpsError::new("Unknown must understand member"));
+            }
+            // Member added by a newer version of the type
+            self.de.deserialize_member(&header, |_| Ok(()))?;
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, RtpsError> {
+        let header = self
+            .header
+            .take()
+            .ok_or_else(|| RtpsError::new("Member value without key"))?;
+        let extensibility = self.extensibility;
+        self.de.deserialize_member(&header, |de| {
+            seed.deserialize(Deserializer::nested(de, extensibility, DeContext::Member))
+        })
+    }
+}
+
+// Variant of an enumeration or union
+struct EnumAccess<'s, 'de> {
+    de: &'s mut CdrDeserializer<'de>,
+    extensibility: Extensibility,
+}
+
+impl<'s, 'de> de::EnumAccess<'de> for EnumAccess<'s, 'de> {
+    type Error = RtpsError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), RtpsError> {
+        let discriminator: de::value::U32Deserializer<RtpsError> =
+            self.de.deserialize_enum()?.into_deserializer();
+        let variant = seed.deserialize(discriminator)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'s, 'de> de::VariantAccess<'de> for EnumAccess<'s, 'de> {
+    type Error = RtpsError;
+
+    fn unit_variant(self) -> Result<(), RtpsError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, RtpsError> {
+        seed.deserialize(Deserializer::nested(
+            self.de,
+            self.extensibility,
+            DeContext::Value,
+        ))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, RtpsError> {
+        visitor.visit_seq(FieldAccess {
+            de: self.de,
+            extensibility: self.extensibility,
+            next_id: 0,
+            count: len as u32,
+        })
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RtpsError> {
+        self.tuple_variant(fields.len(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Color {
+        Red,
+        Green,
+        Blue,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rectangle { width: u32, height: u32 },
+        Triangle(u8, u8, u8),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point(i32, i32);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        id: u32,
+        text: String,
+        color: Color,
+        shapes: Vec<Shape>,
+        tags: Vec<String>,
+        empty: Vec<String>,
+        values: [u16; 3],
+        names: [String; 2],
+        origin: Point,
+        label: Option<String>,
+        count: Option<u64>,
+        letter: char,
+        map: BTreeMap<u8, String>,
+        flag: bool,
+    }
+
+    fn message() -> Message {
+        let mut map = BTreeMap::new();
+        map.insert(1, "one".to_string());
+        map.insert(2, "two".to_string());
+        Message {
+            id: 42,
+            text: "hello".to_string(),
+            color: Color::Green,
+            shapes: vec![
+                Shape::Circle(1.5),
+                Shape::Empty,
+                Shape::Rectangle {
+                    width: 2,
+                    height: 3,
+                },
+                Shape::Triangle(1, 2, 3),
+            ],
+            tags: vec!["a".to_string(), "bc".to_string()],
+            empty: vec![],
+            values: [1, 2, 3],
+            names: ["x".to_string(), "y".to_string()],
+            origin: Point(-1, 1),
+            label: Some("label".to_string()),
+            count: None,
+            letter: 'é',
+            map,
+            flag: true,
+        }
+    }
+
+    #[test]
+    fn round_trip_test() {
+        for representation in [
+            RepresentationId_t::CDR_LE,
+            RepresentationId_t::CDR_BE,
+            RepresentationId_t::PL_CDR_LE,
+            RepresentationId_t::PL_CDR_BE,
+            RepresentationId_t::CDR2_LE,
+            RepresentationId_t::CDR2_BE,
+            RepresentationId_t::D_CDR2_LE,
+            RepresentationId_t::D_CDR2_BE,
+            RepresentationId_t::PL_CDR2_LE,
+            RepresentationId_t::PL_CDR2_BE,
+        ] {
+            let mut payload = SerializedPayload_t::default();
+            to_payload(&message(), representation, &mut payload).unwrap();
+            assert_eq!(representation, payload.representation_id().unwrap());
+            let decoded: Message = from_payload(&payload).unwrap();
+            assert_eq!(message(), decoded);
+        }
+    }
+
+    // Same type written with the CDR traits
+    struct Sample<'a> {
+        id: u32,
+        text: &'a str,
+        tags: &'a [String],
+        label: Option<&'a String>,
+        position: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct SerdeSample {
+        id: u32,
+        text: String,
+        tags: Vec<String>,
+        label: Option<String>,
+        position: u64,
+    }
+
+    impl<'a> CdrSerialize for Sample<'a> {
+        fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+            let extensibility = struct_extensibility(ser.representation());
+            ser.serialize_struct(extensibility, |ser| {
+                if extensibility == Extensibility::MUTABLE {
+                    ser.serialize_member(0, false, &self.id)?;
+                    ser.serialize_member(1, false, self.text)?;
+                    ser.serialize_member(2, false, &self.tags.to_vec())?;
+                    if let Some(label) = self.label {
+                        ser.serialize_member(3, false, label)?;
+                    }
+                    return ser.serialize_member(4, false, &self.position);
+                }
+                ser.serialize(&self.id)?;
+                ser.serialize(self.text)?;
+                ser.serialize_sequence(self.tags)?;
+                ser.serialize_optional(3, false, self.label)?;
+                ser.serialize(&self.position)
+            })
+        }
+    }
+
+    #[test]
+    fn same_encoding_test() {
+        let tags = vec!["red".to_string(), "big".to_string()];
+        let label = "label".to_string();
+        for representation in [
+            RepresentationId_t::CDR_LE,
+            RepresentationId_t::PL_CDR_BE,
+            RepresentationId_t::CDR2_BE,
+            RepresentationId_t::D_CDR2_LE,
+            RepresentationId_t::PL_CDR2_LE,
+        ] {
+            for label in [None, Some(&label)] {
+                let sample = Sample {
+                    id: 7,
+                    text: "text",
+                    tags: &tags,
+                    label,
+                    position: 0x0102030405060708,
+                };
+                let mut expected = SerializedPayload_t::default();
+                let mut ser = CdrSerializer::new(&mut expected, representation).unwrap();
+                ser.serialize(&sample).unwrap();
+                ser.finish().unwrap();
+
+                let serde_sample = SerdeSample {
+                    id: 7,
+                    text: "text".to_string(),
+                    tags: tags.clone(),
+                    label: label.cloned(),
+                    position: 0x0102030405060708,
+                };
+                let mut payload = SerializedPayload_t::default();
+                to_payload(&serde_sample, representation, &mut payload).unwrap();
+                assert_eq!(expected.data(), payload.data());
+            }
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct PointV1 {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct PointV2 {
+        x: i32,
+        y: i32,
+        z: Option<i32>,
+        name: String,
+    }
+
+    #[test]
+    fn type_evolution_test() {
+        let v2 = PointV2 {
+            x: 1,
+            y: 2,
+            z: Some(3),
+            name: "p".to_string(),
+        };
+        for representation in [
+            RepresentationId_t::PL_CDR_LE,
+            RepresentationId_t::D_CDR2_LE,
+            RepresentationId_t::PL_CDR2_BE,
+        ] {
+            let mut payload = SerializedPayload_t::default();
+            to_payload(&vec![&v2, &v2], representation, &mut payload).unwrap();
+            let v1: Vec<PointV1> = from_payload(&payload).unwrap();
+            assert_eq!(vec![PointV1 { x: 1, y: 2 }, PointV1 { x: 1, y: 2 }], v1);
+        }
+
+        // Missing optional members of mutable types
+        let v1 = PointV1 { x: 1, y: 2 };
+        let mut payload = SerializedPayload_t::default();
+        to_payload(&v1, RepresentationId_t::PL_CDR2_LE, &mut payload).unwrap();
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct PointV3 {
+            y: i32,
+            x: i32,
+            z: Option<i32>,
+        }
+        let v3: PointV3 = from_payload(&payload).unwrap();
+        assert_eq!(None, v3.z);
+    }
+
+    #[test]
+    fn borrowed_test() {
+        #[derive(Serialize, Deserialize)]
+        struct Borrowed<'a> {
+            text: &'a str,
+        }
+
+        let mut payload = SerializedPayload_t::default();
+        to_payload(
+            &Borrowed { text: "borrowed" },
+            RepresentationId_t::CDR_LE,
+            &mut payload,
+        )
+        .unwrap();
+        let decoded: Borrowed = from_payload(&payload).unwrap();
+        assert_eq!("borrowed", decoded.text);
+    }
+
+    #[test]
+    fn error_test() {
+        let mut payload = SerializedPayload_t::default();
+        assert!(to_payload(&'€', RepresentationId_t::CDR_LE, &mut payload).is_err());
+        assert!(to_payload(&1_u128, RepresentationId_t::CDR_LE, &mut payload).is_err());
+
+        to_payload(&1_u8, RepresentationId_t::CDR_LE, &mut payload).unwrap();
+        assert!(from_payload::<u32>(&payload).is_err());
+        assert!(from_payload::<serde::de::IgnoredAny>(&payload).is_err());
+    }
+}
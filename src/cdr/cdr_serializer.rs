@@ -0,0 +1,988 @@
+use crate::cdr::encoding::*;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::serialized_payload::*;
+
+/**
+ * @brief Types that can be encoded with a CDR serializer.
+ */
+pub trait CdrSerialize {
+    /// Whether the type is a primitive type. XCDR2 adds a DHEADER to collections of
+    /// non primitive types, and encodes primitive members without NEXTINT.
+    const IS_PRIMITIVE: bool = false;
+
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError>;
+
+    /**
+     * @brief Biggest encoding of the type.
+     *
+     * @param version CDR version.
+     * @param position Position of the value, relative to the alignment origin.
+     * @return Position after the value, or None if the size of the type is not bounded.
+     */
+    fn max_serialized_size(_version: CdrVersion, _position: usize) -> Option<usize> {
+        None
+    }
+}
+
+/**
+ * @brief CDR encoder writing into a serialized payload.
+ *        The representation header is written when the serializer is created, and
+ *        alignment is computed from the first byte after it.
+ */
+pub struct CdrSerializer<'a> {
+    payload: &'a mut SerializedPayload_t,
+    representation: RepresentationId_t,
+    endianness: Endianness,
+    version: CdrVersion,
+    // Position from which alignment is computed
+    origin: usize,
+}
+
+/**
+ * @brief Structure being encoded, see CdrSerializer::begin_struct.
+ */
+pub struct StructScope {
+    extensibility: Extensibility,
+    delimiter: Option<Delimiter>,
+}
+
+/**
+ * @brief Member being encoded, see CdrSerializer::begin_member.
+ */
+pub struct MemberScope {
+    member_id: u32,
+    must_understand: bool,
+    primitive: bool,
+    // Position of the member header
+    header_pos: usize,
+    // Position of the member value
+    data_pos: usize,
+    // Alignment origin of the enclosing structure
+    origin: usize,
+}
+
+/**
+ * @brief DHEADER waiting for the size of the value that follows it, see
+ *        CdrSerializer::begin_delimited.
+ */
+pub struct Delimiter {
+    header_pos: usize,
+}
+
+macro_rules! serialize_primitive {
+    ($name:ident, $t:ty) => {
+        pub fn $name(&mut self, value: $t) -> Result<(), RtpsError> {
+            let size = std::mem::size_of::<$t>();
+            self.align(size);
+            match self.endianness {
+                Endianness::BIG_ENDIANNESS => self.payload.write_bytes(&value.to_be_bytes()),
+                Endianness::LITTLE_ENDIANNESS => self.payload.write_bytes(&value.to_le_bytes()),
+            }
+            Ok(())
+        }
+    };
+}
+
+impl<'a> CdrSerializer<'a> {
+    /**
+     * @brief Create a serializer, writing the representation header on the payload.
+     *
+     * @param payload Payload where the data is written.
+     * @param representation Representation identifier of the payload.
+     * @return The serializer, or an error if the representation is not supported.
+     */
+    pub fn new(
+        payload: &'a mut SerializedPayload_t,
+        representation: RepresentationId_t,
+    ) -> Result<Self, RtpsError> {
+        let (endianness, version) = match cdr_encoding(representation) {
+            Some(encoding) => encoding,
+            None => return Err(RtpsError::new("Not a CDR representation")),
+        };
+        payload.begin_write(representation, 0);
+        let origin = payload.pos();
+        Ok(CdrSerializer {
+            payload,
+            representation,
+            endianness,
+            version,
+            origin,
+        })
+    }
+
+    pub fn representation(&self) -> RepresentationId_t {
+        self.representation
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn version(&self) -> CdrVersion {
+        self.version
+    }
+
+    // Number of bytes written after the representation header
+    pub fn serialized_size(&self) -> usize {
+        self.payload.pos() - SerializedPayload_t::representation_header_size
+    }
+
+    /**
+     * @brief Add padding so the next value is aligned to size bytes.
+     *        The alignment is capped by the maximum alignment of the CDR version.
+     *
+     * @param size Size of the next value.
+     */
+    pub fn align(&mut self, size: usize) {
+        let size = std::cmp::min(size, self.version.max_alignment());
+        if size <= 1 {
+            return;
+        }
+        let offset = (self.payload.pos() - self.origin) % size;
+        if offset != 0 {
+            const ZEROS: [u8; 8] = [0; 8];
+            self.payload.write_bytes(&ZEROS[..size - offset]);
+        }
+    }
+
+    /**
+     * @brief Encode a value.
+     *
+     * @param value Value to be encoded.
+     */
+    pub fn serialize<T: CdrSerialize + ?Sized>(&mut self, value: &T) -> Result<(), RtpsError> {
+        value.serialize(self)
+    }
+
+    serialize_primitive!(serialize_u8, u8);
+    serialize_primitive!(serialize_i8, i8);
+    serialize_primitive!(serialize_u16, u16);
+    serialize_primitive!(serialize_i16, i16);
+    serialize_primitive!(serialize_u32, u32);
+    serialize_primitive!(serialize_i32, i32);
+    serialize_primitive!(serialize_u64, u64);
+    serialize_primitive!(serialize_i64, i64);
+    serialize_primitive!(serialize_f32, f32);
+    serialize_primitive!(serialize_f64, f64);
+
+    pub fn serialize_bool(&mut self, value: bool) -> Result<(), RtpsError> {
+        self.serialize_u8(value as u8)
+    }
+
+    /**
+     * @brief Encode a string: length including the NUL terminator, characters and terminator.
+     *
+     * @param value String to be encoded. It must not contain NUL characters.
+     */
+    pub fn serialize_string(&mut self, value: &str) -> Result<(), RtpsError> {
+        if value.as_bytes().contains(&0) {
+            return Err(RtpsError::new("String contains NUL characters"));
+        }
+        self.serialize_length(value.len() + 1)?;
+        self.payload.write_bytes(value.as_bytes());
+        self.payload.write_bytes(&[0]);
+        Ok(())
+    }
+
+    /**
+     * @brief Encode a sequence: number of elements followed by the elements.
+     *
+     * @param values Elements of the sequence.
+     */
+    pub fn serialize_sequence<T: CdrSerialize>(&mut self, values: &[T]) -> Result<(), RtpsError> {
+        if self.needs_dheader::<T>() {
+            return self.serialize_delimited(|ser| {
+                ser.serialize_length(values.len())?;
+                ser.serialize_elements(values)
+            });
+        }
+        self.serialize_length(values.len())?;
+        self.serialize_elements(values)
+    }
+
+    /**
+     * @brief Encode an array: the elements, without length.
+     *
+     * @param values Elements of the array.
+     */
+    pub fn serialize_array<T: CdrSerialize>(&mut self, values: &[T]) -> Result<(), RtpsError> {
+        if self.needs_dheader::<T>() {
+            return self.serialize_delimited(|ser| ser.serialize_elements(values));
+        }
+        self.serialize_elements(values)
+    }
+
+    /**
+     * @brief Encode a sequence of octets, copying it at once.
+     *
+     * @param values Octets of the sequence.
+     */
+    pub fn serialize_bytes(&mut self, values: &[u8]) -> Result<(), RtpsError> {
+        self.serialize_length(values.len())?;
+        self.payload.write_bytes(values);
+        Ok(())
+    }
+
+    /**
+     * @brief Encode the value of an enumeration, which uses 32 bits.
+     *
+     * @param value Enumerator value.
+     */
+    pub fn serialize_enum(&mut self, value: u32) -> Result<(), RtpsError> {
+        self.serialize_u32(value)
+    }
+
+    /**
+     * @brief Encode a union: discriminator followed by the selected member.
+     *
+     * @param discriminator Union discriminator.
+     * @param value Selected member, None when the discriminator selects no member.
+     */
+    pub fn serialize_union<D: CdrSerialize, T: CdrSerialize>(
+        &mut self,
+        discriminator: &D,
+        value: Option<&T>,
+    ) -> Result<(), RtpsError> {
+        discriminator.serialize(self)?;
+        if let Some(value) = value {
+            value.serialize(self)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * @brief Encode a structure.
+     *        On XCDR2 appendable and mutable structures are preceded by a DHEADER holding
+     *        their size. On XCDR1 mutable structures end with a PID_LIST_END sentinel.
+     *        Members of mutable structures must be encoded with serialize_member.
+     *
+     * @param extensibility Extensibility kind of the structure.
+     * @param members Function encoding the members.
+     */
+    pub fn serialize_struct<F>(
+        &mut self,
+        extensibility: Extensibility,
+        members: F,
+    ) -> Result<(), RtpsError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), RtpsError>,
+    {
+        let scope = self.begin_struct(extensibility)?;
+        members(self)?;
+        self.end_struct(scope)
+    }
+
+    /**
+     * @brief Start a structure, to be closed with end_struct once its members are encoded.
+     *
+     * @param extensibility Extensibility kind of the structure.
+     * @return The open structure.
+     */
+    pub fn begin_struct(&mut self, extensibility: Extensibility) -> Result<StructScope, RtpsError> {
+        let delimiter = match (self.version, extensibility) {
+            (_, Extensibility::FINAL) | (CdrVersion::XCDRv1, _) => None,
+            (CdrVersion::XCDRv2, _) => Some(self.begin_delimited()?),
+        };
+        Ok(StructScope {
+            extensibility,
+            delimiter,
+        })
+    }
+
+    /**
+     * @brief Close a structure opened with begin_struct.
+     *
+     * @param scope The open structure.
+     */
+    pub fn end_struct(&mut self, scope: StructScope) -> Result<(), RtpsError> {
+        if let Some(delimiter) = scope.delimiter {
+            return self.end_delimited(delimiter);
+        }
+        if self.version == CdrVersion::XCDRv1 && scope.extensibility == Extensibility::MUTABLE {
+            self.align(4);
+            self.serialize_u16(PID_LIST_END)?;
+            self.serialize_u16(0)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * @brief Encode a member of a mutable structure, preceded by its member header.
+     *        XCDR1 uses a parameter header. XCDR2 uses an EMHEADER, followed by the
+     *        member size (NEXTINT) unless the member is primitive.
+     *
+     * @param member_id Member id.
+     * @param must_understand Whether readers must understand the member.
+     * @param value Member value.
+     */
+    pub fn serialize_member<T: CdrSerialize + ?Sized>(
+        &mut self,
+        member_id: u32,
+        must_understand: bool,
+        value: &T,
+    ) -> Result<(), RtpsError> {
+        let scope = self.begin_member(member_id, must_understand, T::IS_PRIMITIVE)?;
+        value.serialize(self)?;
+        self.end_member(scope)
+    }
+
+    /**
+     * @brief Start a member of a mutable structure, to be closed with end_member once its
+     *        value is encoded.
+     *
+     * @param member_id Member id.
+     * @param must_understand Whether readers must understand the member.
+     * @param primitive Whether the member is primitive. Primitive members must use 1, 2, 4
+     *        or 8 bytes.
+     * @return The open member.
+     */
+    pub fn begin_member(
+        &mut self,
+        member_id: u32,
+        must_understand: bool,
+        primitive: bool,
+    ) -> Result<MemberScope, RtpsError> {
+        if self.version == CdrVersion::XCDRv1 {
+            return self.begin_parameter(member_id, must_understand);
+        }
+        if member_id > EMHEADER_ID_MASK {
+            return Err(RtpsError::new("Member id does not fit in 28 bits"));
+        }
+
+        self.align(4);
+        let header_pos = self.payload.pos();
+        self.serialize_u32(0)?;
+        if !primitive {
+            self.serialize_u32(0)?;
+        }
+        Ok(MemberScope {
+            member_id,
+            must_understand,
+            primitive,
+            header_pos,
+            data_pos: self.payload.pos(),
+            origin: self.origin,
+        })
+    }
+
+    /**
+     * @brief Close a member opened with begin_member or begin_optional, writing its size.
+     *
+     * @param scope The open member.
+     */
+    pub fn end_member(&mut self, scope: MemberScope) -> Result<(), RtpsError> {
+        let size = self.payload.pos() - scope.data_pos;
+        self.origin = scope.origin;
+        if self.version == CdrVersion::XCDRv1 {
+            return self.end_parameter(scope, size);
+        }
+
+        let flags = if scope.must_understand {
+            EMHEADER_MUST_UNDERSTAND_FLAG
+        } else {
+            0
+        };
+        let lc = if scope.primitive {
+            match size {
+                1 => 0,
+                2 => 1,
+                4 => 2,
+                8 => 3,
+                _ => return Err(RtpsError::new("Invalid primitive member size")),
+            }
+        } else {
+            EMHEADER_LC_NEXTINT
+        };
+        let end_pos = self.payload.pos();
+        self.payload.set_pos(scope.header_pos);
+        self.serialize_u32(flags | (lc << EMHEADER_LC_SHIFT) | scope.member_id)?;
+        if !scope.primitive {
+            self.serialize_length(size)?;
+        }
+        self.payload.set_pos(end_pos);
+        Ok(())
+    }
+
+    /**
+     * @brief Encode an optional member of a final or appendable structure.
+     *        On XCDR1 the member is preceded by a parameter header holding its id and size,
+     *        which is 0 when the member is not present, and alignment restarts at the member
+     *        value. On XCDR2 the member is preceded by a boolean telling whether it is present.
+     *        Optional members of mutable structures are encoded with serialize_member, or
+     *        not encoded at all when not present.
+     *
+     * @param member_id Member id.
+     * @param must_understand Whether readers must understand the member.
+     * @param value Member value, None when it is not present.
+     */
+    pub fn serialize_optional<T: CdrSerialize>(
+        &mut self,
+        member_id: u32,
+        must_understand: bool,
+        value: Option<&T>,
+    ) -> Result<(), RtpsError> {
+        let scope = self.begin_optional(member_id, must_understand, value.is_some())?;
+        if let Some(value) = value {
+            value.serialize(self)?;
+        }
+        match scope {
+            Some(scope) => self.end_member(scope),
+            None => Ok(()),
+        }
+    }
+
+    /**
+     * @brief Start an optional member of a final or appendable structure, see
+     *        serialize_optional. When the member is present its value must be encoded next.
+     *
+     * @param member_id Member id.
+     * @param must_understand Whether readers must understand the member.
+     * @param present Whether the member is present.
+     * @return The open member, to be closed with end_member, if any.
+     */
+    pub fn begin_optional(
+        &mut self,
+        member_id: u32,
+        must_understand: bool,
+        present: bool,
+    ) -> Result<Option<MemberScope>, RtpsError> {
+        if self.version == CdrVersion::XCDRv2 {
+            self.serialize_bool(present)?;
+            return Ok(None);
+        }
+
+        let scope = self.begin_parameter(member_id, must_understand)?;
+        if !present {
+            self.end_member(scope)?;
+            return Ok(None);
+        }
+        Ok(Some(scope))
+    }
+
+    /**
+     * @brief Encode a value preceded by a DHEADER holding its size.
+     *
+     * @param value Function encoding the value.
+     */
+    pub fn serialize_delimited<F>(&mut self, value: F) -> Result<(), RtpsError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), RtpsError>,
+    {
+        let delimiter = self.begin_delimited()?;
+        value(self)?;
+        self.end_delimited(delimiter)
+    }
+
+    /**
+     * @brief Write a DHEADER, to be filled by end_delimited once the value is encoded.
+     *
+     * @return The open DHEADER.
+     */
+    pub fn begin_delimited(&mut self) -> Result<Delimiter, RtpsError> {
+        self.align(4);
+        let header_pos = self.payload.pos();
+        self.serialize_u32(0)?;
+        Ok(Delimiter { header_pos })
+    }
+
+    /**
+     * @brief Fill a DHEADER written by begin_delimited with the size of the value.
+     *
+     * @param delimiter The open DHEADER.
+     */
+    pub fn end_delimited(&mut self, delimiter: Delimiter) -> Result<(), RtpsError> {
+        let data_pos = delimiter.header_pos + 4;
+        let end_pos = self.payload.pos();
+        self.payload.set_pos(delimiter.header_pos);
+        self.serialize_length(end_pos - data_pos)?;
+        self.payload.set_pos(end_pos);
+        Ok(())
+    }
+
+    fn needs_dheader<T: CdrSerialize>(&self) -> bool {
+        self.version == CdrVersion::XCDRv2 && !T::IS_PRIMITIVE
+    }
+
+    fn serialize_elements<T: CdrSerialize>(&mut self, values: &[T]) -> Result<(), RtpsError> {
+        for value in values {
+            value.serialize(self)?;
+        }
+        Ok(())
+    }
+
+    // Parameter header of an XCDR1 member, alignment restarts at the member value
+    fn begin_parameter(
+        &mut self,
+        member_id: u32,
+        must_understand: bool,
+    ) -> Result<MemberScope, RtpsError> {
+        self.align(4);
+        let header_pos = self.payload.pos();
+        let extended = member_id > PID_MAX_SHORT_ID;
+        self.write_member_header(member_id, must_understand, extended, 0)?;
+
+        let data_pos = self.payload.pos();
+        let origin = std::mem::replace(&mut self.origin, data_pos);
+        Ok(MemberScope {
+            member_id,
+            must_understand,
+            primitive: false,
+            header_pos,
+            data_pos,
+            origin,
+        })
+    }
+
+    fn end_parameter(&mut self, scope: MemberScope, size: usize) -> Result<(), RtpsError> {
+        let extended = scope.member_id > PID_MAX_SHORT_ID;
+        if !extended && size > u16::MAX as usize {
+            // Move the member to make room for the extended header
+            let data = self.payload.data()[scope.data_pos..scope.data_pos + size].to_vec();
+            self.payload.set_pos(scope.header_pos);
+            self.write_member_header(scope.member_id, scope.must_understand, true, size)?;
+            self.payload.write_bytes(&data);
+            return Ok(());
+        }
+
+        let end_pos = self.payload.pos();
+        self.payload.set_pos(scope.header_pos);
+        self.write_member_header(scope.member_id, scope.must_understand, extended, size)?;
+        self.payload.set_pos(end_pos);
+        Ok(())
+    }
+
+    /**
+     * @brief Finish the encoding: the payload is padded to a multiple of 4 bytes and the
+     *        number of padding bytes is stored on the representation options.
+     */
+    pub fn finish(self) -> Result<(), RtpsError> {
+        let padding = (4 - self.payload.pos() % 4) % 4;
+        self.payload.write_bytes(&[0; 3][..padding]);
+        self.payload.set_padding(padding as u8)
+    }
+
+    fn serialize_length(&mut self, length: usize) -> Result<(), RtpsError> {
+        if length > u32::MAX as usize {
+            return Err(RtpsError::new("Length does not fit in 32 bits"));
+        }
+        self.serialize_u32(length as u32)
+    }
+
+    fn write_member_header(
+        &mut self,
+        member_id: u32,
+        must_understand: bool,
+        extended: bool,
+        size: usize,
+    ) -> Result<(), RtpsError> {
+        let flags = if must_understand {
+            PID_MUST_UNDERSTAND_FLAG
+        } else {
+            0
+        };
+        if extended {
+            self.serialize_u16(PID_EXTENDED | flags)?;
+            self.serialize_u16(8)?;
+            self.serialize_u32(member_id)?;
+            self.serialize_length(size)
+        } else {
+            self.serialize_u16(member_id as u16 | flags)?;
+            self.serialize_u16(size as u16)
+        }
+    }
+}
+
+macro_rules! impl_serialize_primitive {
+    ($t:ty, $name:ident) => {
+        impl CdrSerialize for $t {
+            const IS_PRIMITIVE: bool = true;
+
+            fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+                ser.$name(*self)
+            }
+
+            fn max_serialized_size(version: CdrVersion, position: usize) -> Option<usize> {
+                let size = std::mem::size_of::<$t>();
+                Some(version.align(position, size) + size)
+            }
+        }
+    };
+}
+
+impl_serialize_primitive!(u8, serialize_u8);
+impl_serialize_primitive!(i8, serialize_i8);
+impl_serialize_primitive!(u16, serialize_u16);
+impl_serialize_primitive!(i16, serialize_i16);
+impl_serialize_primitive!(u32, serialize_u32);
+impl_serialize_primitive!(i32, serialize_i32);
+impl_serialize_primitive!(u64, serialize_u64);
+impl_serialize_primitive!(i64, serialize_i64);
+impl_serialize_primitive!(f32, serialize_f32);
+impl_serialize_primitive!(f64, serialize_f64);
+impl_serialize_primitive!(bool, serialize_bool);
+
+impl CdrSerialize for str {
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        ser.serialize_string(self)
+    }
+}
+
+impl CdrSerialize for String {
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        ser.serialize_string(self)
+    }
+}
+
+impl<T: CdrSerialize> CdrSerialize for Vec<T> {
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        ser.serialize_sequence(self)
+    }
+}
+
+impl<T: CdrSerialize, const N: usize> CdrSerialize for [T; N] {
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        ser.serialize_array(self)
+    }
+
+    fn max_serialized_size(version: CdrVersion, position: usize) -> Option<usize> {
+        let mut position = position;
+        if version == CdrVersion::XCDRv2 && !T::IS_PRIMITIVE {
+            position = version.align(position, 4) + 4;
+        }
+        for _ in 0..N {
+            position = T::max_serialized_size(version, position)?;
+        }
+        Some(position)
+    }
+}
+
+impl<T: CdrSerialize + ?Sized> CdrSerialize for &T {
+    const IS_PRIMITIVE: bool = T::IS_PRIMITIVE;
+
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        (**self).serialize(ser)
+    }
+
+    fn max_serialized_size(version: CdrVersion, position: usize) -> Option<usize> {
+        T::max_serialized_size(version, position)
+    }
+}
+
+impl<T: CdrSerialize + ?Sized> CdrSerialize for Box<T> {
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        (**self).serialize(ser)
+    }
+
+    fn max_serialized_size(version: CdrVersion, position: usize) -> Option<usize> {
+        T::max_serialized_size(version, position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sample {
+        a: u8,
+        b: u32,
+        c: i16,
+        d: f64,
+        e: String,
+    }
+
+    impl CdrSerialize for Sample {
+        fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+            ser.serialize(&self.a)?;
+            ser.serialize(&self.b)?;
+            ser.serialize(&self.c)?;
+            ser.serialize(&self.d)?;
+            ser.serialize(&self.e)
+        }
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            a: 0x01,
+            b: 0x02030405,
+            c: -2,
+            d: 1.0,
+            e: "hi".to_string(),
+        }
+    }
+
+    #[test]
+    fn little_endian_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        ser.serialize(&sample()).unwrap();
+        ser.finish().unwrap();
+
+        #[rustfmt::skip]
+        let expected: [u8; 36] = [
+            0x00, 0x01, 0x00, 0x01,                         // header, 1 byte of padding
+            0x01, 0x00, 0x00, 0x00,                         // a + padding
+            0x05, 0x04, 0x03, 0x02,                         // b
+            0xfe, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // c + padding to 8
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, // d
+            0x03, 0x00, 0x00, 0x00,                         // e length
+            b'h', b'i', 0x00, 0x00,                         // e + final padding
+        ];
+        assert_eq!(&expected[..], payload.data());
+        assert_eq!(1, payload.padding());
+    }
+
+    #[test]
+    fn big_endian_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_BE).unwrap();
+        ser.serialize(&sample()).unwrap();
+        assert_eq!(31, ser.serialized_size());
+        ser.finish().unwrap();
+
+        #[rustfmt::skip]
+        let expected: [u8; 36] = [
+            0x00, 0x00, 0x00, 0x01,
+            0x01, 0x00, 0x00, 0x00,
+            0x02, 0x03, 0x04, 0x05,
+            0xff, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x3f, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x03,
+            b'h', b'i', 0x00, 0x00,
+        ];
+        assert_eq!(&expected[..], payload.data());
+    }
+
+    #[test]
+    fn sequence_array_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        ser.serialize(&true).unwrap();
+        ser.serialize(&vec![1_u16, 2, 3]).unwrap();
+        ser.serialize(&[7_u8, 8]).unwrap();
+        ser.serialize(&vec![0x0102030405060708_u64]).unwrap();
+        ser.serialize_bytes(&[9, 10]).unwrap();
+        ser.serialize("").unwrap();
+        ser.finish().unwrap();
+
+        #[rustfmt::skip]
+        let expected: [u8; 44] = [
+            0x00, 0x01, 0x00, 0x03,
+            0x01, 0x00, 0x00, 0x00,                         // bool + padding
+            0x03, 0x00, 0x00, 0x00,                         // sequence length
+            0x01, 0x00, 0x02, 0x00, 0x03, 0x00,             // sequence
+            0x07, 0x08,                                     // array
+            0x01, 0x00, 0x00, 0x00,                         // sequence length
+            0x00, 0x00, 0x00, 0x00,                         // padding to 8
+            0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01,
+            0x02, 0x00, 0x00, 0x00,                         // octets length
+            0x09, 0x0a,                                     // octets
+            0x00, 0x00,                                     // padding
+        ];
+        assert_eq!(&expected[..], &payload.data()[..44]);
+        assert_eq!(
+            &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            &payload.data()[44..]
+        );
+        assert_eq!(3, payload.padding());
+    }
+
+    #[test]
+    fn enum_union_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_BE).unwrap();
+        ser.serialize_u8(1).unwrap();
+        ser.serialize_enum(2).unwrap();
+        ser.serialize_union(&3_i32, Some(&4.0_f32)).unwrap();
+        ser.serialize_union::<i16, u8>(&-1, None).unwrap();
+        ser.finish().unwrap();
+
+        #[rustfmt::skip]
+        let expected: [u8; 24] = [
+            0x00, 0x00, 0x00, 0x02,
+            0x01, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x03,
+            0x40, 0x80, 0x00, 0x00,
+            0xff, 0xff, 0x00, 0x00,
+        ];
+        assert_eq!(&expected[..], payload.data());
+    }
+
+    #[test]
+    fn optional_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        ser.serialize_u8(1).unwrap();
+        ser.serialize_optional(1, false, Some(&2_u64)).unwrap();
+        ser.serialize_optional::<u32>(2, true, None).unwrap();
+        ser.serialize_optional(0x4000, false, Some(&3_u16)).unwrap();
+        ser.finish().unwrap();
+
+        #[rustfmt::skip]
+        let expected: [u8; 40] = [
+            0x00, 0x01, 0x00, 0x02,
+            0x01, 0x00, 0x00, 0x00,                         // u8 + padding
+            0x01, 0x00, 0x08, 0x00,                         // id 1, length 8
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // aligned on the member start
+            0x02, 0x40, 0x00, 0x00,                         // id 2, must understand, absent
+            0x01, 0x3f, 0x08, 0x00,                         // extended header
+            0x00, 0x40, 0x00, 0x00,                         // id 0x4000
+            0x02, 0x00, 0x00, 0x00,                         // length 2
+            0x03, 0x00, 0x00, 0x00,                         // value + final padding
+        ];
+        assert_eq!(&expected[..], payload.data());
+    }
+
+    #[test]
+    fn big_optional_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        let big = vec![0xaa_u8; 0x10000];
+        ser.serialize_optional(5, false, Some(&big)).unwrap();
+        ser.finish().unwrap();
+
+        let data = payload.data();
+        assert_eq!(4 + 12 + 4 + 0x10000, data.len());
+        assert_eq!(&[0x01, 0x3f, 0x08, 0x00], &data[4..8]);
+        assert_eq!(&[0x05, 0x00, 0x00, 0x00], &data[8..12]);
+        assert_eq!(&[0x04, 0x00, 0x01, 0x00], &data[12..16]);
+        assert_eq!(&[0x00, 0x00, 0x01, 0x00], &data[16..20]);
+        assert!(data[20..].iter().all(|b| *b == 0xaa));
+    }
+
+    #[test]
+    fn xcdr2_final_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR2_LE).unwrap();
+        assert_eq!(CdrVersion::XCDRv2, ser.version());
+        ser.serialize(&sample()).unwrap();
+        ser.serialize_optional(1, false, Some(&7_u16)).unwrap();
+        ser.serialize_optional::<u16>(2, false, None).unwrap();
+        ser.finish().unwrap();
+
+        #[rustfmt::skip]
+        let expected: [u8; 36] = [
+            0x00, 0x07, 0x00, 0x01,
+            0x01, 0x00, 0x00, 0x00,                         // a + padding
+            0x05, 0x04, 0x03, 0x02,                         // b
+            0xfe, 0xff, 0x00, 0x00,                         // c + padding to 4
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, // d
+            0x03, 0x00, 0x00, 0x00,                         // e length
+            b'h', b'i', 0x00,                               // e
+            0x01,                                           // present flag
+            0x07, 0x00,                                     // value
+            0x00,                                           // absent flag
+            0x00,
+        ];
+        assert_eq!(&expected[..], payload.data());
+        assert_eq!(1, payload.padding());
+    }
+
+    #[test]
+    fn xcdr2_appendable_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::D_CDR2_BE).unwrap();
+        ser.serialize_struct(Extensibility::APPENDABLE, |ser| {
+            ser.serialize_u8(1)?;
+            ser.serialize(&vec!["a".to_string(), "bc".to_string()])?;
+            ser.serialize(&vec![2_u16])
+        })
+        .unwrap();
+        ser.finish().unwrap();
+
+        #[rustfmt::skip]
+        let expected: [u8; 36] = [
+            0x00, 0x08, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x22,                         // DHEADER
+            0x01, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x13,                         // DHEADER of the sequence of strings
+            0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x02, b'a', 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x03, b'b', b'c', 0x00,
+            0x00,                                           // padding
+        ];
+        assert_eq!(&expected[..], &payload.data()[..36]);
+        assert_eq!(
+            &[0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00],
+            &payload.data()[36..]
+        );
+    }
+
+    #[test]
+    fn xcdr2_mutable_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::PL_CDR2_LE).unwrap();
+        ser.serialize_struct(Extensibility::MUTABLE, |ser| {
+            ser.serialize_member(1, true, &3_u8)?;
+            ser.serialize_member(2, false, &4_u64)?;
+            ser.serialize_member(0x100, false, "hi")
+        })
+        .unwrap();
+        ser.finish().unwrap();
+
+        #[rustfmt::skip]
+        let expected: [u8; 44] = [
+            0x00, 0x0b, 0x00, 0x01,
+            0x23, 0x00, 0x00, 0x00,                         // DHEADER
+            0x01, 0x00, 0x00, 0x80,                         // EMHEADER, must understand, LC 0
+            0x03, 0x00, 0x00, 0x00,
+            0x02, 0x00, 0x00, 0x30,                         // EMHEADER, LC 3
+            0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x01, 0x00, 0x40,                         // EMHEADER, LC 4
+            0x07, 0x00, 0x00, 0x00,                         // NEXTINT
+            0x03, 0x00, 0x00, 0x00, b'h', b'i', 0x00,
+            0x00,
+        ];
+        assert_eq!(&expected[..], payload.data());
+        assert_eq!(1, payload.padding());
+    }
+
+    #[test]
+    fn xcdr1_mutable_test() {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::PL_CDR_LE).unwrap();
+        ser.serialize_struct(Extensibility::MUTABLE, |ser| {
+            ser.serialize_member(1, true, &3_u8)?;
+            ser.serialize_member(2, false, &4_u16)
+        })
+        .unwrap();
+        ser.finish().unwrap();
+
+        #[rustfmt::skip]
+        let expected: [u8; 24] = [
+            0x00, 0x03, 0x00, 0x00,
+            0x01, 0x40, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00,
+            0x02, 0x00, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00,
+            0x02, 0x3f, 0x00, 0x00,                         // PID_LIST_END
+        ];
+        assert_eq!(&expected[..], payload.data());
+    }
+
+    #[test]
+    fn max_serialized_size_test() {
+        assert_eq!(Some(16), u64::max_serialized_size(CdrVersion::XCDRv1, 1));
+        assert_eq!(Some(12), u64::max_serialized_size(CdrVersion::XCDRv2, 1));
+        assert_eq!(
+            Some(8),
+            <[u16; 3]>::max_serialized_size(CdrVersion::XCDRv1, 1)
+        );
+        assert_eq!(
+            Some(24),
+            <[[u32; 2]; 2]>::max_serialized_size(CdrVersion::XCDRv2, 2)
+        );
+        assert_eq!(None, String::max_serialized_size(CdrVersion::XCDRv1, 0));
+        assert_eq!(
+            None,
+            <[String; 2]>::max_serialized_size(CdrVersion::XCDRv1, 0)
+        );
+    }
+
+    #[test]
+    fn error_test() {
+        let mut payload = SerializedPayload_t::default();
+        assert!(CdrSerializer::new(&mut payload, RepresentationId_t::XML).is_err());
+
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        assert!(ser.serialize("a\0b").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::rtps::common::serialized_payload::RepresentationId_t;
+
+/// Parameter id announcing an extended member header
+pub const PID_EXTENDED: u16 = 0x3f01;
+/// Parameter id closing a parameter list
+pub const PID_LIST_END: u16 = 0x3f02;
+/// Biggest member id that fits on a short member header
+pub const PID_MAX_SHORT_ID: u32 = 0x3f00;
+/// Must understand flag of a short member header
+pub const PID_MUST_UNDERSTAND_FLAG: u16 = 0x4000;
+/// Mask of the member id on a short member header
+pub const PID_ID_MASK: u16 = 0x3fff;
+
+/// Must understand flag of an XCDR2 member header (EMHEADER)
+pub const EMHEADER_MUST_UNDERSTAND_FLAG: u32 = 0x8000_0000;
+/// Mask of the member id on an XCDR2 member header
+pub const EMHEADER_ID_MASK: u32 = 0x0fff_ffff;
+/// Position of the length code on an XCDR2 member header
+pub const EMHEADER_LC_SHIFT: u32 = 28;
+/// Length code of a member whose size follows the header (NEXTINT)
+pub const EMHEADER_LC_NEXTINT: u32 = 4;
+
+/**
+ * @brief Byte order of the encoded data.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    BIG_ENDIANNESS,
+    LITTLE_ENDIANNESS,
+}
+
+impl Endianness {
+    #[cfg(target_endian = "little")]
+    pub const DEFAULT_ENDIAN: Endianness = Endianness::LITTLE_ENDIANNESS;
+    #[cfg(target_endian = "big")]
+    pub const DEFAULT_ENDIAN: Endianness = Endianness::BIG_ENDIANNESS;
+}
+
+/**
+ * @brief Version of the CDR encoding, as specified in the DDS-XTypes 1.3 specification chapter 7.4.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CdrVersion {
+    /// Classic CDR, used by the XCDR1 representations
+    XCDRv1,
+    /// Extended CDR version 2
+    XCDRv2,
+}
+
+impl CdrVersion {
+    /// Biggest alignment used by the encoding
+    pub fn max_alignment(self) -> usize {
+        match self {
+            CdrVersion::XCDRv1 => 8,
+            CdrVersion::XCDRv2 => 4,
+        }
+    }
+
+    /// Position of a value of the given size placed at position or after it
+    pub fn align(self, position: usize, size: usize) -> usize {
+        let size = std::cmp::min(size, self.max_alignment()).max(1);
+        position.next_multiple_of(size)
+    }
+}
+
+/**
+ * @brief Extensibility kind of a constructed type, as specified in the DDS-XTypes 1.3
+ *        specification chapter 7.2.2.4.4.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Extensibility {
+    /// Members can not be added or removed
+    FINAL,
+    /// Members can be added at the end
+    APPENDABLE,
+    /// Members can be added, removed and reordered
+    MUTABLE,
+}
+
+/**
+ * @brief Get the endianness and CDR version of a representation.
+ *
+ * @param representation Representation identifier.
+ * @return Endianness and version, or None if it is not a CDR representation.
+ */
+pub fn cdr_encoding(representation: RepresentationId_t) -> Option<(Endianness, CdrVersion)> {
+    if representation == RepresentationId_t::XML {
+        return None;
+    }
+
+    let endianness = if representation.is_little_endian() {
+        Endianness::LITTLE_ENDIANNESS
+    } else {
+        Endianness::BIG_ENDIANNESS
+    };
+    let version = if representation.is_xcdr2() {
+        CdrVersion::XCDRv2
+    } else {
+        CdrVersion::XCDRv1
+    };
+    Some((endianness, version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cdr_encoding_test() {
+        assert_eq!(
+            Some((Endianness::LITTLE_ENDIANNESS, CdrVersion::XCDRv1)),
+            cdr_encoding(RepresentationId_t::CDR_LE)
+        );
+        assert_eq!(
+            Some((Endianness::BIG_ENDIANNESS, CdrVersion::XCDRv1)),
+            cdr_encoding(RepresentationId_t::PL_CDR_BE)
+        );
+        assert_eq!(
+            Some((Endianness::BIG_ENDIANNESS, CdrVersion::XCDRv2)),
+            cdr_encoding(RepresentationId_t::D_CDR2_BE)
+        );
+        assert_eq!(None, cdr_encoding(RepresentationId_t::XML));
+        assert_eq!(8, CdrVersion::XCDRv1.max_alignment());
+        assert_eq!(4, CdrVersion::XCDRv2.max_alignment());
+        assert_eq!(8, CdrVersion::XCDRv1.align(5, 8));
+        assert_eq!(8, CdrVersion::XCDRv2.align(5, 8));
+        assert_eq!(4, CdrVersion::XCDRv2.align(4, 8));
+        assert_eq!(5, CdrVersion::XCDRv1.align(5, 1));
+    }
+}
@@ -1 +1,3 @@
-
+pub mod core;
+pub mod subscription;
+pub mod topic;
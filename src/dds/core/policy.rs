@@ -0,0 +1,249 @@
+/*
+ * QoS policies exchanged by the discovery and sent as inline QoS, with their wire
+ * representation as specified in the DDSI-RTPS 2.5 specification chapter 9.6.3.
+ */
+
+use crate::cdr::cdr_deserializer::*;
+use crate::cdr::cdr_serializer::*;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::time::Duration_t;
+
+// Kind of a policy, sent as an unsigned long
+macro_rules! qos_policy_kind {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $value:expr),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        pub enum $name {
+            $($variant = $value),+
+        }
+
+        impl CdrSerialize for $name {
+            fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+                ser.serialize_u32(*self as u32)
+            }
+        }
+
+        impl CdrDeserialize for $name {
+            fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+                match de.deserialize_u32()? {
+                    $(value if value == $value => Ok($name::$variant),)+
+                    _ => Err(RtpsError::new(concat!("Unknown ", stringify!($name)))),
+                }
+            }
+        }
+    };
+}
+
+// Policy whose members are sent in order
+macro_rules! qos_policy {
+    ($(#[$meta:meta])* $name:ident { $($field:ident: $t:ty),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct $name {
+            $(pub $field: $t),+
+        }
+
+        impl CdrSerialize for $name {
+            fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+                $(ser.serialize(&self.$field)?;)+
+                Ok(())
+            }
+        }
+
+        impl CdrDeserialize for $name {
+            fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+                Ok($name {
+                    $($field: de.deserialize()?),+
+                })
+            }
+        }
+    };
+}
+
+qos_policy_kind!(DurabilityQosPolicyKind_t {
+    VOLATILE_DURABILITY_QOS = 0,
+    TRANSIENT_LOCAL_DURABILITY_QOS = 1,
+    TRANSIENT_DURABILITY_QOS = 2,
+    PERSISTENT_DURABILITY_QOS = 3,
+});
+
+qos_policy_kind!(LivelinessQosPolicyKind {
+    AUTOMATIC_LIVELINESS_QOS = 0,
+    MANUAL_BY_PARTICIPANT_LIVELINESS_QOS = 1,
+    MANUAL_BY_TOPIC_LIVELINESS_QOS = 2,
+});
+
+qos_policy_kind!(ReliabilityQosPolicyKind {
+    BEST_EFFORT_RELIABILITY_QOS = 1,
+    RELIABLE_RELIABILITY_QOS = 2,
+});
+
+qos_policy_kind!(DestinationOrderQosPolicyKind {
+    BY_RECEPTION_TIMESTAMP_DESTINATIONORDER_QOS = 0,
+    BY_SOURCE_TIMESTAMP_DESTINATIONORDER_QOS = 1,
+});
+
+qos_policy_kind!(HistoryQosPolicyKind {
+    KEEP_LAST_HISTORY_QOS = 0,
+    KEEP_ALL_HISTORY_QOS = 1,
+});
+
+qos_policy_kind!(OwnershipQosPolicyKind {
+    SHARED_OWNERSHIP_QOS = 0,
+    EXCLUSIVE_OWNERSHIP_QOS = 1,
+});
+
+qos_policy_kind!(PresentationQosPolicyAccessScopeKind {
+    INSTANCE_PRESENTATION_QOS = 0,
+    TOPIC_PRESENTATION_QOS = 1,
+    GROUP_PRESENTATION_QOS = 2,
+});
+
+qos_policy!(
+    /// Whether samples are kept for late joining readers
+    DurabilityQosPolicy {
+        kind: DurabilityQosPolicyKind_t,
+    }
+);
+
+qos_policy!(
+    /// Maximum period between samples of an instance
+    DeadlineQosPolicy { period: Duration_t }
+);
+
+qos_policy!(
+    /// Acceptable delay from the writing of a sample to its reception
+    LatencyBudgetQosPolicy { duration: Duration_t }
+);
+
+qos_policy!(
+    /// How the liveliness of the writers is asserted
+    LivelinessQosPolicy {
+        kind: LivelinessQosPolicyKind,
+        lease_duration: Duration_t,
+    }
+);
+
+qos_policy!(
+    /// Whether lost samples are repaired
+    ReliabilityQosPolicy {
+        kind: ReliabilityQosPolicyKind,
+        max_blocking_time: Duration_t,
+    }
+);
+
+qos_policy!(
+    /// Order of the samples of different writers
+    DestinationOrderQosPolicy {
+        kind: DestinationOrderQosPolicyKind,
+    }
+);
+
+qos_policy!(
+    /// Samples kept by the histories
+    HistoryQosPolicy {
+        kind: HistoryQosPolicyKind,
+        depth: i32,
+    }
+);
+
+qos_policy!(
+    /// Resources used by the histories, negative values mean unlimited
+    ResourceLimitsQosPolicy {
+        max_samples: i32,
+        max_instances: i32,
+        max_samples_per_instance: i32,
+    }
+);
+
+qos_policy!(
+    /// Expiration time of the samples
+    LifespanQosPolicy { duration: Duration_t }
+);
+
+qos_policy!(
+    /// Whether several writers may update the same instance
+    OwnershipQosPolicy {
+        kind: OwnershipQosPolicyKind,
+    }
+);
+
+qos_policy!(
+    /// Strength used to choose the owner of an instance
+    OwnershipStrengthQosPolicy { value: u32 }
+);
+
+qos_policy!(
+    /// Scope and ordering of the changes presented to readers
+    PresentationQosPolicy {
+        access_scope: PresentationQosPolicyAccessScopeKind,
+        coherent_access: bool,
+        ordered_access: bool,
+    }
+);
+
+qos_policy!(
+    /// Logical partitions of the publishers and subscribers
+    PartitionQosPolicy { names: Vec<String> }
+);
+
+qos_policy!(
+    /// Minimum separation between the samples delivered to a reader
+    TimeBasedFilterQosPolicy {
+        minimum_separation: Duration_t,
+    }
+);
+
+qos_policy!(
+    /// Priority of the transport of the data
+    TransportPriorityQosPolicy { value: u32 }
+);
+
+qos_policy!(
+    /// Application data attached to a participant or endpoint
+    UserDataQosPolicy { value: Vec<u8> }
+);
+
+qos_policy!(
+    /// Application data attached to a topic
+    TopicDataQosPolicy { value: Vec<u8> }
+);
+
+qos_policy!(
+    /// Application data attached to a publisher or subscriber
+    GroupDataQosPolicy { value: Vec<u8> }
+);
+
+qos_policy!(
+    /// Name and value pair of a property list
+    Property_t { name: String, value: String }
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtps::common::serialized_payload::*;
+
+    #[test]
+    fn encoding_test() {
+        let reliability = ReliabilityQosPolicy {
+            kind: ReliabilityQosPolicyKind::RELIABLE_RELIABILITY_QOS,
+            max_blocking_time: Duration_t::new(0, 500_000_000),
+        };
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        ser.serialize(&reliability).unwrap();
+        ser.finish().unwrap();
+        assert_eq!(
+            [0x00, 0x01, 0x00, 0x00, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80],
+            payload.data()
+        );
+
+        let mut de = CdrDeserializer::new(&payload).unwrap();
+        assert_eq!(reliability, de.deserialize().unwrap());
+
+        let data = [0, 0, 0, 3];
+        let mut de = CdrDeserializer::from_bytes(&data, RepresentationId_t::CDR_BE).unwrap();
+        assert!(de.deserialize::<ReliabilityQosPolicy>().is_err());
+    }
+}
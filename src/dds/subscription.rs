@@ -0,0 +1 @@
+pub mod sample_info;
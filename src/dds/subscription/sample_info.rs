@@ -0,0 +1,64 @@
+/*
+ * States of the samples and instances seen by a DataReader, as specified in the DDS 1.4
+ * specification chapter 2.2.2.5.1.
+ */
+
+use crate::rtps::common::instance_handle::InstanceHandle_t;
+use crate::rtps::common::time::Time_t;
+
+pub type SampleStateMask = u32;
+pub type ViewStateMask = u32;
+pub type InstanceStateMask = u32;
+
+/// Whether the sample has already been read
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleStateKind {
+    READ_SAMPLE_STATE = 0x0001,
+    NOT_READ_SAMPLE_STATE = 0x0002,
+}
+
+/// Whether the reader has already seen the current generation of the instance
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ViewStateKind {
+    NEW_VIEW_STATE = 0x0001,
+    NOT_NEW_VIEW_STATE = 0x0002,
+}
+
+/// Whether the instance is alive, disposed or without alive writers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InstanceStateKind {
+    ALIVE_INSTANCE_STATE = 0x0001,
+    NOT_ALIVE_DISPOSED_INSTANCE_STATE = 0x0002,
+    NOT_ALIVE_NO_WRITERS_INSTANCE_STATE = 0x0004,
+}
+
+pub const ANY_SAMPLE_STATE: SampleStateMask = 0xffff;
+pub const ANY_VIEW_STATE: ViewStateMask = 0xffff;
+pub const NOT_ALIVE_INSTANCE_STATE: InstanceStateMask = 0x0006;
+pub const ANY_INSTANCE_STATE: InstanceStateMask = 0xffff;
+
+/**
+ * @brief Information of a sample returned by read and take.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SampleInfo {
+    pub sample_state: SampleStateKind,
+    pub view_state: ViewStateKind,
+    pub instance_state: InstanceStateKind,
+    /// Times the instance became alive again after being disposed, when the sample was received
+    pub disposed_generation_count: i32,
+    /// Times the instance became alive again after having no writers, when the sample was received
+    pub no_writers_generation_count: i32,
+    /// Samples of the same instance following this one in the returned collection
+    pub sample_rank: i32,
+    /// Generations between this sample and the most recent sample of the instance in the collection
+    pub generation_rank: i32,
+    /// Generations between this sample and the most recent sample of the instance received
+    pub absolute_generation_rank: i32,
+    pub source_timestamp: Time_t,
+    pub instance_handle: InstanceHandle_t,
+    /// Instance handle of the writer of the sample
+    pub publication_handle: InstanceHandle_t,
+    /// Whether the sample has data, samples notifying a change of the instance state have none
+    pub valid_data: bool,
+}
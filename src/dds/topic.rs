@@ -0,0 +1 @@
+pub mod dds_type;
@@ -0,0 +1,302 @@
+use crate::cdr::cdr_deserializer::CdrDeserialize;
+use crate::cdr::cdr_serializer::*;
+use crate::cdr::encoding::Extensibility;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::instance_handle::InstanceHandle_t;
+use crate::rtps::common::serialized_payload::*;
+
+#[cfg(feature = "derive")]
+pub use lix_dds_derive::DdsType;
+
+/**
+ * @brief Type support of a topic data type: encoding of its samples and of their key.
+ *        It is usually implemented with #[derive(DdsType)], see the lix-dds-derive crate.
+ */
+pub trait DdsType: CdrSerialize + CdrDeserialize {
+    /// Name of the type
+    const TYPE_NAME: &'static str;
+    /// Extensibility kind of the type
+    const EXTENSIBILITY: Extensibility;
+    /// Whether the type has key members
+    const IS_KEY_DEFINED: bool;
+
+    /**
+     * @brief Encode the key members of a sample, in member id order.
+     *
+     * @param ser Serializer where the key is written.
+     */
+    fn serialize_key(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError>;
+
+    /**
+     * @brief Biggest XCDR2 encoding of the key members.
+     *
+     * @return Size, or None if the size of the key is not bounded.
+     */
+    fn key_max_serialized_size() -> Option<usize>;
+
+    /**
+     * @brief Encode the key of a sample as the payload of a key-only DATA, used to notify
+     *        the dispose or unregister of an instance.
+     *
+     * @param representation Data representation of the payload.
+     * @return The payload.
+     */
+    fn serialize_key_payload(
+        &self,
+        representation: RepresentationId_t,
+    ) -> Result<SerializedPayload_t, RtpsError> {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, representation)?;
+        self.serialize_key(&mut ser)?;
+        ser.finish()?;
+        Ok(payload)
+    }
+
+    /**
+     * @brief Compute the instance handle of a sample, as specified in the DDS-XTypes 1.3
+     *        specification chapter 7.6.8. The key members are encoded with big endian XCDR2.
+     *        Keys that always fit in 16 bytes are used as they are, padded with zeros. Other
+     *        keys are hashed with MD5.
+     *
+     * @param force_md5 Hash the key even if it fits in 16 bytes.
+     * @return The instance handle, c_InstanceHandle_Unknown if the type has no key.
+     */
+    fn get_key(&self, force_md5: bool) -> Result<InstanceHandle_t, RtpsError> {
+        let mut handle = InstanceHandle_t::new();
+        if !Self::IS_KEY_DEFINED {
+            return Ok(handle);
+        }
+
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR2_BE)?;
+        self.serialize_key(&mut ser)?;
+        let size = ser.serialized_size();
+        let key = &payload.data()[SerializedPayload_t::representation_header_size..][..size];
+
+        match Self::key_max_serialized_size() {
+            Some(max_size) if max_size <= InstanceHandle_t::SIZE && !force_md5 => {
+                handle.value[..size].copy_from_slice(key)
+            }
+            _ => handle.value = md5::compute(key).0,
+        }
+        Ok(handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdr::cdr_deserializer::CdrDeserializer;
+    use lix_dds_derive::DdsType;
+
+    #[derive(Debug, PartialEq, DdsType)]
+    #[dds(name = "shapes::ShapeType")]
+    struct Shape {
+        #[key]
+        color: String,
+        x: i32,
+        y: i32,
+        size: Option<u32>,
+    }
+
+    #[derive(Debug, PartialEq, DdsType)]
+    #[dds(appendable)]
+    struct Sensor {
+        #[id(3)]
+        value: f64,
+        #[key]
+        id: u32,
+        #[key]
+        #[id(0)]
+        area: u8,
+        readings: Vec<Shape>,
+    }
+
+    #[derive(Debug, PartialEq, DdsType)]
+    #[dds(mutable)]
+    struct Device {
+        #[id(10)]
+        name: String,
+        #[key]
+        #[id(2)]
+        id: u32,
+        label: Option<String>,
+        position: [f32; 3],
+    }
+
+    #[derive(Debug, PartialEq, DdsType)]
+    #[dds(mutable)]
+    struct DeviceV2 {
+        #[id(10)]
+        name: String,
+        #[key]
+        #[id(2)]
+        id: u32,
+        label: Option<String>,
+        position: [f32; 3],
+        #[id(20)]
+        status: u8,
+    }
+
+    #[derive(Debug, PartialEq, DdsType)]
+    struct Point(i16, i16);
+
+    fn round_trip<T: DdsType + PartialEq + std::fmt::Debug>(sample: &T) {
+        for representation in [
+            RepresentationId_t::CDR_LE,
+            RepresentationId_t::CDR_BE,
+            RepresentationId_t::CDR2_LE,
+            RepresentationId_t::CDR2_BE,
+        ] {
+            let mut payload = SerializedPayload_t::default();
+            let mut ser = CdrSerializer::new(&mut payload, representation).unwrap();
+            ser.serialize(sample).unwrap();
+            ser.finish().unwrap();
+            let mut de = CdrDeserializer::new(&payload).unwrap();
+            assert_eq!(*sample, de.deserialize::<T>().unwrap());
+        }
+    }
+
+    fn shape() -> Shape {
+        Shape {
+            color: "abc".to_string(),
+            x: 1,
+            y: -2,
+            size: Some(30),
+        }
+    }
+
+    fn device() -> Device {
+        Device {
+            name: "device".to_string(),
+            id: 7,
+            label: None,
+            position: [1.0, 2.0, 3.0],
+        }
+    }
+
+    #[test]
+    fn round_trip_test() {
+        round_trip(&shape());
+        round_trip(&Sensor {
+            value: 0.5,
+            id: 2,
+            area: 1,
+            readings: vec![shape(), shape()],
+        });
+        round_trip(&device());
+        round_trip(&Point(3, -4));
+    }
+
+    #[test]
+    fn type_information_test() {
+        assert_eq!("shapes::ShapeType", Shape::TYPE_NAME);
+        assert_eq!("Sensor", Sensor::TYPE_NAME);
+        assert_eq!(Extensibility::FINAL, Shape::EXTENSIBILITY);
+        assert_eq!(Extensibility::APPENDABLE, Sensor::EXTENSIBILITY);
+        assert_eq!(Extensibility::MUTABLE, Device::EXTENSIBILITY);
+        assert_eq!(
+            (true, false),
+            (Shape::IS_KEY_DEFINED, Point::IS_KEY_DEFINED)
+        );
+
+        assert_eq!(None, Shape::key_max_serialized_size());
+        assert_eq!(Some(8), Sensor::key_max_serialized_size());
+        assert_eq!(Some(4), Device::key_max_serialized_size());
+        assert_eq!(Some(0), Point::key_max_serialized_size());
+        assert_eq!(
+            Some(8),
+            Point::max_serialized_size(crate::cdr::encoding::CdrVersion::XCDRv2, 3)
+        );
+        assert_eq!(
+            None,
+            Device::max_serialized_size(crate::cdr::encoding::CdrVersion::XCDRv2, 0)
+        );
+    }
+
+    #[test]
+    fn key_test() {
+        // Unbounded keys are hashed
+        let handle = shape().get_key(false).unwrap();
+        assert_eq!(
+            [
+                0x1a, 0x69, 0x74, 0xca, 0xe0, 0xba, 0x21, 0xbf, 0x15, 0xf8, 0x8d, 0x75, 0x9c, 0x31,
+                0xea, 0xf8
+            ],
+            handle.value
+        );
+
+        // Small keys are used as they are, in member id order
+        let sensor = Sensor {
+            value: 0.5,
+            id: 2,
+            area: 1,
+            readings: vec![],
+        };
+        let handle = sensor.get_key(false).unwrap();
+        assert_eq!(
+            [1, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0],
+            handle.value
+        );
+        let handle = sensor.get_key(true).unwrap();
+        assert_eq!(
+            [
+                0xc1, 0xc1, 0x52, 0xad, 0x02, 0xcd, 0x77, 0x95, 0x26, 0x23, 0x2d, 0x23, 0xbf, 0x98,
+                0xe8, 0xc9
+            ],
+            handle.value
+        );
+
+        assert_eq!(
+            [0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            device().get_key(false).unwrap().value
+        );
+        assert!(!Point(1, 2).get_key(false).unwrap().isDefined());
+
+        let payload = device()
+            .serialize_key_payload(RepresentationId_t::CDR_BE)
+            .unwrap();
+        assert_eq!([0, 0, 0, 0, 0, 0, 0, 7], payload.data());
+    }
+
+    #[test]
+    fn type_evolution_test() {
+        let v2 = DeviceV2 {
+            name: "device".to_string(),
+            id: 7,
+            label: Some("label".to_string()),
+            position: [1.0, 2.0, 3.0],
+            status: 1,
+        };
+        for representation in [
+            RepresentationId_t::PL_CDR_LE,
+            RepresentationId_t::PL_CDR2_BE,
+        ] {
+            let mut payload = SerializedPayload_t::default();
+            let mut ser = CdrSerializer::new(&mut payload, representation).unwrap();
+            ser.serialize(&v2).unwrap();
+            ser.finish().unwrap();
+            let mut de = CdrDeserializer::new(&payload).unwrap();
+            let v1: Device = de.deserialize().unwrap();
+            assert_eq!(Some("label".to_string()), v1.label);
+            assert_eq!(v2.position, v1.position);
+
+            // Members added by the new version take their default value
+            let mut payload = SerializedPayload_t::default();
+            let mut ser = CdrSerializer::new(&mut payload, representation).unwrap();
+            ser.serialize(&device()).unwrap();
+            ser.finish().unwrap();
+            let mut de = CdrDeserializer::new(&payload).unwrap();
+            assert_eq!(
+                DeviceV2 {
+                    name: "device".to_string(),
+                    id: 7,
+                    label: None,
+                    position: [1.0, 2.0, 3.0],
+                    status: 0,
+                },
+                de.deserialize::<DeviceV2>().unwrap()
+            );
+        }
+    }
+}
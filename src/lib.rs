@@ -7,10 +7,13 @@
 extern crate bit_set;
 extern crate bitintr;
 extern crate speedy;
+// Lets the code generated by lix-dds-derive refer to this crate as lix_dds
+extern crate self as lix_dds;
 
 #[macro_use]
 pub mod rtps;
 pub mod base;
+pub mod cdr;
 pub mod dds;
 
 #[macro_use]
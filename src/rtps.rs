@@ -1,6 +1,8 @@
 pub mod behavior;
 pub mod common;
 pub mod discovery;
+pub mod flowcontrol;
 pub mod messages;
 pub mod structure;
+pub mod transport;
 pub mod utils;
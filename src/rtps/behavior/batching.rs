@@ -0,0 +1,304 @@
+use crate::rtps::behavior::timed_event::{ResourceEvent, TimedEvent};
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::flowcontrol::flow_controller::*;
+use crate::rtps::structure::rtps_writer::RTPSWriter;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+/**
+ * @brief Limits of the batches of a writer. A batch is sent when one of them is reached.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BatchingAttributes {
+    /// Bytes of payload of a batch, 0 for no limit
+    pub max_bytes: usize,
+    /// Samples of a batch, 0 for no limit
+    pub max_samples: usize,
+    /// Time the first sample of a batch waits for the next ones at most
+    pub max_delay: Duration,
+}
+
+impl Default for BatchingAttributes {
+    fn default() -> Self {
+        BatchingAttributes {
+            max_bytes: 8192,
+            max_samples: 0,
+            max_delay: Duration::from_millis(1),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SampleBatch {
+    sequenceNumbers: Vec<SequenceNumber_t>,
+    bytes: usize,
+}
+
+/**
+ * @brief Batch of the new samples of a writer, so that small samples written at a high rate
+ *        are sent together in as few messages as possible instead of a message each.
+ *        The samples of a batch keep their own DATA, sequence number and source timestamp,
+ *        readers receive them as if they were sent one by one.
+ */
+pub struct SampleBatcher {
+    attributes: BatchingAttributes,
+    batch: Mutex<SampleBatch>,
+    // Flushes the batch max_delay after its first sample
+    event: TimedEvent,
+}
+
+impl SampleBatcher {
+    /**
+     * @brief Create a batcher.
+     *
+     * @param attributes Limits of the batches.
+     * @param service Service running the flush after max_delay.
+     * @param flush Called when max_delay is reached, to take the batch with take and send it.
+     * @return The batcher.
+     */
+    pub fn new<F>(attributes: BatchingAttributes, service: &ResourceEvent, flush: F) -> Self
+    where
+        F: Fn() + Send + 'static,
+    {
+        SampleBatcher {
+            attributes,
+            batch: Mutex::new(SampleBatch::default()),
+            event: TimedEvent::new(service, attributes.max_delay, move |_| {
+                flush();
+                false
+            }),
+        }
+    }
+
+    pub fn attributes(&self) -> &BatchingAttributes {
+        &self.attributes
+    }
+
+    // Samples waiting in the batch
+    pub fn len(&self) -> usize {
+        self.batch.lock().unwrap().sequenceNumbers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * @brief Add a sample to the batch.
+     *
+     * @param sequenceNumber Sequence number of the sample.
+     * @param bytes Size of the payload of the sample.
+     * @return The samples of the batch, to send now, if a limit is reached.
+     */
+    pub fn add(
+        &self,
+        sequenceNumber: SequenceNumber_t,
+        bytes: usize,
+    ) -> Option<Vec<SequenceNumber_t>> {
+        let mut batch = self.batch.lock().unwrap();
+        if batch.sequenceNumbers.is_empty() {
+            self.event.restart_timer();
+        }
+        batch.sequenceNumbers.push(sequenceNumber);
+        batch.bytes += bytes;
+        let full = (self.attributes.max_samples > 0
+            && batch.sequenceNumbers.len() >= self.attributes.max_samples)
+            || (self.attributes.max_bytes > 0 && batch.bytes >= self.attributes.max_bytes);
+        if full {
+            self.event.cancel_timer();
+            Some(std::mem::take(&mut *batch).sequenceNumbers)
+        } else {
+            None
+        }
+    }
+
+    // Take the samples of the batch, in order, to send them now
+    pub fn take(&self) -> Vec<SequenceNumber_t> {
+        let mut batch = self.batch.lock().unwrap();
+        self.event.cancel_timer();
+        std::mem::take(&mut *batch).sequenceNumbers
+    }
+}
+
+/**
+ * @brief Writer whose new changes may be sent in batches, through a flow controller or right
+ *        away. The stateless and stateful writers share the batching and the flow control
+ *        through this trait.
+ *        With a flow controller, the new changes are sent when the controller gives them
+ *        budget, the ones too large for a period as DATA_FRAG over several periods.
+ *        With batching, the new changes wait until their batch is full or max_delay has
+ *        elapsed, and are then sent together.
+ */
+pub trait BatchingWriter: RTPSWriter + FlowControlledWriter + Sized + 'static {
+    // Batcher of the writer, None without batching
+    fn batcher(&self) -> &Mutex<Option<Arc<SampleBatcher>>>;
+
+    // Flow controller of the writer, None without flow control
+    fn flow_controller(&self) -> &Mutex<Option<Arc<FlowController>>>;
+
+    // Send the unsent changes right away, without flow control
+    fn send_unsent(&self);
+
+    /**
+     * @brief Send the next new changes through a flow controller, instead of right away.
+     *
+     * @param controller Flow controller of the writer.
+     * @param priority Priority of the writer for the HIGH_PRIORITY scheduler.
+     */
+    fn set_flow_controller(self: &Arc<Self>, controller: &Arc<FlowController>, priority: i32) {
+        let weak: Weak<Self> = Arc::downgrade(self);
+        controller.register_writer(*self.getGuid(), weak, priority);
+        let previous = self
+            .flow_controller()
+            .lock()
+            .unwrap()
+            .replace(controller.clone());
+        if let Some(previous) = previous {
+            if !Arc::ptr_eq(&previous, controller) {
+                previous.unregister_writer(self.getGuid());
+            }
+        }
+    }
+
+    // Largest payload of a DATA, and size of the fragments of the larger changes
+    fn max_payload(&self) -> usize {
+        match &*self.flow_controller().lock().unwrap() {
+            Some(controller) => controller.max_payload(),
+            None => MAX_PAYLOAD_SIZE,
+        }
+    }
+
+    /**
+     * @brief Send the next new changes in batches, instead of one by one. The changes of the
+     *        previous batch, if any, are sent now.
+     *
+     * @param attributes Limits of the batches.
+     * @param service Service sending the batches after max_delay.
+     */
+    fn set_batching(self: &Arc<Self>, attributes: BatchingAttributes, service: &ResourceEvent) {
+        let weak = Arc::downgrade(self);
+        let batcher = SampleBatcher::new(attributes, service, move || {
+            if let Some(writer) = weak.upgrade() {
+                writer.flush_batch();
+            }
+        });
+        let previous = self.batcher().lock().unwrap().replace(Arc::new(batcher));
+        if let Some(previous) = previous {
+            self.send_new_changes(&previous.take());
+        }
+    }
+
+    /**
+     * @brief Send the changes of the current batch now.
+     *
+     * @return The number of changes of the batch.
+     */
+    fn flush_batch(&self) -> usize {
+        let batcher = self.batcher().lock().unwrap().clone();
+        match batcher {
+            Some(batcher) => {
+                let sequenceNumbers = batcher.take();
+                self.send_new_changes(&sequenceNumbers);
+                sequenceNumbers.len()
+            }
+            None => 0,
+        }
+    }
+
+    // Add a new change to the batch, or send it if there is no batching
+    fn batch_new_change(&self, sequenceNumber: SequenceNumber_t) {
+        let batcher = self.batcher().lock().unwrap().clone();
+        match batcher {
+            Some(batcher) => {
+                let bytes = self
+                    .history()
+                    .with_change(sequenceNumber, |change| change.serializedPayload.length())
+                    .unwrap_or(0);
+                if let Some(batch) = batcher.add(sequenceNumber, bytes) {
+                    self.send_new_changes(&batch);
+                }
+            }
+            None => self.send_new_changes(&[sequenceNumber]),
+        }
+    }
+
+    /**
+     * @brief Send new changes together through the flow controller, or right away. The writer
+     *        must not be locked.
+     *
+     * @param sequenceNumbers Sequence numbers of the changes, in order.
+     */
+    fn send_new_changes(&self, sequenceNumbers: &[SequenceNumber_t]) {
+        if sequenceNumbers.is_empty() {
+            return;
+        }
+        let controller = self.flow_controller().lock().unwrap().clone();
+        match controller {
+            Some(controller) => {
+                controller.add_new_samples(self.getGuid(), sequenceNumbers);
+            }
+            None => self.send_unsent(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtps::behavior::timed_event::VirtualClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn sns(values: &[u64]) -> Vec<SequenceNumber_t> {
+        values.iter().map(|v| SequenceNumber_t::from(*v)).collect()
+    }
+
+    #[test]
+    fn sample_batcher_test() {
+        let clock = Arc::new(VirtualClock::new());
+        let service = ResourceEvent::new(clock.clone());
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let counter = flushes.clone();
+        let batcher = SampleBatcher::new(
+            BatchingAttributes {
+                max_bytes: 100,
+                max_samples: 3,
+                max_delay: Duration::from_millis(5),
+            },
+            &service,
+            move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+
+        // Sample count limit
+        assert_eq!(None, batcher.add(SequenceNumber_t::from(1), 10));
+        assert_eq!(None, batcher.add(SequenceNumber_t::from(2), 10));
+        assert_eq!(
+            Some(sns(&[1, 2, 3])),
+            batcher.add(SequenceNumber_t::from(3), 10)
+        );
+        assert!(batcher.is_empty());
+        assert_eq!(None, service.next_deadline());
+
+        // Byte limit
+        assert_eq!(None, batcher.add(SequenceNumber_t::from(4), 60));
+        assert_eq!(
+            Some(sns(&[4, 5])),
+            batcher.add(SequenceNumber_t::from(5), 40)
+        );
+
+        // Delay limit, from the first sample of the batch
+        assert_eq!(None, batcher.add(SequenceNumber_t::from(6), 10));
+        clock.advance(Duration::from_millis(3));
+        assert_eq!(None, batcher.add(SequenceNumber_t::from(7), 10));
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(0, service.run_due());
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(1, service.run_due());
+        assert_eq!(1, flushes.load(Ordering::SeqCst));
+        assert_eq!(2, batcher.len());
+        assert_eq!(sns(&[6, 7]), batcher.take());
+        assert_eq!(None, service.next_deadline());
+    }
+}
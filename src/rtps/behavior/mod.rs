@@ -1 +1,6 @@
-
+pub mod batching;
+pub mod stateful_reader;
+pub mod stateful_writer;
+pub mod stateless_reader;
+pub mod stateless_writer;
+pub mod timed_event;
@@ -0,0 +1,1248 @@
+use crate::rtps::behavior::timed_event::{Clock, ResourceEvent, SystemClock, TimedEvent};
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::fragment_number_set::FragmentNumberSet_t;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::locator::Locator_t;
+use crate::rtps::common::locator_list::LocatorList;
+use crate::rtps::common::payload_pool::PayloadPool;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::common::sequence_number_set::SequenceNumberSet_t;
+use crate::rtps::common::time::{Duration_t, Time_t};
+use crate::rtps::messages::message_group::{RTPSMessageGroup, DEFAULT_ENDIANNESS};
+use crate::rtps::messages::message_header::Header_t;
+use crate::rtps::messages::message_receiver::{MessageReceiverListener, ReceiverState};
+use crate::rtps::messages::submessages::acknack::AckNackSubmessage;
+use crate::rtps::messages::submessages::data::DataSubmessage;
+use crate::rtps::messages::submessages::data_frag::DataFragSubmessage;
+use crate::rtps::messages::submessages::gap::GapSubmessage;
+use crate::rtps::messages::submessages::heartbeat::HeartbeatSubmessage;
+use crate::rtps::messages::submessages::nack_frag::NackFragSubmessage;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use crate::rtps::structure::endpoint::*;
+use crate::rtps::structure::intraprocess::{find_local_writer, intraprocess_state};
+use crate::rtps::structure::reader_history::ReaderHistory;
+use crate::rtps::structure::rtps_reader::{RTPSReader, ReaderListener};
+use crate::rtps::transport::udp_transport::UdpSender;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::Instant;
+
+// Changes an ACKNACK can request after the last change received in order
+const ACKNACK_WINDOW: u32 = 256;
+
+/**
+ * @brief Status of a change from a matched writer, as specified in the DDSI-RTPS 2.5
+ *        specification chapter 8.4.10.4. The lost changes are reported to the listener
+ *        and no longer tracked.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeFromWriterStatusKind {
+    // Not known to be available in the writer
+    UNKNOWN,
+    // Available in the writer and not received yet, or only some of its fragments
+    MISSING,
+    // Received, or not relevant to the reader
+    RECEIVED,
+}
+
+#[derive(Debug)]
+struct ChangeFromWriter_t {
+    status: ChangeFromWriterStatusKind,
+    // Change waiting for the previous ones when RECEIVED, being reassembled when MISSING,
+    // None for the irrelevant changes
+    change: Option<CacheChange_t>,
+}
+
+/**
+ * @brief Timing of a stateful reader, as specified in the DDSI-RTPS 2.5 specification
+ *        chapter 8.4.10.1.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReaderTimes {
+    /// Delay before answering a HEARTBEAT, so that close HEARTBEAT are answered together
+    pub heartbeatResponseDelay: Duration_t,
+}
+
+impl Default for ReaderTimes {
+    fn default() -> Self {
+        ReaderTimes {
+            heartbeatResponseDelay: Time_t::new(0, 5_000_000),
+        }
+    }
+}
+
+// Changes a writer proxy releases to the history, in order, and the number of changes lost
+// before them
+#[derive(Default)]
+struct Delivery {
+    changes: Vec<CacheChange_t>,
+    lost: u64,
+}
+
+impl Delivery {
+    fn append(&mut self, mut other: Delivery) {
+        self.changes.append(&mut other.changes);
+        self.lost += other.lost;
+    }
+}
+
+/**
+ * @brief State of a matched writer kept by a stateful reader, as specified in the
+ *        DDSI-RTPS 2.5 specification chapter 8.4.10.4.
+ *        Only the changes after changes_low_mark are kept: every change up to it was
+ *        delivered, is not relevant to the reader or was lost.
+ *        Changes from reliable writers are delivered strictly in order, the ones received
+ *        early wait for the missing ones before them. Best-effort writers have their changes
+ *        delivered as they arrive, the skipped ones being lost.
+ */
+#[derive(Debug)]
+pub struct WriterProxy {
+    attributes: RemoteEndpointAttributes,
+    reliable: bool,
+    changes_low_mark: SequenceNumber_t,
+    changes_from_writer: BTreeMap<SequenceNumber_t, ChangeFromWriter_t>,
+    // Last change known to be available in the writer
+    max_available: SequenceNumber_t,
+    // Count of the last HEARTBEAT processed
+    last_heartbeat_count: Option<u32>,
+    acknack_count: u32,
+    nack_frag_count: u32,
+    // Time the ACKNACK answering the HEARTBEAT is sent at
+    acknack_at: Option<Instant>,
+    // Address the last submessage of the writer was received from
+    source_locator: Option<Locator_t>,
+    // Writer in the same process, the ACKNACK are handed over to it directly
+    local_writer: Option<Weak<dyn MessageReceiverListener>>,
+}
+
+impl WriterProxy {
+    fn new(attributes: RemoteEndpointAttributes, reliable: bool) -> Self {
+        WriterProxy {
+            attributes,
+            reliable,
+            changes_low_mark: SequenceNumber_t::new(0, 0),
+            changes_from_writer: BTreeMap::new(),
+            max_available: SequenceNumber_t::new(0, 0),
+            last_heartbeat_count: None,
+            acknack_count: 0,
+            nack_frag_count: 0,
+            acknack_at: None,
+            source_locator: None,
+            local_writer: None,
+        }
+    }
+
+    pub fn remoteWriterGuid(&self) -> &GUID_t {
+        &self.attributes.guid
+    }
+
+    pub fn attributes(&self) -> &RemoteEndpointAttributes {
+        &self.attributes
+    }
+
+    pub fn is_reliable(&self) -> bool {
+        self.reliable
+    }
+
+    // Last change such that every change up to it was delivered, is irrelevant or was lost
+    pub fn available_changes_max(&self) -> SequenceNumber_t {
+        self.changes_low_mark
+    }
+
+    /**
+     * @brief Status of a change from this writer.
+     *
+     * @param sequenceNumber Sequence number of the change.
+     * @return The status, None if the change is no longer tracked: delivered, irrelevant or lost.
+     */
+    pub fn change_from_writer_status(
+        &self,
+        sequenceNumber: SequenceNumber_t,
+    ) -> Option<ChangeFromWriterStatusKind> {
+        if sequenceNumber <= self.changes_low_mark {
+            return None;
+        }
+        match self.changes_from_writer.get(&sequenceNumber) {
+            Some(change) => Some(change.status),
+            None if sequenceNumber <= self.max_available => {
+                Some(ChangeFromWriterStatusKind::MISSING)
+            }
+            None => Some(ChangeFromWriterStatusKind::UNKNOWN),
+        }
+    }
+
+    // Missing changes an ACKNACK can request, in order
+    pub fn missing_changes(&self) -> Vec<SequenceNumber_t> {
+        (1..=ACKNACK_WINDOW)
+            .map(|offset| self.changes_low_mark + offset)
+            .take_while(|sequenceNumber| *sequenceNumber <= self.max_available)
+            .filter(|sequenceNumber| {
+                self.change_from_writer_status(*sequenceNumber)
+                    == Some(ChangeFromWriterStatusKind::MISSING)
+            })
+            .collect()
+    }
+
+    // Unicast locators of the writer, its multicast ones if it has none, else the address
+    // its messages come from
+    fn locators(&self) -> LocatorList {
+        if !self.attributes.unicastLocatorList.is_empty() {
+            self.attributes.unicastLocatorList.clone()
+        } else if !self.attributes.multicastLocatorList.is_empty() {
+            self.attributes.multicastLocatorList.clone()
+        } else {
+            let mut locators = LocatorList::new();
+            if let Some(locator) = self.source_locator {
+                locators.push_back(locator);
+            }
+            locators
+        }
+    }
+
+    fn note_available(&mut self, sequenceNumber: SequenceNumber_t) {
+        if sequenceNumber > self.max_available {
+            self.max_available = sequenceNumber;
+        }
+    }
+
+    // Stop tracking the changes up to sequenceNumber, releasing the received ones
+    fn move_low_mark(&mut self, sequenceNumber: SequenceNumber_t, count_lost: bool) -> Delivery {
+        let mut delivery = Delivery::default();
+        if sequenceNumber <= self.changes_low_mark {
+            return delivery;
+        }
+        let later = self.changes_from_writer.split_off(&(sequenceNumber + 1));
+        let passed = std::mem::replace(&mut self.changes_from_writer, later);
+        let mut received = 0;
+        for (_, change) in passed {
+            if change.status == ChangeFromWriterStatusKind::RECEIVED {
+                received += 1;
+                delivery.changes.extend(change.change);
+            }
+        }
+        if count_lost {
+            delivery.lost = (sequenceNumber - self.changes_low_mark).to64long() - received;
+        }
+        self.changes_low_mark = sequenceNumber;
+        self.note_available(sequenceNumber);
+        delivery
+    }
+
+    // Release the received changes following the low mark
+    fn release_received(&mut self) -> Delivery {
+        let mut delivery = Delivery::default();
+        while let Some(entry) = self.changes_from_writer.first_entry() {
+            if *entry.key() != self.changes_low_mark + 1
+                || entry.get().status != ChangeFromWriterStatusKind::RECEIVED
+            {
+                break;
+            }
+            self.changes_low_mark = *entry.key();
+            delivery.changes.extend(entry.remove().change);
+        }
+        delivery
+    }
+
+    fn received_change_set(&mut self, change: CacheChange_t) -> Delivery {
+        let sequenceNumber = change.sequenceNumber;
+        if sequenceNumber <= self.changes_low_mark {
+            return Delivery::default();
+        }
+        self.note_available(sequenceNumber);
+        if !self.reliable {
+            let mut delivery = self.move_low_mark(sequenceNumber - 1, true);
+            self.changes_from_writer.remove(&sequenceNumber);
+            self.changes_low_mark = sequenceNumber;
+            delivery.changes.push(change);
+            return delivery;
+        }
+        if self.change_from_writer_status(sequenceNumber)
+            == Some(ChangeFromWriterStatusKind::RECEIVED)
+        {
+            return Delivery::default();
+        }
+        self.changes_from_writer.insert(
+            sequenceNumber,
+            ChangeFromWriter_t {
+                status: ChangeFromWriterStatusKind::RECEIVED,
+                change: Some(change),
+            },
+        );
+        self.release_received()
+    }
+
+    // Add fragments to the change they belong to, returning it once complete. Samples larger
+    // than maxSampleSize are dropped, the others are reassembled in a buffer of payloadPool
+    fn fragments_received(
+        &mut self,
+        state: &ReceiverState,
+        data_frag: &DataFragSubmessage,
+        maxSampleSize: usize,
+        payloadPool: Option<&PayloadPool>,
+    ) -> Option<CacheChange_t> {
+        let sequenceNumber = data_frag.writerSN;
+        if sequenceNumber <= self.changes_low_mark || data_frag.sampleSize as usize > maxSampleSize
+        {
+            return None;
+        }
+        self.note_available(sequenceNumber);
+        let entry = self
+            .changes_from_writer
+            .entry(sequenceNumber)
+            .or_insert(ChangeFromWriter_t {
+                status: ChangeFromWriterStatusKind::MISSING,
+                change: None,
+            });
+        if entry.status == ChangeFromWriterStatusKind::RECEIVED {
+            return None;
+        }
+        entry.status = ChangeFromWriterStatusKind::MISSING;
+        let reassembled = matches!(&entry.change, Some(partial)
+            if partial.getFragmentSize() == data_frag.fragmentSize
+                && partial.serializedPayload.length() == data_frag.sampleSize as usize);
+        if !reassembled {
+            entry.change = Some(
+                data_frag
+                    .to_cache_change(
+                        state.sourceGuidPrefix,
+                        state.timestamp,
+                        maxSampleSize,
+                        payloadPool,
+                    )
+                    .ok()?,
+            );
+        }
+        let partial = entry.change.as_mut()?;
+        if !partial.add_fragments(
+            &data_frag.fragments,
+            data_frag.fragmentStartingNum,
+            data_frag.fragmentsInSubmessage as u32,
+        ) || !partial.is_fully_assembled()
+        {
+            return None;
+        }
+        let mut change = entry.change.take()?;
+        change.serializedPayload.begin_read();
+        Some(change)
+    }
+
+    fn irrelevant_change_set(&mut self, sequenceNumber: SequenceNumber_t) {
+        if sequenceNumber <= self.changes_low_mark {
+            return;
+        }
+        self.note_available(sequenceNumber);
+        let change = self
+            .changes_from_writer
+            .entry(sequenceNumber)
+            .or_insert(ChangeFromWriter_t {
+                status: ChangeFromWriterStatusKind::MISSING,
+                change: None,
+            });
+        if change.status != ChangeFromWriterStatusKind::RECEIVED {
+            change.status = ChangeFromWriterStatusKind::RECEIVED;
+            change.change = None;
+        }
+    }
+
+    fn gap_received(&mut self, gap: &GapSubmessage) -> Delivery {
+        if !self.reliable {
+            return Delivery::default();
+        }
+        let end = gap.gapList.base();
+        let mut delivery = Delivery::default();
+        if gap.gapStart <= self.changes_low_mark + 1 {
+            if end > SequenceNumber_t::new(0, 0) {
+                delivery = self.move_low_mark(end - 1, false);
+            }
+        } else {
+            // Changes past the ACKNACK window are requested again later, and get a new GAP
+            let limit = self.changes_low_mark + ACKNACK_WINDOW;
+            let mut sequenceNumber = gap.gapStart;
+            while sequenceNumber < end && sequenceNumber <= limit {
+                self.irrelevant_change_set(sequenceNumber);
+                sequenceNumber = sequenceNumber + 1;
+            }
+        }
+        for sequenceNumber in gap.gapList.iter() {
+            self.irrelevant_change_set(sequenceNumber);
+        }
+        delivery.append(self.release_received());
+        delivery
+    }
+
+    /**
+     * @brief Process a HEARTBEAT. The changes before firstSN that were never received are
+     *        lost, except on the first HEARTBEAT: they were removed before the reader was
+     *        matched. An ACKNACK is scheduled unless the HEARTBEAT is final and no change is
+     *        missing.
+     */
+    fn heartbeat_received(
+        &mut self,
+        heartbeat: &HeartbeatSubmessage,
+        times: &ReaderTimes,
+        now: Instant,
+    ) -> Delivery {
+        if !self.reliable {
+            return Delivery::default();
+        }
+        if let Some(count) = self.last_heartbeat_count {
+            if heartbeat.count <= count {
+                return Delivery::default();
+            }
+        }
+        let first = self.last_heartbeat_count.is_none();
+        self.last_heartbeat_count = Some(heartbeat.count);
+        self.note_available(heartbeat.lastSN);
+        let mut delivery = Delivery::default();
+        if heartbeat.firstSN > SequenceNumber_t::new(0, 0) {
+            delivery = self.move_low_mark(heartbeat.firstSN - 1, !first);
+        }
+        delivery.append(self.release_received());
+
+        if (!heartbeat.finalFlag || !self.missing_changes().is_empty()) && self.acknack_at.is_none()
+        {
+            if let Some(delay) = times.heartbeatResponseDelay.to_duration() {
+                self.acknack_at = now.checked_add(delay);
+            }
+        }
+        delivery
+    }
+}
+
+// Timed events of a reader, see set_timed_events
+struct ReaderEvents {
+    clock: Arc<dyn Clock>,
+    // Sends the ACKNACK once heartbeatResponseDelay has elapsed
+    acknack: TimedEvent,
+}
+
+/**
+ * @brief Stateful reader, as specified in the DDSI-RTPS 2.5 specification chapter 8.4.12.
+ *        It keeps a WriterProxy per matched writer and only accepts the changes of the
+ *        matched writers. Reliable readers answer the HEARTBEAT of their writers with an
+ *        ACKNACK requesting the missing changes, and a NACK_FRAG for every change only some
+ *        fragments of were received.
+ *        The ACKNACK are sent once heartbeatResponseDelay has elapsed, by a timed event with
+ *        set_timed_events, otherwise by calling send_acknacks.
+ *        Writers in the same process hand their changes over directly, and are handed the
+ *        ACKNACK without serializing them.
+ */
+pub struct StatefulReader {
+    guid: GUID_t,
+    attributes: EndpointAttributes,
+    history: Mutex<ReaderHistory>,
+    listener: RwLock<Option<Arc<dyn ReaderListener>>>,
+    sender: Arc<UdpSender>,
+    header: Header_t,
+    times: ReaderTimes,
+    // Held while changes are delivered, so that the changes of a writer reach the history in
+    // order without the proxies locked
+    delivery: Mutex<()>,
+    matched_writers: Mutex<BTreeMap<GUID_t, WriterProxy>>,
+    // None until set_timed_events
+    events: Mutex<Option<ReaderEvents>>,
+}
+
+impl StatefulReader {
+    /**
+     * @brief Create a stateful reader.
+     *
+     * @param guid GUID of the reader.
+     * @param attributes Attributes of the reader.
+     * @param history History of the reader.
+     * @param sender Sockets of the participant.
+     * @param header Header of the messages of the participant.
+     * @param times Timing of the ACKNACK.
+     * @return The reader.
+     */
+    pub fn new(
+        guid: GUID_t,
+        attributes: EndpointAttributes,
+        history: ReaderHistory,
+        sender: Arc<UdpSender>,
+        header: Header_t,
+        times: ReaderTimes,
+    ) -> Result<Self, RtpsError> {
+        Ok(StatefulReader {
+            guid,
+            attributes,
+            history: Mutex::new(history),
+            listener: RwLock::new(None),
+            sender,
+            header,
+            times,
+            delivery: Mutex::new(()),
+            matched_writers: Mutex::new(BTreeMap::new()),
+            events: Mutex::new(None),
+        })
+    }
+
+    pub fn times(&self) -> &ReaderTimes {
+        &self.times
+    }
+
+    /**
+     * @brief Send the ACKNACK with a timed event of a service, whose clock also gives the time
+     *        the HEARTBEAT are received at.
+     *
+     * @param service Service running the event.
+     */
+    pub fn set_timed_events(self: &Arc<Self>, service: &ResourceEvent) {
+        let weak = Arc::downgrade(self);
+        let delay = self.times.heartbeatResponseDelay.to_duration();
+        let acknack = TimedEvent::new(service, delay.unwrap_or_default(), move |now| {
+            if let Some(reader) = weak.upgrade() {
+                reader.send_acknacks(now);
+            }
+            false
+        });
+        *self.events.lock().unwrap() = Some(ReaderEvents {
+            clock: service.clock().clone(),
+            acknack,
+        });
+        self.schedule_acknack(&self.matched_writers.lock().unwrap());
+    }
+
+    // Current time of the clock of the timed events, of the system without them
+    fn now(&self) -> Instant {
+        match &*self.events.lock().unwrap() {
+            Some(events) => events.clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    // Run the ACKNACK timer at the earliest ACKNACK time of the writers
+    fn schedule_acknack(&self, matched_writers: &BTreeMap<GUID_t, WriterProxy>) {
+        if let Some(events) = &*self.events.lock().unwrap() {
+            match matched_writers
+                .values()
+                .filter_map(|proxy| proxy.acknack_at)
+                .min()
+            {
+                Some(deadline) => events.acknack.restart_timer_at(deadline),
+                None => events.acknack.cancel_timer(),
+            }
+        }
+    }
+
+    /**
+     * @brief Look at the state of a matched writer.
+     *
+     * @param writerGuid GUID of the writer.
+     * @param f Function called with the proxy of the writer.
+     * @return What f returns, None if the writer is not matched.
+     */
+    pub fn with_matched_writer<R>(
+        &self,
+        writerGuid: &GUID_t,
+        f: impl FnOnce(&WriterProxy) -> R,
+    ) -> Option<R> {
+        self.matched_writers.lock().unwrap().get(writerGuid).map(f)
+    }
+
+    // Update the proxy of the writer of a submessage, then deliver the changes it releases
+    fn update_proxy(
+        &self,
+        state: &ReceiverState,
+        writerId: EntityId_t,
+        f: impl FnOnce(&mut WriterProxy) -> Delivery,
+    ) {
+        let writerGUID = GUID_t {
+            guidPrefix: state.sourceGuidPrefix,
+            entityId: writerId,
+        };
+        let _delivery = self.delivery.lock().unwrap();
+        let delivery = {
+            let mut matched_writers = self.matched_writers.lock().unwrap();
+            let proxy = match matched_writers.get_mut(&writerGUID) {
+                Some(proxy) => proxy,
+                None => return,
+            };
+            proxy.source_locator = Some(state.sourceLocator);
+            f(proxy)
+        };
+
+        if delivery.lost > 0 {
+            if let Some(listener) = self.getListener() {
+                let lost = std::cmp::min(delivery.lost, i32::MAX as u64) as i32;
+                listener.on_sample_lost(&self.guid, &writerGUID, lost);
+            }
+        }
+        for change in delivery.changes {
+            // The writer considers it received: a change the history rejects is dropped
+            let _ = self.deliver_change(change);
+        }
+    }
+
+    /**
+     * @brief Process a HEARTBEAT received at a given time.
+     *
+     * @param state State of the receiver, giving the participant of the writer.
+     * @param heartbeat Received HEARTBEAT.
+     * @param now Time the HEARTBEAT is received at.
+     */
+    pub fn process_heartbeat(
+        &self,
+        state: &ReceiverState,
+        heartbeat: &HeartbeatSubmessage,
+        now: Instant,
+    ) {
+        let times = self.times;
+        self.update_proxy(state, heartbeat.writerId, |proxy| {
+            proxy.heartbeat_received(heartbeat, &times, now)
+        });
+        self.schedule_acknack(&self.matched_writers.lock().unwrap());
+    }
+
+    /**
+     * @brief Send an ACKNACK to the writers whose heartbeatResponseDelay has elapsed. The
+     *        changes only some fragments of were received are requested by a NACK_FRAG
+     *        instead.
+     *
+     * @param now Current time.
+     * @return The number of ACKNACK sent.
+     */
+    pub fn send_acknacks(&self, now: Instant) -> usize {
+        let mut sent = 0;
+        // Handed over once the proxies are unlocked, the writer may answer right away
+        let mut local = Vec::new();
+        let mut matched_writers = self.matched_writers.lock().unwrap();
+        for proxy in matched_writers.values_mut() {
+            match proxy.acknack_at {
+                Some(deadline) if deadline <= now => proxy.acknack_at = None,
+                _ => continue,
+            }
+            let writerId = proxy.attributes.guid.entityId;
+            let mut set = SequenceNumberSet_t::new(proxy.changes_low_mark + 1);
+            let mut nack_frags = Vec::new();
+            for sequenceNumber in proxy.missing_changes() {
+                let partial = proxy
+                    .changes_from_writer
+                    .get(&sequenceNumber)
+                    .and_then(|change| change.change.as_ref());
+                match partial {
+                    Some(partial) => {
+                        let missing = partial.get_missing_fragments();
+                        let mut fragments = FragmentNumberSet_t::new(missing[0]);
+                        for fragment in missing {
+                            fragments.add(fragment);
+                        }
+                        nack_frags.push((sequenceNumber, fragments));
+                    }
+                    None => {
+                        set.add(sequenceNumber);
+                    }
+                }
+            }
+
+            proxy.acknack_count += 1;
+            let acknack = AckNackSubmessage::new(
+                DEFAULT_ENDIANNESS,
+                self.guid.entityId,
+                writerId,
+                set,
+                proxy.acknack_count,
+                set.is_empty(),
+            );
+            let nack_frags: Vec<NackFragSubmessage> = nack_frags
+                .into_iter()
+                .map(|(sequenceNumber, fragments)| {
+                    proxy.nack_frag_count += 1;
+                    NackFragSubmessage::new(
+                        DEFAULT_ENDIANNESS,
+                        self.guid.entityId,
+                        writerId,
+                        sequenceNumber,
+                        fragments,
+                        proxy.nack_frag_count,
+                    )
+                })
+                .collect();
+            if let Some(writer) = proxy.local_writer.as_ref().and_then(Weak::upgrade) {
+                local.push((
+                    writer,
+                    proxy.attributes.guid.guidPrefix,
+                    acknack,
+                    nack_frags,
+                ));
+                sent += 1;
+                continue;
+            }
+
+            let mut group = RTPSMessageGroup::new(&self.sender, self.header, proxy.locators());
+            group.set_destination(Some(proxy.attributes.guid.guidPrefix));
+            let mut submessage = Vec::new();
+            acknack.write(&mut submessage);
+            if group.add_submessage(&submessage).is_ok() {
+                sent += 1;
+            }
+            for nack_frag in nack_frags {
+                let mut submessage = Vec::new();
+                nack_frag.write(&mut submessage);
+                let _ = group.add_submessage(&submessage);
+            }
+        }
+        self.schedule_acknack(&matched_writers);
+        drop(matched_writers);
+
+        for (writer, destination, acknack, nack_frags) in local {
+            let state = intraprocess_state(self.guid.guidPrefix, destination, None);
+            writer.on_acknack(&state, &acknack);
+            for nack_frag in nack_frags {
+                writer.on_nack_frag(&state, &nack_frag);
+            }
+        }
+        sent
+    }
+
+    // Time send_acknacks has an ACKNACK to send at, None if no HEARTBEAT is to be answered
+    pub fn next_acknack(&self) -> Option<Instant> {
+        self.matched_writers
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|proxy| proxy.acknack_at)
+            .min()
+    }
+}
+
+impl Endpoint for StatefulReader {
+    fn getGuid(&self) -> &GUID_t {
+        &self.guid
+    }
+
+    fn getAttributes(&self) -> &EndpointAttributes {
+        &self.attributes
+    }
+}
+
+impl MessageReceiverListener for StatefulReader {
+    fn on_data(&self, state: &ReceiverState, data: &DataSubmessage) {
+        if let Ok(change) = data.to_cache_change(
+            state.sourceGuidPrefix,
+            state.timestamp,
+            self.attributes.payloadPool.as_deref(),
+        ) {
+            self.update_proxy(state, data.writerId, |proxy| {
+                proxy.received_change_set(change)
+            });
+        }
+    }
+
+    fn on_data_frag(&self, state: &ReceiverState, data_frag: &DataFragSubmessage) {
+        let maxSampleSize = self.attributes.maxSampleSize;
+        let payloadPool = self.attributes.payloadPool.as_deref();
+        self.update_proxy(state, data_frag.writerId, |proxy| {
+            match proxy.fragments_received(state, data_frag, maxSampleSize, payloadPool) {
+                Some(change) => proxy.received_change_set(change),
+                None => Delivery::default(),
+            }
+        });
+    }
+
+    fn on_heartbeat(&self, state: &ReceiverState, heartbeat: &HeartbeatSubmessage) {
+        self.process_heartbeat(state, heartbeat, self.now());
+    }
+
+    fn on_gap(&self, state: &ReceiverState, gap: &GapSubmessage) {
+        self.update_proxy(state, gap.writerId, |proxy| proxy.gap_received(gap));
+    }
+
+    fn on_intraprocess_change(&self, state: &ReceiverState, change: &CacheChange_t) {
+        let mut shared = CacheChange_t::new();
+        shared.share(change);
+        self.update_proxy(state, change.writerGUID.entityId, |proxy| {
+            proxy.received_change_set(shared)
+        });
+    }
+}
+
+impl RTPSReader for StatefulReader {
+    fn history(&self) -> &Mutex<ReaderHistory> {
+        &self.history
+    }
+
+    fn getListener(&self) -> Option<Arc<dyn ReaderListener>> {
+        self.listener.read().unwrap().clone()
+    }
+
+    fn setListener(&self, listener: Option<Arc<dyn ReaderListener>>) {
+        *self.listener.write().unwrap() = listener;
+    }
+
+    /**
+     * @brief Add a proxy for a writer. Reliable readers only match reliable writers, the
+     *        changes of reliable writers are best effort for best-effort readers.
+     *
+     * @param writer Matched writer.
+     * @return False if the writer was already matched, or is best effort while the reader is
+     *         reliable.
+     */
+    fn matched_writer_add(&self, writer: &RemoteEndpointAttributes) -> bool {
+        let reliable = self.attributes.reliabilityKind == ReliabilityKind_t::RELIABLE;
+        if reliable && writer.reliabilityKind != ReliabilityKind_t::RELIABLE {
+            return false;
+        }
+        {
+            let mut matched_writers = self.matched_writers.lock().unwrap();
+            if matched_writers.contains_key(&writer.guid) {
+                return false;
+            }
+            let mut proxy = WriterProxy::new(writer.clone(), reliable);
+            proxy.local_writer = find_local_writer(&self.guid, &writer.guid);
+            matched_writers.insert(writer.guid, proxy);
+        }
+        if let Some(listener) = self.getListener() {
+            listener.on_reader_matched(&self.guid, &writer.guid, true);
+        }
+        true
+    }
+
+    // The instances of the writer lose it, and the changes waiting for delivery are dropped
+    fn matched_writer_remove(&self, writerGuid: &GUID_t) -> bool {
+        let _delivery = self.delivery.lock().unwrap();
+        if self
+            .matched_writers
+            .lock()
+            .unwrap()
+            .remove(writerGuid)
+            .is_none()
+        {
+            return false;
+        }
+        self.history.lock().unwrap().writer_removed(writerGuid);
+        if let Some(listener) = self.getListener() {
+            listener.on_reader_matched(&self.guid, writerGuid, false);
+        }
+        true
+    }
+
+    fn matched_writer_is_matched(&self, writerGuid: &GUID_t) -> bool {
+        self.matched_writers
+            .lock()
+            .unwrap()
+            .contains_key(writerGuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdr::encoding::Endianness;
+    use crate::dds::core::policy::*;
+    use crate::rtps::behavior::timed_event::VirtualClock;
+    use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+    use crate::rtps::common::protocol_version::c_ProtocolVersion;
+    use crate::rtps::common::serialized_payload::*;
+    use crate::rtps::common::vendor_id::c_VendorId_Unknown;
+    use crate::rtps::messages::message_receiver::*;
+    use crate::rtps::transport::udp_transport::*;
+    use crate::rtps::utils::ip_locator::setIPv4;
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Received {
+        AckNack(u64, Vec<u64>, bool),
+        NackFrag(u64, Vec<u32>),
+    }
+
+    #[derive(Default)]
+    struct Writer {
+        received: Mutex<Vec<Received>>,
+    }
+
+    impl MessageReceiverListener for Writer {
+        fn on_acknack(&self, _state: &ReceiverState, acknack: &AckNackSubmessage) {
+            self.received.lock().unwrap().push(Received::AckNack(
+                acknack.readerSNState.base().to64long(),
+                acknack
+                    .readerSNState
+                    .iter()
+                    .map(|sn| sn.to64long())
+                    .collect(),
+                acknack.finalFlag,
+            ));
+        }
+
+        fn on_nack_frag(&self, _state: &ReceiverState, nack_frag: &NackFragSubmessage) {
+            self.received.lock().unwrap().push(Received::NackFrag(
+                nack_frag.writerSN.to64long(),
+                nack_frag.fragmentNumberState.iter().collect(),
+            ));
+        }
+    }
+
+    #[derive(Default)]
+    struct Listener {
+        added: Mutex<Vec<u64>>,
+        lost: Mutex<Vec<i32>>,
+        matched: Mutex<Vec<bool>>,
+    }
+
+    impl ReaderListener for Listener {
+        fn on_new_cache_change_added(
+            &self,
+            _reader: &GUID_t,
+            _writerGUID: &GUID_t,
+            sequenceNumber: SequenceNumber_t,
+        ) {
+            self.added.lock().unwrap().push(sequenceNumber.to64long());
+        }
+
+        fn on_sample_lost(&self, _reader: &GUID_t, _writerGUID: &GUID_t, count: i32) {
+            self.lost.lock().unwrap().push(count);
+        }
+
+        fn on_reader_matched(&self, _reader: &GUID_t, _writerGUID: &GUID_t, matched: bool) {
+            self.matched.lock().unwrap().push(matched);
+        }
+    }
+
+    // Remote writer listening on a loopback socket
+    struct RemoteWriter {
+        attributes: RemoteEndpointAttributes,
+        socket: UdpSocket,
+        heartbeat_count: u32,
+    }
+
+    impl RemoteWriter {
+        fn new(id: u8, reliabilityKind: ReliabilityKind_t) -> Self {
+            let mut locator = Locator_t::new_from_port(0);
+            setIPv4(&mut locator, "127.0.0.1").unwrap();
+            let (socket, locator) = open_input_channel(&locator).unwrap();
+            let mut guid = GUID_t::unknown();
+            guid.guidPrefix = prefix(id);
+            guid.entityId =
+                EntityId_t::new_from_key_kind(1, TopicKind_t::NO_KEY.writer_entity_kind());
+            let mut attributes = RemoteEndpointAttributes::new(guid, reliabilityKind);
+            attributes.unicastLocatorList.push_back(locator);
+            RemoteWriter {
+                attributes,
+                socket,
+                heartbeat_count: 0,
+            }
+        }
+
+        // Submessages received until the socket times out
+        fn receive(&self) -> Vec<Received> {
+            let writer = Arc::new(Writer::default());
+            let endpoints = Arc::new(EndpointRegistry::new());
+            endpoints.add_writer(self.attributes.guid.entityId, writer.clone());
+            let receiver = MessageReceiver::new(self.attributes.guid.guidPrefix, endpoints);
+            let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE];
+            while let Ok((length, _)) = self.socket.recv_from(&mut buffer) {
+                receiver
+                    .process_message(&buffer[..length], &Locator_t::new_from_port(0))
+                    .unwrap();
+            }
+            let received = std::mem::take(&mut *writer.received.lock().unwrap());
+            received
+        }
+
+        fn state(&self) -> ReceiverState {
+            ReceiverState {
+                sourceVersion: c_ProtocolVersion,
+                sourceVendorId: c_VendorId_Unknown,
+                sourceGuidPrefix: self.attributes.guid.guidPrefix,
+                destGuidPrefix: prefix(1),
+                timestamp: None,
+                sourceLocator: Locator_t::new_from_port(0),
+            }
+        }
+
+        fn data(&self, sn: u64) -> DataSubmessage {
+            DataSubmessage::new(
+                Endianness::LITTLE_ENDIANNESS,
+                EntityId_t::c_EntityId_Unknown,
+                self.attributes.guid.entityId,
+                SequenceNumber_t::from(sn),
+                payload(4),
+            )
+        }
+
+        fn heartbeat(
+            &mut self,
+            reader: &StatefulReader,
+            first: u64,
+            last: u64,
+            finalFlag: bool,
+            now: Instant,
+        ) {
+            self.heartbeat_count += 1;
+            let mut heartbeat = HeartbeatSubmessage::new(
+                Endianness::LITTLE_ENDIANNESS,
+                EntityId_t::c_EntityId_Unknown,
+                self.attributes.guid.entityId,
+                SequenceNumber_t::from(first),
+                SequenceNumber_t::from(last),
+                self.heartbeat_count,
+            );
+            heartbeat.finalFlag = finalFlag;
+            reader.process_heartbeat(&self.state(), &heartbeat, now);
+        }
+    }
+
+    fn prefix(id: u8) -> GuidPrefix_t {
+        let mut prefix = GuidPrefix_t::unknown();
+        prefix.value[0] = id;
+        prefix
+    }
+
+    fn payload(length: usize) -> SerializedPayload_t {
+        let mut payload = SerializedPayload_t::default();
+        payload.begin_write(RepresentationId_t::CDR_LE, 0);
+        payload.write_bytes(&(0..length).map(|n| n as u8).collect::<Vec<u8>>());
+        payload
+    }
+
+    fn reader(reliabilityKind: ReliabilityKind_t) -> (StatefulReader, Arc<Listener>) {
+        let mut guid = GUID_t::unknown();
+        guid.guidPrefix = prefix(1);
+        guid.entityId = EntityId_t::new_from_key_kind(1, TopicKind_t::NO_KEY.reader_entity_kind());
+        let history = ReaderHistory::new(
+            HistoryQosPolicy {
+                kind: HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+                depth: 1,
+            },
+            ResourceLimitsQosPolicy {
+                max_samples: -1,
+                max_instances: -1,
+                max_samples_per_instance: -1,
+            },
+        );
+        let reader = StatefulReader::new(
+            guid,
+            EndpointAttributes::new(EndpointKind_t::READER, TopicKind_t::NO_KEY, reliabilityKind),
+            history,
+            Arc::new(UdpSender::new().unwrap()),
+            Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, guid.guidPrefix),
+            ReaderTimes {
+                heartbeatResponseDelay: Time_t::new(0, 10_000_000),
+            },
+        )
+        .unwrap();
+        let listener = Arc::new(Listener::default());
+        reader.setListener(Some(listener.clone()));
+        (reader, listener)
+    }
+
+    #[test]
+    fn reliable_writer_test() {
+        let (reader, listener) = reader(ReliabilityKind_t::RELIABLE);
+        let mut writer = RemoteWriter::new(2, ReliabilityKind_t::RELIABLE);
+        let guid = writer.attributes.guid;
+        let best_effort = RemoteWriter::new(3, ReliabilityKind_t::BEST_EFFORT);
+        assert!(!reader.matched_writer_add(&best_effort.attributes));
+
+        // Not matched yet
+        reader.on_data(&writer.state(), &writer.data(1));
+        assert!(listener.added.lock().unwrap().is_empty());
+        assert!(reader.matched_writer_add(&writer.attributes));
+        assert!(!reader.matched_writer_add(&writer.attributes));
+
+        // Change 3 waits for change 2
+        for sn in [1, 3, 1] {
+            reader.on_data(&writer.state(), &writer.data(sn));
+        }
+        assert_eq!(vec![1], *listener.added.lock().unwrap());
+        let missing = reader.with_matched_writer(&guid, |proxy| proxy.missing_changes());
+        assert_eq!(Some(vec![SequenceNumber_t::from(2)]), missing);
+
+        // The HEARTBEAT is answered after heartbeatResponseDelay
+        let t0 = Instant::now();
+        writer.heartbeat(&reader, 1, 4, true, t0);
+        let deadline = t0 + Duration::from_millis(10);
+        assert_eq!(Some(deadline), reader.next_acknack());
+        assert_eq!(0, reader.send_acknacks(t0));
+        assert_eq!(1, reader.send_acknacks(deadline));
+        assert_eq!(None, reader.next_acknack());
+        assert_eq!(
+            vec![Received::AckNack(2, vec![2, 4], false)],
+            writer.receive()
+        );
+
+        // Duplicate HEARTBEAT are ignored
+        let heartbeat = HeartbeatSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            guid.entityId,
+            SequenceNumber_t::from(1),
+            SequenceNumber_t::from(4),
+            writer.heartbeat_count,
+        );
+        reader.process_heartbeat(&writer.state(), &heartbeat, deadline);
+        assert_eq!(None, reader.next_acknack());
+
+        // Change 2 is not relevant: change 3 is delivered
+        let gap = GapSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            guid.entityId,
+            SequenceNumber_t::from(2),
+            SequenceNumberSet_t::new(SequenceNumber_t::from(3)),
+        );
+        reader.on_gap(&writer.state(), &gap);
+        assert_eq!(vec![1, 3], *listener.added.lock().unwrap());
+
+        // Change 4 removed from the writer before being received
+        reader.on_data(&writer.state(), &writer.data(6));
+        writer.heartbeat(&reader, 5, 6, true, deadline);
+        assert_eq!(vec![1], *listener.lost.lock().unwrap());
+        let status = reader.with_matched_writer(&guid, |proxy| {
+            (
+                proxy.available_changes_max(),
+                proxy.change_from_writer_status(SequenceNumber_t::from(6)),
+            )
+        });
+        assert_eq!(
+            Some((
+                SequenceNumber_t::from(4),
+                Some(ChangeFromWriterStatusKind::RECEIVED)
+            )),
+            status
+        );
+        reader.on_data(&writer.state(), &writer.data(5));
+        assert_eq!(vec![1, 3, 5, 6], *listener.added.lock().unwrap());
+        let deadline = deadline + Duration::from_millis(10);
+        assert_eq!(1, reader.send_acknacks(deadline));
+        assert_eq!(vec![Received::AckNack(7, vec![], true)], writer.receive());
+
+        // Final HEARTBEAT with nothing missing are not answered, the others are
+        writer.heartbeat(&reader, 5, 6, true, deadline);
+        assert_eq!(None, reader.next_acknack());
+        writer.heartbeat(&reader, 5, 6, false, deadline);
+        assert_eq!(
+            1,
+            reader.send_acknacks(deadline + Duration::from_millis(10))
+        );
+        assert_eq!(vec![Received::AckNack(7, vec![], true)], writer.receive());
+
+        assert!(reader.matched_writer_remove(&guid));
+        assert!(!reader.matched_writer_remove(&guid));
+        assert_eq!(vec![true, false], *listener.matched.lock().unwrap());
+    }
+
+    #[test]
+    fn timed_events_test() {
+        let clock = Arc::new(VirtualClock::new());
+        let service = ResourceEvent::new(clock.clone());
+        let (reader, _listener) = reader(ReliabilityKind_t::RELIABLE);
+        let reader = Arc::new(reader);
+        reader.set_timed_events(&service);
+        let writer = RemoteWriter::new(2, ReliabilityKind_t::RELIABLE);
+        assert!(reader.matched_writer_add(&writer.attributes));
+
+        // Received at the time of the clock, the HEARTBEAT is answered after
+        // heartbeatResponseDelay
+        let heartbeat = HeartbeatSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            writer.attributes.guid.entityId,
+            SequenceNumber_t::from(1),
+            SequenceNumber_t::from(2),
+            1,
+        );
+        reader.on_heartbeat(&writer.state(), &heartbeat);
+        let deadline = clock.now() + Duration::from_millis(10);
+        assert_eq!(Some(deadline), service.next_deadline());
+        clock.advance(Duration::from_millis(9));
+        assert_eq!(0, service.run_due());
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(1, service.run_due());
+        assert_eq!(
+            vec![Received::AckNack(1, vec![1, 2], false)],
+            writer.receive()
+        );
+        assert_eq!(None, service.next_deadline());
+        assert_eq!(None, reader.next_acknack());
+    }
+
+    #[test]
+    fn fragments_test() {
+        let (reader, listener) = reader(ReliabilityKind_t::RELIABLE);
+        let mut writer = RemoteWriter::new(2, ReliabilityKind_t::RELIABLE);
+        assert!(reader.matched_writer_add(&writer.attributes));
+        let writerId = writer.attributes.guid.entityId;
+        let sample = payload(246);
+        let frag = |sn: u64, start: u32| {
+            DataFragSubmessage::new(
+                Endianness::LITTLE_ENDIANNESS,
+                EntityId_t::c_EntityId_Unknown,
+                writerId,
+                SequenceNumber_t::from(sn),
+                sample.data(),
+                100,
+                start,
+                1,
+            )
+            .unwrap()
+        };
+        let frag1_1 = frag(1, 1);
+        let frag1_2 = frag(1, 2);
+        let frag2_1 = frag(2, 1);
+        let frag2_2 = frag(2, 2);
+        let frag2_3 = frag(2, 3);
+
+        // Change 2 complete before change 1
+        for data_frag in [&frag1_1, &frag2_1, &frag2_3, &frag2_2] {
+            reader.on_data_frag(&writer.state(), data_frag);
+        }
+        assert!(listener.added.lock().unwrap().is_empty());
+        let now = Instant::now();
+        writer.heartbeat(&reader, 1, 3, true, now);
+        assert_eq!(1, reader.send_acknacks(now + Duration::from_millis(10)));
+        assert_eq!(
+            vec![
+                Received::AckNack(1, vec![3], false),
+                Received::NackFrag(1, vec![2, 3])
+            ],
+            writer.receive()
+        );
+
+        reader.on_data_frag(&writer.state(), &frag1_2);
+        reader.on_data_frag(&writer.state(), &frag(1, 3));
+        assert_eq!(vec![1, 2], *listener.added.lock().unwrap());
+        {
+            let history = reader.history().lock().unwrap();
+            let change = history.cache().changes().next().unwrap();
+            assert_eq!(sample.data(), change.serializedPayload.data());
+        }
+        // Fragments of delivered changes are dropped
+        reader.on_data_frag(&writer.state(), &frag1_1);
+        assert_eq!(2, reader.history().lock().unwrap().len());
+        assert!(listener.lost.lock().unwrap().is_empty());
+
+        // Samples larger than the reader accepts are dropped
+        let mut oversized = frag(3, 1);
+        oversized.fragmentSize = 1;
+        oversized.sampleSize = u32::MAX;
+        oversized.fragments = vec![0];
+        reader.on_data_frag(&writer.state(), &oversized);
+        assert!(reader
+            .with_matched_writer(&writer.attributes.guid, |proxy| proxy
+                .changes_from_writer
+                .is_empty())
+            .unwrap());
+    }
+
+    #[test]
+    fn best_effort_reader_test() {
+        let (reader, listener) = reader(ReliabilityKind_t::BEST_EFFORT);
+        let mut writer = RemoteWriter::new(2, ReliabilityKind_t::RELIABLE);
+        assert!(reader.matched_writer_add(&writer.attributes));
+        for sn in [1, 3, 2, 4] {
+            reader.on_data(&writer.state(), &writer.data(sn));
+        }
+        assert_eq!(vec![1, 3, 4], *listener.added.lock().unwrap());
+        assert_eq!(vec![1], *listener.lost.lock().unwrap());
+        assert!(!reader
+            .with_matched_writer(&writer.attributes.guid, |proxy| proxy.is_reliable())
+            .unwrap());
+
+        // Never acknowledges
+        writer.heartbeat(&reader, 1, 6, false, Instant::now());
+        assert_eq!(None, reader.next_acknack());
+    }
+}
@@ -0,0 +1,1627 @@
+use crate::dds::core::policy::DurabilityQosPolicyKind_t;
+use crate::rtps::behavior::batching::{BatchingWriter, SampleBatcher};
+use crate::rtps::behavior::timed_event::{Clock, ResourceEvent, SystemClock, TimedEvent};
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+use crate::rtps::common::locator_list::LocatorList;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::common::sequence_number_set::SequenceNumberSet_t;
+use crate::rtps::common::time::{Duration_t, Time_t};
+use crate::rtps::flowcontrol::flow_controller::*;
+use crate::rtps::messages::message_group::{RTPSMessageGroup, DEFAULT_ENDIANNESS};
+use crate::rtps::messages::message_header::Header_t;
+use crate::rtps::messages::message_receiver::{MessageReceiverListener, ReceiverState};
+use crate::rtps::messages::submessages::acknack::AckNackSubmessage;
+use crate::rtps::messages::submessages::gap::GapSubmessage;
+use crate::rtps::messages::submessages::heartbeat::HeartbeatSubmessage;
+use crate::rtps::messages::submessages::nack_frag::NackFragSubmessage;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use crate::rtps::structure::endpoint::*;
+use crate::rtps::structure::intraprocess::{find_local_reader, intraprocess_state};
+use crate::rtps::structure::rtps_writer::RTPSWriter;
+use crate::rtps::structure::writer_history::WriterHistory;
+use crate::rtps::transport::udp_transport::UdpSender;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Instant;
+
+/**
+ * @brief Status of a change for a matched reader, as specified in the DDSI-RTPS 2.5
+ *        specification chapter 8.4.9.2.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeForReaderStatusKind {
+    // Not sent yet
+    UNSENT,
+    // Requested by a NACK, sent again after nackResponseDelay
+    REQUESTED,
+    // Sent, waiting for the reader to acknowledge it
+    UNACKNOWLEDGED,
+    // Acknowledged by the reader, or not relevant to it
+    ACKNOWLEDGED,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct ChangeForReader_t {
+    status: ChangeForReaderStatusKind,
+    // Last time the change was sent, for the NACK suppression
+    last_sent: Option<Instant>,
+    // First fragment left to send, when the flow controller ran out of budget during the change
+    next_fragment: u32,
+}
+
+impl ChangeForReader_t {
+    fn new(status: ChangeForReaderStatusKind, last_sent: Option<Instant>) -> Self {
+        ChangeForReader_t {
+            status,
+            last_sent,
+            next_fragment: 1,
+        }
+    }
+}
+
+/**
+ * @brief Timing of a stateful writer, as specified in the DDSI-RTPS 2.5 specification
+ *        chapter 8.4.7.1.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WriterTimes {
+    /// Period the HEARTBEAT are sent with
+    pub heartbeatPeriod: Duration_t,
+    /// Delay before answering a NACK, so that close NACK are answered together
+    pub nackResponseDelay: Duration_t,
+    /// Time after sending a change during which the NACK requesting it are ignored
+    pub nackSuppressionDuration: Duration_t,
+}
+
+impl Default for WriterTimes {
+    fn default() -> Self {
+        WriterTimes {
+            heartbeatPeriod: Time_t::new(3, 0),
+            nackResponseDelay: Time_t::new(0, 5_000_000),
+            nackSuppressionDuration: Time_t::c_TimeZero,
+        }
+    }
+}
+
+/**
+ * @brief State of a matched reader kept by a stateful writer, as specified in the
+ *        DDSI-RTPS 2.5 specification chapter 8.4.7.5.
+ *        Only the changes not acknowledged yet are kept: every change up to acked_up_to is
+ *        acknowledged or not relevant to the reader.
+ */
+#[derive(Clone, Debug)]
+pub struct ReaderProxy {
+    attributes: RemoteEndpointAttributes,
+    acked_up_to: SequenceNumber_t,
+    changes_for_reader: BTreeMap<SequenceNumber_t, ChangeForReader_t>,
+    // Count of the last ACKNACK processed
+    last_acknack_count: Option<u32>,
+    // Count of the last NACK_FRAG processed
+    last_nack_frag_count: Option<u32>,
+    // Time the requested changes are sent at
+    nack_response_at: Option<Instant>,
+    // Reader in the same process, the changes are handed over to it directly
+    local_reader: Option<Weak<dyn MessageReceiverListener>>,
+}
+
+impl ReaderProxy {
+    fn new(attributes: RemoteEndpointAttributes, acked_up_to: SequenceNumber_t) -> Self {
+        ReaderProxy {
+            attributes,
+            acked_up_to,
+            changes_for_reader: BTreeMap::new(),
+            last_acknack_count: None,
+            last_nack_frag_count: None,
+            nack_response_at: None,
+            local_reader: None,
+        }
+    }
+
+    pub fn remoteReaderGuid(&self) -> &GUID_t {
+        &self.attributes.guid
+    }
+
+    pub fn attributes(&self) -> &RemoteEndpointAttributes {
+        &self.attributes
+    }
+
+    pub fn is_reliable(&self) -> bool {
+        self.attributes.reliabilityKind == ReliabilityKind_t::RELIABLE
+    }
+
+    /**
+     * @brief Status of a change for this reader.
+     *
+     * @param sequenceNumber Sequence number of the change.
+     * @return The status, None if the change was written before the reader was matched.
+     */
+    pub fn change_for_reader_status(
+        &self,
+        sequenceNumber: SequenceNumber_t,
+    ) -> Option<ChangeForReaderStatusKind> {
+        if sequenceNumber <= self.acked_up_to {
+            return Some(ChangeForReaderStatusKind::ACKNOWLEDGED);
+        }
+        self.changes_for_reader
+            .get(&sequenceNumber)
+            .map(|change| change.status)
+    }
+
+    // Changes with a status, in order
+    pub fn changes_with_status(&self, status: ChangeForReaderStatusKind) -> Vec<SequenceNumber_t> {
+        self.changes_for_reader
+            .iter()
+            .filter(|(_, change)| change.status == status)
+            .map(|(sn, _)| *sn)
+            .collect()
+    }
+
+    // Whether the changes are handed over directly to the reader, in the same process
+    pub fn is_local(&self) -> bool {
+        self.local_reader.is_some()
+    }
+
+    // Unicast locators of the reader, its multicast ones if it has none
+    fn locators(&self) -> LocatorList {
+        if self.attributes.unicastLocatorList.is_empty() {
+            self.attributes.multicastLocatorList.clone()
+        } else {
+            self.attributes.unicastLocatorList.clone()
+        }
+    }
+
+    fn change_sent(&mut self, sequenceNumber: SequenceNumber_t, now: Instant) {
+        if self.is_reliable() {
+            self.changes_for_reader.insert(
+                sequenceNumber,
+                ChangeForReader_t::new(ChangeForReaderStatusKind::UNACKNOWLEDGED, Some(now)),
+            );
+        } else {
+            // Best-effort readers never acknowledge, the changes are sent in order
+            self.changes_for_reader.remove(&sequenceNumber);
+            if sequenceNumber > self.acked_up_to {
+                self.acked_up_to = sequenceNumber;
+            }
+        }
+    }
+
+    // Every change before base is acknowledged
+    fn acked_changes_set(&mut self, base: SequenceNumber_t) {
+        if base > SequenceNumber_t::new(0, 0) && base - 1 > self.acked_up_to {
+            self.acked_up_to = base - 1;
+            self.changes_for_reader = self.changes_for_reader.split_off(&base);
+        }
+    }
+}
+
+// Submessages for a reader of the same process, handed over once the writer is unlocked since
+// the reader may write to the writer right away
+struct LocalDelivery {
+    reader: Arc<dyn MessageReceiverListener>,
+    destination: GuidPrefix_t,
+    changes: Vec<CacheChange_t>,
+    gaps: Vec<GapSubmessage>,
+    heartbeat: Option<HeartbeatSubmessage>,
+}
+
+// Timed events of a writer, see set_timed_events
+struct WriterEvents {
+    clock: Arc<dyn Clock>,
+    // Sends the HEARTBEAT every heartbeatPeriod, None for an infinite period
+    heartbeat: Option<TimedEvent>,
+    // Answers the NACK once nackResponseDelay has elapsed
+    nack_response: TimedEvent,
+}
+
+// Submessages waiting to be handed over to the readers of the same process, in order
+#[derive(Default)]
+struct LocalQueue {
+    deliveries: VecDeque<LocalDelivery>,
+    // Whether a thread is handing them over, the ones queued meanwhile included
+    handing_over: bool,
+}
+
+struct StatefulWriterState {
+    matched_readers: BTreeMap<GUID_t, ReaderProxy>,
+    heartbeat_count: u32,
+}
+
+/**
+ * @brief Stateful writer, as specified in the DDSI-RTPS 2.5 specification chapter 8.4.9.
+ *        It keeps a ReaderProxy per matched reader and sends the changes to each reader on
+ *        its own, after an INFO_DST. Reliable readers are sent HEARTBEAT and their NACK are
+ *        answered with the requested changes, or with a GAP for the changes no longer in the
+ *        history.
+ *        With set_timed_events, the HEARTBEAT are sent every heartbeatPeriod while reliable
+ *        readers have unacknowledged changes, and the NACK are answered once
+ *        nackResponseDelay has elapsed. Otherwise the writer is driven from the outside, by
+ *        send_heartbeat and send_nack_responses.
+ *        The NACK responses are not batched nor flow controlled, and the changes of a batch
+ *        are followed by a single HEARTBEAT.
+ *        Readers in the same process are handed the changes of the history directly, sharing
+ *        their payload, and their HEARTBEAT and GAP without serializing them. They acknowledge
+ *        the changes as remote readers do. The flow controller and fragmentation only apply
+ *        to the network: the changes are handed over as soon as they are sent, once the
+ *        writer is unlocked so that their readers may write to it.
+ *        Changes of VOLATILE writers are removed from the history once acknowledged by every
+ *        reliable reader, TRANSIENT_LOCAL writers keep them for the late joiners.
+ */
+pub struct StatefulWriter {
+    guid: GUID_t,
+    attributes: EndpointAttributes,
+    history: WriterHistory,
+    sender: Arc<UdpSender>,
+    header: Header_t,
+    times: WriterTimes,
+    // Sends the new changes at a limited rate, None to send them right away
+    flow_controller: Mutex<Option<Arc<FlowController>>>,
+    // Collects the new changes to send them together, None to send them one by one
+    batcher: Mutex<Option<Arc<SampleBatcher>>>,
+    state: Mutex<StatefulWriterState>,
+    local: Mutex<LocalQueue>,
+    // None until set_timed_events
+    events: Mutex<Option<WriterEvents>>,
+}
+
+impl StatefulWriter {
+    /**
+     * @brief Create a stateful writer.
+     *
+     * @param guid GUID of the writer.
+     * @param attributes Attributes of the writer.
+     * @param history History of the writer.
+     * @param sender Sockets of the participant.
+     * @param header Header of the messages of the participant.
+     * @param times Timing of the HEARTBEAT and of the NACK responses.
+     * @return The writer.
+     */
+    pub fn new(
+        guid: GUID_t,
+        attributes: EndpointAttributes,
+        history: WriterHistory,
+        sender: Arc<UdpSender>,
+        header: Header_t,
+        times: WriterTimes,
+    ) -> Result<Self, RtpsError> {
+        Ok(StatefulWriter {
+            guid,
+            attributes,
+            history,
+            sender,
+            header,
+            times,
+            flow_controller: Mutex::new(None),
+            batcher: Mutex::new(None),
+            state: Mutex::new(StatefulWriterState {
+                matched_readers: BTreeMap::new(),
+                heartbeat_count: 0,
+            }),
+            local: Mutex::new(LocalQueue::default()),
+            events: Mutex::new(None),
+        })
+    }
+
+    pub fn times(&self) -> &WriterTimes {
+        &self.times
+    }
+
+    /**
+     * @brief Send the HEARTBEAT and answer the NACK with timed events of a service, whose
+     *        clock also gives the time the ACKNACK are received at and the changes sent at.
+     *
+     * @param service Service running the events.
+     */
+    pub fn set_timed_events(self: &Arc<Self>, service: &ResourceEvent) {
+        let weak = Arc::downgrade(self);
+        let heartbeat = self.times.heartbeatPeriod.to_duration().map(|period| {
+            let weak = weak.clone();
+            // Restarted by send_heartbeat while changes are unacknowledged
+            TimedEvent::new(service, period, move |_| {
+                if let Some(writer) = weak.upgrade() {
+                    writer.send_heartbeat();
+                }
+                false
+            })
+        });
+        let delay = self.times.nackResponseDelay.to_duration();
+        let nack_response = TimedEvent::new(service, delay.unwrap_or_default(), move |now| {
+            if let Some(writer) = weak.upgrade() {
+                writer.send_nack_responses(now);
+            }
+            false
+        });
+        *self.events.lock().unwrap() = Some(WriterEvents {
+            clock: service.clock().clone(),
+            heartbeat,
+            nack_response,
+        });
+        let state = self.state.lock().unwrap();
+        self.schedule_heartbeat(&state, false);
+        self.schedule_nack_response(&state);
+    }
+
+    // Current time of the clock of the timed events, of the system without them
+    fn now(&self) -> Instant {
+        match &*self.events.lock().unwrap() {
+            Some(events) => events.clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    // Keep the HEARTBEAT timer running while reliable readers have unacknowledged changes,
+    // restarting the period if asked to
+    fn schedule_heartbeat(&self, state: &StatefulWriterState, restart: bool) {
+        let events = self.events.lock().unwrap();
+        let event = match events.as_ref().and_then(|events| events.heartbeat.as_ref()) {
+            Some(event) => event,
+            None => return,
+        };
+        let lastSN = self.history.last_sequence_number();
+        let unacknowledged = state
+            .matched_readers
+            .values()
+            .any(|proxy| proxy.is_reliable() && proxy.acked_up_to < lastSN);
+        if !unacknowledged {
+            event.cancel_timer();
+        } else if restart || !event.is_scheduled() {
+            event.restart_timer();
+        }
+    }
+
+    // Run the NACK responses timer at the earliest response time of the readers
+    fn schedule_nack_response(&self, state: &StatefulWriterState) {
+        if let Some(events) = &*self.events.lock().unwrap() {
+            match state
+                .matched_readers
+                .values()
+                .filter_map(|proxy| proxy.nack_response_at)
+                .min()
+            {
+                Some(deadline) => events.nack_response.restart_timer_at(deadline),
+                None => events.nack_response.cancel_timer(),
+            }
+        }
+    }
+
+    // State of a matched reader
+    pub fn matched_reader_lookup(&self, readerGuid: &GUID_t) -> Option<ReaderProxy> {
+        self.state
+            .lock()
+            .unwrap()
+            .matched_readers
+            .get(readerGuid)
+            .cloned()
+    }
+
+    fn heartbeat(&self, proxy: &ReaderProxy, count: &mut u32) -> HeartbeatSubmessage {
+        let lastSN = self.history.last_sequence_number();
+        let firstSN = std::cmp::max(
+            self.history.get_seq_num_min().unwrap_or(lastSN + 1),
+            proxy.acked_up_to + 1,
+        );
+        *count += 1;
+        HeartbeatSubmessage::new(
+            DEFAULT_ENDIANNESS,
+            proxy.attributes.guid.entityId,
+            self.guid.entityId,
+            firstSN,
+            lastSN,
+            *count,
+        )
+    }
+
+    /**
+     * @brief Send changes to a reader within a budget, with a GAP for the ones no longer in
+     *        the history. When the budget runs out during a change, the fragment it stopped at
+     *        is kept and the next changes are not sent. The changes for a reader of the same
+     *        process are added to local instead, to hand them over once the writer is unlocked.
+     *
+     * @return The number of changes completely sent, and whether every change was.
+     */
+    fn send_changes(
+        &self,
+        proxy: &mut ReaderProxy,
+        sequenceNumbers: &[SequenceNumber_t],
+        heartbeat_count: &mut u32,
+        budget: &mut SendBudget,
+        now: Instant,
+        local: &mut Vec<LocalDelivery>,
+    ) -> (usize, bool) {
+        if let Some(reader) = proxy.local_reader.as_ref().and_then(Weak::upgrade) {
+            let delivery =
+                self.local_delivery(reader, proxy, sequenceNumbers, heartbeat_count, now);
+            let sent = delivery.changes.len();
+            local.push(delivery);
+            return (sent, true);
+        }
+        let mut group = RTPSMessageGroup::new(&self.sender, self.header, proxy.locators());
+        group.set_destination(Some(proxy.attributes.guid.guidPrefix));
+        let readerId = proxy.attributes.guid.entityId;
+        let maxPayload = self.max_payload();
+        let mut sent = 0;
+        let mut complete = true;
+        let mut irrelevant: Vec<SequenceNumber_t> = Vec::new();
+        for sequenceNumber in sequenceNumbers {
+            let next_fragment = proxy
+                .changes_for_reader
+                .get(sequenceNumber)
+                .map_or(1, |change| change.next_fragment);
+            match self.history.with_change(*sequenceNumber, |change| {
+                group.add_change(change, readerId, maxPayload, next_fragment, budget)
+            }) {
+                Some(Ok(None)) => sent += 1,
+                Some(Ok(Some(next_fragment))) => {
+                    if let Some(change) = proxy.changes_for_reader.get_mut(sequenceNumber) {
+                        change.next_fragment = next_fragment;
+                    }
+                    complete = false;
+                    break;
+                }
+                // A change that cannot be sent will never be
+                _ => irrelevant.push(*sequenceNumber),
+            }
+            proxy.change_sent(*sequenceNumber, now);
+        }
+
+        for gap in self.gaps(readerId, &irrelevant) {
+            let mut submessage = Vec::new();
+            gap.write(&mut submessage);
+            // Always fits in a message
+            let _ = group.add_submessage(&submessage);
+        }
+
+        // Ask the reader to acknowledge what was sent
+        if proxy.is_reliable() && (sent > 0 || !irrelevant.is_empty()) {
+            let mut submessage = Vec::new();
+            self.heartbeat(proxy, heartbeat_count)
+                .write(&mut submessage);
+            let _ = group.add_submessage(&submessage);
+        }
+        (sent, complete)
+    }
+
+    /**
+     * @brief Changes for a reader of the same process, with a GAP for the ones no longer in
+     *        the history. The changes share the payload of the history.
+     *
+     * @return The submessages to hand over to the reader.
+     */
+    fn local_delivery(
+        &self,
+        reader: Arc<dyn MessageReceiverListener>,
+        proxy: &mut ReaderProxy,
+        sequenceNumbers: &[SequenceNumber_t],
+        heartbeat_count: &mut u32,
+        now: Instant,
+    ) -> LocalDelivery {
+        let mut changes = Vec::new();
+        let mut irrelevant: Vec<SequenceNumber_t> = Vec::new();
+        for sequenceNumber in sequenceNumbers {
+            let shared = self.history.with_change(*sequenceNumber, |change| {
+                let mut shared = CacheChange_t::new();
+                shared.share(change);
+                shared
+            });
+            match shared {
+                Some(change) => changes.push(change),
+                None => irrelevant.push(*sequenceNumber),
+            }
+            proxy.change_sent(*sequenceNumber, now);
+        }
+
+        let heartbeat = if proxy.is_reliable() && !sequenceNumbers.is_empty() {
+            Some(self.heartbeat(proxy, heartbeat_count))
+        } else {
+            None
+        };
+        LocalDelivery {
+            reader,
+            destination: proxy.attributes.guid.guidPrefix,
+            changes,
+            gaps: self.gaps(proxy.attributes.guid.entityId, &irrelevant),
+            heartbeat,
+        }
+    }
+
+    /**
+     * @brief Hand submessages over to the readers of the same process, after unlocking the
+     *        writer. They are queued while it is locked, so that they are handed over in the
+     *        order they were collected in. When a reader writes to the writer while handed
+     *        over a change, the submessages of that write are handed over after the current
+     *        ones by the same thread, instead of within the reader.
+     *
+     * @param guard Lock of the writer, released before handing the submessages over.
+     * @param local Submessages collected under the lock.
+     */
+    fn hand_over(&self, guard: MutexGuard<StatefulWriterState>, local: Vec<LocalDelivery>) {
+        {
+            let mut queue = self.local.lock().unwrap();
+            queue.deliveries.extend(local);
+            drop(guard);
+            if queue.handing_over {
+                return;
+            }
+            queue.handing_over = true;
+        }
+        loop {
+            let delivery = {
+                let mut queue = self.local.lock().unwrap();
+                match queue.deliveries.pop_front() {
+                    Some(delivery) => delivery,
+                    None => {
+                        queue.handing_over = false;
+                        return;
+                    }
+                }
+            };
+            let reader = &delivery.reader;
+            for change in &delivery.changes {
+                let state = intraprocess_state(
+                    self.guid.guidPrefix,
+                    delivery.destination,
+                    Some(change.sourceTimestamp),
+                );
+                reader.on_intraprocess_change(&state, change);
+            }
+            let state = intraprocess_state(self.guid.guidPrefix, delivery.destination, None);
+            for gap in &delivery.gaps {
+                reader.on_gap(&state, gap);
+            }
+            if let Some(heartbeat) = &delivery.heartbeat {
+                reader.on_heartbeat(&state, heartbeat);
+            }
+        }
+    }
+
+    // A GAP per range of consecutive irrelevant changes
+    fn gaps(&self, readerId: EntityId_t, irrelevant: &[SequenceNumber_t]) -> Vec<GapSubmessage> {
+        let mut gaps = Vec::new();
+        let mut start = 0;
+        while start < irrelevant.len() {
+            let mut end = start;
+            while end + 1 < irrelevant.len() && irrelevant[end + 1] == irrelevant[end] + 1 {
+                end += 1;
+            }
+            gaps.push(GapSubmessage::new(
+                DEFAULT_ENDIANNESS,
+                readerId,
+                self.guid.entityId,
+                irrelevant[start],
+                SequenceNumberSet_t::new(irrelevant[end] + 1),
+            ));
+            start = end + 1;
+        }
+        gaps
+    }
+
+    /**
+     * @brief Send the unsent changes to every matched reader, without flow control.
+     *
+     * @return The number of changes sent.
+     */
+    pub fn send_any(&self) -> usize {
+        let now = self.now();
+        let mut sent = 0;
+        let mut local = Vec::new();
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        for proxy in state.matched_readers.values_mut() {
+            let unsent = proxy.changes_with_status(ChangeForReaderStatusKind::UNSENT);
+            if !unsent.is_empty() {
+                let mut budget = SendBudget::unlimited();
+                sent += self
+                    .send_changes(
+                        proxy,
+                        &unsent,
+                        &mut state.heartbeat_count,
+                        &mut budget,
+                        now,
+                        &mut local,
+                    )
+                    .0;
+            }
+        }
+        self.schedule_heartbeat(&guard, false);
+        self.hand_over(guard, local);
+        sent
+    }
+
+    /**
+     * @brief Send a HEARTBEAT to every reliable reader that has not acknowledged every change.
+     *        Called every heartbeatPeriod, the timed event is restarted for the next one.
+     *
+     * @return The number of HEARTBEAT sent.
+     */
+    pub fn send_heartbeat(&self) -> usize {
+        let lastSN = self.history.last_sequence_number();
+        let mut sent = 0;
+        let mut local = Vec::new();
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        for proxy in state.matched_readers.values() {
+            if !proxy.is_reliable() || proxy.acked_up_to >= lastSN {
+                continue;
+            }
+            let heartbeat = self.heartbeat(proxy, &mut state.heartbeat_count);
+            if let Some(reader) = proxy.local_reader.as_ref().and_then(Weak::upgrade) {
+                local.push(LocalDelivery {
+                    reader,
+                    destination: proxy.attributes.guid.guidPrefix,
+                    changes: Vec::new(),
+                    gaps: Vec::new(),
+                    heartbeat: Some(heartbeat),
+                });
+                sent += 1;
+                continue;
+            }
+            let mut submessage = Vec::new();
+            heartbeat.write(&mut submessage);
+            let mut group = RTPSMessageGroup::new(&self.sender, self.header, proxy.locators());
+            group.set_destination(Some(proxy.attributes.guid.guidPrefix));
+            if group.add_submessage(&submessage).is_ok() {
+                sent += 1;
+            }
+        }
+        self.schedule_heartbeat(&guard, true);
+        self.hand_over(guard, local);
+        sent
+    }
+
+    fn respond_to_nacks(
+        &self,
+        state: &mut StatefulWriterState,
+        now: Instant,
+        local: &mut Vec<LocalDelivery>,
+    ) -> usize {
+        let mut sent = 0;
+        for proxy in state.matched_readers.values_mut() {
+            match proxy.nack_response_at {
+                Some(deadline) if deadline <= now => proxy.nack_response_at = None,
+                _ => continue,
+            }
+            let requested = proxy.changes_with_status(ChangeForReaderStatusKind::REQUESTED);
+            let mut budget = SendBudget::unlimited();
+            sent += self
+                .send_changes(
+                    proxy,
+                    &requested,
+                    &mut state.heartbeat_count,
+                    &mut budget,
+                    now,
+                    local,
+                )
+                .0;
+        }
+        sent
+    }
+
+    /**
+     * @brief Send the changes requested by the readers whose nackResponseDelay has elapsed.
+     *
+     * @param now Current time.
+     * @return The number of DATA sent.
+     */
+    pub fn send_nack_responses(&self, now: Instant) -> usize {
+        let mut local = Vec::new();
+        let mut state = self.state.lock().unwrap();
+        let sent = self.respond_to_nacks(&mut state, now, &mut local);
+        self.schedule_nack_response(&state);
+        self.hand_over(state, local);
+        sent
+    }
+
+    // Time send_nack_responses has changes to send at, None if no change is requested
+    pub fn next_nack_response(&self) -> Option<Instant> {
+        self.state
+            .lock()
+            .unwrap()
+            .matched_readers
+            .values()
+            .filter_map(|proxy| proxy.nack_response_at)
+            .min()
+    }
+
+    /**
+     * @brief Process an ACKNACK received at a given time.
+     *        The changes before the base of the set are acknowledged, and the ones in the set
+     *        requested, except the ones sent less than nackSuppressionDuration ago. ACKNACK
+     *        with a count not greater than the last one are ignored.
+     *
+     * @param state State of the receiver, giving the participant of the reader.
+     * @param acknack Received ACKNACK.
+     * @param now Time the ACKNACK is received at.
+     */
+    pub fn process_acknack(
+        &self,
+        state: &ReceiverState,
+        acknack: &AckNackSubmessage,
+        now: Instant,
+    ) {
+        let readerGuid = GUID_t {
+            guidPrefix: state.sourceGuidPrefix,
+            entityId: acknack.readerId,
+        };
+        let lastSN = self.history.last_sequence_number();
+        let mut guard = self.state.lock().unwrap();
+        let proxy = match guard.matched_readers.get_mut(&readerGuid) {
+            Some(proxy) if proxy.is_reliable() => proxy,
+            _ => return,
+        };
+        if let Some(count) = proxy.last_acknack_count {
+            if acknack.count <= count {
+                return;
+            }
+        }
+        proxy.last_acknack_count = Some(acknack.count);
+        let acked_up_to = proxy.acked_up_to;
+        proxy.acked_changes_set(acknack.readerSNState.base());
+        let acknowledged = proxy.acked_up_to > acked_up_to;
+
+        let suppression = self.times.nackSuppressionDuration.to_duration();
+        let mut requested = false;
+        for sequenceNumber in acknack.readerSNState.iter() {
+            if sequenceNumber <= proxy.acked_up_to || sequenceNumber > lastSN {
+                continue;
+            }
+            // Changes the writer never sent to the reader, such as the ones removed from the
+            // history before it was matched, get a GAP
+            let change =
+                proxy
+                    .changes_for_reader
+                    .entry(sequenceNumber)
+                    .or_insert(ChangeForReader_t::new(
+                        ChangeForReaderStatusKind::UNACKNOWLEDGED,
+                        None,
+                    ));
+            let suppressed = match (change.last_sent, suppression) {
+                (None, _) => false,
+                (Some(sent), Some(suppression)) => {
+                    sent.checked_add(suppression).is_none_or(|end| now < end)
+                }
+                (Some(_), None) => true,
+            };
+            if change.status != ChangeForReaderStatusKind::UNSENT && !suppressed {
+                change.status = ChangeForReaderStatusKind::REQUESTED;
+                change.next_fragment = 1;
+                requested = true;
+            }
+        }
+
+        let mut respond_now = false;
+        if requested && proxy.nack_response_at.is_none() {
+            if let Some(delay) = self.times.nackResponseDelay.to_duration() {
+                proxy.nack_response_at = now.checked_add(delay);
+                respond_now = delay.as_nanos() == 0;
+            }
+        }
+        let mut local = Vec::new();
+        if respond_now {
+            self.respond_to_nacks(&mut guard, now, &mut local);
+        }
+        self.remove_acknowledged_changes(&guard);
+        if acknowledged {
+            self.history.changes_acknowledged();
+        }
+        self.schedule_heartbeat(&guard, false);
+        self.schedule_nack_response(&guard);
+        self.hand_over(guard, local);
+    }
+
+    /**
+     * @brief Process a NACK_FRAG: the requested fragments of a change are sent again right
+     *        away. Fragments not sent yet by the flow controller are left to it, and NACK_FRAG
+     *        with a count not greater than the last one are ignored.
+     *
+     * @param state State of the receiver, giving the participant of the reader.
+     * @param nack_frag Received NACK_FRAG.
+     * @return The number of fragments sent.
+     */
+    pub fn process_nack_frag(
+        &self,
+        state: &ReceiverState,
+        nack_frag: &NackFragSubmessage,
+    ) -> usize {
+        let readerGuid = GUID_t {
+            guidPrefix: state.sourceGuidPrefix,
+            entityId: nack_frag.readerId,
+        };
+        let maxPayload = self.max_payload();
+        let mut guard = self.state.lock().unwrap();
+        let proxy = match guard.matched_readers.get_mut(&readerGuid) {
+            Some(proxy) if proxy.is_reliable() => proxy,
+            _ => return 0,
+        };
+        if proxy
+            .last_nack_frag_count
+            .is_some_and(|count| nack_frag.count <= count)
+        {
+            return 0;
+        }
+        proxy.last_nack_frag_count = Some(nack_frag.count);
+        let sent_fragments = match proxy.changes_for_reader.get(&nack_frag.writerSN) {
+            Some(change) if change.status == ChangeForReaderStatusKind::UNSENT => {
+                change.next_fragment
+            }
+            Some(_) => u32::MAX,
+            None => return 0,
+        };
+
+        let mut group = RTPSMessageGroup::new(&self.sender, self.header, proxy.locators());
+        group.set_destination(Some(readerGuid.guidPrefix));
+        let fragmentSize = std::cmp::min(maxPayload, u16::MAX as usize) as u16;
+        self.history
+            .with_change(nack_frag.writerSN, |change| {
+                nack_frag
+                    .fragmentNumberState
+                    .iter()
+                    .filter(|fragment| *fragment < sent_fragments)
+                    .filter(|fragment| {
+                        group
+                            .add_data_frag(change, readerGuid.entityId, fragmentSize, *fragment, 1)
+                            .is_ok()
+                    })
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    // Remove the changes of a VOLATILE writer acknowledged by every reliable reader
+    fn remove_acknowledged_changes(&self, state: &StatefulWriterState) {
+        if self.attributes.durabilityKind != DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS {
+            return;
+        }
+        if let Some(acked) = state
+            .matched_readers
+            .values()
+            .filter(|proxy| proxy.is_reliable())
+            .map(|proxy| proxy.acked_up_to)
+            .min()
+        {
+            self.history.remove_changes_up_to(acked);
+        }
+    }
+}
+
+impl Endpoint for StatefulWriter {
+    fn getGuid(&self) -> &GUID_t {
+        &self.guid
+    }
+
+    fn getAttributes(&self) -> &EndpointAttributes {
+        &self.attributes
+    }
+}
+
+impl MessageReceiverListener for StatefulWriter {
+    fn on_acknack(&self, state: &ReceiverState, acknack: &AckNackSubmessage) {
+        self.process_acknack(state, acknack, self.now());
+    }
+
+    fn on_nack_frag(&self, state: &ReceiverState, nack_frag: &NackFragSubmessage) {
+        self.process_nack_frag(state, nack_frag);
+    }
+}
+
+impl FlowControlledWriter for StatefulWriter {
+    // Send changes to the readers they are still unsent to, in a message group per reader
+    fn deliver_samples(
+        &self,
+        sequenceNumbers: &[SequenceNumber_t],
+        budget: &mut SendBudget,
+    ) -> bool {
+        let now = self.now();
+        let mut local = Vec::new();
+        let mut complete = true;
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        for proxy in state.matched_readers.values_mut() {
+            let unsent: Vec<SequenceNumber_t> = sequenceNumbers
+                .iter()
+                .filter(|sequenceNumber| {
+                    proxy
+                        .changes_for_reader
+                        .get(sequenceNumber)
+                        .is_some_and(|change| change.status == ChangeForReaderStatusKind::UNSENT)
+                })
+                .copied()
+                .collect();
+            if !unsent.is_empty()
+                && !self
+                    .send_changes(
+                        proxy,
+                        &unsent,
+                        &mut state.heartbeat_count,
+                        budget,
+                        now,
+                        &mut local,
+                    )
+                    .1
+            {
+                complete = false;
+                break;
+            }
+        }
+        self.schedule_heartbeat(&guard, false);
+        self.hand_over(guard, local);
+        complete
+    }
+}
+
+impl BatchingWriter for StatefulWriter {
+    fn batcher(&self) -> &Mutex<Option<Arc<SampleBatcher>>> {
+        &self.batcher
+    }
+
+    fn flow_controller(&self) -> &Mutex<Option<Arc<FlowController>>> {
+        &self.flow_controller
+    }
+
+    fn send_unsent(&self) {
+        self.send_any();
+    }
+}
+
+impl RTPSWriter for StatefulWriter {
+    fn history(&self) -> &WriterHistory {
+        &self.history
+    }
+
+    fn unsent_change_added_to_history(&self, sequenceNumber: SequenceNumber_t) {
+        for proxy in self.state.lock().unwrap().matched_readers.values_mut() {
+            proxy.changes_for_reader.insert(
+                sequenceNumber,
+                ChangeForReader_t::new(ChangeForReaderStatusKind::UNSENT, None),
+            );
+        }
+        self.batch_new_change(sequenceNumber);
+    }
+
+    /**
+     * @brief Add a proxy for a reader. The changes in the history are sent to it for
+     *        TRANSIENT_LOCAL writers, VOLATILE writers only send it the next changes.
+     *
+     * @param reader Matched reader.
+     * @return False if the reader was already matched, or is reliable while the writer is not.
+     */
+    fn matched_reader_add(&self, reader: &RemoteEndpointAttributes) -> bool {
+        if reader.reliabilityKind == ReliabilityKind_t::RELIABLE
+            && self.attributes.reliabilityKind != ReliabilityKind_t::RELIABLE
+        {
+            return false;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.matched_readers.contains_key(&reader.guid) {
+            return false;
+        }
+        let mut proxy = if self.attributes.durabilityKind
+            == DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS
+        {
+            ReaderProxy::new(reader.clone(), self.history.last_sequence_number())
+        } else {
+            let mut proxy = ReaderProxy::new(reader.clone(), SequenceNumber_t::new(0, 0));
+            self.history.with_cache(|cache| {
+                for change in cache.changes() {
+                    proxy.changes_for_reader.insert(
+                        change.sequenceNumber,
+                        ChangeForReader_t::new(ChangeForReaderStatusKind::UNSENT, None),
+                    );
+                }
+            });
+            proxy
+        };
+        proxy.local_reader = find_local_reader(&self.guid, &reader.guid);
+        state.matched_readers.insert(reader.guid, proxy);
+        true
+    }
+
+    fn matched_reader_remove(&self, readerGuid: &GUID_t) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.matched_readers.remove(readerGuid).is_none() {
+            return false;
+        }
+        // The changes may only have been waiting for this reader
+        self.remove_acknowledged_changes(&state);
+        self.history.changes_acknowledged();
+        true
+    }
+
+    fn matched_reader_is_matched(&self, readerGuid: &GUID_t) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .matched_readers
+            .contains_key(readerGuid)
+    }
+
+    fn is_acked_by_all(&self, sequenceNumber: SequenceNumber_t) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .matched_readers
+            .values()
+            .filter(|proxy| proxy.is_reliable())
+            .all(|proxy| proxy.acked_up_to >= sequenceNumber)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dds::core::policy::*;
+    use crate::rtps::behavior::batching::BatchingAttributes;
+    use crate::rtps::behavior::timed_event::{Clock, ResourceEvent, VirtualClock};
+    use crate::rtps::common::change_kind_t::ChangeKind_t;
+    use crate::rtps::common::entity_id_t::EntityId_t;
+    use crate::rtps::common::fragment_number_set::FragmentNumberSet_t;
+    use crate::rtps::common::instance_handle::InstanceHandle_t;
+    use crate::rtps::common::locator::Locator_t;
+    use crate::rtps::common::protocol_version::c_ProtocolVersion;
+    use crate::rtps::common::serialized_payload::RepresentationId_t;
+    use crate::rtps::common::vendor_id::c_VendorId_Unknown;
+    use crate::rtps::messages::message_receiver::*;
+    use crate::rtps::messages::submessages::data::DataSubmessage;
+    use crate::rtps::messages::submessages::data_frag::DataFragSubmessage;
+    use crate::rtps::transport::udp_transport::*;
+    use crate::rtps::utils::ip_locator::setIPv4;
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Received {
+        Data(u64),
+        // Sequence number and first fragment
+        DataFrag(u64, u32),
+        Heartbeat(u64, u64),
+        Gap(u64, u64),
+    }
+
+    #[derive(Default)]
+    struct Reader {
+        received: Mutex<Vec<Received>>,
+    }
+
+    impl MessageReceiverListener for Reader {
+        fn on_data(&self, _state: &ReceiverState, data: &DataSubmessage) {
+            let sn = data.writerSN.low() as u64;
+            self.received.lock().unwrap().push(Received::Data(sn));
+        }
+
+        fn on_data_frag(&self, _state: &ReceiverState, data_frag: &DataFragSubmessage) {
+            self.received.lock().unwrap().push(Received::DataFrag(
+                data_frag.writerSN.low() as u64,
+                data_frag.fragmentStartingNum,
+            ));
+        }
+
+        fn on_heartbeat(&self, _state: &ReceiverState, heartbeat: &HeartbeatSubmessage) {
+            self.received.lock().unwrap().push(Received::Heartbeat(
+                heartbeat.firstSN.low() as u64,
+                heartbeat.lastSN.low() as u64,
+            ));
+        }
+
+        fn on_gap(&self, _state: &ReceiverState, gap: &GapSubmessage) {
+            self.received.lock().unwrap().push(Received::Gap(
+                gap.gapStart.low() as u64,
+                gap.gapList.base().low() as u64,
+            ));
+        }
+    }
+
+    // Remote reader listening on a loopback socket
+    struct RemoteReader {
+        attributes: RemoteEndpointAttributes,
+        socket: UdpSocket,
+        acknack_count: u32,
+    }
+
+    impl RemoteReader {
+        fn new(id: u8, reliabilityKind: ReliabilityKind_t) -> Self {
+            let mut locator = Locator_t::new_from_port(0);
+            setIPv4(&mut locator, "127.0.0.1").unwrap();
+            let (socket, locator) = open_input_channel(&locator).unwrap();
+            let mut guid = GUID_t::unknown();
+            guid.guidPrefix.value[0] = id;
+            guid.entityId =
+                EntityId_t::new_from_key_kind(1, TopicKind_t::NO_KEY.reader_entity_kind());
+            let mut attributes = RemoteEndpointAttributes::new(guid, reliabilityKind);
+            attributes.unicastLocatorList.push_back(locator);
+            RemoteReader {
+                attributes,
+                socket,
+                acknack_count: 0,
+            }
+        }
+
+        // Submessages received until the socket times out
+        fn receive(&self) -> Vec<Received> {
+            let reader = Arc::new(Reader::default());
+            let endpoints = Arc::new(EndpointRegistry::new());
+            endpoints.add_reader(self.attributes.guid.entityId, reader.clone());
+            let receiver = MessageReceiver::new(self.attributes.guid.guidPrefix, endpoints);
+            let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE];
+            while let Ok((length, source)) = self.socket.recv_from(&mut buffer) {
+                let mut locator = Locator_t::new_from_port(source.port() as u32);
+                setIPv4(&mut locator, "127.0.0.1").unwrap();
+                receiver
+                    .process_message(&buffer[..length], &locator)
+                    .unwrap();
+            }
+            let received = std::mem::take(&mut *reader.received.lock().unwrap());
+            received
+        }
+
+        fn acknack(&mut self, writer: &StatefulWriter, base: u64, requested: &[u64], now: Instant) {
+            let mut set = SequenceNumberSet_t::new(SequenceNumber_t::from(base));
+            for sn in requested {
+                set.add(SequenceNumber_t::from(*sn));
+            }
+            self.acknack_count += 1;
+            self.acknack_with_count(writer, set, self.acknack_count, now);
+        }
+
+        fn acknack_with_count(
+            &self,
+            writer: &StatefulWriter,
+            set: SequenceNumberSet_t,
+            count: u32,
+            now: Instant,
+        ) {
+            let acknack = AckNackSubmessage::new(
+                DEFAULT_ENDIANNESS,
+                self.attributes.guid.entityId,
+                writer.getGuid().entityId,
+                set,
+                count,
+                false,
+            );
+            let state = ReceiverState {
+                sourceVersion: c_ProtocolVersion,
+                sourceVendorId: c_VendorId_Unknown,
+                sourceGuidPrefix: self.attributes.guid.guidPrefix,
+                destGuidPrefix: writer.getGuid().guidPrefix,
+                timestamp: None,
+                sourceLocator: Locator_t::new_from_port(0),
+            };
+            writer.process_acknack(&state, &acknack, now);
+        }
+    }
+
+    fn writer(durabilityKind: DurabilityQosPolicyKind_t, times: WriterTimes) -> StatefulWriter {
+        writer_with_history(
+            durabilityKind,
+            times,
+            HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+        )
+    }
+
+    // Writer keeping 2 changes
+    fn writer_with_history(
+        durabilityKind: DurabilityQosPolicyKind_t,
+        times: WriterTimes,
+        historyKind: HistoryQosPolicyKind,
+    ) -> StatefulWriter {
+        let mut guid = GUID_t::unknown();
+        guid.guidPrefix.value[0] = 1;
+        guid.entityId = EntityId_t::new_from_key_kind(1, TopicKind_t::NO_KEY.writer_entity_kind());
+        let mut attributes = EndpointAttributes::new(
+            EndpointKind_t::WRITER,
+            TopicKind_t::NO_KEY,
+            ReliabilityKind_t::RELIABLE,
+        );
+        attributes.durabilityKind = durabilityKind;
+        let history = WriterHistory::new(
+            guid,
+            HistoryQosPolicy {
+                kind: historyKind,
+                depth: 2,
+            },
+            ResourceLimitsQosPolicy {
+                max_samples: 2,
+                max_instances: -1,
+                max_samples_per_instance: -1,
+            },
+            &ReliabilityQosPolicy {
+                kind: ReliabilityQosPolicyKind::RELIABLE_RELIABILITY_QOS,
+                max_blocking_time: Time_t::c_TimeZero,
+            },
+        );
+        StatefulWriter::new(
+            guid,
+            attributes,
+            history,
+            Arc::new(UdpSender::new().unwrap()),
+            Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, guid.guidPrefix),
+            times,
+        )
+        .unwrap()
+    }
+
+    fn write(writer: &StatefulWriter) -> SequenceNumber_t {
+        write_bytes(writer, &[1, 2, 3, 4])
+    }
+
+    fn write_bytes(writer: &StatefulWriter, bytes: &[u8]) -> SequenceNumber_t {
+        let mut change = writer.new_change(ChangeKind_t::ALIVE, InstanceHandle_t::new());
+        change
+            .serializedPayload
+            .begin_write(RepresentationId_t::CDR_LE, 0);
+        change.serializedPayload.write_bytes(bytes);
+        writer.add_change(change).unwrap()
+    }
+
+    #[test]
+    fn reliable_reader_test() {
+        let writer = writer(
+            DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS,
+            WriterTimes {
+                heartbeatPeriod: Time_t::new(1, 0),
+                nackResponseDelay: Time_t::new(0, 10_000_000),
+                nackSuppressionDuration: Time_t::new(0, 50_000_000),
+            },
+        );
+        let mut reader = RemoteReader::new(2, ReliabilityKind_t::RELIABLE);
+        let guid = reader.attributes.guid;
+        assert!(writer.matched_reader_add(&reader.attributes));
+        assert!(!writer.matched_reader_add(&reader.attributes));
+
+        let sn1 = write(&writer);
+        let sn2 = write(&writer);
+        let t0 = Instant::now();
+        assert_eq!(
+            vec![
+                Received::Data(1),
+                Received::Heartbeat(1, 1),
+                Received::Data(2),
+                Received::Heartbeat(1, 2)
+            ],
+            reader.receive()
+        );
+        let proxy = writer.matched_reader_lookup(&guid).unwrap();
+        assert_eq!(
+            Some(ChangeForReaderStatusKind::UNACKNOWLEDGED),
+            proxy.change_for_reader_status(sn2)
+        );
+        assert!(!writer.is_acked_by_all(sn1));
+
+        // Change 1 acknowledged, the NACK of change 2 is suppressed
+        reader.acknack(&writer, 2, &[2], t0);
+        assert!(writer.is_acked_by_all(sn1));
+        assert_eq!(Some(sn2), writer.history().get_seq_num_min());
+        assert_eq!(None, writer.next_nack_response());
+
+        // Duplicate ACKNACK are ignored
+        let mut set = SequenceNumberSet_t::new(sn2);
+        set.add(sn2);
+        let later = t0 + Duration::from_millis(100);
+        reader.acknack_with_count(&writer, set, reader.acknack_count, later);
+        assert_eq!(None, writer.next_nack_response());
+
+        // Sent again after nackResponseDelay
+        reader.acknack(&writer, 2, &[2], later);
+        let proxy = writer.matched_reader_lookup(&guid).unwrap();
+        assert_eq!(
+            vec![sn2],
+            proxy.changes_with_status(ChangeForReaderStatusKind::REQUESTED)
+        );
+        assert_eq!(
+            Some(later + Duration::from_millis(10)),
+            writer.next_nack_response()
+        );
+        assert_eq!(0, writer.send_nack_responses(later));
+        assert_eq!(
+            1,
+            writer.send_nack_responses(later + Duration::from_millis(10))
+        );
+        assert_eq!(
+            vec![Received::Data(2), Received::Heartbeat(2, 2)],
+            reader.receive()
+        );
+
+        // Change 2 replaced in the history: a GAP is sent instead
+        write(&writer);
+        write(&writer);
+        assert_eq!(
+            vec![
+                Received::Data(3),
+                Received::Heartbeat(2, 3),
+                Received::Data(4),
+                Received::Heartbeat(3, 4)
+            ],
+            reader.receive()
+        );
+        let later = later + Duration::from_millis(100);
+        reader.acknack(&writer, 2, &[2], later);
+        assert_eq!(
+            0,
+            writer.send_nack_responses(later + Duration::from_millis(10))
+        );
+        assert_eq!(
+            vec![Received::Gap(2, 3), Received::Heartbeat(3, 4)],
+            reader.receive()
+        );
+
+        assert_eq!(1, writer.send_heartbeat());
+        assert_eq!(vec![Received::Heartbeat(3, 4)], reader.receive());
+
+        // Everything acknowledged
+        reader.acknack(&writer, 5, &[], later);
+        assert!(writer.is_acked_by_all(SequenceNumber_t::from(4)));
+        assert!(writer.history().is_empty());
+        assert_eq!(0, writer.send_heartbeat());
+    }
+
+    #[test]
+    fn timed_events_test() {
+        let clock = Arc::new(VirtualClock::new());
+        let service = ResourceEvent::new(clock.clone());
+        let writer = Arc::new(writer(
+            DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS,
+            WriterTimes {
+                heartbeatPeriod: Time_t::new(1, 0),
+                nackResponseDelay: Time_t::new(0, 10_000_000),
+                nackSuppressionDuration: Time_t::c_TimeZero,
+            },
+        ));
+        writer.set_timed_events(&service);
+        let mut reader = RemoteReader::new(2, ReliabilityKind_t::RELIABLE);
+        assert!(writer.matched_reader_add(&reader.attributes));
+        assert_eq!(None, service.next_deadline());
+
+        // The HEARTBEAT are sent every period while the change is unacknowledged
+        write(&writer);
+        assert_eq!(
+            vec![Received::Data(1), Received::Heartbeat(1, 1)],
+            reader.receive()
+        );
+        for _ in 0..2 {
+            clock.advance(Duration::from_millis(999));
+            assert_eq!(0, service.run_due());
+            clock.advance(Duration::from_millis(1));
+            assert_eq!(1, service.run_due());
+            assert_eq!(vec![Received::Heartbeat(1, 1)], reader.receive());
+        }
+
+        // The NACK is answered after nackResponseDelay
+        reader.acknack(&writer, 1, &[1], clock.now());
+        assert_eq!(
+            Some(clock.now() + Duration::from_millis(10)),
+            service.next_deadline()
+        );
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(1, service.run_due());
+        assert_eq!(
+            vec![Received::Data(1), Received::Heartbeat(1, 1)],
+            reader.receive()
+        );
+
+        // Once everything is acknowledged, no HEARTBEAT is sent any more
+        reader.acknack(&writer, 2, &[], clock.now());
+        assert_eq!(None, service.next_deadline());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(0, service.run_due());
+        assert!(reader.receive().is_empty());
+    }
+
+    #[test]
+    fn keep_all_test() {
+        let writer = writer_with_history(
+            DurabilityQosPolicyKind_t::TRANSIENT_LOCAL_DURABILITY_QOS,
+            WriterTimes::default(),
+            HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+        );
+        let best_effort = RemoteReader::new(21, ReliabilityKind_t::BEST_EFFORT);
+        assert!(writer.matched_reader_add(&best_effort.attributes));
+
+        // Without reliable readers, a full history makes room right away
+        for n in 1..=3 {
+            assert_eq!(SequenceNumber_t::from(n), write(&writer));
+        }
+        assert_eq!(2, writer.history().len());
+        assert_eq!(
+            Some(SequenceNumber_t::from(2)),
+            writer.history().get_seq_num_min()
+        );
+
+        // With a reliable reader, once the oldest change is acknowledged
+        let mut reliable = RemoteReader::new(22, ReliabilityKind_t::RELIABLE);
+        assert!(writer.matched_reader_add(&reliable.attributes));
+        writer.send_any();
+        let mut change = writer.new_change(ChangeKind_t::ALIVE, InstanceHandle_t::new());
+        change
+            .serializedPayload
+            .begin_write(RepresentationId_t::CDR_LE, 0);
+        assert!(writer.add_change(change).is_err());
+        reliable.acknack(&writer, 3, &[], Instant::now());
+        assert_eq!(SequenceNumber_t::from(4), write(&writer));
+        assert_eq!(
+            Some(SequenceNumber_t::from(3)),
+            writer.history().get_seq_num_min()
+        );
+    }
+
+    #[test]
+    fn matched_readers_test() {
+        let writer = writer(
+            DurabilityQosPolicyKind_t::TRANSIENT_LOCAL_DURABILITY_QOS,
+            WriterTimes {
+                nackResponseDelay: Time_t::c_TimeZero,
+                ..WriterTimes::default()
+            },
+        );
+        let sn1 = write(&writer);
+        let mut reliable = RemoteReader::new(2, ReliabilityKind_t::RELIABLE);
+        let best_effort = RemoteReader::new(3, ReliabilityKind_t::BEST_EFFORT);
+
+        // Transient local: the history is sent to the late joiners
+        assert!(writer.matched_reader_add(&reliable.attributes));
+        assert_eq!(1, writer.send_any());
+        assert_eq!(
+            vec![Received::Data(1), Received::Heartbeat(1, 1)],
+            reliable.receive()
+        );
+        assert!(writer.matched_reader_add(&best_effort.attributes));
+        let sn2 = write(&writer);
+        assert_eq!(
+            vec![Received::Data(2), Received::Heartbeat(1, 2)],
+            reliable.receive()
+        );
+        assert_eq!(
+            vec![Received::Data(1), Received::Data(2)],
+            best_effort.receive()
+        );
+        let proxy = writer
+            .matched_reader_lookup(&best_effort.attributes.guid)
+            .unwrap();
+        assert_eq!(
+            Some(ChangeForReaderStatusKind::ACKNOWLEDGED),
+            proxy.change_for_reader_status(sn2)
+        );
+
+        // Without nackResponseDelay, the NACK is answered right away
+        reliable.acknack(&writer, 1, &[1], Instant::now());
+        assert_eq!(
+            vec![Received::Data(1), Received::Heartbeat(1, 2)],
+            reliable.receive()
+        );
+        assert!(!writer.is_acked_by_all(sn1));
+
+        // Acknowledged changes are kept for the late joiners
+        reliable.acknack(&writer, 3, &[], Instant::now());
+        assert!(writer.is_acked_by_all(sn2));
+        assert_eq!(2, writer.history().len());
+
+        assert!(writer.matched_reader_remove(&reliable.attributes.guid));
+        assert!(!writer.matched_reader_remove(&reliable.attributes.guid));
+        assert!(!writer.matched_reader_is_matched(&reliable.attributes.guid));
+        assert!(writer.matched_reader_is_matched(&best_effort.attributes.guid));
+    }
+    #[test]
+    fn flow_controller_test() {
+        let clock = Arc::new(VirtualClock::new());
+        let service = ResourceEvent::new(clock.clone());
+        let controller = FlowController::new(
+            FlowControllerDescriptor {
+                max_bytes_per_period: 1000,
+                period_ms: 10,
+                ..FlowControllerDescriptor::default()
+            },
+            &service,
+        );
+        let writer = Arc::new(writer(
+            DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS,
+            WriterTimes::default(),
+        ));
+        writer.set_flow_controller(&controller, 0);
+        let reader = RemoteReader::new(2, ReliabilityKind_t::RELIABLE);
+        assert!(writer.matched_reader_add(&reader.attributes));
+
+        // 2504 bytes: a fragment of 1000 bytes per period
+        let sn = write_bytes(&writer, &[7; 2500]);
+        assert_eq!(vec![Received::DataFrag(1, 1)], reader.receive());
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(1, service.run_due());
+        assert_eq!(vec![Received::DataFrag(1, 2)], reader.receive());
+
+        // Only the fragments already sent are sent again
+        let nack_frag = |fragments: &[u32], count: u32| {
+            let mut set = FragmentNumberSet_t::new(1);
+            for fragment in fragments {
+                set.add(*fragment);
+            }
+            let nack_frag = NackFragSubmessage::new(
+                DEFAULT_ENDIANNESS,
+                reader.attributes.guid.entityId,
+                writer.getGuid().entityId,
+                sn,
+                set,
+                count,
+            );
+            let state = ReceiverState {
+                sourceVersion: c_ProtocolVersion,
+                sourceVendorId: c_VendorId_Unknown,
+                sourceGuidPrefix: reader.attributes.guid.guidPrefix,
+                destGuidPrefix: writer.getGuid().guidPrefix,
+                timestamp: None,
+                sourceLocator: Locator_t::new_from_port(0),
+            };
+            writer.process_nack_frag(&state, &nack_frag)
+        };
+        assert_eq!(2, nack_frag(&[1, 2, 3], 1));
+        assert_eq!(
+            vec![Received::DataFrag(1, 1), Received::DataFrag(1, 2)],
+            reader.receive()
+        );
+
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(1, service.run_due());
+        assert_eq!(
+            vec![Received::DataFrag(1, 3), Received::Heartbeat(1, 1)],
+            reader.receive()
+        );
+        assert_eq!(0, controller.pending_samples());
+        assert_eq!(
+            Some(ChangeForReaderStatusKind::UNACKNOWLEDGED),
+            writer
+                .matched_reader_lookup(&reader.attributes.guid)
+                .unwrap()
+                .change_for_reader_status(sn)
+        );
+
+        // Duplicate NACK_FRAG are ignored
+        assert_eq!(1, nack_frag(&[3], 2));
+        assert_eq!(0, nack_frag(&[3], 2));
+        assert_eq!(vec![Received::DataFrag(1, 3)], reader.receive());
+    }
+
+    #[test]
+    fn batching_test() {
+        let clock = Arc::new(VirtualClock::new());
+        let service = ResourceEvent::new(clock.clone());
+        let writer = Arc::new(writer(
+            DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS,
+            WriterTimes::default(),
+        ));
+        writer.set_batching(
+            BatchingAttributes {
+                max_bytes: 0,
+                max_samples: 2,
+                max_delay: Duration::from_millis(5),
+            },
+            &service,
+        );
+        let reader = RemoteReader::new(2, ReliabilityKind_t::RELIABLE);
+        assert!(writer.matched_reader_add(&reader.attributes));
+
+        // Sent together when the batch is full, with a single HEARTBEAT
+        write(&writer);
+        assert_eq!(
+            Some(clock.now() + Duration::from_millis(5)),
+            service.next_deadline()
+        );
+        write(&writer);
+        assert_eq!(
+            vec![
+                Received::Data(1),
+                Received::Data(2),
+                Received::Heartbeat(1, 2)
+            ],
+            reader.receive()
+        );
+        assert_eq!(None, service.next_deadline());
+
+        // Sent after max_delay
+        write(&writer);
+        assert!(reader.receive().is_empty());
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(1, service.run_due());
+        assert_eq!(
+            vec![Received::Data(3), Received::Heartbeat(2, 3)],
+            reader.receive()
+        );
+        assert_eq!(0, writer.flush_batch());
+    }
+}
@@ -0,0 +1,515 @@
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::messages::message_receiver::{MessageReceiverListener, ReceiverState};
+use crate::rtps::messages::submessages::data::DataSubmessage;
+use crate::rtps::messages::submessages::data_frag::DataFragSubmessage;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use crate::rtps::structure::endpoint::*;
+use crate::rtps::structure::reader_history::ReaderHistory;
+use crate::rtps::structure::rtps_reader::{RTPSReader, ReaderListener};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+
+// Changes received from a writer
+#[derive(Default)]
+struct WriterState {
+    // Last change delivered or dropped, only the later ones are accepted
+    last_sequence_number: Option<SequenceNumber_t>,
+    // Change being reassembled from DATA_FRAG
+    partial: Option<CacheChange_t>,
+}
+
+/**
+ * @brief Best-effort stateless reader, as specified in the DDSI-RTPS 2.5 specification
+ *        chapter 8.4.11.1. It keeps no state per matched writer besides the last sequence
+ *        number received from it: duplicate and out-of-order changes are dropped, and the
+ *        skipped ones are reported as lost.
+ *        Changes are accepted from the matched writers, and from every writer with the
+ *        trusted entity id, such as the SPDP writers of the participants not discovered yet.
+ */
+pub struct StatelessReader {
+    guid: GUID_t,
+    attributes: EndpointAttributes,
+    history: Mutex<ReaderHistory>,
+    listener: RwLock<Option<Arc<dyn ReaderListener>>>,
+    trustedWriterEntityId: RwLock<Option<EntityId_t>>,
+    matched_writers: Mutex<BTreeMap<GUID_t, RemoteEndpointAttributes>>,
+    writers: Mutex<HashMap<GUID_t, WriterState>>,
+}
+
+impl StatelessReader {
+    /**
+     * @brief Create a stateless reader.
+     *
+     * @param guid GUID of the reader.
+     * @param attributes Attributes of the reader, it must be best effort.
+     * @param history History of the reader.
+     * @return The reader, an error if it is reliable.
+     */
+    pub fn new(
+        guid: GUID_t,
+        attributes: EndpointAttributes,
+        history: ReaderHistory,
+    ) -> Result<Self, RtpsError> {
+        if attributes.reliabilityKind != ReliabilityKind_t::BEST_EFFORT {
+            return Err(RtpsError::new("Stateless readers are best effort"));
+        }
+        Ok(StatelessReader {
+            guid,
+            attributes,
+            history: Mutex::new(history),
+            listener: RwLock::new(None),
+            trustedWriterEntityId: RwLock::new(None),
+            matched_writers: Mutex::new(BTreeMap::new()),
+            writers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /**
+     * @brief Accept the changes of writers that are not matched.
+     *
+     * @param writerId Entity id of the writers, c_EntityId_Unknown for every writer.
+     */
+    pub fn setTrustedWriter(&self, writerId: EntityId_t) {
+        *self.trustedWriterEntityId.write().unwrap() = Some(writerId);
+    }
+
+    fn accepts(&self, writerGUID: &GUID_t) -> bool {
+        match *self.trustedWriterEntityId.read().unwrap() {
+            Some(id) if id == EntityId_t::c_EntityId_Unknown || id == writerGUID.entityId => {
+                return true;
+            }
+            _ => {}
+        }
+        self.matched_writers
+            .lock()
+            .unwrap()
+            .contains_key(writerGUID)
+    }
+
+    // Deliver a complete change, unless an equal or later one was received from its writer
+    fn change_received(&self, change: CacheChange_t) {
+        let writerGUID = change.writerGUID;
+        let sequenceNumber = change.sequenceNumber;
+        let lost = {
+            let mut writers = self.writers.lock().unwrap();
+            let writer = writers.entry(writerGUID).or_default();
+            let lost = match writer.last_sequence_number {
+                Some(last) if sequenceNumber <= last => return,
+                Some(last) => (sequenceNumber - last).to64long() - 1,
+                None => 0,
+            };
+            writer.last_sequence_number = Some(sequenceNumber);
+            if let Some(partial) = &writer.partial {
+                if partial.sequenceNumber <= sequenceNumber {
+                    writer.partial = None;
+                }
+            }
+            lost
+        };
+
+        let listener = self.getListener();
+        if lost > 0 {
+            if let Some(listener) = &listener {
+                let lost = std::cmp::min(lost, i32::MAX as u64) as i32;
+                listener.on_sample_lost(&self.guid, &writerGUID, lost);
+            }
+        }
+        // Best effort: a change the history rejects is lost
+        let _ = self.deliver_change(change);
+    }
+}
+
+impl Endpoint for StatelessReader {
+    fn getGuid(&self) -> &GUID_t {
+        &self.guid
+    }
+
+    fn getAttributes(&self) -> &EndpointAttributes {
+        &self.attributes
+    }
+}
+
+impl MessageReceiverListener for StatelessReader {
+    fn on_data(&self, state: &ReceiverState, data: &DataSubmessage) {
+        let writerGUID = GUID_t {
+            guidPrefix: state.sourceGuidPrefix,
+            entityId: data.writerId,
+        };
+        if !self.accepts(&writerGUID) {
+            return;
+        }
+        if let Ok(change) = data.to_cache_change(
+            state.sourceGuidPrefix,
+            state.timestamp,
+            self.attributes.payloadPool.as_deref(),
+        ) {
+            self.change_received(change);
+        }
+    }
+
+    /**
+     * @brief Add the fragments to the change of their writer being reassembled, and deliver
+     *        it once complete. Only the latest change of a writer is reassembled: the
+     *        fragments of a later change replace it.
+     */
+    fn on_data_frag(&self, state: &ReceiverState, data_frag: &DataFragSubmessage) {
+        let writerGUID = GUID_t {
+            guidPrefix: state.sourceGuidPrefix,
+            entityId: data_frag.writerId,
+        };
+        if !self.accepts(&writerGUID) {
+            return;
+        }
+        let complete = {
+            let mut writers = self.writers.lock().unwrap();
+            let writer = writers.entry(writerGUID).or_default();
+            if let Some(last) = writer.last_sequence_number {
+                if data_frag.writerSN <= last {
+                    return;
+                }
+            }
+            match &writer.partial {
+                Some(partial) if partial.sequenceNumber > data_frag.writerSN => return,
+                Some(partial)
+                    if partial.sequenceNumber == data_frag.writerSN
+                        && partial.getFragmentSize() == data_frag.fragmentSize
+                        && partial.serializedPayload.length() == data_frag.sampleSize as usize => {}
+                _ => match data_frag.to_cache_change(
+                    state.sourceGuidPrefix,
+                    state.timestamp,
+                    self.attributes.maxSampleSize,
+                    self.attributes.payloadPool.as_deref(),
+                ) {
+                    Ok(change) => writer.partial = Some(change),
+                    Err(_) => return,
+                },
+            }
+            let partial = writer.partial.as_mut().unwrap();
+            if !partial.add_fragments(
+                &data_frag.fragments,
+                data_frag.fragmentStartingNum,
+                data_frag.fragmentsInSubmessage as u32,
+            ) || !partial.is_fully_assembled()
+            {
+                return;
+            }
+            writer.partial.take()
+        };
+        if let Some(mut change) = complete {
+            change.serializedPayload.begin_read();
+            self.change_received(change);
+        }
+    }
+
+    fn on_intraprocess_change(&self, _state: &ReceiverState, change: &CacheChange_t) {
+        if self.accepts(&change.writerGUID) {
+            let mut shared = CacheChange_t::new();
+            shared.share(change);
+            self.change_received(shared);
+        }
+    }
+}
+
+impl RTPSReader for StatelessReader {
+    fn history(&self) -> &Mutex<ReaderHistory> {
+        &self.history
+    }
+
+    fn getListener(&self) -> Option<Arc<dyn ReaderListener>> {
+        self.listener.read().unwrap().clone()
+    }
+
+    fn setListener(&self, listener: Option<Arc<dyn ReaderListener>>) {
+        *self.listener.write().unwrap() = listener;
+    }
+
+    fn matched_writer_add(&self, writer: &RemoteEndpointAttributes) -> bool {
+        {
+            let mut matched_writers = self.matched_writers.lock().unwrap();
+            if matched_writers.contains_key(&writer.guid) {
+                return false;
+            }
+            matched_writers.insert(writer.guid, writer.clone());
+        }
+        if let Some(listener) = self.getListener() {
+            listener.on_reader_matched(&self.guid, &writer.guid, true);
+        }
+        true
+    }
+
+    // The instances of the writer lose it, and the changes being reassembled are dropped
+    fn matched_writer_remove(&self, writerGuid: &GUID_t) -> bool {
+        if self
+            .matched_writers
+            .lock()
+            .unwrap()
+            .remove(writerGuid)
+            .is_none()
+        {
+            return false;
+        }
+        self.writers.lock().unwrap().remove(writerGuid);
+        self.history.lock().unwrap().writer_removed(writerGuid);
+        if let Some(listener) = self.getListener() {
+            listener.on_reader_matched(&self.guid, writerGuid, false);
+        }
+        true
+    }
+
+    fn matched_writer_is_matched(&self, writerGuid: &GUID_t) -> bool {
+        self.matched_writers
+            .lock()
+            .unwrap()
+            .contains_key(writerGuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdr::encoding::Endianness;
+    use crate::dds::core::policy::*;
+    use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+    use crate::rtps::common::locator::Locator_t;
+    use crate::rtps::common::protocol_version::c_ProtocolVersion;
+    use crate::rtps::common::serialized_payload::*;
+    use crate::rtps::common::time::Time_t;
+    use crate::rtps::common::vendor_id::c_VendorId_Unknown;
+    use crate::rtps::messages::message_header::Header_t;
+    use crate::rtps::messages::message_receiver::*;
+
+    #[derive(Default)]
+    struct Listener {
+        added: Mutex<Vec<u32>>,
+        lost: Mutex<Vec<i32>>,
+        matched: Mutex<Vec<bool>>,
+    }
+
+    impl ReaderListener for Listener {
+        fn on_new_cache_change_added(
+            &self,
+            _reader: &GUID_t,
+            _writerGUID: &GUID_t,
+            sequenceNumber: SequenceNumber_t,
+        ) {
+            self.added.lock().unwrap().push(sequenceNumber.low());
+        }
+
+        fn on_sample_lost(&self, _reader: &GUID_t, _writerGUID: &GUID_t, count: i32) {
+            self.lost.lock().unwrap().push(count);
+        }
+
+        fn on_reader_matched(&self, _reader: &GUID_t, _writerGUID: &GUID_t, matched: bool) {
+            self.matched.lock().unwrap().push(matched);
+        }
+    }
+
+    fn prefix(id: u8) -> GuidPrefix_t {
+        let mut prefix = GuidPrefix_t::unknown();
+        prefix.value[0] = id;
+        prefix
+    }
+
+    fn reader() -> (StatelessReader, Arc<Listener>) {
+        let mut guid = GUID_t::unknown();
+        guid.guidPrefix = prefix(1);
+        guid.entityId = EntityId_t::new_from_key_kind(1, TopicKind_t::NO_KEY.reader_entity_kind());
+        let history = ReaderHistory::new(
+            HistoryQosPolicy {
+                kind: HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+                depth: 1,
+            },
+            ResourceLimitsQosPolicy {
+                max_samples: -1,
+                max_instances: -1,
+                max_samples_per_instance: -1,
+            },
+        );
+        let reader = StatelessReader::new(
+            guid,
+            EndpointAttributes::new(
+                EndpointKind_t::READER,
+                TopicKind_t::NO_KEY,
+                ReliabilityKind_t::BEST_EFFORT,
+            ),
+            history,
+        )
+        .unwrap();
+        let listener = Arc::new(Listener::default());
+        reader.setListener(Some(listener.clone()));
+        (reader, listener)
+    }
+
+    fn state(id: u8) -> ReceiverState {
+        ReceiverState {
+            sourceVersion: c_ProtocolVersion,
+            sourceVendorId: c_VendorId_Unknown,
+            sourceGuidPrefix: prefix(id),
+            destGuidPrefix: prefix(1),
+            timestamp: Some(Time_t::new(10, 0)),
+            sourceLocator: Locator_t::new_from_port(7411),
+        }
+    }
+
+    fn writer_guid(id: u8) -> GUID_t {
+        GUID_t {
+            guidPrefix: prefix(id),
+            entityId: EntityId_t::new_from_key_kind(1, TopicKind_t::NO_KEY.writer_entity_kind()),
+        }
+    }
+
+    fn payload(length: usize) -> SerializedPayload_t {
+        let mut payload = SerializedPayload_t::default();
+        payload.begin_write(RepresentationId_t::CDR_LE, 0);
+        payload.write_bytes(&(0..length).map(|n| n as u8).collect::<Vec<u8>>());
+        payload
+    }
+
+    fn data(sn: u64) -> DataSubmessage {
+        DataSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            writer_guid(2).entityId,
+            SequenceNumber_t::from(sn),
+            payload(4),
+        )
+    }
+
+    // Sequence numbers in the history, per writer
+    fn received(reader: &StatelessReader, id: u8) -> Vec<u32> {
+        let history = reader.history().lock().unwrap();
+        history
+            .cache()
+            .changes()
+            .filter(|change| change.writerGUID.guidPrefix == prefix(id))
+            .map(|change| change.sequenceNumber.low())
+            .collect()
+    }
+
+    #[test]
+    fn data_test() {
+        let (reader, listener) = reader();
+        let writer = RemoteEndpointAttributes::new(writer_guid(2), ReliabilityKind_t::RELIABLE);
+
+        // Not matched
+        reader.on_data(&state(2), &data(1));
+        assert!(received(&reader, 2).is_empty());
+
+        assert!(reader.matched_writer_add(&writer));
+        assert!(!reader.matched_writer_add(&writer));
+        for sn in [1, 2, 2, 5, 3, 6] {
+            reader.on_data(&state(2), &data(sn));
+        }
+        assert_eq!(vec![1, 2, 5, 6], received(&reader, 2));
+        assert_eq!(vec![1, 2, 5, 6], *listener.added.lock().unwrap());
+        assert_eq!(vec![2], *listener.lost.lock().unwrap());
+        {
+            let history = reader.history().lock().unwrap();
+            let change = history.cache().changes().next().unwrap();
+            assert_eq!(writer_guid(2), change.writerGUID);
+            assert_eq!(Time_t::new(10, 0), change.sourceTimestamp);
+            assert_eq!(payload(4).data(), change.serializedPayload.data());
+        }
+
+        // Trusted writers of other participants
+        reader.on_data(&state(3), &data(1));
+        assert!(received(&reader, 3).is_empty());
+        reader.setTrustedWriter(writer_guid(3).entityId);
+        reader.on_data(&state(3), &data(1));
+        assert_eq!(vec![1], received(&reader, 3));
+
+        assert!(reader.matched_writer_remove(&writer.guid));
+        assert!(!reader.matched_writer_remove(&writer.guid));
+        assert_eq!(vec![true, false], *listener.matched.lock().unwrap());
+        // Still accepted as a trusted writer, starting over
+        reader.on_data(&state(2), &data(8));
+        assert_eq!(vec![1, 2, 5, 6, 8], received(&reader, 2));
+        assert_eq!(vec![2], *listener.lost.lock().unwrap());
+
+        // Gaps beyond the low word of the sequence numbers, reported up to i32::MAX
+        reader.on_data(&state(2), &data((1 << 32) + 9));
+        reader.on_data(&state(2), &data((1 << 32) + 12));
+        assert_eq!(vec![2, i32::MAX, 2], *listener.lost.lock().unwrap());
+    }
+
+    #[test]
+    fn data_frag_test() {
+        let (reader, listener) = reader();
+        reader.setTrustedWriter(EntityId_t::c_EntityId_Unknown);
+        let sample = payload(246);
+        let frag = |sn: u64, start: u32| {
+            DataFragSubmessage::new(
+                Endianness::LITTLE_ENDIANNESS,
+                EntityId_t::c_EntityId_Unknown,
+                writer_guid(2).entityId,
+                SequenceNumber_t::from(sn),
+                sample.data(),
+                100,
+                start,
+                1,
+            )
+            .unwrap()
+        };
+
+        // Routed by the message receiver, fragments out of order
+        let endpoints = Arc::new(EndpointRegistry::new());
+        let reader = Arc::new(reader);
+        endpoints.add_reader(reader.getGuid().entityId, reader.clone());
+        let receiver = MessageReceiver::new(prefix(1), endpoints);
+        let mut message = Vec::new();
+        Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, prefix(2)).write(&mut message);
+        for start in [3, 1] {
+            frag(1, start).write(&mut message).unwrap();
+        }
+        receiver
+            .process_message(&message, &Locator_t::new_from_port(7411))
+            .unwrap();
+        assert!(received(&reader, 2).is_empty());
+        reader.on_data_frag(&state(2), &frag(1, 2));
+        assert_eq!(vec![1], received(&reader, 2));
+        {
+            let history = reader.history().lock().unwrap();
+            let change = history.cache().changes().next().unwrap();
+            assert_eq!(sample.data(), change.serializedPayload.data());
+            assert_eq!(
+                Some(RepresentationId_t::CDR_LE),
+                change.serializedPayload.representation_id()
+            );
+        }
+
+        // Duplicate fragment, then a later change replacing the one being reassembled
+        reader.on_data_frag(&state(2), &frag(1, 1));
+        reader.on_data_frag(&state(2), &frag(2, 1));
+        reader.on_data_frag(&state(2), &frag(3, 1));
+        reader.on_data_frag(&state(2), &frag(2, 2));
+        for start in [2, 3] {
+            reader.on_data_frag(&state(2), &frag(3, start));
+        }
+        assert_eq!(vec![1, 3], received(&reader, 2));
+        assert_eq!(vec![1], *listener.lost.lock().unwrap());
+        // A complete DATA of a later change too
+        reader.on_data_frag(&state(2), &frag(4, 1));
+        reader.on_data(&state(2), &data(5));
+        reader.on_data_frag(&state(2), &frag(4, 2));
+        reader.on_data_frag(&state(2), &frag(4, 3));
+        assert_eq!(vec![1, 3, 5], received(&reader, 2));
+        assert_eq!(vec![1, 1], *listener.lost.lock().unwrap());
+
+        // A sample larger than the reader accepts is dropped before allocating it
+        let mut oversized = frag(6, 1);
+        oversized.fragmentSize = 1;
+        oversized.sampleSize = u32::MAX;
+        oversized.fragments = vec![0];
+        reader.on_data_frag(&state(2), &oversized);
+        assert!(reader.writers.lock().unwrap()[&writer_guid(2)]
+            .partial
+            .is_none());
+        reader.on_data_frag(&state(2), &frag(6, 1));
+        assert!(reader.writers.lock().unwrap()[&writer_guid(2)]
+            .partial
+            .is_some());
+    }
+}
@@ -0,0 +1,837 @@
+use crate::dds::core::policy::DurabilityQosPolicyKind_t;
+use crate::rtps::behavior::batching::{BatchingWriter, SampleBatcher};
+use crate::rtps::behavior::timed_event::{ResourceEvent, TimedEvent};
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::locator::Locator_t;
+use crate::rtps::common::locator_list::LocatorList;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::common::time::Duration_t;
+use crate::rtps::flowcontrol::flow_controller::*;
+use crate::rtps::messages::message_group::RTPSMessageGroup;
+use crate::rtps::messages::message_header::Header_t;
+use crate::rtps::messages::message_receiver::MessageReceiverListener;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use crate::rtps::structure::endpoint::*;
+use crate::rtps::structure::intraprocess::{find_local_reader, intraprocess_state};
+use crate::rtps::structure::rtps_writer::RTPSWriter;
+use crate::rtps::structure::writer_history::WriterHistory;
+use crate::rtps::transport::udp_transport::UdpSender;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, Weak};
+
+/**
+ * @brief Locator the changes of a stateless writer are sent to, as specified in the
+ *        DDSI-RTPS 2.5 specification chapter 8.4.7.5.
+ */
+#[derive(Clone, Debug)]
+pub struct ReaderLocator {
+    pub locator: Locator_t,
+    pub expectsInlineQos: bool,
+    // Unsent changes, with the first fragment left to send
+    unsent_changes: BTreeMap<SequenceNumber_t, u32>,
+    // Matched readers reached through this locator
+    readers: BTreeSet<GUID_t>,
+    // Added with reader_locator_add, kept when no matched reader uses it
+    fixed: bool,
+}
+
+impl ReaderLocator {
+    fn new(locator: Locator_t, expectsInlineQos: bool) -> Self {
+        ReaderLocator {
+            locator,
+            expectsInlineQos,
+            unsent_changes: BTreeMap::new(),
+            readers: BTreeSet::new(),
+            fixed: false,
+        }
+    }
+
+    // Changes not sent yet to the locator, in order
+    pub fn unsent_changes(&self) -> impl Iterator<Item = &SequenceNumber_t> {
+        self.unsent_changes.keys()
+    }
+}
+
+/**
+ * @brief Best-effort stateless writer, as specified in the DDSI-RTPS 2.5 specification
+ *        chapter 8.4.8.1. It keeps no state per remote reader: every change is sent once to
+ *        each reader locator, and ACKNACK are ignored.
+ *        The whole history is sent again every resendDataPeriod by the timed event of
+ *        set_timed_events, for announcements such as the SPDP ones that late joiners must
+ *        receive, or with unsent_changes_reset. Only the
+ *        new changes are batched and flow controlled, the history sent again is not.
+ *        Readers in the same process are handed the changes directly, sharing their payload,
+ *        as soon as they are added to the history. Their locators are not sent to, and the
+ *        history is not sent to them again.
+ */
+pub struct StatelessWriter {
+    guid: GUID_t,
+    attributes: EndpointAttributes,
+    history: WriterHistory,
+    sender: Arc<UdpSender>,
+    header: Header_t,
+    resendDataPeriod: Duration_t,
+    // Sends the new changes at a limited rate, None to send them right away
+    flow_controller: Mutex<Option<Arc<FlowController>>>,
+    // Collects the new changes to send them together, None to send them one by one
+    batcher: Mutex<Option<Arc<SampleBatcher>>>,
+    reader_locators: Mutex<Vec<ReaderLocator>>,
+    matched_readers: Mutex<BTreeMap<GUID_t, RemoteEndpointAttributes>>,
+    // Matched readers of the same process, handed the changes directly
+    local_readers: Mutex<BTreeMap<GUID_t, Weak<dyn MessageReceiverListener>>>,
+    // Sends the history again every resendDataPeriod, None until set_timed_events
+    resend: Mutex<Option<TimedEvent>>,
+}
+
+impl StatelessWriter {
+    /**
+     * @brief Create a stateless writer.
+     *
+     * @param guid GUID of the writer.
+     * @param attributes Attributes of the writer, it must be best effort.
+     * @param history History of the writer.
+     * @param sender Sockets of the participant.
+     * @param header Header of the messages of the participant.
+     * @param resendDataPeriod Period the history is sent again with, c_TimeInfinite for never.
+     * @return The writer, an error if it is reliable.
+     */
+    pub fn new(
+        guid: GUID_t,
+        attributes: EndpointAttributes,
+        history: WriterHistory,
+        sender: Arc<UdpSender>,
+        header: Header_t,
+        resendDataPeriod: Duration_t,
+    ) -> Result<Self, RtpsError> {
+        if attributes.reliabilityKind != ReliabilityKind_t::BEST_EFFORT {
+            return Err(RtpsError::new("Stateless writers are best effort"));
+        }
+        Ok(StatelessWriter {
+            guid,
+            attributes,
+            history,
+            sender,
+            header,
+            resendDataPeriod,
+            flow_controller: Mutex::new(None),
+            batcher: Mutex::new(None),
+            reader_locators: Mutex::new(Vec::new()),
+            matched_readers: Mutex::new(BTreeMap::new()),
+            local_readers: Mutex::new(BTreeMap::new()),
+            resend: Mutex::new(None),
+        })
+    }
+
+    pub fn resendDataPeriod(&self) -> Duration_t {
+        self.resendDataPeriod
+    }
+
+    /**
+     * @brief Send the whole history again every resendDataPeriod with a timed event of a
+     *        service. Nothing is done for a c_TimeInfinite period.
+     *
+     * @param service Service running the event.
+     */
+    pub fn set_timed_events(self: &Arc<Self>, service: &ResourceEvent) {
+        let period = match self.resendDataPeriod.to_duration() {
+            Some(period) => period,
+            None => return,
+        };
+        let weak = Arc::downgrade(self);
+        let event = TimedEvent::new(service, period, move |_| match weak.upgrade() {
+            Some(writer) => {
+                writer.unsent_changes_reset();
+                writer.send_any();
+                true
+            }
+            None => false,
+        });
+        event.restart_timer();
+        *self.resend.lock().unwrap() = Some(event);
+    }
+
+    // Add a locator, the changes already in the history are sent to it for TRANSIENT_LOCAL writers
+    fn add_locator(
+        &self,
+        reader_locators: &mut Vec<ReaderLocator>,
+        locator: Locator_t,
+        expectsInlineQos: bool,
+    ) -> usize {
+        if let Some(index) = reader_locators.iter().position(|r| r.locator == locator) {
+            reader_locators[index].expectsInlineQos |= expectsInlineQos;
+            return index;
+        }
+        let mut reader_locator = ReaderLocator::new(locator, expectsInlineQos);
+        if self.attributes.durabilityKind != DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS {
+            self.history.with_cache(|cache| {
+                for change in cache.changes() {
+                    reader_locator
+                        .unsent_changes
+                        .insert(change.sequenceNumber, 1);
+                }
+            });
+        }
+        reader_locators.push(reader_locator);
+        reader_locators.len() - 1
+    }
+
+    /**
+     * @brief Add a locator the changes are sent to.
+     *
+     * @param locator Locator of the readers.
+     * @param expectsInlineQos Whether the readers expect the inline QoS.
+     * @return False if the locator was already added.
+     */
+    pub fn reader_locator_add(&self, locator: Locator_t, expectsInlineQos: bool) -> bool {
+        let mut reader_locators = self.reader_locators.lock().unwrap();
+        if reader_locators
+            .iter()
+            .any(|r| r.locator == locator && r.fixed)
+        {
+            return false;
+        }
+        let index = self.add_locator(&mut reader_locators, locator, expectsInlineQos);
+        reader_locators[index].fixed = true;
+        true
+    }
+
+    // Stop sending changes to a locator added with reader_locator_add
+    pub fn reader_locator_remove(&self, locator: &Locator_t) -> bool {
+        let mut reader_locators = self.reader_locators.lock().unwrap();
+        match reader_locators
+            .iter()
+            .position(|r| r.locator == *locator && r.fixed)
+        {
+            Some(index) => {
+                if reader_locators[index].readers.is_empty() {
+                    reader_locators.remove(index);
+                } else {
+                    reader_locators[index].fixed = false;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn reader_locators(&self) -> Vec<ReaderLocator> {
+        self.reader_locators.lock().unwrap().clone()
+    }
+
+    // Send every change of the history again to every locator
+    pub fn unsent_changes_reset(&self) {
+        let sequenceNumbers: Vec<SequenceNumber_t> = self
+            .history
+            .with_cache(|cache| cache.changes().map(|c| c.sequenceNumber).collect());
+        let mut reader_locators = self.reader_locators.lock().unwrap();
+        for reader_locator in reader_locators.iter_mut() {
+            for sequenceNumber in &sequenceNumbers {
+                reader_locator.unsent_changes.insert(*sequenceNumber, 1);
+            }
+        }
+    }
+
+    /**
+     * @brief Send unsent changes to a locator within a budget. When the budget runs out
+     *        during a change, the fragment it stopped at is kept and the next changes are not
+     *        sent.
+     *
+     * @return The number of changes completely sent, and whether every change was.
+     */
+    fn send_changes(
+        &self,
+        reader_locator: &mut ReaderLocator,
+        sequenceNumbers: &[SequenceNumber_t],
+        budget: &mut SendBudget,
+    ) -> (usize, bool) {
+        let mut group = RTPSMessageGroup::new(
+            &self.sender,
+            self.header,
+            LocatorList::from(vec![reader_locator.locator]),
+        );
+        let maxPayload = self.max_payload();
+        let mut sent = 0;
+        for sequenceNumber in sequenceNumbers {
+            let next_fragment = match reader_locator.unsent_changes.remove(sequenceNumber) {
+                Some(next_fragment) => next_fragment,
+                None => continue,
+            };
+            let added = self.history.with_change(*sequenceNumber, |change| {
+                group.add_change(
+                    change,
+                    EntityId_t::c_EntityId_Unknown,
+                    maxPayload,
+                    next_fragment,
+                    budget,
+                )
+            });
+            match added {
+                Some(Ok(None)) => sent += 1,
+                Some(Ok(Some(next_fragment))) => {
+                    reader_locator
+                        .unsent_changes
+                        .insert(*sequenceNumber, next_fragment);
+                    return (sent, false);
+                }
+                // Best effort: a change that cannot be sent is lost
+                _ => {}
+            }
+        }
+        (sent, true)
+    }
+
+    /**
+     * @brief Hand changes over to the readers of the same process. The writer must not be
+     *        locked, since the readers may write to it right away.
+     *
+     * @param readers Readers to hand the changes to, all the local readers if None.
+     * @param sequenceNumbers Sequence numbers of the changes, the ones no longer in the
+     *        history are skipped.
+     */
+    fn hand_over(&self, readers: Option<&[GUID_t]>, sequenceNumbers: &[SequenceNumber_t]) {
+        let readers: Vec<(GUID_t, Arc<dyn MessageReceiverListener>)> = self
+            .local_readers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(guid, _)| readers.is_none_or(|readers| readers.contains(guid)))
+            .filter_map(|(guid, reader)| reader.upgrade().map(|reader| (*guid, reader)))
+            .collect();
+        if readers.is_empty() {
+            return;
+        }
+        for sequenceNumber in sequenceNumbers {
+            let shared = self.history.with_change(*sequenceNumber, |change| {
+                let mut shared = CacheChange_t::new();
+                shared.share(change);
+                shared
+            });
+            let change = match shared {
+                Some(change) => change,
+                None => continue,
+            };
+            for (guid, reader) in &readers {
+                let state = intraprocess_state(
+                    self.guid.guidPrefix,
+                    guid.guidPrefix,
+                    Some(change.sourceTimestamp),
+                );
+                reader.on_intraprocess_change(&state, &change);
+            }
+        }
+    }
+
+    /**
+     * @brief Send the unsent changes to each locator, without flow control. Changes removed
+     *        from the history in the meantime are skipped.
+     *
+     * @return The number of changes sent.
+     */
+    pub fn send_any(&self) -> usize {
+        let mut sent = 0;
+        let mut reader_locators = self.reader_locators.lock().unwrap();
+        for reader_locator in reader_locators.iter_mut() {
+            let unsent: Vec<SequenceNumber_t> =
+                reader_locator.unsent_changes.keys().copied().collect();
+            sent += self
+                .send_changes(reader_locator, &unsent, &mut SendBudget::unlimited())
+                .0;
+        }
+        sent
+    }
+}
+
+impl Endpoint for StatelessWriter {
+    fn getGuid(&self) -> &GUID_t {
+        &self.guid
+    }
+
+    fn getAttributes(&self) -> &EndpointAttributes {
+        &self.attributes
+    }
+}
+
+// Best-effort writers ignore ACKNACK
+impl MessageReceiverListener for StatelessWriter {}
+
+impl FlowControlledWriter for StatelessWriter {
+    // Send changes to the locators they are still unsent to, in a message group per locator
+    fn deliver_samples(
+        &self,
+        sequenceNumbers: &[SequenceNumber_t],
+        budget: &mut SendBudget,
+    ) -> bool {
+        let mut reader_locators = self.reader_locators.lock().unwrap();
+        reader_locators
+            .iter_mut()
+            .all(|reader_locator| self.send_changes(reader_locator, sequenceNumbers, budget).1)
+    }
+}
+
+impl BatchingWriter for StatelessWriter {
+    fn batcher(&self) -> &Mutex<Option<Arc<SampleBatcher>>> {
+        &self.batcher
+    }
+
+    fn flow_controller(&self) -> &Mutex<Option<Arc<FlowController>>> {
+        &self.flow_controller
+    }
+
+    fn send_unsent(&self) {
+        self.send_any();
+    }
+}
+
+impl RTPSWriter for StatelessWriter {
+    fn history(&self) -> &WriterHistory {
+        &self.history
+    }
+
+    fn unsent_change_added_to_history(&self, sequenceNumber: SequenceNumber_t) {
+        for reader_locator in self.reader_locators.lock().unwrap().iter_mut() {
+            reader_locator.unsent_changes.insert(sequenceNumber, 1);
+        }
+        self.hand_over(None, &[sequenceNumber]);
+        self.batch_new_change(sequenceNumber);
+    }
+
+    /**
+     * @brief Send the changes to the multicast locators of a reader, or to its unicast ones if
+     *        it has none. A reader of the same process is handed the changes directly
+     *        instead, the history first for TRANSIENT_LOCAL writers.
+     *
+     * @param reader Matched reader.
+     * @return False if the reader was already matched or is reliable.
+     */
+    fn matched_reader_add(&self, reader: &RemoteEndpointAttributes) -> bool {
+        if reader.reliabilityKind == ReliabilityKind_t::RELIABLE {
+            return false;
+        }
+        let mut matched_readers = self.matched_readers.lock().unwrap();
+        if matched_readers.contains_key(&reader.guid) {
+            return false;
+        }
+        matched_readers.insert(reader.guid, reader.clone());
+        if let Some(local) = find_local_reader(&self.guid, &reader.guid) {
+            self.local_readers
+                .lock()
+                .unwrap()
+                .insert(reader.guid, local);
+            drop(matched_readers);
+            if self.attributes.durabilityKind != DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS
+            {
+                let sequenceNumbers: Vec<SequenceNumber_t> = self
+                    .history
+                    .with_cache(|cache| cache.changes().map(|c| c.sequenceNumber).collect());
+                self.hand_over(Some(&[reader.guid]), &sequenceNumbers);
+            }
+            return true;
+        }
+        let locators = if reader.multicastLocatorList.is_empty() {
+            &reader.unicastLocatorList
+        } else {
+            &reader.multicastLocatorList
+        };
+        let mut reader_locators = self.reader_locators.lock().unwrap();
+        for locator in locators {
+            let index = self.add_locator(&mut reader_locators, *locator, reader.expectsInlineQos);
+            reader_locators[index].readers.insert(reader.guid);
+        }
+        true
+    }
+
+    fn matched_reader_remove(&self, readerGuid: &GUID_t) -> bool {
+        if self
+            .matched_readers
+            .lock()
+            .unwrap()
+            .remove(readerGuid)
+            .is_none()
+        {
+            return false;
+        }
+        self.local_readers.lock().unwrap().remove(readerGuid);
+        let mut reader_locators = self.reader_locators.lock().unwrap();
+        for reader_locator in reader_locators.iter_mut() {
+            reader_locator.readers.remove(readerGuid);
+        }
+        reader_locators.retain(|r| r.fixed || !r.readers.is_empty());
+        true
+    }
+
+    fn matched_reader_is_matched(&self, readerGuid: &GUID_t) -> bool {
+        self.matched_readers
+            .lock()
+            .unwrap()
+            .contains_key(readerGuid)
+    }
+
+    // Nothing is acknowledged in best effort, no change has to be kept for a reader
+    fn is_acked_by_all(&self, _sequenceNumber: SequenceNumber_t) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dds::core::policy::*;
+    use crate::rtps::behavior::batching::BatchingAttributes;
+    use crate::rtps::behavior::timed_event::VirtualClock;
+    use crate::rtps::common::change_kind_t::ChangeKind_t;
+    use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+    use crate::rtps::common::instance_handle::InstanceHandle_t;
+    use crate::rtps::common::protocol_version::c_ProtocolVersion;
+    use crate::rtps::common::sequence_number_set::SequenceNumberSet_t;
+    use crate::rtps::common::serialized_payload::RepresentationId_t;
+    use crate::rtps::common::time::Time_t;
+    use crate::rtps::common::vendor_id::c_VendorId_Unknown;
+    use crate::rtps::messages::message_group::DEFAULT_ENDIANNESS;
+    use crate::rtps::messages::message_receiver::*;
+    use crate::rtps::messages::submessages::acknack::AckNackSubmessage;
+    use crate::rtps::messages::submessages::data::DataSubmessage;
+    use crate::rtps::structure::intraprocess::{register_participant, unregister_participant};
+    use crate::rtps::transport::udp_transport::*;
+    use crate::rtps::utils::ip_locator::setIPv4;
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Reader {
+        received: Mutex<Vec<SequenceNumber_t>>,
+    }
+
+    impl MessageReceiverListener for Reader {
+        fn on_data(&self, _state: &ReceiverState, data: &DataSubmessage) {
+            self.received.lock().unwrap().push(data.writerSN);
+        }
+    }
+
+    struct Input {
+        socket: UdpSocket,
+        locator: Locator_t,
+    }
+
+    impl Input {
+        fn new() -> Self {
+            let mut locator = Locator_t::new_from_port(0);
+            setIPv4(&mut locator, "127.0.0.1").unwrap();
+            let (socket, locator) = open_input_channel(&locator).unwrap();
+            Input { socket, locator }
+        }
+
+        // Sequence numbers received until the socket times out
+        fn receive(&self) -> Vec<SequenceNumber_t> {
+            self.receive_messages().concat()
+        }
+
+        // Sequence numbers of each message received until the socket times out
+        fn receive_messages(&self) -> Vec<Vec<SequenceNumber_t>> {
+            let reader = Arc::new(Reader::default());
+            let endpoints = Arc::new(EndpointRegistry::new());
+            endpoints.add_reader(EntityId_t::c_EntityId_Unknown, reader.clone());
+            let receiver = MessageReceiver::new(GuidPrefix_t::unknown(), endpoints);
+            let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE];
+            let mut messages = Vec::new();
+            while let Ok((length, _)) = self.socket.recv_from(&mut buffer) {
+                receiver
+                    .process_message(&buffer[..length], &self.locator)
+                    .unwrap();
+                messages.push(std::mem::take(&mut *reader.received.lock().unwrap()));
+            }
+            messages
+        }
+    }
+
+    fn writer(durabilityKind: DurabilityQosPolicyKind_t) -> StatelessWriter {
+        writer_with_resend(durabilityKind, Time_t::new(3, 0))
+    }
+
+    fn writer_with_resend(
+        durabilityKind: DurabilityQosPolicyKind_t,
+        resendDataPeriod: Duration_t,
+    ) -> StatelessWriter {
+        let mut guid = GUID_t::unknown();
+        guid.entityId = EntityId_t::new_from_key_kind(1, TopicKind_t::NO_KEY.writer_entity_kind());
+        let mut attributes = EndpointAttributes::new(
+            EndpointKind_t::WRITER,
+            TopicKind_t::NO_KEY,
+            ReliabilityKind_t::BEST_EFFORT,
+        );
+        attributes.durabilityKind = durabilityKind;
+        let history = WriterHistory::new(
+            guid,
+            HistoryQosPolicy {
+                kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+                depth: 2,
+            },
+            ResourceLimitsQosPolicy {
+                max_samples: -1,
+                max_instances: -1,
+                max_samples_per_instance: -1,
+            },
+            &ReliabilityQosPolicy {
+                kind: ReliabilityQosPolicyKind::BEST_EFFORT_RELIABILITY_QOS,
+                max_blocking_time: Time_t::c_TimeZero,
+            },
+        );
+        StatelessWriter::new(
+            guid,
+            attributes,
+            history,
+            Arc::new(UdpSender::new().unwrap()),
+            Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, guid.guidPrefix),
+            resendDataPeriod,
+        )
+        .unwrap()
+    }
+
+    fn write(writer: &StatelessWriter) -> SequenceNumber_t {
+        let mut change = writer.new_change(ChangeKind_t::ALIVE, InstanceHandle_t::new());
+        change
+            .serializedPayload
+            .begin_write(RepresentationId_t::CDR_LE, 0);
+        change.serializedPayload.write_bytes(&[1, 2, 3, 4]);
+        writer.add_change(change).unwrap()
+    }
+
+    #[test]
+    fn reader_locators_test() {
+        let writer = writer(DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS);
+        let first = Input::new();
+        let second = Input::new();
+        assert!(writer.reader_locator_add(first.locator, false));
+        assert!(!writer.reader_locator_add(first.locator, false));
+        assert!(writer.reader_locator_add(second.locator, false));
+
+        let sn1 = write(&writer);
+        let sn2 = write(&writer);
+        assert_eq!(vec![sn1, sn2], first.receive());
+        assert_eq!(vec![sn1, sn2], second.receive());
+
+        // ACKNACK requesting the changes again are ignored
+        let mut requested = SequenceNumberSet_t::new(sn1);
+        requested.add(sn1);
+        requested.add(sn2);
+        let acknack = AckNackSubmessage::new(
+            DEFAULT_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            writer.getGuid().entityId,
+            requested,
+            1,
+            false,
+        );
+        let state = ReceiverState {
+            sourceVersion: c_ProtocolVersion,
+            sourceVendorId: c_VendorId_Unknown,
+            sourceGuidPrefix: GuidPrefix_t::unknown(),
+            destGuidPrefix: GuidPrefix_t::unknown(),
+            timestamp: None,
+            sourceLocator: first.locator,
+        };
+        writer.on_acknack(&state, &acknack);
+        assert_eq!(0, writer.send_any());
+        assert!(first.receive().is_empty());
+
+        // Periodic resend
+        assert!(writer.reader_locator_remove(&second.locator));
+        writer.unsent_changes_reset();
+        assert_eq!(2, writer.send_any());
+        assert_eq!(vec![sn1, sn2], first.receive());
+        assert!(second.receive().is_empty());
+    }
+
+    #[test]
+    fn resend_test() {
+        let clock = Arc::new(VirtualClock::new());
+        let service = ResourceEvent::new(clock.clone());
+        let writer = Arc::new(writer(DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS));
+        writer.set_timed_events(&service);
+        let input = Input::new();
+        assert!(writer.reader_locator_add(input.locator, false));
+        let sn1 = write(&writer);
+        assert_eq!(vec![sn1], input.receive());
+
+        // The history is sent again every resendDataPeriod
+        for _ in 0..2 {
+            clock.advance(Duration::from_millis(2999));
+            assert_eq!(0, service.run_due());
+            assert!(input.receive().is_empty());
+            clock.advance(Duration::from_millis(1));
+            assert_eq!(1, service.run_due());
+            assert_eq!(vec![sn1], input.receive());
+        }
+
+        // Never sent again with an infinite period
+        let writer = Arc::new(writer_with_resend(
+            DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS,
+            Time_t::c_TimeInfinite,
+        ));
+        let service = ResourceEvent::new(clock.clone());
+        writer.set_timed_events(&service);
+        assert!(service.is_empty());
+    }
+
+    #[test]
+    fn matched_readers_test() {
+        let writer = writer(DurabilityQosPolicyKind_t::TRANSIENT_LOCAL_DURABILITY_QOS);
+        let sn1 = write(&writer);
+        let input = Input::new();
+
+        let mut reliable =
+            RemoteEndpointAttributes::new(GUID_t::unknown(), ReliabilityKind_t::RELIABLE);
+        reliable.unicastLocatorList.push_back(input.locator);
+        assert!(!writer.matched_reader_add(&reliable));
+
+        // Two readers on the same locator
+        let mut reader =
+            RemoteEndpointAttributes::new(GUID_t::unknown(), ReliabilityKind_t::BEST_EFFORT);
+        reader.unicastLocatorList.push_back(input.locator);
+        assert!(writer.matched_reader_add(&reader));
+        assert!(!writer.matched_reader_add(&reader));
+        let mut other = reader.clone();
+        other.guid.entityId =
+            EntityId_t::new_from_key_kind(2, TopicKind_t::NO_KEY.reader_entity_kind());
+        assert!(writer.matched_reader_add(&other));
+        assert_eq!(1, writer.reader_locators().len());
+
+        // Transient local: the history is sent to the new locator
+        assert_eq!(1, writer.send_any());
+        let sn2 = write(&writer);
+        assert_eq!(vec![sn1, sn2], input.receive());
+
+        assert!(writer.matched_reader_remove(&reader.guid));
+        assert!(!writer.matched_reader_remove(&reader.guid));
+        assert_eq!(1, writer.reader_locators().len());
+        assert!(writer.matched_reader_remove(&other.guid));
+        assert!(writer.reader_locators().is_empty());
+        assert!(!writer.matched_reader_is_matched(&other.guid));
+    }
+
+    // Reader of the same process recording the changes handed over
+    #[derive(Default)]
+    struct LocalReader {
+        received: Mutex<Vec<SequenceNumber_t>>,
+    }
+
+    impl MessageReceiverListener for LocalReader {
+        fn on_intraprocess_change(&self, _state: &ReceiverState, change: &CacheChange_t) {
+            self.received.lock().unwrap().push(change.sequenceNumber);
+        }
+    }
+
+    #[test]
+    fn local_reader_test() {
+        let writer = writer(DurabilityQosPolicyKind_t::TRANSIENT_LOCAL_DURABILITY_QOS);
+        let sn1 = write(&writer);
+        let input = Input::new();
+
+        // Same process as the writer
+        let mut guid = GUID_t::unknown();
+        guid.guidPrefix.value[11] = 0x51;
+        guid.entityId = EntityId_t::new_from_key_kind(1, TopicKind_t::NO_KEY.reader_entity_kind());
+        let local = Arc::new(LocalReader::default());
+        let endpoints = Arc::new(EndpointRegistry::new());
+        endpoints.add_reader(guid.entityId, local.clone());
+        register_participant(guid.guidPrefix, &endpoints);
+
+        // Handed the history, its locator is not sent to
+        let mut reader = RemoteEndpointAttributes::new(guid, ReliabilityKind_t::BEST_EFFORT);
+        reader.unicastLocatorList.push_back(input.locator);
+        assert!(writer.matched_reader_add(&reader));
+        assert!(writer.reader_locators().is_empty());
+        assert_eq!(vec![sn1], *local.received.lock().unwrap());
+
+        let sn2 = write(&writer);
+        assert_eq!(vec![sn1, sn2], *local.received.lock().unwrap());
+        writer.unsent_changes_reset();
+        assert_eq!(0, writer.send_any());
+        assert!(input.receive().is_empty());
+
+        assert!(writer.matched_reader_remove(&guid));
+        write(&writer);
+        assert_eq!(2, local.received.lock().unwrap().len());
+        unregister_participant(&guid.guidPrefix, &endpoints);
+    }
+
+    #[test]
+    fn flow_controller_test() {
+        let clock = Arc::new(VirtualClock::new());
+        let service = ResourceEvent::new(clock.clone());
+        // Two changes of 8 bytes per period
+        let controller = FlowController::new(
+            FlowControllerDescriptor {
+                max_bytes_per_period: 16,
+                period_ms: 10,
+                ..FlowControllerDescriptor::default()
+            },
+            &service,
+        );
+        let writer = Arc::new(writer(DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS));
+        writer.set_flow_controller(&controller, 0);
+        let input = Input::new();
+        assert!(writer.reader_locator_add(input.locator, false));
+
+        let sn1 = write(&writer);
+        let sn2 = write(&writer);
+        let sn3 = write(&writer);
+        assert_eq!(vec![sn1, sn2], input.receive());
+        assert_eq!(
+            vec![sn3],
+            writer.reader_locators()[0]
+                .unsent_changes()
+                .copied()
+                .collect::<Vec<SequenceNumber_t>>()
+        );
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(1, service.run_due());
+        assert_eq!(vec![sn3], input.receive());
+        assert_eq!(0, controller.pending_samples());
+    }
+
+    #[test]
+    fn batching_test() {
+        let clock = Arc::new(VirtualClock::new());
+        let service = ResourceEvent::new(clock.clone());
+        // Two changes of 8 bytes per period
+        let controller = FlowController::new(
+            FlowControllerDescriptor {
+                max_bytes_per_period: 16,
+                period_ms: 10,
+                ..FlowControllerDescriptor::default()
+            },
+            &service,
+        );
+        let writer = Arc::new(writer(DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS));
+        writer.set_flow_controller(&controller, 0);
+        writer.set_batching(
+            BatchingAttributes {
+                max_bytes: 0,
+                max_samples: 2,
+                max_delay: Duration::from_millis(5),
+            },
+            &service,
+        );
+        let input = Input::new();
+        assert!(writer.reader_locator_add(input.locator, false));
+
+        // Sent in a single message when the batch is full
+        let sn1 = write(&writer);
+        assert!(input.receive().is_empty());
+        let sn2 = write(&writer);
+        assert_eq!(vec![vec![sn1, sn2]], input.receive_messages());
+
+        // After max_delay, once the controller has budget again
+        let sn3 = write(&writer);
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(1, service.run_due());
+        assert!(input.receive().is_empty());
+        assert_eq!(1, controller.pending_samples());
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(1, service.run_due());
+        assert_eq!(vec![vec![sn3]], input.receive_messages());
+        assert_eq!(0, writer.flush_batch());
+    }
+}
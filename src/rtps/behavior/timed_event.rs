@@ -0,0 +1,501 @@
+use crate::rtps::common::error::RtpsError;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/**
+ * @brief Source of the current time of the timed events.
+ */
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+// Monotonic time of the system
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/**
+ * @brief Clock that only moves when told to, so that tests run the timed events at chosen
+ *        times instead of sleeping.
+ */
+#[derive(Debug)]
+pub struct VirtualClock {
+    now: Mutex<Instant>,
+}
+
+impl VirtualClock {
+    // Clock starting at the current time of the system
+    pub fn new() -> Self {
+        VirtualClock::starting_at(Instant::now())
+    }
+
+    pub fn starting_at(start: Instant) -> Self {
+        VirtualClock {
+            now: Mutex::new(start),
+        }
+    }
+
+    // Move the clock forward, returning the new time
+    pub fn advance(&self, duration: Duration) -> Instant {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+        *now
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        VirtualClock::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+// Called with the time the event runs at, returns whether to run again after the interval
+type Callback = Box<dyn FnMut(Instant) -> bool + Send>;
+
+struct EventEntry {
+    callback: Arc<Mutex<Callback>>,
+    interval: Duration,
+    deadline: Option<Instant>,
+    // Incremented whenever the event is scheduled or cancelled, making its queued
+    // deadlines stale
+    generation: u64,
+}
+
+#[derive(Default)]
+struct EventQueue {
+    events: HashMap<u64, EventEntry>,
+    // Deadlines by time, with the id and generation of their event
+    deadlines: BinaryHeap<Reverse<(Instant, u64, u64)>>,
+    last_id: u64,
+    stopped: bool,
+}
+
+impl EventQueue {
+    fn is_current(&self, id: u64, generation: u64) -> bool {
+        self.events
+            .get(&id)
+            .is_some_and(|event| event.generation == generation)
+    }
+
+    // Next deadline, dropping the stale ones before it
+    fn next_deadline(&mut self) -> Option<Instant> {
+        while let Some(Reverse((deadline, id, generation))) = self.deadlines.peek().copied() {
+            if self.is_current(id, generation) {
+                return Some(deadline);
+            }
+            self.deadlines.pop();
+        }
+        None
+    }
+
+    fn schedule(&mut self, id: u64, deadline: Option<Instant>) -> bool {
+        let event = match self.events.get_mut(&id) {
+            Some(event) => event,
+            None => return false,
+        };
+        event.generation += 1;
+        event.deadline = deadline;
+        if let Some(deadline) = deadline {
+            self.deadlines
+                .push(Reverse((deadline, id, event.generation)));
+        }
+        true
+    }
+}
+
+struct Shared {
+    clock: Arc<dyn Clock>,
+    queue: Mutex<EventQueue>,
+    // Notified when the deadlines change or the thread must stop
+    wakeup: Condvar,
+}
+
+impl Shared {
+    fn schedule(&self, id: u64, deadline: Option<Instant>) -> bool {
+        let scheduled = self.queue.lock().unwrap().schedule(id, deadline);
+        self.wakeup.notify_all();
+        scheduled
+    }
+
+    fn run_due(&self) -> usize {
+        let now = self.clock.now();
+        let mut due = Vec::new();
+        {
+            let mut queue = self.queue.lock().unwrap();
+            while let Some(deadline) = queue.next_deadline() {
+                if deadline > now {
+                    break;
+                }
+                let Reverse((_, id, generation)) = queue.deadlines.pop().unwrap();
+                due.push((id, generation));
+            }
+        }
+
+        let mut ran = 0;
+        for (id, generation) in due {
+            // The callbacks run without the queue locked, so that they can schedule events
+            let callback = {
+                let queue = self.queue.lock().unwrap();
+                if !queue.is_current(id, generation) {
+                    // Cancelled or rescheduled by an earlier callback
+                    continue;
+                }
+                queue.events[&id].callback.clone()
+            };
+            let restart = (callback.lock().unwrap())(now);
+            ran += 1;
+
+            let mut queue = self.queue.lock().unwrap();
+            if queue.is_current(id, generation) {
+                let deadline = if restart {
+                    now.checked_add(queue.events[&id].interval)
+                } else {
+                    None
+                };
+                queue.schedule(id, deadline);
+            }
+        }
+        ran
+    }
+
+    fn run(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        while !queue.stopped {
+            let now = self.clock.now();
+            queue = match queue.next_deadline() {
+                Some(deadline) if deadline <= now => {
+                    drop(queue);
+                    self.run_due();
+                    self.queue.lock().unwrap()
+                }
+                Some(deadline) => self.wakeup.wait_timeout(queue, deadline - now).unwrap().0,
+                None => self.wakeup.wait(queue).unwrap(),
+            };
+        }
+    }
+}
+
+/**
+ * @brief Service running the timed events of the protocol behaviors, such as the periodic
+ *        HEARTBEAT and the delayed NACK responses, when their deadline is reached. The
+ *        writers and readers register their events with set_timed_events.
+ *        The deadlines are kept in a heap: scheduling and cancelling an event is logarithmic
+ *        in the number of scheduled events.
+ *        Events are run by the thread started with init_thread, or by calling run_due, which
+ *        lets tests drive the events deterministically with a VirtualClock.
+ */
+pub struct ResourceEvent {
+    shared: Arc<Shared>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ResourceEvent {
+    /**
+     * @brief Create a service without starting its thread.
+     *
+     * @param clock Clock giving the time the deadlines are compared with.
+     * @return The service.
+     */
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        ResourceEvent {
+            shared: Arc::new(Shared {
+                clock,
+                queue: Mutex::new(EventQueue::default()),
+                wakeup: Condvar::new(),
+            }),
+            thread: Mutex::new(None),
+        }
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.shared.clock
+    }
+
+    /**
+     * @brief Start the thread running the events as their deadline is reached, until the
+     *        service is dropped. The thread waits in system time: use run_due with a
+     *        VirtualClock.
+     *
+     * @return An error if the thread cannot be started. Nothing is done if it already runs.
+     */
+    pub fn init_thread(&self) -> Result<(), RtpsError> {
+        let mut thread = self.thread.lock().unwrap();
+        if thread.is_none() {
+            let shared = self.shared.clone();
+            *thread = Some(
+                thread::Builder::new()
+                    .name("rtps-event".to_string())
+                    .spawn(move || shared.run())?,
+            );
+        }
+        Ok(())
+    }
+
+    /**
+     * @brief Run the events whose deadline is reached. An event run again after its
+     *        interval is not due before the next call, even if its interval is zero.
+     *
+     * @return The number of events run.
+     */
+    pub fn run_due(&self) -> usize {
+        self.shared.run_due()
+    }
+
+    // Earliest deadline of the scheduled events
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.shared.queue.lock().unwrap().next_deadline()
+    }
+
+    // Number of events registered, scheduled or not
+    pub fn len(&self) -> usize {
+        self.shared.queue.lock().unwrap().events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for ResourceEvent {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().stopped = true;
+        self.shared.wakeup.notify_all();
+        if let Some(handle) = self.thread.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/**
+ * @brief Event of a ResourceEvent, run once its timer expires. The timer is not started when
+ *        the event is created. The event is removed from the service when dropped.
+ */
+pub struct TimedEvent {
+    shared: Arc<Shared>,
+    id: u64,
+}
+
+impl TimedEvent {
+    /**
+     * @brief Register an event.
+     *
+     * @param service Service running the event.
+     * @param interval Time between the start of the timer and the run of the event.
+     * @param callback Called with the time the event runs at. It returns true to run again
+     *        after the interval.
+     * @return The event, with its timer stopped.
+     */
+    pub fn new<F>(service: &ResourceEvent, interval: Duration, callback: F) -> Self
+    where
+        F: FnMut(Instant) -> bool + Send + 'static,
+    {
+        let mut queue = service.shared.queue.lock().unwrap();
+        queue.last_id += 1;
+        let id = queue.last_id;
+        queue.events.insert(
+            id,
+            EventEntry {
+                callback: Arc::new(Mutex::new(Box::new(callback))),
+                interval,
+                deadline: None,
+                generation: 0,
+            },
+        );
+        TimedEvent {
+            shared: service.shared.clone(),
+            id,
+        }
+    }
+
+    // Start the timer from now, replacing the current deadline
+    pub fn restart_timer(&self) {
+        let now = self.shared.clock.now();
+        self.restart_timer_at(now + self.getInterval());
+    }
+
+    // Run the event at a given time, replacing the current deadline
+    pub fn restart_timer_at(&self, deadline: Instant) {
+        self.shared.schedule(self.id, Some(deadline));
+    }
+
+    pub fn cancel_timer(&self) {
+        self.shared.schedule(self.id, None);
+    }
+
+    // Set the interval used by the next start of the timer
+    pub fn update_interval(&self, interval: Duration) {
+        if let Some(event) = self.shared.queue.lock().unwrap().events.get_mut(&self.id) {
+            event.interval = interval;
+        }
+    }
+
+    pub fn getInterval(&self) -> Duration {
+        self.shared.queue.lock().unwrap().events[&self.id].interval
+    }
+
+    // Time the event runs at, None if its timer is stopped
+    pub fn next_trigger(&self) -> Option<Instant> {
+        self.shared.queue.lock().unwrap().events[&self.id].deadline
+    }
+
+    pub fn is_scheduled(&self) -> bool {
+        self.next_trigger().is_some()
+    }
+}
+
+impl Drop for TimedEvent {
+    fn drop(&mut self) {
+        // The queued deadlines of the event become stale
+        self.shared.queue.lock().unwrap().events.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    fn service() -> (ResourceEvent, Arc<VirtualClock>) {
+        let clock = Arc::new(VirtualClock::new());
+        (ResourceEvent::new(clock.clone()), clock)
+    }
+
+    // Event recording the times it runs at, running again while it has runs left
+    fn recorded_event(
+        service: &ResourceEvent,
+        interval_ms: u64,
+        runs: usize,
+    ) -> (TimedEvent, Arc<Mutex<Vec<Instant>>>) {
+        let times = Arc::new(Mutex::new(Vec::new()));
+        let recorded = times.clone();
+        let event = TimedEvent::new(service, Duration::from_millis(interval_ms), move |now| {
+            let mut times = recorded.lock().unwrap();
+            times.push(now);
+            times.len() < runs
+        });
+        (event, times)
+    }
+
+    #[test]
+    fn periodic_event_test() {
+        let (service, clock) = service();
+        let start = clock.now();
+        let (event, times) = recorded_event(&service, 10, 3);
+        assert!(!event.is_scheduled());
+        assert_eq!(0, service.run_due());
+
+        event.restart_timer();
+        assert_eq!(
+            Some(start + Duration::from_millis(10)),
+            event.next_trigger()
+        );
+        assert_eq!(event.next_trigger(), service.next_deadline());
+        clock.advance(Duration::from_millis(9));
+        assert_eq!(0, service.run_due());
+        for _ in 0..5 {
+            clock.advance(Duration::from_millis(10));
+            service.run_due();
+        }
+        let elapsed: Vec<u128> = times
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|time| (*time - start).as_millis())
+            .collect();
+        assert_eq!(vec![19, 29, 39], elapsed);
+        assert!(!event.is_scheduled());
+        assert_eq!(None, service.next_deadline());
+
+        // Reschedule and interval update
+        event.update_interval(Duration::from_millis(50));
+        event.restart_timer();
+        let now = clock.now();
+        event.restart_timer_at(now + Duration::from_millis(5));
+        assert_eq!(Some(now + Duration::from_millis(5)), event.next_trigger());
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(1, service.run_due());
+        assert_eq!(Duration::from_millis(50), event.getInterval());
+
+        // An interval of zero runs once per call
+        let (zero, zero_times) = recorded_event(&service, 0, usize::MAX);
+        zero.restart_timer();
+        assert_eq!(1, service.run_due());
+        assert_eq!(1, service.run_due());
+        assert_eq!(2, zero_times.lock().unwrap().len());
+    }
+
+    #[test]
+    fn cancel_test() {
+        let (service, clock) = service();
+        let (first, first_times) = recorded_event(&service, 10, 1);
+        let (second, second_times) = recorded_event(&service, 20, 1);
+        first.restart_timer();
+        second.restart_timer();
+        first.cancel_timer();
+        assert_eq!(second.next_trigger(), service.next_deadline());
+        clock.advance(Duration::from_millis(30));
+        assert_eq!(1, service.run_due());
+        assert!(first_times.lock().unwrap().is_empty());
+        assert_eq!(1, second_times.lock().unwrap().len());
+
+        // An event cancelled by an earlier callback of the same run is skipped
+        let second = Arc::new(Mutex::new(second));
+        let cancelled = second.clone();
+        let canceller = TimedEvent::new(&service, Duration::from_millis(10), move |_| {
+            cancelled.lock().unwrap().cancel_timer();
+            false
+        });
+        canceller.restart_timer();
+        let now = clock.now();
+        second
+            .lock()
+            .unwrap()
+            .restart_timer_at(now + Duration::from_millis(11));
+        clock.advance(Duration::from_millis(11));
+        assert_eq!(1, service.run_due());
+        assert_eq!(1, second_times.lock().unwrap().len());
+
+        // Dropped events are removed
+        assert_eq!(3, service.len());
+        first.restart_timer();
+        drop(first);
+        drop(canceller);
+        assert_eq!(1, service.len());
+        assert_eq!(None, service.next_deadline());
+    }
+
+    #[test]
+    fn thread_test() {
+        let service = ResourceEvent::new(Arc::new(SystemClock));
+        service.init_thread().unwrap();
+        service.init_thread().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let event = TimedEvent::new(&service, Duration::from_millis(5), move |now| {
+            let _ = sender.send(now);
+            true
+        });
+        let start = Instant::now();
+        event.restart_timer();
+        for _ in 0..2 {
+            let time = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert!(time >= start + Duration::from_millis(5));
+        }
+        event.cancel_timer();
+        drop(event);
+        drop(service);
+    }
+}
@@ -1,7 +1,93 @@
-#[derive(Debug, PartialOrd, PartialEq, Ord, Eq)]
+// Flags of the STATUS_INFO inline QoS, as specified in the DDSI-RTPS 2.5 specification chapter 9.6.4.9
+pub const STATUS_INFO_DISPOSED_FLAG: u8 = 0x01;
+pub const STATUS_INFO_UNREGISTERED_FLAG: u8 = 0x02;
+pub const STATUS_INFO_FILTERED_FLAG: u8 = 0x04;
+
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq, Hash)]
 pub enum ChangeKind_t {
     ALIVE,
     NOT_ALIVE_DISPOSED,
     NOT_ALIVE_UNREGISTERED,
     NOT_ALIVE_DISPOSED_UNREGISTERED,
 }
+
+impl ChangeKind_t {
+    pub fn is_alive(&self) -> bool {
+        *self == ChangeKind_t::ALIVE
+    }
+
+    /**
+     * @brief Value of the STATUS_INFO inline QoS telling the kind of the change.
+     *        The flags are on the last octet, so the value does not depend on endianness.
+     *
+     * @return STATUS_INFO value.
+     */
+    pub fn to_status_info(&self) -> [u8; 4] {
+        let flags = match self {
+            ChangeKind_t::ALIVE => 0,
+            ChangeKind_t::NOT_ALIVE_DISPOSED => STATUS_INFO_DISPOSED_FLAG,
+            ChangeKind_t::NOT_ALIVE_UNREGISTERED => STATUS_INFO_UNREGISTERED_FLAG,
+            ChangeKind_t::NOT_ALIVE_DISPOSED_UNREGISTERED => {
+                STATUS_INFO_DISPOSED_FLAG | STATUS_INFO_UNREGISTERED_FLAG
+            }
+        };
+        [0, 0, 0, flags]
+    }
+
+    /**
+     * @brief Kind of a change from its STATUS_INFO inline QoS.
+     *        The filtered flag does not change the kind of the change, and the flags this
+     *        implementation does not know, such as the ones of other vendors, are ignored.
+     *
+     * @param status_info STATUS_INFO value.
+     * @return The kind of the change.
+     */
+    pub fn from_status_info(status_info: &[u8; 4]) -> Self {
+        let flags = status_info[3];
+        let disposed = (flags & STATUS_INFO_DISPOSED_FLAG) != 0;
+        let unregistered = (flags & STATUS_INFO_UNREGISTERED_FLAG) != 0;
+        match (disposed, unregistered) {
+            (false, false) => ChangeKind_t::ALIVE,
+            (true, false) => ChangeKind_t::NOT_ALIVE_DISPOSED,
+            (false, true) => ChangeKind_t::NOT_ALIVE_UNREGISTERED,
+            (true, true) => ChangeKind_t::NOT_ALIVE_DISPOSED_UNREGISTERED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_info_test() {
+        for kind in [
+            ChangeKind_t::ALIVE,
+            ChangeKind_t::NOT_ALIVE_DISPOSED,
+            ChangeKind_t::NOT_ALIVE_UNREGISTERED,
+            ChangeKind_t::NOT_ALIVE_DISPOSED_UNREGISTERED,
+        ] {
+            let status_info = kind.to_status_info();
+            assert_eq!(kind, ChangeKind_t::from_status_info(&status_info));
+        }
+        assert_eq!(
+            [0, 0, 0, 3],
+            ChangeKind_t::NOT_ALIVE_DISPOSED_UNREGISTERED.to_status_info()
+        );
+        assert_eq!(
+            ChangeKind_t::NOT_ALIVE_DISPOSED,
+            ChangeKind_t::from_status_info(&[0, 0, 0, 5])
+        );
+        // Unknown and reserved flags are ignored
+        assert_eq!(
+            ChangeKind_t::NOT_ALIVE_UNREGISTERED,
+            ChangeKind_t::from_status_info(&[0, 0, 0, 0x0a])
+        );
+        assert_eq!(
+            ChangeKind_t::ALIVE,
+            ChangeKind_t::from_status_info(&[1, 0, 0x80, 0])
+        );
+        assert!(ChangeKind_t::ALIVE.is_alive());
+        assert!(!ChangeKind_t::NOT_ALIVE_UNREGISTERED.is_alive());
+    }
+}
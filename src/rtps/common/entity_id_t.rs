@@ -1,6 +1,6 @@
 use std::mem;
 
-#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq, Hash)]
 pub struct EntityId_t {
     pub value: [u8; EntityId_t::SIZE],
 }
@@ -8,6 +8,21 @@ pub struct EntityId_t {
 impl EntityId_t {
     pub const SIZE: usize = 4;
 
+    // Entity kinds, last octet of the entity id, as specified in the DDSI-RTPS 2.5 specification table 9.1
+    pub const ENTITYKIND_USER_UNKNOWN: u8 = 0x00;
+    pub const ENTITYKIND_USER_WRITER_WITH_KEY: u8 = 0x02;
+    pub const ENTITYKIND_USER_WRITER_NO_KEY: u8 = 0x03;
+    pub const ENTITYKIND_USER_READER_NO_KEY: u8 = 0x04;
+    pub const ENTITYKIND_USER_READER_WITH_KEY: u8 = 0x07;
+    pub const ENTITYKIND_USER_WRITER_GROUP: u8 = 0x08;
+    pub const ENTITYKIND_USER_READER_GROUP: u8 = 0x09;
+    pub const ENTITYKIND_BUILTIN_UNKNOWN: u8 = 0xc0;
+    pub const ENTITYKIND_BUILTIN_PARTICIPANT: u8 = 0xc1;
+    pub const ENTITYKIND_BUILTIN_WRITER_WITH_KEY: u8 = 0xc2;
+    pub const ENTITYKIND_BUILTIN_WRITER_NO_KEY: u8 = 0xc3;
+    pub const ENTITYKIND_BUILTIN_READER_NO_KEY: u8 = 0xc4;
+    pub const ENTITYKIND_BUILTIN_READER_WITH_KEY: u8 = 0xc7;
+
     pub const ENTITYID_UNKNOWN: u32 = 0x00000000;
     pub const ENTITYID_RTPSParticipant: u32 = 0x000001c1;
     pub const ENTITYID_SEDP_BUILTIN_TOPIC_WRITER: u32 = 0x000002c2;
@@ -159,6 +174,32 @@ impl EntityId_t {
         }
     }
 
+    /**
+     * @brief Build an entity id from its key and kind.
+     *
+     * @param key Entity key, only its 3 lower octets are used.
+     * @param kind Entity kind, one of the ENTITYKIND_ constants.
+     * @return The entity id.
+     */
+    pub fn new_from_key_kind(key: u32, kind: u8) -> Self {
+        let key = key.to_be_bytes();
+        EntityId_t {
+            value: [key[1], key[2], key[3], kind],
+        }
+    }
+
+    pub fn entity_kind(&self) -> u8 {
+        self.value[3]
+    }
+
+    pub fn is_writer(&self) -> bool {
+        matches!(self.entity_kind() & 0x3f, 0x02 | 0x03)
+    }
+
+    pub fn is_reader(&self) -> bool {
+        matches!(self.entity_kind() & 0x3f, 0x04 | 0x07)
+    }
+
     pub fn hash(k: &EntityId_t) -> usize {
         (k.value[0] as usize) << 16 | (k.value[1] as usize) << 8 | (k.value[2] as usize)
     }
@@ -390,4 +431,17 @@ mod tests {
         assert!(id1 == EntityId_t::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_WRITER);
         assert!(EntityId_t::ENTITYID_SEDP_BUILTIN_PUBLICATIONS_WRITER == id1);
     }
+
+    #[test]
+    fn entity_kind_test() {
+        let id =
+            EntityId_t::new_from_key_kind(0x010203, EntityId_t::ENTITYKIND_USER_WRITER_WITH_KEY);
+        assert_eq!([0x01, 0x02, 0x03, 0x02], id.value);
+        assert!(id.is_writer());
+        assert!(!id.is_reader());
+        assert!(EntityId_t::c_EntityId_SPDPWriter.is_writer());
+        assert!(EntityId_t::c_EntityId_SEDPSubReader.is_reader());
+        assert!(!EntityId_t::c_EntityId_RTPSParticipant.is_writer());
+        assert!(!EntityId_t::c_EntityId_RTPSParticipant.is_reader());
+    }
 }
@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt;
+use std::io;
 use std::num::ParseFloatError;
 use std::num::ParseIntError;
 
@@ -42,3 +43,9 @@ impl From<ParseIntError> for RtpsError {
         RtpsError::new(&err.to_string())
     }
 }
+
+impl From<io::Error> for RtpsError {
+    fn from(err: io::Error) -> Self {
+        RtpsError::new(&err.to_string())
+    }
+}
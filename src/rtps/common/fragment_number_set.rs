@@ -0,0 +1,154 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::messages::submessages::submessage_header::*;
+
+// Fragment numbers a set can hold from its base
+const MAX_BITS: u32 = 256;
+
+/**
+ * @brief Set of up to 256 fragment numbers from a base, as sent by NACK_FRAG and specified in
+ *        the DDSI-RTPS 2.5 specification chapter 9.4.2.8.
+ *        Bit 31 of the first word is the base, bit 30 the next fragment number, and so on.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FragmentNumberSet_t {
+    base: u32,
+    numBits: u32,
+    bitmap: [u32; 8],
+}
+
+impl FragmentNumberSet_t {
+    pub fn new(base: u32) -> Self {
+        FragmentNumberSet_t {
+            base,
+            numBits: 0,
+            bitmap: [0; 8],
+        }
+    }
+
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+
+    // Number of fragment numbers covered by the bitmap
+    pub fn numBits(&self) -> u32 {
+        self.numBits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bitmap.iter().all(|word| *word == 0)
+    }
+
+    fn offset(&self, fragmentNumber: u32) -> Option<u32> {
+        match fragmentNumber.checked_sub(self.base) {
+            Some(offset) if offset < MAX_BITS => Some(offset),
+            _ => None,
+        }
+    }
+
+    /**
+     * @brief Add a fragment number to the set.
+     *
+     * @param fragmentNumber Fragment number to add.
+     * @return False if it is out of the range of the set.
+     */
+    pub fn add(&mut self, fragmentNumber: u32) -> bool {
+        match self.offset(fragmentNumber) {
+            Some(offset) => {
+                self.bitmap[(offset / 32) as usize] |= 0x8000_0000 >> (offset % 32);
+                self.numBits = std::cmp::max(self.numBits, offset + 1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_set(&self, fragmentNumber: u32) -> bool {
+        match self.offset(fragmentNumber) {
+            Some(offset) if offset < self.numBits => {
+                (self.bitmap[(offset / 32) as usize] & (0x8000_0000 >> (offset % 32))) != 0
+            }
+            _ => false,
+        }
+    }
+
+    // Fragment numbers of the set, in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.numBits)
+            .filter(move |offset| {
+                (self.bitmap[(offset / 32) as usize] & (0x8000_0000 >> (offset % 32))) != 0
+            })
+            .map(move |offset| self.base + offset)
+    }
+
+    /**
+     * @brief Encode the set: base, number of bits and the words of the bitmap in use.
+     *
+     * @param buffer Buffer where the set is appended.
+     * @param endianness Endianness of the submessage.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>, endianness: Endianness) {
+        write_u32(buffer, endianness, self.base);
+        write_u32(buffer, endianness, self.numBits);
+        for word in &self.bitmap[..self.numBits.div_ceil(32) as usize] {
+            write_u32(buffer, endianness, *word);
+        }
+    }
+
+    /**
+     * @brief Decode a set.
+     *
+     * @param data Data starting with the set.
+     * @param endianness Endianness of the submessage.
+     * @return The set and the number of octets it takes, an error if it is malformed.
+     */
+    pub fn read(data: &[u8], endianness: Endianness) -> Result<(Self, usize), RtpsError> {
+        if data.len() < 8 {
+            return Err(RtpsError::new("Fragment number set too short"));
+        }
+        let mut set = FragmentNumberSet_t::new(read_u32(data, endianness));
+        set.numBits = read_u32(&data[4..], endianness);
+        if set.numBits > MAX_BITS {
+            return Err(RtpsError::new("Fragment number set too large"));
+        }
+        let words = set.numBits.div_ceil(32) as usize;
+        let length = 8 + 4 * words;
+        if data.len() < length {
+            return Err(RtpsError::new("Fragment number set too short"));
+        }
+        for (n, word) in set.bitmap[..words].iter_mut().enumerate() {
+            *word = read_u32(&data[8 + 4 * n..], endianness);
+        }
+        Ok((set, length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragment_number_set_test() {
+        let mut set = FragmentNumberSet_t::new(3);
+        assert!(!set.add(2));
+        assert!(!set.add(3 + 256));
+        assert!(set.add(4));
+        assert!(set.add(40));
+        assert_eq!(38, set.numBits());
+        assert!(set.is_set(40));
+        assert!(!set.is_set(3));
+        assert_eq!(vec![4, 40], set.iter().collect::<Vec<u32>>());
+
+        let mut buffer = Vec::new();
+        set.write(&mut buffer, Endianness::LITTLE_ENDIANNESS);
+        assert_eq!(
+            vec![3, 0, 0, 0, 38, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0, 0x04],
+            buffer
+        );
+        assert_eq!(
+            (set, buffer.len()),
+            FragmentNumberSet_t::read(&buffer, Endianness::LITTLE_ENDIANNESS).unwrap()
+        );
+        assert!(FragmentNumberSet_t::read(&buffer[..12], Endianness::LITTLE_ENDIANNESS).is_err());
+    }
+}
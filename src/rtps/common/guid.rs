@@ -1,7 +1,11 @@
+use crate::cdr::cdr_deserializer::*;
+use crate::cdr::cdr_serializer::*;
+use crate::cdr::encoding::CdrVersion;
 use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
 use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
 
-#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq, Hash)]
 pub struct GUID_t {
     pub guidPrefix: GuidPrefix_t,
     pub entityId: EntityId_t,
@@ -35,6 +39,26 @@ impl GUID_t {
     }
 }
 
+impl CdrSerialize for GUID_t {
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        ser.serialize_array(&self.guidPrefix.value)?;
+        ser.serialize_array(&self.entityId.value)
+    }
+
+    fn max_serialized_size(_version: CdrVersion, position: usize) -> Option<usize> {
+        Some(position + 16)
+    }
+}
+
+impl CdrDeserialize for GUID_t {
+    fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+        let mut guid = GUID_t::unknown();
+        guid.guidPrefix.value = de.deserialize()?;
+        guid.entityId.value = de.deserialize()?;
+        Ok(guid)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
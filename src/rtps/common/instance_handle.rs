@@ -1,6 +1,6 @@
 use crate::rtps::common::guid::GUID_t;
 
-#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq, Hash)]
 pub struct InstanceHandle_t {
     pub value: [u8; InstanceHandle_t::SIZE],
 }
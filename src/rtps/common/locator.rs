@@ -1,6 +1,10 @@
 use crate::base::net::lookup::*;
+use crate::cdr::cdr_deserializer::*;
+use crate::cdr::cdr_serializer::*;
+use crate::cdr::encoding::CdrVersion;
 use crate::rtps::common::error::*;
 use crate::rtps::utils::ip_locator::*;
+use crate::rtps::utils::shm_locator::*;
 use crate::LOCATOR_ADDRESS_INVALID;
 use core::str::FromStr;
 use std::string::ToString;
@@ -30,6 +34,7 @@ macro_rules! LOCATOR_INVALID {
         $loc.kind = LOCATOR_KIND_INVALID;
         $loc.port = LOCATOR_PORT_INVALID;
         LOCATOR_ADDRESS_INVALID!($loc.address);
+        $loc.scope_id = 0;
     };
 }
 
@@ -53,6 +58,8 @@ pub struct Locator_t {
     pub port: u32,
     /// IP address
     pub address: [u8; 16],
+    /// Interface index for IPv6 link-local addresses (0 when not scoped)
+    pub scope_id: u32,
 }
 
 impl Default for Locator_t {
@@ -61,6 +68,7 @@ impl Default for Locator_t {
             kind: LOCATOR_KIND_UDPv4,
             port: 0,
             address: [0_u8; 16],
+            scope_id: 0,
         }
     }
 }
@@ -71,6 +79,7 @@ impl Locator_t {
             kind: kind,
             port: port,
             address: address,
+            scope_id: 0,
         }
     }
 
@@ -79,6 +88,7 @@ impl Locator_t {
             kind: LOCATOR_KIND_UDPv4,
             port: port,
             address: [0_u8; 16],
+            scope_id: 0,
         }
     }
 
@@ -87,6 +97,7 @@ impl Locator_t {
             kind: kind,
             port: port,
             address: [0_u8; 16],
+            scope_id: 0,
         }
     }
 
@@ -98,6 +109,7 @@ impl Locator_t {
      */
     pub fn set_address(&mut self, other: &Self) -> bool {
         self.address = other.address;
+        self.scope_id = other.scope_id;
         return true;
     }
 
@@ -125,6 +137,7 @@ impl Locator_t {
      */
     pub fn set_Invalid_Address(&mut self) {
         LOCATOR_ADDRESS_INVALID!(self.address);
+        self.scope_id = 0;
     }
 }
 
@@ -137,8 +150,12 @@ impl Locator_t {
  *            - TCPv4
  *            - TCPv6
  *            - SHM
- *        \c address IP address unless \c kind is SHM
+ *        \c address IP address unless \c kind is SHM, where it is U@host[#segment] for
+ *        unicast, M[#segment] for multicast or _ otherwise
  *        \c port number
+ *        TCPv4 locators append the WAN address and LAN id to the address when they are
+ *        defined, as lan@wan;lan_id, and TCP locators append the logical port to the
+ *        physical port when it is defined, as physical-logical.
  *
  * @param output Output stream where the serialized locator is appended.
  * @param loc Locator to be serialized/inserted.
@@ -161,20 +178,34 @@ impl ToString for Locator_t {
         };
 
         // Stream address
-        let address = if self.kind == LOCATOR_KIND_UDPv4 || self.kind == LOCATOR_KIND_TCPv4 {
+        let address = if self.kind == LOCATOR_KIND_UDPv4 {
             toIPv4string(self)
+        } else if self.kind == LOCATOR_KIND_TCPv4 {
+            let mut address = toIPv4string(self);
+            if hasWan(self) {
+                address = format!("{}@{}", address, toWanstring(self));
+            }
+            if hasLanID(self) {
+                address = format!("{};{}", address, toLanIDstring(self));
+            }
+            address
         } else if self.kind == LOCATOR_KIND_UDPv6 || self.kind == LOCATOR_KIND_TCPv6 {
             toIPv6string(self)
         } else if self.kind == LOCATOR_KIND_SHM {
-            if self.address[0] == 'M' as u8 {
-                "M".to_string()
-            } else {
-                "_".to_string()
-            }
+            toSHMstring(self)
         } else {
             "_".to_string()
         };
 
+        // Stream port
+        let port = if (self.kind == LOCATOR_KIND_TCPv4 || self.kind == LOCATOR_KIND_TCPv6)
+            && getLogicalPort(self) != 0
+        {
+            format!("{}-{}", getPhysicalPort(self), getLogicalPort(self))
+        } else {
+            port.to_string()
+        };
+
         format!("{}{}{}{}{}", kind, ":[", address, "]:", port)
     }
 }
@@ -188,8 +219,11 @@ impl ToString for Locator_t {
  *            - TCPv4
  *            - TCPv6
  *            - SHM
- *        \c address must be either a name which can be resolved by DNS or the IP address unless \c kind is SHM
+ *        \c address must be either a name which can be resolved by DNS or the IP address unless \c kind is SHM,
+ *        where it uses the format written by the insertion operator
  *        \c port number
+ *        TCPv4 addresses may carry a WAN address and a LAN id, as lan@wan;lan_id, and TCP
+ *        ports may carry a logical port, as physical-logical.
  *
  * @param input Input stream where the locator to be deserialized is located.
  * @param loc Locator where the deserialized locator is saved.
@@ -227,13 +261,27 @@ impl FromStr for Locator_t {
         }
 
         // Get address in strings
-        let str_address: &str;
+        let mut str_address: &str;
         if let Some(i) = s.find("]") {
             // Ignore chars :[
             str_address = s.substring(str_kind_index + 2, i);
         } else {
             return Err(RtpsError::new("Get address"));
         };
+
+        // TCPv4 may carry WAN address and LAN id after the LAN address
+        let mut str_wan: Option<&str> = None;
+        let mut str_lan_id: Option<&str> = None;
+        if kind == LOCATOR_KIND_TCPv4 {
+            if let Some(i) = str_address.find(";") {
+                str_lan_id = Some(str_address.substring(i + 1, str_address.len()));
+                str_address = str_address.substring(0, i);
+            }
+            if let Some(i) = str_address.find("@") {
+                str_wan = Some(str_address.substring(i + 1, str_address.len()));
+                str_address = str_address.substring(0, i);
+            }
+        }
         address = str_address.to_string();
 
         // check if this is a valid IPv4 or IPv6 and call DNS if not
@@ -265,16 +313,38 @@ impl FromStr for Locator_t {
         let str_port: &str;
         if let Some(i) = s.find("]:") {
             str_port = s.substring(i + 2, s.len());
-            port = str_port.parse()?;
+            match str_port.find("-") {
+                Some(j) if kind == LOCATOR_KIND_TCPv4 || kind == LOCATOR_KIND_TCPv6 => {
+                    let physical: u16 = str_port.substring(0, j).parse()?;
+                    let logical: u16 = str_port.substring(j + 1, str_port.len()).parse()?;
+                    port = ((logical as u32) << 16) | physical as u32;
+                }
+                _ => port = str_port.parse()?,
+            }
         }
 
         loc.kind = kind;
-        match createLocator(kind, &address, port, &mut loc) {
-            Ok(_) => Ok(loc),
-            Err(_) => {
-                return Err(RtpsError::new("Create locator"));
+        if createLocator(kind, &address, port, &mut loc).is_err() {
+            return Err(RtpsError::new("Create locator"));
+        }
+
+        if kind == LOCATOR_KIND_SHM && !setSHMAddress(&mut loc, &address) {
+            return Err(RtpsError::new("Parse SHM address"));
+        }
+
+        if let Some(wan) = str_wan {
+            if !setWan(&mut loc, wan) {
+                return Err(RtpsError::new("Parse WAN address"));
+            }
+        }
+
+        if let Some(lan_id) = str_lan_id {
+            if !setLanID(&mut loc, lan_id) {
+                return Err(RtpsError::new("Parse LAN id"));
             }
         }
+
+        Ok(loc)
     }
 }
 
@@ -287,9 +357,8 @@ impl FromStr for Locator_t {
  */
 #[inline]
 fn IsAddressDefined(loc: &Locator_t) -> bool {
-    if loc.kind == LOCATOR_KIND_UDPv4 || loc.kind == LOCATOR_KIND_TCPv4
-    // WAN addr in TCPv4 is optional, isn't?
-    {
+    // WAN address and LAN id in TCPv4 are optional, only the LAN address counts
+    if loc.kind == LOCATOR_KIND_UDPv4 || loc.kind == LOCATOR_KIND_TCPv4 {
         for i in 12..16 {
             if loc.address[i] != 0 {
                 return true;
@@ -319,6 +388,28 @@ fn IsLocatorValid(loc: &Locator_t) -> bool {
 
 type LocatorList = std::vec::Vec<Locator_t>;
 
+// On the wire a locator is its kind, port and address. The scope id is not sent.
+impl CdrSerialize for Locator_t {
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        ser.serialize_i32(self.kind)?;
+        ser.serialize_u32(self.port)?;
+        ser.serialize_array(&self.address)
+    }
+
+    fn max_serialized_size(version: CdrVersion, position: usize) -> Option<usize> {
+        Some(version.align(position, 4) + 24)
+    }
+}
+
+impl CdrDeserialize for Locator_t {
+    fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+        let kind = de.deserialize_i32()?;
+        let port = de.deserialize_u32()?;
+        let address = de.deserialize()?;
+        Ok(Locator_t::new(kind, port, address))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -401,7 +492,10 @@ mod tests {
         );
 
         locator.kind = LOCATOR_KIND_TCPv4;
-        assert_eq!("TCPv4:[255.255.255.255]:1", locator.to_string());
+        assert_eq!(
+            "TCPv4:[255.255.255.255@255.255.255.255;255.255.255.255.255.255.255.255]:1",
+            locator.to_string()
+        );
 
         locator.kind = LOCATOR_KIND_TCPv6;
         assert_eq!(
@@ -452,4 +546,92 @@ mod tests {
         loc2 = Locator_t::from_str("TCPv6:[localhost]:2").unwrap();
         assert_eq!("TCPv6:[::1]:2", loc2.to_string());
     }
+
+    #[test]
+    fn tcp_text_format_test() {
+        let mut loc1 = Locator_t::new_from_kind_port(LOCATOR_KIND_TCPv4, 5100);
+        setIPv4(&mut loc1, "192.168.1.2").unwrap();
+        assert_eq!("TCPv4:[192.168.1.2]:5100", loc1.to_string());
+
+        setLogicalPort(&mut loc1, 7410);
+        assert_eq!("TCPv4:[192.168.1.2]:5100-7410", loc1.to_string());
+
+        assert!(setWan(&mut loc1, "80.1.2.3"));
+        assert_eq!("TCPv4:[192.168.1.2@80.1.2.3]:5100-7410", loc1.to_string());
+
+        setLanID(&mut loc1, "0.0.0.0.0.0.0.9");
+        assert_eq!(
+            "TCPv4:[192.168.1.2@80.1.2.3;0.0.0.0.0.0.0.9]:5100-7410",
+            loc1.to_string()
+        );
+
+        let loc2 = Locator_t::from_str(&loc1.to_string()).unwrap();
+        assert_eq!(loc1, loc2);
+        assert_eq!(7410, getLogicalPort(&loc2));
+        assert_eq!(5100, getPhysicalPort(&loc2));
+
+        // Only the LAN address is resolved
+        let loc3 = Locator_t::from_str("TCPv4:[localhost@80.1.2.3]:5100-7410").unwrap();
+        assert_eq!("TCPv4:[127.0.0.1@80.1.2.3]:5100-7410", loc3.to_string());
+
+        let mut loc4 = Locator_t::new_from_kind_port(LOCATOR_KIND_TCPv6, 5100);
+        setIPv6(&mut loc4, "2001:db8::1").unwrap();
+        setLogicalPort(&mut loc4, 1);
+        assert_eq!("TCPv6:[2001:db8::1]:5100-1", loc4.to_string());
+        assert_eq!(loc4, Locator_t::from_str(&loc4.to_string()).unwrap());
+
+        // Physical and logical ports only apply to TCP
+        assert!(Locator_t::from_str("UDPv4:[127.0.0.1]:5100-7410").is_err());
+        assert!(Locator_t::from_str("TCPv4:[127.0.0.1]:70000-1").is_err());
+        assert!(Locator_t::from_str("TCPv4:[127.0.0.1@80.1.2]:5100").is_err());
+        assert!(Locator_t::from_str("TCPv4:[127.0.0.1;0.1]:5100").is_err());
+    }
+
+    #[test]
+    fn shm_text_format_test() {
+        let mut loc1 = Locator_t::default();
+        createSHMLocator(7400, false, &mut loc1);
+        setSHMHostId(&mut loc1, 0xbeef);
+        assert_eq!("SHM:[U@beef]:7400", loc1.to_string());
+        assert_eq!(loc1, Locator_t::from_str(&loc1.to_string()).unwrap());
+
+        setSHMSegmentId(&mut loc1, 0x1234);
+        assert_eq!("SHM:[U@beef#00001234]:7400", loc1.to_string());
+        assert_eq!(loc1, Locator_t::from_str(&loc1.to_string()).unwrap());
+
+        createSHMLocator(7401, true, &mut loc1);
+        assert_eq!("SHM:[M]:7401", loc1.to_string());
+        let loc2 = Locator_t::from_str(&loc1.to_string()).unwrap();
+        assert_eq!(loc1, loc2);
+        assert!(isSHMAndFromThisHost(&loc2));
+
+        assert!(Locator_t::from_str("SHM:[Q]:7401").is_err());
+    }
+
+    #[test]
+    fn scope_id_test() {
+        let loc1 = Locator_t::from_str("UDPv6:[fe80::92f0:f536:e3cc:11c6%4]:7400").unwrap();
+        assert_eq!(4, loc1.scope_id);
+        let loc2 = Locator_t::from_str(&loc1.to_string()).unwrap();
+        assert_eq!(loc1, loc2);
+
+        let loc3 = Locator_t::from_str("UDPv6:[fe80::92f0:f536:e3cc:11c6]:7400").unwrap();
+        assert_ne!(loc1, loc3);
+
+        let mut loc4 = Locator_t::default();
+        loc4.set_address(&loc1);
+        assert_eq!(4, loc4.scope_id);
+        loc4.set_Invalid_Address();
+        assert_eq!(0, loc4.scope_id);
+
+        assert!(Locator_t::from_str("UDPv6:[fe80::1%no-such-interface0]:7400").is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn scope_interface_name_test() {
+        let loc1 = Locator_t::from_str("TCPv6:[fe80::1%lo]:7400").unwrap();
+        assert_ne!(0, loc1.scope_id);
+        assert_eq!("TCPv6:[fe80::1%lo]:7400", loc1.to_string());
+    }
 }
@@ -0,0 +1,101 @@
+use crate::rtps::common::locator::Locator_t;
+
+/**
+ * @brief List of locators without duplicates, in insertion order.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LocatorList {
+    locators: Vec<Locator_t>,
+}
+
+impl LocatorList {
+    pub fn new() -> Self {
+        LocatorList::default()
+    }
+
+    /**
+     * @brief Add a locator to the list.
+     *
+     * @param locator Locator to add.
+     * @return False if the locator was already in the list.
+     */
+    pub fn push_back(&mut self, locator: Locator_t) -> bool {
+        if self.contains(&locator) {
+            return false;
+        }
+        self.locators.push(locator);
+        true
+    }
+
+    // Add every locator of another list
+    pub fn push_back_list(&mut self, other: &LocatorList) {
+        for locator in other.iter() {
+            self.push_back(*locator);
+        }
+    }
+
+    pub fn contains(&self, locator: &Locator_t) -> bool {
+        self.locators.contains(locator)
+    }
+
+    pub fn clear(&mut self) {
+        self.locators.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.locators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locators.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Locator_t> {
+        self.locators.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Locator_t> {
+        self.locators.iter_mut()
+    }
+}
+
+impl From<Vec<Locator_t>> for LocatorList {
+    fn from(locators: Vec<Locator_t>) -> Self {
+        let mut list = LocatorList::new();
+        for locator in locators {
+            list.push_back(locator);
+        }
+        list
+    }
+}
+
+impl<'a> IntoIterator for &'a LocatorList {
+    type Item = &'a Locator_t;
+    type IntoIter = std::slice::Iter<'a, Locator_t>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.locators.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_back_test() {
+        let mut list = LocatorList::new();
+        assert!(list.push_back(Locator_t::new_from_port(7400)));
+        assert!(list.push_back(Locator_t::new_from_port(7410)));
+        assert!(!list.push_back(Locator_t::new_from_port(7400)));
+        assert_eq!(2, list.len());
+
+        let other = LocatorList::from(vec![
+            Locator_t::new_from_port(7410),
+            Locator_t::new_from_port(7411),
+        ]);
+        list.push_back_list(&other);
+        let ports: Vec<u32> = list.iter().map(|l| l.port).collect();
+        assert_eq!(vec![7400, 7410, 7411], ports);
+    }
+}
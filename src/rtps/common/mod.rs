@@ -1,9 +1,16 @@
 pub mod change_kind_t;
 pub mod entity_id_t;
 pub mod error;
+pub mod fragment_number_set;
 pub mod guid;
 pub mod guid_prefix_t;
 pub mod instance_handle;
 pub mod locator;
+pub mod locator_list;
+pub mod payload_pool;
+pub mod protocol_version;
 pub mod sequence_number;
+pub mod sequence_number_set;
 pub mod serialized_payload;
+pub mod time;
+pub mod vendor_id;
@@ -0,0 +1,496 @@
+use crate::rtps::common::serialized_payload::*;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Smallest buffer handed out by a size class pool
+const MIN_SIZE_CLASS: usize = 64;
+
+/**
+ * @brief Memory management policy of a payload pool.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PayloadPoolMode {
+    /// Every buffer has the same fixed size, bigger payloads are refused
+    PREALLOCATED,
+    /// Buffers start with a fixed size and are grown when a bigger payload is requested
+    PREALLOCATED_WITH_REALLOC,
+    /// Buffers are grouped in power of two size classes, each request uses the smallest class that fits
+    SIZE_CLASS,
+}
+
+/**
+ * @brief Configuration of a payload pool.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Memory management policy
+    pub mode: PayloadPoolMode,
+    /// Size of each buffer when it is allocated. Not used on SIZE_CLASS mode.
+    pub payload_initial_size: usize,
+    /// Number of buffers allocated when the pool is created
+    pub initial_size: usize,
+    /// Maximum number of buffers handed out at the same time (0 for unlimited)
+    pub maximum_size: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            mode: PayloadPoolMode::PREALLOCATED_WITH_REALLOC,
+            payload_initial_size: 512,
+            initial_size: 0,
+            maximum_size: 0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    // Free buffers indexed by their size, the size of their class on SIZE_CLASS mode
+    free: BTreeMap<usize, Vec<Vec<u8>>>,
+    // Buffers handed out and not given back yet
+    outstanding: usize,
+    // Number of times memory was requested to the allocator
+    allocations: usize,
+}
+
+/**
+ * @brief Part of a payload pool referenced by the buffers it hands out, so they can
+ *        go back to it when they are dropped.
+ */
+#[derive(Debug)]
+pub struct PoolShared {
+    config: PoolConfig,
+    state: Mutex<PoolState>,
+}
+
+impl PoolShared {
+    // Give back the last reference of a buffer of this pool
+    pub fn release(&self, buffer: Vec<u8>) {
+        let mut state = self.state.lock().unwrap();
+        self.give_back(&mut state, buffer);
+    }
+
+    fn give_back(&self, state: &mut PoolState, mut buffer: Vec<u8>) {
+        state.outstanding = state.outstanding.saturating_sub(1);
+        let key = match self.config.mode {
+            PayloadPoolMode::SIZE_CLASS => {
+                // Largest class the buffer holds, it may have been grown by a write
+                let capacity = std::cmp::max(buffer.capacity(), MIN_SIZE_CLASS);
+                let class = 1 << (usize::BITS - 1 - capacity.leading_zeros());
+                buffer.resize(class, 0);
+                class
+            }
+            _ => buffer.len(),
+        };
+        state.free.entry(key).or_default().push(buffer);
+    }
+}
+
+/**
+ * @brief Pool of serialized payload buffers.
+ *        Writers and readers get buffers with get_payload and give them back with
+ *        release_payload, or by dropping them. A received payload can be handed to several
+ *        readers with get_payload_from, which shares the buffer instead of copying it; the
+ *        buffer goes back to the pool when the last payload referencing it is released.
+ *        Only the buffers handed out by the pool are counted and kept; a shared buffer
+ *        copied by a write is a plain allocation.
+ */
+#[derive(Debug)]
+pub struct PayloadPool {
+    shared: Arc<PoolShared>,
+}
+
+impl PayloadPool {
+    pub fn new(config: PoolConfig) -> Self {
+        let pool = PayloadPool {
+            shared: Arc::new(PoolShared {
+                config,
+                state: Mutex::new(PoolState::default()),
+            }),
+        };
+
+        {
+            let mut state = pool.shared.state.lock().unwrap();
+            let size = pool.class_size(config.payload_initial_size);
+            for _ in 0..config.initial_size {
+                state.allocations += 1;
+                state.free.entry(size).or_default().push(vec![0_u8; size]);
+            }
+        }
+        pool
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.shared.config
+    }
+
+    /*
+     * Get a buffer of at least size bytes for a payload.
+     * Any buffer previously held by the payload is released first, unless the request fails.
+     * @param size Number of bytes needed
+     * @param payload Payload where the buffer is placed
+     * @return True if correct, false if the pool cannot serve the request
+     */
+    pub fn get_payload(&self, size: usize, payload: &mut SerializedPayload_t) -> bool {
+        let config = self.config();
+        if config.mode == PayloadPoolMode::PREALLOCATED && size > config.payload_initial_size {
+            return false;
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        // The payload is left untouched if the request fails. Its buffer goes back to the
+        // pool first, if the pool handed it out and other payloads do not share it.
+        let released = payload.buffer().is_from(&self.shared) && payload.ref_count() == 1;
+        if config.maximum_size != 0
+            && state.outstanding.saturating_sub(released as usize) >= config.maximum_size
+        {
+            return false;
+        }
+        let previous = payload.take_buffer();
+        let previous = if previous.is_from(&self.shared) {
+            if let Some(buffer) = previous.into_last() {
+                self.shared.give_back(&mut state, buffer);
+            }
+            None
+        } else {
+            Some(previous)
+        };
+
+        let class = self.class_size(size);
+        let mut buffer = match config.mode {
+            PayloadPoolMode::SIZE_CLASS => state.free.get_mut(&class).and_then(|f| f.pop()),
+            // Smallest buffer that fits
+            _ => state.free.range_mut(size..).find_map(|(_, f)| f.pop()),
+        };
+        if buffer.is_none() && config.mode == PayloadPoolMode::PREALLOCATED_WITH_REALLOC {
+            // Grow the largest free buffer
+            buffer = state.free.values_mut().rev().find_map(|f| f.pop());
+            if let Some(b) = buffer.as_mut() {
+                state.allocations += 1;
+                b.resize(size, 0);
+            }
+        }
+        let buffer = buffer.unwrap_or_else(|| {
+            state.allocations += 1;
+            vec![0_u8; std::cmp::max(class, size)]
+        });
+
+        state.outstanding += 1;
+        drop(state);
+        // A buffer of another pool goes back to it once this one is unlocked
+        drop(previous);
+        payload.set_buffer(PayloadData::from_pool(buffer, Arc::downgrade(&self.shared)));
+        true
+    }
+
+    /*
+     * Make a payload reference the buffer of another one, without copying it.
+     * Any buffer previously held by the destination is released first.
+     * @param data Payload whose buffer is shared
+     * @param payload Payload where the buffer is referenced
+     * @return True if correct
+     */
+    pub fn get_payload_from(
+        &self,
+        data: &SerializedPayload_t,
+        payload: &mut SerializedPayload_t,
+    ) -> bool {
+        if data.shares_buffer_with(payload) {
+            return true;
+        }
+        self.release_payload(payload);
+        payload.share(data);
+        true
+    }
+
+    /*
+     * Release the buffer of a payload, which is left empty.
+     * The buffer goes back to the pool when no other payload references it. A buffer the
+     * pool did not hand out is just dropped.
+     * @param payload Payload to release
+     * @return True if the buffer went back to the pool
+     */
+    pub fn release_payload(&self, payload: &mut SerializedPayload_t) -> bool {
+        if payload.max_size() == 0 {
+            return false;
+        }
+
+        let buffer = payload.take_buffer();
+        if !buffer.is_from(&self.shared) {
+            return false;
+        }
+        match buffer.into_last() {
+            Some(buffer) => {
+                self.shared.release(buffer);
+                true
+            }
+            // Still referenced by other payloads
+            None => false,
+        }
+    }
+
+    // Number of buffers handed out and not given back yet
+    pub fn outstanding(&self) -> usize {
+        self.shared.state.lock().unwrap().outstanding
+    }
+
+    // Number of free buffers kept by the pool
+    pub fn free_count(&self) -> usize {
+        self.shared
+            .state
+            .lock()
+            .unwrap()
+            .free
+            .values()
+            .map(|f| f.len())
+            .sum()
+    }
+
+    // Number of times the pool requested memory to the allocator
+    pub fn allocations(&self) -> usize {
+        self.shared.state.lock().unwrap().allocations
+    }
+
+    fn class_size(&self, size: usize) -> usize {
+        match self.config().mode {
+            PayloadPoolMode::SIZE_CLASS => std::cmp::max(size, MIN_SIZE_CLASS).next_power_of_two(),
+            _ => self.config().payload_initial_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    fn config(mode: PayloadPoolMode, initial_size: usize, maximum_size: usize) -> PoolConfig {
+        PoolConfig {
+            mode,
+            payload_initial_size: 128,
+            initial_size,
+            maximum_size,
+        }
+    }
+
+    #[test]
+    fn preallocated_test() {
+        let pool = PayloadPool::new(config(PayloadPoolMode::PREALLOCATED, 2, 2));
+        assert_eq!(2, pool.allocations());
+        assert_eq!(2, pool.free_count());
+
+        let mut p1 = SerializedPayload_t::default();
+        let mut p2 = SerializedPayload_t::default();
+        let mut p3 = SerializedPayload_t::default();
+        assert!(pool.get_payload(100, &mut p1));
+        assert_eq!(128, p1.max_size());
+        assert!(!pool.get_payload(129, &mut p2));
+        assert!(pool.get_payload(128, &mut p2));
+        assert!(!pool.get_payload(10, &mut p3));
+        assert_eq!(2, pool.outstanding());
+        assert_eq!(2, pool.allocations());
+
+        assert!(pool.release_payload(&mut p1));
+        assert_eq!(0, p1.max_size());
+        assert!(pool.get_payload(10, &mut p3));
+        assert_eq!(2, pool.allocations());
+
+        // A refused request leaves the payload as it was
+        p2.begin_write(RepresentationId_t::CDR_LE, 0);
+        p2.write_bytes(&[1, 2, 3, 4]);
+        assert!(!pool.get_payload(10, &mut p1));
+        assert_eq!(8, p2.length());
+        // The buffer of the payload itself can be reused
+        assert!(pool.get_payload(10, &mut p2));
+        assert_eq!(0, p2.length());
+        assert_eq!(2, pool.outstanding());
+        assert_eq!(2, pool.allocations());
+    }
+
+    #[test]
+    fn realloc_test() {
+        let pool = PayloadPool::new(config(PayloadPoolMode::PREALLOCATED_WITH_REALLOC, 1, 0));
+        let mut p1 = SerializedPayload_t::default();
+        assert!(pool.get_payload(1000, &mut p1));
+        assert_eq!(1000, p1.max_size());
+        assert_eq!(2, pool.allocations());
+        assert!(pool.release_payload(&mut p1));
+
+        // The grown buffer is reused
+        assert!(pool.get_payload(1000, &mut p1));
+        assert_eq!(2, pool.allocations());
+
+        // Unlimited pool
+        let mut p2 = SerializedPayload_t::default();
+        assert!(pool.get_payload(10, &mut p2));
+        assert_eq!(128, p2.max_size());
+        assert_eq!(3, pool.allocations());
+        assert_eq!(2, pool.outstanding());
+    }
+
+    #[test]
+    fn size_class_test() {
+        let pool = PayloadPool::new(config(PayloadPoolMode::SIZE_CLASS, 0, 0));
+        let mut p1 = SerializedPayload_t::default();
+        let mut p2 = SerializedPayload_t::default();
+        assert!(pool.get_payload(10, &mut p1));
+        assert_eq!(64, p1.max_size());
+        assert!(pool.get_payload(1000, &mut p2));
+        assert_eq!(1024, p2.max_size());
+        assert!(pool.release_payload(&mut p1));
+        assert!(pool.release_payload(&mut p2));
+        assert_eq!(2, pool.free_count());
+
+        // Each request goes to its own class
+        assert!(pool.get_payload(600, &mut p1));
+        assert_eq!(1024, p1.max_size());
+        assert!(pool.get_payload(64, &mut p2));
+        assert_eq!(64, p2.max_size());
+        assert_eq!(2, pool.allocations());
+
+        let mut p3 = SerializedPayload_t::default();
+        assert!(pool.get_payload(65, &mut p3));
+        assert_eq!(128, p3.max_size());
+        assert_eq!(3, pool.allocations());
+
+        // A buffer grown by a write goes back to the largest class it holds
+        assert!(pool.release_payload(&mut p3));
+        assert!(pool.get_payload(64, &mut p3));
+        p3.begin_write(RepresentationId_t::CDR_LE, 0);
+        p3.write_bytes(&[0; 200]);
+        assert!(pool.release_payload(&mut p3));
+        let allocations = pool.allocations();
+        assert!(pool.get_payload(100, &mut p3));
+        assert!(pool.get_payload(100, &mut p1));
+        assert_eq!(allocations, pool.allocations());
+    }
+
+    #[test]
+    fn shared_payload_test() {
+        let pool = PayloadPool::new(config(PayloadPoolMode::PREALLOCATED, 1, 1));
+        let mut received = SerializedPayload_t::default();
+        assert!(pool.get_payload(8, &mut received));
+        received.begin_write(RepresentationId_t::CDR_LE, 0);
+        received.write_bytes(&[1, 2, 3, 4]);
+
+        // Deliver to two local readers without copies
+        let mut reader1 = SerializedPayload_t::default();
+        let mut reader2 = SerializedPayload_t::default();
+        assert!(pool.get_payload_from(&received, &mut reader1));
+        assert!(pool.get_payload_from(&received, &mut reader2));
+        assert!(pool.get_payload_from(&received, &mut reader2));
+        assert_eq!(3, received.ref_count());
+        assert!(reader1.shares_buffer_with(&received));
+        assert_eq!(received, reader2);
+        assert_eq!(1, pool.outstanding());
+
+        assert!(!pool.release_payload(&mut received));
+        assert!(!pool.release_payload(&mut reader1));
+        assert_eq!(0, pool.free_count());
+        assert!(pool.release_payload(&mut reader2));
+        assert_eq!(1, pool.free_count());
+        assert_eq!(0, pool.outstanding());
+        assert!(!pool.release_payload(&mut reader2));
+        assert_eq!(1, pool.allocations());
+    }
+
+    #[test]
+    fn share_write_release_test() {
+        let pool = PayloadPool::new(config(PayloadPoolMode::PREALLOCATED, 1, 1));
+        let mut received = SerializedPayload_t::default();
+        assert!(pool.get_payload(8, &mut received));
+        received.begin_write(RepresentationId_t::CDR_LE, 0);
+        received.write_bytes(&[1, 2, 3, 4]);
+
+        // A reader writing on the shared buffer gets its own copy, which is not pooled
+        let mut reader = SerializedPayload_t::default();
+        assert!(pool.get_payload_from(&received, &mut reader));
+        reader.data_mut()[4] = 9;
+        assert!(!reader.shares_buffer_with(&received));
+        assert!(!pool.release_payload(&mut reader));
+        assert_eq!(1, pool.outstanding());
+        assert_eq!(0, pool.free_count());
+        assert!(pool.release_payload(&mut received));
+        assert_eq!(0, pool.outstanding());
+        assert_eq!(1, pool.free_count());
+
+        // Same when the payload the pool handed out is the one written
+        assert!(pool.get_payload(8, &mut received));
+        assert!(pool.get_payload_from(&received, &mut reader));
+        received.begin_write(RepresentationId_t::CDR_LE, 0);
+        assert!(!pool.release_payload(&mut received));
+        assert_eq!(1, pool.outstanding());
+        assert!(pool.release_payload(&mut reader));
+        assert_eq!(0, pool.outstanding());
+        assert_eq!(1, pool.free_count());
+        assert_eq!(1, pool.allocations());
+    }
+
+    #[test]
+    fn foreign_and_dropped_test() {
+        let pool = PayloadPool::new(config(PayloadPoolMode::PREALLOCATED, 1, 1));
+        let other = PayloadPool::new(config(PayloadPoolMode::PREALLOCATED, 1, 1));
+
+        // Buffers the pool did not hand out are neither counted nor kept
+        let mut payload = SerializedPayload_t::new(8);
+        assert!(!pool.release_payload(&mut payload));
+        assert!(other.get_payload(8, &mut payload));
+        assert!(!pool.release_payload(&mut payload));
+        assert_eq!(1, pool.free_count());
+        assert_eq!(0, other.outstanding());
+        assert_eq!(1, other.free_count());
+
+        // A buffer of another pool goes back to it when the payload gets a new one
+        assert!(other.get_payload(8, &mut payload));
+        assert!(pool.get_payload(8, &mut payload));
+        assert_eq!(0, other.outstanding());
+        assert_eq!(1, pool.outstanding());
+
+        // The last reference dropped gives the buffer back
+        let shared = payload.buffer().clone();
+        drop(payload);
+        assert_eq!(1, pool.outstanding());
+        drop(shared);
+        assert_eq!(0, pool.outstanding());
+        assert_eq!(1, pool.free_count());
+        assert_eq!(1, pool.allocations());
+    }
+
+    #[test]
+    fn concurrent_release_test() {
+        let pool = Arc::new(PayloadPool::new(config(
+            PayloadPoolMode::PREALLOCATED,
+            1,
+            1,
+        )));
+        for _ in 0..100 {
+            let mut received = SerializedPayload_t::default();
+            assert!(pool.get_payload(8, &mut received));
+            let barrier = Arc::new(Barrier::new(4));
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    let mut reader = SerializedPayload_t::default();
+                    pool.get_payload_from(&received, &mut reader);
+                    let pool = pool.clone();
+                    let barrier = barrier.clone();
+                    thread::spawn(move || {
+                        barrier.wait();
+                        pool.release_payload(&mut reader)
+                    })
+                })
+                .collect();
+            let mut released = pool.release_payload(&mut received) as usize;
+            for thread in threads {
+                released += thread.join().unwrap() as usize;
+            }
+
+            // Exactly one of the last references gives the buffer back
+            assert_eq!(1, released);
+            assert_eq!(0, pool.outstanding());
+            assert_eq!(1, pool.free_count());
+        }
+        assert_eq!(1, pool.allocations());
+    }
+}
@@ -0,0 +1,30 @@
+/**
+ * @brief Version of the RTPS protocol, as specified in the DDSI-RTPS 2.5 specification chapter 9.3.2.
+ */
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq, Hash)]
+pub struct ProtocolVersion_t {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl ProtocolVersion_t {
+    pub const fn new(major: u8, minor: u8) -> Self {
+        ProtocolVersion_t { major, minor }
+    }
+}
+
+pub const c_ProtocolVersion_2_0: ProtocolVersion_t = ProtocolVersion_t::new(2, 0);
+pub const c_ProtocolVersion_2_1: ProtocolVersion_t = ProtocolVersion_t::new(2, 1);
+pub const c_ProtocolVersion_2_2: ProtocolVersion_t = ProtocolVersion_t::new(2, 2);
+pub const c_ProtocolVersion_2_3: ProtocolVersion_t = ProtocolVersion_t::new(2, 3);
+pub const c_ProtocolVersion_2_4: ProtocolVersion_t = ProtocolVersion_t::new(2, 4);
+pub const c_ProtocolVersion_2_5: ProtocolVersion_t = ProtocolVersion_t::new(2, 5);
+
+// Version implemented by this library
+pub const c_ProtocolVersion: ProtocolVersion_t = c_ProtocolVersion_2_5;
+
+impl Default for ProtocolVersion_t {
+    fn default() -> Self {
+        c_ProtocolVersion
+    }
+}
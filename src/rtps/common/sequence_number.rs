@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Sub};
 
-#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Ord, Eq, Hash)]
 pub struct SequenceNumber_t {
     high: i32,
     low: u32,
@@ -11,8 +11,16 @@ impl SequenceNumber_t {
         SequenceNumber_t { high: hi, low: lo }
     }
 
+    pub fn high(&self) -> i32 {
+        self.high
+    }
+
+    pub fn low(&self) -> u32 {
+        self.low
+    }
+
     pub fn to64long(&self) -> u64 {
-        (self.high as u64) << 32 + self.low
+        ((self.high as u64) << 32) | self.low as u64
     }
 
     pub fn incrememt(&mut self) -> Self {
@@ -128,6 +136,16 @@ impl SequenceNumberDiff {
 mod tests {
     use super::*;
 
+    #[test]
+    fn to64long_test() {
+        assert_eq!(5, SequenceNumber_t::new(0, 5).to64long());
+        assert_eq!((3 << 32) + 5, SequenceNumber_t::new(3, 5).to64long());
+        assert_eq!(
+            SequenceNumber_t::new(3, 5),
+            SequenceNumber_t::from(SequenceNumber_t::new(3, 5).to64long())
+        );
+    }
+
     #[test]
     fn incremental_operator_test() {
         let mut seq = SequenceNumber_t::new(0, u32::MAX);
@@ -0,0 +1,165 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::messages::submessages::submessage_header::*;
+
+// Sequence numbers a set can hold from its base
+const MAX_BITS: u32 = 256;
+
+/**
+ * @brief Set of up to 256 sequence numbers from a base, as sent by ACKNACK and GAP and
+ *        specified in the DDSI-RTPS 2.5 specification chapter 9.4.2.6.
+ *        Bit 31 of the first word is the base, bit 30 the next sequence number, and so on.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SequenceNumberSet_t {
+    base: SequenceNumber_t,
+    numBits: u32,
+    bitmap: [u32; 8],
+}
+
+impl SequenceNumberSet_t {
+    pub fn new(base: SequenceNumber_t) -> Self {
+        SequenceNumberSet_t {
+            base,
+            numBits: 0,
+            bitmap: [0; 8],
+        }
+    }
+
+    pub fn base(&self) -> SequenceNumber_t {
+        self.base
+    }
+
+    // Number of sequence numbers covered by the bitmap
+    pub fn numBits(&self) -> u32 {
+        self.numBits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bitmap.iter().all(|word| *word == 0)
+    }
+
+    fn offset(&self, sequenceNumber: SequenceNumber_t) -> Option<u32> {
+        if sequenceNumber < self.base {
+            return None;
+        }
+        let offset = sequenceNumber.to64long() - self.base.to64long();
+        if offset >= MAX_BITS as u64 {
+            None
+        } else {
+            Some(offset as u32)
+        }
+    }
+
+    /**
+     * @brief Add a sequence number to the set.
+     *
+     * @param sequenceNumber Sequence number to add.
+     * @return False if it is out of the range of the set.
+     */
+    pub fn add(&mut self, sequenceNumber: SequenceNumber_t) -> bool {
+        match self.offset(sequenceNumber) {
+            Some(offset) => {
+                self.bitmap[(offset / 32) as usize] |= 0x8000_0000 >> (offset % 32);
+                self.numBits = std::cmp::max(self.numBits, offset + 1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_set(&self, sequenceNumber: SequenceNumber_t) -> bool {
+        match self.offset(sequenceNumber) {
+            Some(offset) if offset < self.numBits => {
+                (self.bitmap[(offset / 32) as usize] & (0x8000_0000 >> (offset % 32))) != 0
+            }
+            _ => false,
+        }
+    }
+
+    // Sequence numbers of the set, in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = SequenceNumber_t> + '_ {
+        (0..self.numBits)
+            .filter(move |offset| {
+                (self.bitmap[(offset / 32) as usize] & (0x8000_0000 >> (offset % 32))) != 0
+            })
+            .map(move |offset| SequenceNumber_t::from(self.base.to64long() + offset as u64))
+    }
+
+    /**
+     * @brief Encode the set: base, number of bits and the words of the bitmap in use.
+     *
+     * @param buffer Buffer where the set is appended.
+     * @param endianness Endianness of the submessage.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>, endianness: Endianness) {
+        write_sequence_number(buffer, endianness, self.base);
+        write_u32(buffer, endianness, self.numBits);
+        for word in &self.bitmap[..self.numBits.div_ceil(32) as usize] {
+            write_u32(buffer, endianness, *word);
+        }
+    }
+
+    /**
+     * @brief Decode a set.
+     *
+     * @param data Data starting with the set.
+     * @param endianness Endianness of the submessage.
+     * @return The set and the number of octets it takes, an error if it is malformed.
+     */
+    pub fn read(data: &[u8], endianness: Endianness) -> Result<(Self, usize), RtpsError> {
+        if data.len() < 12 {
+            return Err(RtpsError::new("Sequence number set too short"));
+        }
+        let mut set = SequenceNumberSet_t::new(read_sequence_number(data, endianness));
+        set.numBits = read_u32(&data[8..], endianness);
+        if set.numBits > MAX_BITS {
+            return Err(RtpsError::new("Sequence number set too large"));
+        }
+        let words = set.numBits.div_ceil(32) as usize;
+        let length = 12 + 4 * words;
+        if data.len() < length {
+            return Err(RtpsError::new("Sequence number set too short"));
+        }
+        for (n, word) in set.bitmap[..words].iter_mut().enumerate() {
+            *word = read_u32(&data[12 + 4 * n..], endianness);
+        }
+        Ok((set, length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_number_set_test() {
+        let mut set = SequenceNumberSet_t::new(SequenceNumber_t::from(10));
+        assert!(set.is_empty());
+        assert!(!set.add(SequenceNumber_t::from(9)));
+        assert!(!set.add(SequenceNumber_t::from(10 + 256)));
+        assert!(set.add(SequenceNumber_t::from(10)));
+        assert!(set.add(SequenceNumber_t::from(42)));
+        assert_eq!(33, set.numBits());
+        assert!(set.is_set(SequenceNumber_t::from(42)));
+        assert!(!set.is_set(SequenceNumber_t::from(41)));
+        let sns: Vec<SequenceNumber_t> = set.iter().collect();
+        assert_eq!(
+            vec![SequenceNumber_t::from(10), SequenceNumber_t::from(42)],
+            sns
+        );
+
+        let mut buffer = Vec::new();
+        set.write(&mut buffer, Endianness::BIG_ENDIANNESS);
+        assert_eq!(
+            vec![0, 0, 0, 0, 0, 0, 0, 10, 0, 0, 0, 33, 0x80, 0, 0, 0, 0x80, 0, 0, 0],
+            buffer
+        );
+        assert_eq!(
+            (set, buffer.len()),
+            SequenceNumberSet_t::read(&buffer, Endianness::BIG_ENDIANNESS).unwrap()
+        );
+        assert!(SequenceNumberSet_t::read(&buffer[..16], Endianness::BIG_ENDIANNESS).is_err());
+    }
+}
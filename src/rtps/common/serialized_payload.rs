@@ -1,26 +1,273 @@
-const CDR_BE: u16 = 0x0000;
-const CDR_LE: u16 = 0x0001;
-const PL_CDR_BE: u16 = 0x0002;
-const PL_CDR_LE: u16 = 0x0003;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::payload_pool::PoolShared;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Weak};
+
+/// Classic CDR, big endian
+pub const CDR_BE: u16 = 0x0000;
+/// Classic CDR, little endian
+pub const CDR_LE: u16 = 0x0001;
+/// Parameter list CDR, big endian
+pub const PL_CDR_BE: u16 = 0x0002;
+/// Parameter list CDR, little endian
+pub const PL_CDR_LE: u16 = 0x0003;
+/// XML representation
+pub const XML: u16 = 0x0004;
+/// XCDR2 plain CDR, big endian
+pub const CDR2_BE: u16 = 0x0006;
+/// XCDR2 plain CDR, little endian
+pub const CDR2_LE: u16 = 0x0007;
+/// XCDR2 delimited CDR, big endian
+pub const D_CDR2_BE: u16 = 0x0008;
+/// XCDR2 delimited CDR, little endian
+pub const D_CDR2_LE: u16 = 0x0009;
+/// XCDR2 parameter list CDR, big endian
+pub const PL_CDR2_BE: u16 = 0x000a;
+/// XCDR2 parameter list CDR, little endian
+pub const PL_CDR2_LE: u16 = 0x000b;
 
 #[cfg(target_endian = "little")]
-const DEFAULT_ENCAPSULATION: u16 = CDR_LE;
+pub const DEFAULT_ENCAPSULATION: u16 = CDR_LE;
 #[cfg(target_endian = "little")]
-const PL_DEFAULT_ENCAPSULATION: u16 = PL_CDR_LE;
+pub const PL_DEFAULT_ENCAPSULATION: u16 = PL_CDR_LE;
 
 #[cfg(target_endian = "big")]
-const DEFAULT_ENCAPSULATION: u16 = CDR_LE;
+pub const DEFAULT_ENCAPSULATION: u16 = CDR_BE;
 #[cfg(target_endian = "big")]
-const PL_DEFAULT_ENCAPSULATION: u16 = PL_CDR_BE;
+pub const PL_DEFAULT_ENCAPSULATION: u16 = PL_CDR_BE;
+
+/// Mask of the representation options bits holding the number of padding bytes
+pub const REPRESENTATION_OPTIONS_PADDING_MASK: u16 = 0x0003;
+
+/**
+ * @brief Representation identifier of a serialized payload,
+ *        as specified in the DDS-XTypes 1.3 specification chapter 7.6.3.1.2.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RepresentationId_t {
+    CDR_BE,
+    CDR_LE,
+    PL_CDR_BE,
+    PL_CDR_LE,
+    CDR2_BE,
+    CDR2_LE,
+    D_CDR2_BE,
+    D_CDR2_LE,
+    PL_CDR2_BE,
+    PL_CDR2_LE,
+    XML,
+}
+
+impl RepresentationId_t {
+    /**
+     * @brief Get the representation identifier for a wire value.
+     *
+     * @param value Representation identifier as sent on the wire.
+     * @return The identifier, or None if the value is unknown.
+     */
+    pub fn from_u16(value: u16) -> Option<Self> {
+        match value {
+            CDR_BE => Some(RepresentationId_t::CDR_BE),
+            CDR_LE => Some(RepresentationId_t::CDR_LE),
+            PL_CDR_BE => Some(RepresentationId_t::PL_CDR_BE),
+            PL_CDR_LE => Some(RepresentationId_t::PL_CDR_LE),
+            CDR2_BE => Some(RepresentationId_t::CDR2_BE),
+            CDR2_LE => Some(RepresentationId_t::CDR2_LE),
+            D_CDR2_BE => Some(RepresentationId_t::D_CDR2_BE),
+            D_CDR2_LE => Some(RepresentationId_t::D_CDR2_LE),
+            PL_CDR2_BE => Some(RepresentationId_t::PL_CDR2_BE),
+            PL_CDR2_LE => Some(RepresentationId_t::PL_CDR2_LE),
+            XML => Some(RepresentationId_t::XML),
+            _ => None,
+        }
+    }
+
+    /// Representation identifier as sent on the wire
+    pub fn to_u16(self) -> u16 {
+        match self {
+            RepresentationId_t::CDR_BE => CDR_BE,
+            RepresentationId_t::CDR_LE => CDR_LE,
+            RepresentationId_t::PL_CDR_BE => PL_CDR_BE,
+            RepresentationId_t::PL_CDR_LE => PL_CDR_LE,
+            RepresentationId_t::CDR2_BE => CDR2_BE,
+            RepresentationId_t::CDR2_LE => CDR2_LE,
+            RepresentationId_t::D_CDR2_BE => D_CDR2_BE,
+            RepresentationId_t::D_CDR2_LE => D_CDR2_LE,
+            RepresentationId_t::PL_CDR2_BE => PL_CDR2_BE,
+            RepresentationId_t::PL_CDR2_LE => PL_CDR2_LE,
+            RepresentationId_t::XML => XML,
+        }
+    }
+
+    /// Whether the payload is encoded in little endian. XML has no byte order and returns false.
+    pub fn is_little_endian(self) -> bool {
+        self != RepresentationId_t::XML && (self.to_u16() & 0x0001) == 0x0001
+    }
+
+    /// Whether the payload uses the XCDR2 (DDS-XTypes) encoding
+    pub fn is_xcdr2(self) -> bool {
+        matches!(
+            self,
+            RepresentationId_t::CDR2_BE
+                | RepresentationId_t::CDR2_LE
+                | RepresentationId_t::D_CDR2_BE
+                | RepresentationId_t::D_CDR2_LE
+                | RepresentationId_t::PL_CDR2_BE
+                | RepresentationId_t::PL_CDR2_LE
+        )
+    }
+
+    /// Whether the payload is a parameter list
+    pub fn is_parameter_list(self) -> bool {
+        matches!(
+            self,
+            RepresentationId_t::PL_CDR_BE
+                | RepresentationId_t::PL_CDR_LE
+                | RepresentationId_t::PL_CDR2_BE
+                | RepresentationId_t::PL_CDR2_LE
+        )
+    }
+}
+
+/**
+ * @brief Reference counted buffer of a serialized payload.
+ *        Several payloads may share the same buffer, which is copied when one of
+ *        them writes on it while it is shared. A buffer handed out by a payload pool
+ *        goes back to it when its last reference is dropped; the copies made by a write
+ *        do not belong to any pool.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct PayloadData {
+    data: Option<Arc<Vec<u8>>>,
+    // Pool that handed out the buffer, None for plain allocations
+    origin: Option<Weak<PoolShared>>,
+}
+
+impl PayloadData {
+    pub fn from_vec(buffer: Vec<u8>) -> Self {
+        PayloadData {
+            data: Some(Arc::new(buffer)),
+            origin: None,
+        }
+    }
+
+    /*
+     * Wrap a buffer handed out by a pool.
+     * @param buffer Buffer of the pool
+     * @param origin Pool the buffer goes back to
+     */
+    pub fn from_pool(buffer: Vec<u8>, origin: Weak<PoolShared>) -> Self {
+        PayloadData {
+            data: Some(Arc::new(buffer)),
+            origin: Some(origin),
+        }
+    }
+
+    /*
+     * Get back the buffer if this is its last reference. When several references are
+     * dropped at the same time, exactly one of them gets the buffer back. A pooled buffer
+     * does not go back to its pool, the caller owns it from now on.
+     * @return The buffer, None if it is still shared or there is no buffer
+     */
+    pub fn into_last(mut self) -> Option<Vec<u8>> {
+        self.origin = None;
+        self.data.take().and_then(Arc::into_inner)
+    }
+
+    // Whether the buffer was handed out by a pool
+    pub fn is_from(&self, pool: &Arc<PoolShared>) -> bool {
+        self.data.is_some()
+            && self
+                .origin
+                .as_ref()
+                .is_some_and(|origin| origin.as_ptr() == Arc::as_ptr(pool))
+    }
+
+    // Number of payloads referencing this buffer, 0 if there is no buffer
+    pub fn ref_count(&self) -> usize {
+        match &self.data {
+            Some(data) => Arc::strong_count(data),
+            None => 0,
+        }
+    }
+
+    // Whether both buffers are the same memory
+    pub fn ptr_eq(&self, other: &PayloadData) -> bool {
+        match (&self.data, &other.data) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    pub fn resize(&mut self, new_len: usize, value: u8) {
+        match self.make_mut() {
+            Some(data) => data.resize(new_len, value),
+            None => *self = PayloadData::from_vec(vec![value; new_len]),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        // Drop our reference instead of clearing a buffer other payloads may use
+        *self = PayloadData::default();
+    }
+
+    // Buffer to write on, copied first if other payloads share it
+    fn make_mut(&mut self) -> Option<&mut Vec<u8>> {
+        let shared = match self.data.as_mut() {
+            Some(data) => Arc::get_mut(data).is_none(),
+            None => return None,
+        };
+        if shared {
+            // The copy is a plain allocation. The shared buffer is dropped as any other
+            // reference, so it goes back to its pool if the others were released meanwhile.
+            let copy = PayloadData::from_vec(self.to_vec());
+            *self = copy;
+        }
+        self.data.as_mut().and_then(Arc::get_mut)
+    }
+}
+
+impl Drop for PayloadData {
+    fn drop(&mut self) {
+        // The last reference gives a pooled buffer back
+        if let Some(pool) = self.origin.take().and_then(|origin| origin.upgrade()) {
+            if let Some(buffer) = self.data.take().and_then(Arc::into_inner) {
+                pool.release(buffer);
+            }
+        }
+    }
+}
+
+impl Deref for PayloadData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.data {
+            Some(data) => data,
+            None => &[],
+        }
+    }
+}
+
+impl DerefMut for PayloadData {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self.make_mut() {
+            Some(data) => data.as_mut_slice(),
+            None => &mut [],
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct SerializedPayload_t {
     // Encapsulation of the data as suggested in the RTPS 2.1 specification chapter 10.
     encapsulation: u16,
+    // Representation options following the encapsulation in the representation header
+    options: u16,
     // Actual length of the data
     length: usize,
     // Pointer to the data.
-    data: Vec<u8>,
+    data: PayloadData,
     // Maximum size of the payload
     max_size: usize,
     // Position when reading
@@ -31,8 +278,9 @@ impl Default for SerializedPayload_t {
     fn default() -> Self {
         SerializedPayload_t {
             encapsulation: CDR_BE,
+            options: 0,
             length: 0,
-            data: vec![],
+            data: PayloadData::default(),
             max_size: 0,
             pos: 0,
         }
@@ -58,6 +306,16 @@ impl SerializedPayload_t {
     //!Size in bytes of the representation header as specified in the RTPS 2.3 specification chapter 10.
     pub const representation_header_size: usize = 4;
 
+    /*
+     * Create a payload with space for len bytes.
+     * @param len Maximum size of the payload
+     */
+    pub fn new(len: usize) -> Self {
+        let mut payload = SerializedPayload_t::default();
+        payload.reserve(len);
+        payload
+    }
+
     /*
      * Copy another structure (including allocating new space for the data.)
      * @param[in] serData Pointer to the structure to copy
@@ -65,8 +323,6 @@ impl SerializedPayload_t {
      * @return True if correct
      */
     pub fn copy(&mut self, serData: &SerializedPayload_t, with_limit: bool) -> bool {
-        self.length = serData.length;
-
         if serData.length > self.max_size {
             if with_limit {
                 return false;
@@ -74,13 +330,16 @@ impl SerializedPayload_t {
                 self.reserve(serData.length);
             }
         }
+        self.length = serData.length;
         self.encapsulation = serData.encapsulation;
+        self.options = serData.options;
+        self.pos = 0;
         if self.length == 0 {
             return true;
         }
-        self.data.resize(self.length, 0);
+        // Keep the whole reserved buffer, only the valid data is overwritten
         let t = &serData.data[0..self.length];
-        self.data.copy_from_slice(t);
+        self.data[..self.length].copy_from_slice(t);
         return true;
     }
 
@@ -93,6 +352,7 @@ impl SerializedPayload_t {
         self.length = serData.length;
         self.max_size = serData.length;
         self.encapsulation = serData.encapsulation;
+        self.options = serData.options;
         self.data.resize(self.length, 0);
         return true;
     }
@@ -101,7 +361,9 @@ impl SerializedPayload_t {
     pub fn empty(&mut self) {
         self.length = 0;
         self.encapsulation = CDR_BE;
+        self.options = 0;
         self.max_size = 0;
+        self.pos = 0;
         if !self.data.is_empty() {
             self.data.clear();
         }
@@ -115,6 +377,259 @@ impl SerializedPayload_t {
         self.data.resize(new_size, 0);
         self.max_size = new_size;
     }
+
+    /*
+     * Reference the buffer of another payload instead of copying it.
+     * The buffer is copied only if one of the payloads writes on it later.
+     * @param serData Payload to share
+     */
+    pub fn share(&mut self, serData: &SerializedPayload_t) {
+        self.data = serData.data.clone();
+        self.length = serData.length;
+        self.max_size = serData.max_size;
+        self.encapsulation = serData.encapsulation;
+        self.options = serData.options;
+        self.pos = 0;
+    }
+
+    // Number of payloads referencing the buffer of this payload
+    pub fn ref_count(&self) -> usize {
+        self.data.ref_count()
+    }
+
+    // Whether both payloads reference the same buffer
+    pub fn shares_buffer_with(&self, other: &SerializedPayload_t) -> bool {
+        self.data.ptr_eq(&other.data)
+    }
+
+    // Buffer of the payload
+    pub fn buffer(&self) -> &PayloadData {
+        &self.data
+    }
+
+    /*
+     * Take the buffer out of the payload, leaving it empty.
+     * @return The buffer
+     */
+    pub fn take_buffer(&mut self) -> PayloadData {
+        let data = std::mem::take(&mut self.data);
+        self.empty();
+        data
+    }
+
+    /*
+     * Use a buffer for the payload. The payload is emptied and its maximum size
+     * becomes the size of the buffer.
+     * @param data Buffer to use
+     */
+    pub fn set_buffer(&mut self, data: PayloadData) {
+        self.empty();
+        self.max_size = data.len();
+        self.data = data;
+    }
+
+    // Raw encapsulation identifier
+    pub fn encapsulation(&self) -> u16 {
+        self.encapsulation
+    }
+
+    // Typed encapsulation identifier, None if it is not a known representation
+    pub fn representation_id(&self) -> Option<RepresentationId_t> {
+        RepresentationId_t::from_u16(self.encapsulation)
+    }
+
+    /*
+     * Set the encapsulation identifier.
+     * The representation header is updated if it has already been written.
+     * @param encapsulation Representation identifier
+     */
+    pub fn set_encapsulation(&mut self, encapsulation: RepresentationId_t) {
+        self.encapsulation = encapsulation.to_u16();
+        self.update_representation_header();
+    }
+
+    // Representation options, including the padding bits
+    pub fn options(&self) -> u16 {
+        self.options
+    }
+
+    /*
+     * Set the representation options.
+     * The representation header is updated if it has already been written.
+     * @param options Representation options, including the padding bits
+     */
+    pub fn set_options(&mut self, options: u16) {
+        self.options = options;
+        self.update_representation_header();
+    }
+
+    // Number of padding bytes added at the end of the payload
+    pub fn padding(&self) -> u8 {
+        (self.options & REPRESENTATION_OPTIONS_PADDING_MASK) as u8
+    }
+
+    /*
+     * Set the number of padding bytes added at the end of the payload.
+     * @param padding Number of padding bytes, from 0 to 3
+     * @return An error if there are more than 3 padding bytes.
+     */
+    pub fn set_padding(&mut self, padding: u8) -> Result<(), RtpsError> {
+        if padding > 3 {
+            return Err(RtpsError::new("Padding is at most 3 bytes"));
+        }
+        self.options = (self.options & !REPRESENTATION_OPTIONS_PADDING_MASK) | padding as u16;
+        self.update_representation_header();
+        Ok(())
+    }
+
+    // Actual length of the data, including the representation header
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /*
+     * Set the length of the data.
+     * @param length New length, it must not exceed the maximum size
+     * @return True if correct
+     */
+    pub fn set_length(&mut self, length: usize) -> bool {
+        if length > self.max_size {
+            return false;
+        }
+        self.length = length;
+        if self.pos > length {
+            self.pos = length;
+        }
+        true
+    }
+
+    // Maximum size of the payload
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    // Valid data, including the representation header
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.length]
+    }
+
+    // Mutable valid data, including the representation header
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data[..self.length]
+    }
+
+    // Current cursor position
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /*
+     * Move the cursor.
+     * @param pos New position, it must not exceed the length
+     * @return True if correct
+     */
+    pub fn set_pos(&mut self, pos: usize) -> bool {
+        if pos > self.length {
+            return false;
+        }
+        self.pos = pos;
+        true
+    }
+
+    // Bytes left to read from the cursor position
+    pub fn remaining(&self) -> usize {
+        self.length - self.pos
+    }
+
+    /*
+     * Start writing a new payload: the representation header is written and the
+     * cursor is placed just after it.
+     * @param encapsulation Representation identifier
+     * @param options Representation options
+     */
+    pub fn begin_write(&mut self, encapsulation: RepresentationId_t, options: u16) {
+        self.encapsulation = encapsulation.to_u16();
+        self.options = options;
+        self.length = 0;
+        self.pos = 0;
+        let header = self.representation_header();
+        self.write_bytes(&header);
+    }
+
+    /*
+     * Write bytes at the cursor position, growing the payload if needed.
+     * @param bytes Bytes to write
+     */
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        let end = self.pos + bytes.len();
+        if end > self.max_size {
+            // Grow geometrically to avoid reallocating on every write
+            self.reserve(std::cmp::max(end, self.max_size * 2));
+        }
+        self.data[self.pos..end].copy_from_slice(bytes);
+        self.pos = end;
+        if end > self.length {
+            self.length = end;
+        }
+    }
+
+    /*
+     * Start reading a payload: the representation header is parsed and the cursor
+     * is placed just after it.
+     * @return True if the header could be read
+     */
+    pub fn begin_read(&mut self) -> bool {
+        if self.length < SerializedPayload_t::representation_header_size {
+            return false;
+        }
+        self.encapsulation = u16::from_be_bytes([self.data[0], self.data[1]]);
+        self.options = u16::from_be_bytes([self.data[2], self.data[3]]);
+        self.pos = SerializedPayload_t::representation_header_size;
+        true
+    }
+
+    /*
+     * Read bytes from the cursor position.
+     * @param len Number of bytes to read
+     * @return The bytes, or None if there are not enough bytes left
+     */
+    pub fn read_bytes(&mut self, len: usize) -> Option<&[u8]> {
+        if len > self.remaining() {
+            return None;
+        }
+        let start = self.pos;
+        self.pos += len;
+        Some(&self.data[start..self.pos])
+    }
+
+    /*
+     * Read bytes from the cursor position into a buffer.
+     * @param buffer Destination, filled completely
+     * @return True if there were enough bytes left
+     */
+    pub fn read_into(&mut self, buffer: &mut [u8]) -> bool {
+        match self.read_bytes(buffer.len()) {
+            Some(bytes) => {
+                buffer.copy_from_slice(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Representation header as specified in the RTPS 2.3 specification chapter 10.
+    fn representation_header(&self) -> [u8; 4] {
+        let id = self.encapsulation.to_be_bytes();
+        let options = self.options.to_be_bytes();
+        [id[0], id[1], options[0], options[1]]
+    }
+
+    fn update_representation_header(&mut self) {
+        if self.length >= SerializedPayload_t::representation_header_size {
+            let header = self.representation_header();
+            self.data[..SerializedPayload_t::representation_header_size].copy_from_slice(&header);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -140,4 +655,125 @@ mod tests {
 
         assert_eq!(sp1, sp2);
     }
+
+    #[test]
+    fn default_encapsulation_test() {
+        #[cfg(target_endian = "little")]
+        {
+            assert_eq!(CDR_LE, DEFAULT_ENCAPSULATION);
+            assert_eq!(PL_CDR_LE, PL_DEFAULT_ENCAPSULATION);
+        }
+        #[cfg(target_endian = "big")]
+        {
+            assert_eq!(CDR_BE, DEFAULT_ENCAPSULATION);
+            assert_eq!(PL_CDR_BE, PL_DEFAULT_ENCAPSULATION);
+        }
+    }
+
+    #[test]
+    fn representation_id_test() {
+        let ids = [
+            (RepresentationId_t::CDR_BE, 0x0000, false, false, false),
+            (RepresentationId_t::CDR_LE, 0x0001, true, false, false),
+            (RepresentationId_t::PL_CDR_BE, 0x0002, false, false, true),
+            (RepresentationId_t::PL_CDR_LE, 0x0003, true, false, true),
+            (RepresentationId_t::XML, 0x0004, false, false, false),
+            (RepresentationId_t::CDR2_BE, 0x0006, false, true, false),
+            (RepresentationId_t::CDR2_LE, 0x0007, true, true, false),
+            (RepresentationId_t::D_CDR2_BE, 0x0008, false, true, false),
+            (RepresentationId_t::D_CDR2_LE, 0x0009, true, true, false),
+            (RepresentationId_t::PL_CDR2_BE, 0x000a, false, true, true),
+            (RepresentationId_t::PL_CDR2_LE, 0x000b, true, true, true),
+        ];
+        for (id, value, le, xcdr2, pl) in ids.iter() {
+            assert_eq!(*value, id.to_u16());
+            assert_eq!(Some(*id), RepresentationId_t::from_u16(*value));
+            assert_eq!(*le, id.is_little_endian());
+            assert_eq!(*xcdr2, id.is_xcdr2());
+            assert_eq!(*pl, id.is_parameter_list());
+        }
+        assert_eq!(None, RepresentationId_t::from_u16(0x0005));
+        assert_eq!(None, RepresentationId_t::from_u16(0x000c));
+    }
+
+    #[test]
+    fn write_read_test() {
+        let mut sp = SerializedPayload_t::new(4);
+        sp.begin_write(RepresentationId_t::CDR2_LE, 0);
+        assert_eq!(4, sp.pos());
+        sp.write_bytes(&[1, 2, 3, 4, 5]);
+        sp.set_padding(3).unwrap();
+        assert!(sp.set_padding(4).is_err());
+        sp.write_bytes(&[0, 0, 0]);
+        assert_eq!(12, sp.length());
+        assert!(sp.max_size() >= 12);
+        assert_eq!(&[0x00, 0x07, 0x00, 0x03, 1, 2, 3, 4, 5, 0, 0, 0], sp.data());
+
+        let mut received = SerializedPayload_t::default();
+        assert!(received.copy(&sp, false));
+        assert!(received.begin_read());
+        // Copying into a bigger payload keeps its whole buffer
+        let mut bigger = SerializedPayload_t::new(64);
+        assert!(bigger.copy(&sp, true));
+        bigger.set_pos(bigger.length());
+        bigger.write_bytes(&[0; 32]);
+        assert_eq!(44, bigger.length());
+        assert_eq!(64, bigger.max_size());
+        assert_eq!(
+            Some(RepresentationId_t::CDR2_LE),
+            received.representation_id()
+        );
+        assert_eq!(3, received.padding());
+        assert_eq!(8, received.remaining());
+        assert_eq!(Some(&[1_u8, 2][..]), received.read_bytes(2));
+        let mut buffer = [0_u8; 3];
+        assert!(received.read_into(&mut buffer));
+        assert_eq!([3, 4, 5], buffer);
+        assert_eq!(None, received.read_bytes(4));
+        assert!(!received.read_into(&mut [0_u8; 4]));
+        assert_eq!(3, received.remaining());
+
+        assert!(received.set_pos(4));
+        assert!(!received.set_pos(13));
+        received.set_encapsulation(RepresentationId_t::PL_CDR_BE);
+        assert_eq!(&[0x00, 0x02, 0x00, 0x03], &received.data()[..4]);
+
+        assert!(!received.set_length(received.max_size() + 1));
+        assert!(received.set_length(2));
+        assert_eq!(2, received.pos());
+
+        let mut empty = SerializedPayload_t::default();
+        assert!(!empty.begin_read());
+    }
+
+    #[test]
+    fn share_test() {
+        let mut sp1 = SerializedPayload_t::new(8);
+        sp1.begin_write(RepresentationId_t::CDR_LE, 0);
+        sp1.write_bytes(&[1, 2, 3, 4]);
+        assert_eq!(1, sp1.ref_count());
+
+        let mut sp2 = SerializedPayload_t::default();
+        sp2.share(&sp1);
+        assert_eq!(2, sp1.ref_count());
+        assert!(sp1.shares_buffer_with(&sp2));
+        assert_eq!(sp1, sp2);
+
+        // Writing on a shared buffer copies it
+        sp2.data_mut()[4] = 9;
+        assert!(!sp1.shares_buffer_with(&sp2));
+        assert_eq!(1, sp1.ref_count());
+        assert_eq!(1, sp1.data()[4]);
+        assert_eq!(9, sp2.data()[4]);
+
+        let buffer = sp2.take_buffer();
+        assert_eq!(0, sp2.length());
+        assert_eq!(0, sp2.max_size());
+        let buffer = buffer.into_last().unwrap();
+        assert_eq!(8, buffer.len());
+
+        sp2.set_buffer(PayloadData::from_vec(buffer));
+        assert_eq!(8, sp2.max_size());
+        assert_eq!(0, sp2.length());
+    }
 }
@@ -0,0 +1,152 @@
+use crate::cdr::cdr_deserializer::*;
+use crate::cdr::cdr_serializer::*;
+use crate::cdr::encoding::CdrVersion;
+use crate::rtps::common::error::RtpsError;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const NANOSECONDS_PER_SECOND: u64 = 1_000_000_000;
+
+/**
+ * @brief Time or duration, as seconds and nanoseconds.
+ *        On the wire the nanoseconds are sent as a fraction of second (1/2^32 s).
+ */
+#[derive(Copy, Clone, Debug, Default, PartialOrd, PartialEq, Ord, Eq, Hash)]
+pub struct Time_t {
+    pub seconds: i32,
+    pub nanosec: u32,
+}
+
+pub type Duration_t = Time_t;
+
+impl Time_t {
+    pub const c_TimeZero: Time_t = Time_t {
+        seconds: 0,
+        nanosec: 0,
+    };
+    pub const c_TimeInfinite: Time_t = Time_t {
+        seconds: 0x7fffffff,
+        nanosec: 0xffffffff,
+    };
+    pub const c_TimeInvalid: Time_t = Time_t {
+        seconds: -1,
+        nanosec: 0xffffffff,
+    };
+
+    pub fn new(seconds: i32, nanosec: u32) -> Self {
+        Time_t { seconds, nanosec }
+    }
+
+    // Current time since the Unix epoch
+    pub fn now() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Time_t::from(now)
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        *self == Time_t::c_TimeInfinite
+    }
+
+    /**
+     * @brief Nanoseconds as a fraction of second, as sent on the wire.
+     *
+     * @return Fraction, 0xffffffff for the special infinite and invalid values.
+     */
+    pub fn fraction(&self) -> u32 {
+        if self.nanosec == 0xffffffff {
+            return 0xffffffff;
+        }
+        (((self.nanosec as u64) << 32) / NANOSECONDS_PER_SECOND) as u32
+    }
+
+    /**
+     * @brief Build a time from its wire representation.
+     *
+     * @param seconds Seconds.
+     * @param fraction Fraction of second (1/2^32 s).
+     * @return The time.
+     */
+    pub fn from_fraction(seconds: i32, fraction: u32) -> Self {
+        if fraction == 0xffffffff {
+            return Time_t::new(seconds, 0xffffffff);
+        }
+        // Round up so converting back gives the same fraction
+        let nanosec = ((fraction as u64) * NANOSECONDS_PER_SECOND + (1 << 32) - 1) >> 32;
+        Time_t::new(seconds, nanosec as u32)
+    }
+
+    /**
+     * @brief Convert to a standard duration.
+     *
+     * @return The duration, or None if the time is negative, infinite or invalid.
+     */
+    pub fn to_duration(&self) -> Option<Duration> {
+        if self.seconds < 0 || self.nanosec as u64 >= NANOSECONDS_PER_SECOND {
+            return None;
+        }
+        Some(Duration::new(self.seconds as u64, self.nanosec))
+    }
+}
+
+impl From<Duration> for Time_t {
+    fn from(duration: Duration) -> Self {
+        if duration.as_secs() >= Time_t::c_TimeInfinite.seconds as u64 {
+            return Time_t::c_TimeInfinite;
+        }
+        Time_t::new(duration.as_secs() as i32, duration.subsec_nanos())
+    }
+}
+
+impl CdrSerialize for Time_t {
+    fn serialize(&self, ser: &mut CdrSerializer) -> Result<(), RtpsError> {
+        ser.serialize_i32(self.seconds)?;
+        ser.serialize_u32(self.fraction())
+    }
+
+    fn max_serialized_size(version: CdrVersion, position: usize) -> Option<usize> {
+        Some(version.align(position, 4) + 8)
+    }
+}
+
+impl CdrDeserialize for Time_t {
+    fn deserialize(de: &mut CdrDeserializer) -> Result<Self, RtpsError> {
+        let seconds = de.deserialize_i32()?;
+        let fraction = de.deserialize_u32()?;
+        Ok(Time_t::from_fraction(seconds, fraction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fraction_test() {
+        let half = Time_t::new(1, 500_000_000);
+        assert_eq!(0x8000_0000, half.fraction());
+        assert_eq!(half, Time_t::from_fraction(1, 0x8000_0000));
+        assert_eq!(0xffffffff, Time_t::c_TimeInfinite.fraction());
+        assert_eq!(
+            Time_t::c_TimeInfinite,
+            Time_t::from_fraction(0x7fffffff, 0xffffffff)
+        );
+
+        for nanosec in [1, 999, 123_456_789, 999_999_999] {
+            let time = Time_t::new(0, nanosec);
+            let fraction = time.fraction();
+            assert_eq!(fraction, Time_t::from_fraction(0, fraction).fraction());
+        }
+    }
+
+    #[test]
+    fn duration_test() {
+        let duration = Duration::new(3, 250);
+        assert_eq!(Time_t::new(3, 250), Time_t::from(duration));
+        assert_eq!(Some(duration), Time_t::new(3, 250).to_duration());
+        assert_eq!(None, Time_t::c_TimeInfinite.to_duration());
+        assert_eq!(None, Time_t::c_TimeInvalid.to_duration());
+        assert!(Time_t::from(Duration::from_secs(u64::MAX)).is_infinite());
+        assert!(Time_t::now() > Time_t::c_TimeZero);
+    }
+}
@@ -0,0 +1,4 @@
+/// Vendor of the implementation sending a message, as specified in the DDSI-RTPS 2.5 specification chapter 9.3.1.3
+pub type VendorId_t = [u8; 2];
+
+pub const c_VendorId_Unknown: VendorId_t = [0x00, 0x00];
@@ -0,0 +1,622 @@
+use crate::rtps::behavior::timed_event::{Clock, ResourceEvent, TimedEvent};
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+// Largest payload sent in a single DATA, or fragment of a DATA_FRAG, so that it fits in a
+// message with its headers and inline QoS
+pub const MAX_PAYLOAD_SIZE: usize = 64000;
+
+/**
+ * @brief Order the pending samples of the writers of a flow controller are sent in.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FlowControllerSchedulerPolicy {
+    // In the order the samples were written, whatever their writer
+    FIFO,
+    // One sample of each writer in turn
+    ROUND_ROBIN,
+    // Samples of the writers with the lowest priority value first, FIFO among equal priorities
+    HIGH_PRIORITY,
+}
+
+/**
+ * @brief Configuration of a flow controller.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlowControllerDescriptor {
+    pub name: String,
+    pub scheduler: FlowControllerSchedulerPolicy,
+    /// Bytes of payload sent per period at most, 0 for no limit
+    pub max_bytes_per_period: usize,
+    pub period_ms: u64,
+}
+
+impl Default for FlowControllerDescriptor {
+    fn default() -> Self {
+        FlowControllerDescriptor {
+            name: "default".to_string(),
+            scheduler: FlowControllerSchedulerPolicy::FIFO,
+            max_bytes_per_period: 0,
+            period_ms: 100,
+        }
+    }
+}
+
+/**
+ * @brief Bytes a writer may still send.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SendBudget {
+    // None for no limit
+    remaining: Option<usize>,
+    // Whether nothing was taken yet from the budget of a whole period
+    whole: bool,
+}
+
+impl SendBudget {
+    pub fn new(bytes: usize) -> Self {
+        SendBudget {
+            remaining: Some(bytes),
+            whole: false,
+        }
+    }
+
+    // Budget of a period nothing was sent in yet
+    pub fn new_period(bytes: usize) -> Self {
+        SendBudget {
+            remaining: Some(bytes),
+            whole: true,
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        SendBudget {
+            remaining: None,
+            whole: false,
+        }
+    }
+
+    pub fn remaining(&self) -> Option<usize> {
+        self.remaining
+    }
+
+    /**
+     * @brief Take bytes from the budget.
+     *
+     * @param bytes Bytes about to be sent.
+     * @return False, leaving the budget unchanged, if fewer bytes remain.
+     */
+    pub fn try_consume(&mut self, bytes: usize) -> bool {
+        match &mut self.remaining {
+            Some(remaining) if *remaining < bytes => false,
+            Some(remaining) => {
+                *remaining -= bytes;
+                self.whole &= bytes == 0;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /**
+     * @brief Take bytes that can not be split from the budget. Bytes larger than the budget
+     *        of a whole period are taken along with all of it, otherwise they would never be
+     *        sent.
+     *
+     * @param bytes Bytes about to be sent.
+     * @return False, leaving the budget unchanged, if fewer bytes remain.
+     */
+    pub fn try_consume_whole(&mut self, bytes: usize) -> bool {
+        if self.whole && self.remaining.is_some_and(|remaining| remaining < bytes) {
+            self.remaining = Some(0);
+            self.whole = false;
+            return true;
+        }
+        self.try_consume(bytes)
+    }
+}
+
+/**
+ * @brief Writer whose new samples are sent by a flow controller.
+ */
+pub trait FlowControlledWriter: Send + Sync {
+    /**
+     * @brief Send what is left of samples together, within a budget. The samples and
+     *        fragments already sent are not sent again.
+     *
+     * @param sequenceNumbers Sequence numbers of the samples, in order.
+     * @param budget Bytes the writer may send.
+     * @return False if the budget ran out before the samples were completely sent.
+     */
+    fn deliver_samples(
+        &self,
+        sequenceNumbers: &[SequenceNumber_t],
+        budget: &mut SendBudget,
+    ) -> bool;
+}
+
+struct WriterEntry {
+    writer: Weak<dyn FlowControlledWriter>,
+    priority: i32,
+    // Pending samples, in order, with the order they were added in across writers. The
+    // samples added together are sent together
+    samples: VecDeque<(u64, Vec<SequenceNumber_t>)>,
+}
+
+#[derive(Default)]
+struct ControllerState {
+    writers: BTreeMap<GUID_t, WriterEntry>,
+    // Order of the last sample added
+    last_order: u64,
+    // Writer the round robin continues with
+    next_writer: Option<GUID_t>,
+    tokens: usize,
+    // Start of the current period
+    period_start: Option<Instant>,
+}
+
+impl ControllerState {
+    // Writer whose first pending sample is sent next
+    fn select(&self, scheduler: FlowControllerSchedulerPolicy) -> Option<GUID_t> {
+        let pending = self.writers.iter().filter_map(|(guid, entry)| {
+            entry
+                .samples
+                .front()
+                .map(|(order, _)| (guid, entry, *order))
+        });
+        match scheduler {
+            FlowControllerSchedulerPolicy::FIFO => pending
+                .min_by_key(|(_, _, order)| *order)
+                .map(|(guid, _, _)| *guid),
+            FlowControllerSchedulerPolicy::HIGH_PRIORITY => pending
+                .min_by_key(|(_, entry, order)| (entry.priority, *order))
+                .map(|(guid, _, _)| *guid),
+            FlowControllerSchedulerPolicy::ROUND_ROBIN => {
+                let pending: Vec<GUID_t> = pending.map(|(guid, _, _)| *guid).collect();
+                match self.next_writer {
+                    Some(next) => pending
+                        .iter()
+                        .find(|guid| **guid >= next)
+                        .or(pending.first())
+                        .copied(),
+                    None => pending.first().copied(),
+                }
+            }
+        }
+    }
+}
+
+/**
+ * @brief Flow controller, sending the new samples of its writers at a limited rate so that
+ *        writers sending big samples at once do not flood the network.
+ *        Every period the controller gets max_bytes_per_period bytes to send, which are not
+ *        kept for the next periods. The pending samples are given the budget in the order of
+ *        the scheduler, the ones of a writer always in the order they were written. A sample
+ *        is sent as fragments of at most max_payload bytes, so that a sample larger than the
+ *        budget of a period is sent over several periods. A key-only sample, which can not
+ *        be fragmented, takes the whole budget of a period when larger than it.
+ *        Without limit, the samples are sent as soon as they are added.
+ */
+pub struct FlowController {
+    descriptor: FlowControllerDescriptor,
+    clock: Arc<dyn Clock>,
+    // Sends the pending samples when the next period starts
+    event: Mutex<Option<TimedEvent>>,
+    // Held while samples are sent, so that they are sent in the order of the scheduler
+    delivery: Mutex<()>,
+    state: Mutex<ControllerState>,
+}
+
+impl FlowController {
+    /**
+     * @brief Create a flow controller.
+     *
+     * @param descriptor Configuration of the controller.
+     * @param service Service starting the periods, whose clock gives their time.
+     * @return The controller.
+     */
+    pub fn new(descriptor: FlowControllerDescriptor, service: &ResourceEvent) -> Arc<Self> {
+        let period = Duration::from_millis(descriptor.period_ms);
+        let controller = Arc::new(FlowController {
+            descriptor,
+            clock: service.clock().clone(),
+            event: Mutex::new(None),
+            delivery: Mutex::new(()),
+            state: Mutex::new(ControllerState::default()),
+        });
+        let weak = Arc::downgrade(&controller);
+        *controller.event.lock().unwrap() = Some(TimedEvent::new(service, period, move |_| {
+            if let Some(controller) = weak.upgrade() {
+                controller.run();
+            }
+            false
+        }));
+        controller
+    }
+
+    pub fn descriptor(&self) -> &FlowControllerDescriptor {
+        &self.descriptor
+    }
+
+    fn is_limited(&self) -> bool {
+        self.descriptor.max_bytes_per_period > 0
+    }
+
+    // Largest payload of a DATA or of a fragment of the writers, so that it fits in a period
+    pub fn max_payload(&self) -> usize {
+        if self.is_limited() {
+            std::cmp::min(self.descriptor.max_bytes_per_period, MAX_PAYLOAD_SIZE)
+        } else {
+            MAX_PAYLOAD_SIZE
+        }
+    }
+
+    /**
+     * @brief Send the new samples of a writer through this controller.
+     *
+     * @param guid GUID of the writer.
+     * @param writer Writer, kept as a weak reference.
+     * @param priority Priority of the writer for HIGH_PRIORITY, lower values first.
+     */
+    pub fn register_writer(
+        &self,
+        guid: GUID_t,
+        writer: Weak<dyn FlowControlledWriter>,
+        priority: i32,
+    ) {
+        self.state.lock().unwrap().writers.insert(
+            guid,
+            WriterEntry {
+                writer,
+                priority,
+                samples: VecDeque::new(),
+            },
+        );
+    }
+
+    // Forget a writer and its pending samples, false if it was not registered
+    pub fn unregister_writer(&self, guid: &GUID_t) -> bool {
+        self.state.lock().unwrap().writers.remove(guid).is_some()
+    }
+
+    // Samples waiting for budget, with the ones of the batches partially sent
+    pub fn pending_samples(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .writers
+            .values()
+            .flat_map(|entry| entry.samples.iter())
+            .map(|(_, sequenceNumbers)| sequenceNumbers.len())
+            .sum()
+    }
+
+    // Add a new sample of a writer, see add_new_samples
+    pub fn add_new_sample(&self, guid: &GUID_t, sequenceNumber: SequenceNumber_t) -> bool {
+        self.add_new_samples(guid, &[sequenceNumber])
+    }
+
+    /**
+     * @brief Add new samples of a writer, such as a batch, sent right away if the budget
+     *        allows. They are given the budget as a whole and sent together, in as few
+     *        messages as possible. It must be called without the writer locked.
+     *
+     * @param guid GUID of the writer.
+     * @param sequenceNumbers Sequence numbers of the samples, in order.
+     * @return False if the writer is not registered.
+     */
+    pub fn add_new_samples(&self, guid: &GUID_t, sequenceNumbers: &[SequenceNumber_t]) -> bool {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.last_order += 1;
+            let order = state.last_order;
+            match state.writers.get_mut(guid) {
+                Some(entry) => {
+                    if !sequenceNumbers.is_empty() {
+                        entry.samples.push_back((order, sequenceNumbers.to_vec()))
+                    }
+                }
+                None => return false,
+            }
+        }
+        self.run();
+        true
+    }
+
+    // Budget of the current period, starting a new period if the last one is over
+    fn refill(&self, state: &mut ControllerState, now: Instant) -> SendBudget {
+        if !self.is_limited() {
+            return SendBudget::unlimited();
+        }
+        let period = Duration::from_millis(self.descriptor.period_ms);
+        match state.period_start {
+            Some(start) if now < start + period => {}
+            _ => {
+                state.period_start = Some(now);
+                state.tokens = self.descriptor.max_bytes_per_period;
+            }
+        }
+        if state.tokens == self.descriptor.max_bytes_per_period {
+            SendBudget::new_period(state.tokens)
+        } else {
+            SendBudget::new(state.tokens)
+        }
+    }
+
+    /**
+     * @brief Send the pending samples the budget of the current period allows, in the order
+     *        of the scheduler. If samples are left, they are sent when the next period starts.
+     *
+     * @return The number of samples completely sent, the ones of a partially sent batch
+     *         are counted once it is complete.
+     */
+    pub fn run(&self) -> usize {
+        let _delivery = self.delivery.lock().unwrap();
+        let now = self.clock.now();
+        let mut sent = 0;
+        let mut budget = {
+            let mut state = self.state.lock().unwrap();
+            self.refill(&mut state, now)
+        };
+        loop {
+            let (guid, writer, sequenceNumbers) = {
+                let mut state = self.state.lock().unwrap();
+                let guid = match state.select(self.descriptor.scheduler) {
+                    Some(guid) => guid,
+                    None => break,
+                };
+                let entry = &state.writers[&guid];
+                let sequenceNumbers = entry.samples.front().unwrap().1.clone();
+                match entry.writer.upgrade() {
+                    Some(writer) => (guid, writer, sequenceNumbers),
+                    None => {
+                        state.writers.remove(&guid);
+                        continue;
+                    }
+                }
+            };
+
+            // The writer is called without the controller locked
+            let complete = writer.deliver_samples(&sequenceNumbers, &mut budget);
+            let mut state = self.state.lock().unwrap();
+            if let Some(tokens) = budget.remaining() {
+                state.tokens = tokens;
+            }
+            if !complete {
+                break;
+            }
+            sent += sequenceNumbers.len();
+            if let Some(entry) = state.writers.get_mut(&guid) {
+                entry.samples.pop_front();
+            }
+            // The round robin continues with the writer after this one
+            state.next_writer = state.writers.range(guid..).nth(1).map(|(next, _)| *next);
+        }
+
+        // The samples left are sent when the next period starts
+        let state = self.state.lock().unwrap();
+        let pending = state
+            .writers
+            .values()
+            .any(|entry| !entry.samples.is_empty());
+        if let Some(event) = self.event.lock().unwrap().as_ref() {
+            match state.period_start {
+                Some(start) if pending => {
+                    event.restart_timer_at(start + Duration::from_millis(self.descriptor.period_ms))
+                }
+                _ => event.cancel_timer(),
+            }
+        }
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtps::behavior::timed_event::VirtualClock;
+    use crate::rtps::common::entity_id_t::EntityId_t;
+
+    // Writer whose samples take a fixed number of bytes, sent in a single piece
+    struct Writer {
+        id: u8,
+        size: usize,
+        delivered: Mutex<Vec<u64>>,
+        sent: Arc<Mutex<Vec<(u8, u64)>>>,
+    }
+
+    impl FlowControlledWriter for Writer {
+        fn deliver_samples(
+            &self,
+            sequenceNumbers: &[SequenceNumber_t],
+            budget: &mut SendBudget,
+        ) -> bool {
+            let mut delivered = self.delivered.lock().unwrap();
+            for sequenceNumber in sequenceNumbers {
+                let sn = sequenceNumber.to64long();
+                if delivered.contains(&sn) {
+                    continue;
+                }
+                if !budget.try_consume(self.size) {
+                    return false;
+                }
+                delivered.push(sn);
+                self.sent.lock().unwrap().push((self.id, sn));
+            }
+            true
+        }
+    }
+
+    fn guid(id: u8) -> GUID_t {
+        let mut guid = GUID_t::unknown();
+        guid.entityId =
+            EntityId_t::new_from_key_kind(id as u32, EntityId_t::ENTITYKIND_USER_WRITER_NO_KEY);
+        guid
+    }
+
+    struct Fixture {
+        clock: Arc<VirtualClock>,
+        service: Arc<ResourceEvent>,
+        controller: Arc<FlowController>,
+        sent: Arc<Mutex<Vec<(u8, u64)>>>,
+        writers: Vec<Arc<Writer>>,
+    }
+
+    impl Fixture {
+        fn new(scheduler: FlowControllerSchedulerPolicy, max_bytes_per_period: usize) -> Self {
+            let clock = Arc::new(VirtualClock::new());
+            let service = Arc::new(ResourceEvent::new(clock.clone()));
+            let controller = FlowController::new(
+                FlowControllerDescriptor {
+                    name: "test".to_string(),
+                    scheduler,
+                    max_bytes_per_period,
+                    period_ms: 10,
+                },
+                &service,
+            );
+            Fixture {
+                clock,
+                service,
+                controller,
+                sent: Arc::new(Mutex::new(Vec::new())),
+                writers: Vec::new(),
+            }
+        }
+
+        fn add_writer(&mut self, id: u8, size: usize, priority: i32) {
+            let writer = Arc::new(Writer {
+                id,
+                size,
+                delivered: Mutex::new(Vec::new()),
+                sent: self.sent.clone(),
+            });
+            let weak: Weak<dyn FlowControlledWriter> = Arc::downgrade(&writer) as _;
+            self.controller.register_writer(guid(id), weak, priority);
+            self.writers.push(writer);
+        }
+
+        fn add(&self, id: u8, sn: u64) {
+            assert!(self
+                .controller
+                .add_new_sample(&guid(id), SequenceNumber_t::from(sn)));
+        }
+
+        // Samples sent in the next period
+        fn next_period(&self) -> Vec<(u8, u64)> {
+            self.sent.lock().unwrap().clear();
+            self.clock.advance(Duration::from_millis(10));
+            self.service.run_due();
+            std::mem::take(&mut *self.sent.lock().unwrap())
+        }
+    }
+
+    #[test]
+    fn token_bucket_test() {
+        let mut fixture = Fixture::new(FlowControllerSchedulerPolicy::FIFO, 100);
+        assert_eq!(100, fixture.controller.max_payload());
+        fixture.add_writer(1, 40, 0);
+        for sn in 1..=5 {
+            fixture.add(1, sn);
+        }
+        assert_eq!(vec![(1, 1), (1, 2)], *fixture.sent.lock().unwrap());
+        assert_eq!(3, fixture.controller.pending_samples());
+        // Nothing before the next period
+        fixture.clock.advance(Duration::from_millis(9));
+        assert_eq!(0, fixture.service.run_due());
+        assert_eq!(vec![(1, 3), (1, 4)], fixture.next_period());
+        assert_eq!(vec![(1, 5)], fixture.next_period());
+        assert_eq!(0, fixture.controller.pending_samples());
+        assert_eq!(None, fixture.service.next_deadline());
+
+        // The budget left is not kept
+        fixture.clock.advance(Duration::from_millis(100));
+        for sn in 6..=8 {
+            fixture.add(1, sn);
+        }
+        assert_eq!(vec![(1, 6), (1, 7)], *fixture.sent.lock().unwrap());
+        assert_eq!(vec![(1, 8)], fixture.next_period());
+    }
+
+    #[test]
+    fn batch_test() {
+        let mut fixture = Fixture::new(FlowControllerSchedulerPolicy::FIFO, 100);
+        fixture.add_writer(1, 40, 0);
+        fixture.add_writer(2, 40, 0);
+        let batch: Vec<SequenceNumber_t> = (1..=3).map(SequenceNumber_t::from).collect();
+        assert!(fixture.controller.add_new_samples(&guid(1), &batch));
+        fixture.add(2, 1);
+        assert_eq!(vec![(1, 1), (1, 2)], *fixture.sent.lock().unwrap());
+        assert_eq!(4, fixture.controller.pending_samples());
+
+        // The rest of the batch first, without sending its samples again
+        assert_eq!(vec![(1, 3), (2, 1)], fixture.next_period());
+        assert_eq!(0, fixture.controller.pending_samples());
+        assert!(fixture.controller.add_new_samples(&guid(1), &[]));
+        assert_eq!(0, fixture.controller.pending_samples());
+    }
+
+    #[test]
+    fn unlimited_test() {
+        let mut fixture = Fixture::new(FlowControllerSchedulerPolicy::FIFO, 0);
+        assert_eq!(MAX_PAYLOAD_SIZE, fixture.controller.max_payload());
+        fixture.add_writer(1, 100_000, 0);
+        for sn in 1..=3 {
+            fixture.add(1, sn);
+        }
+        assert_eq!(3, fixture.sent.lock().unwrap().len());
+        assert_eq!(None, fixture.service.next_deadline());
+        assert!(!fixture
+            .controller
+            .add_new_sample(&guid(2), SequenceNumber_t::from(1)));
+    }
+
+    // Samples of three writers sent one per period, after the first one
+    fn schedule(scheduler: FlowControllerSchedulerPolicy) -> Vec<(u8, u64)> {
+        let mut fixture = Fixture::new(scheduler, 10);
+        fixture.add_writer(1, 10, 2);
+        fixture.add_writer(2, 10, 1);
+        fixture.add_writer(3, 10, 2);
+        for (id, sn) in [(1, 1), (1, 2), (1, 3), (3, 1), (2, 1), (2, 2), (3, 2)] {
+            fixture.add(id, sn);
+        }
+        let mut sent = std::mem::take(&mut *fixture.sent.lock().unwrap());
+        while fixture.controller.pending_samples() > 0 {
+            sent.extend(fixture.next_period());
+        }
+        sent
+    }
+
+    #[test]
+    fn scheduler_test() {
+        assert_eq!(
+            vec![(1, 1), (1, 2), (1, 3), (3, 1), (2, 1), (2, 2), (3, 2)],
+            schedule(FlowControllerSchedulerPolicy::FIFO)
+        );
+        assert_eq!(
+            vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2), (1, 3)],
+            schedule(FlowControllerSchedulerPolicy::ROUND_ROBIN)
+        );
+        assert_eq!(
+            vec![(1, 1), (2, 1), (2, 2), (1, 2), (1, 3), (3, 1), (3, 2)],
+            schedule(FlowControllerSchedulerPolicy::HIGH_PRIORITY)
+        );
+    }
+
+    #[test]
+    fn dropped_writer_test() {
+        let mut fixture = Fixture::new(FlowControllerSchedulerPolicy::FIFO, 10);
+        fixture.add_writer(1, 10, 0);
+        fixture.add_writer(2, 10, 0);
+        for (id, sn) in [(1, 1), (1, 2), (2, 1)] {
+            fixture.add(id, sn);
+        }
+        fixture.writers.remove(0);
+        assert_eq!(vec![(2, 1)], fixture.next_period());
+        assert!(!fixture.controller.unregister_writer(&guid(1)));
+        assert!(fixture.controller.unregister_writer(&guid(2)));
+    }
+}
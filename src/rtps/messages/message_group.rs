@@ -0,0 +1,465 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+use crate::rtps::common::locator_list::LocatorList;
+use crate::rtps::common::serialized_payload::SerializedPayload_t;
+use crate::rtps::common::time::Time_t;
+use crate::rtps::flowcontrol::flow_controller::SendBudget;
+use crate::rtps::messages::message_header::Header_t;
+use crate::rtps::messages::submessages::data::DataSubmessage;
+use crate::rtps::messages::submessages::data_frag::DataFragSubmessage;
+use crate::rtps::messages::submessages::info_dst::InfoDestinationSubmessage;
+use crate::rtps::messages::submessages::info_ts::InfoTimestampSubmessage;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use crate::rtps::transport::udp_transport::{UdpSender, MAX_MESSAGE_SIZE};
+
+// Endianness of the submessages sent by this library
+pub const DEFAULT_ENDIANNESS: Endianness = Endianness::LITTLE_ENDIANNESS;
+
+/**
+ * @brief Collect the submessages sent by an endpoint to the same destinations into as few
+ *        messages as possible. A message is sent when the next submessage does not fit in it,
+ *        on flush, and when the group is dropped.
+ *        INFO_DST and INFO_TS are repeated at the start of every message, so that every
+ *        message can be interpreted on its own.
+ */
+pub struct RTPSMessageGroup<'a> {
+    sender: &'a UdpSender,
+    header: Header_t,
+    destinations: LocatorList,
+    buffer: Vec<u8>,
+    // Participant the submessages are sent to, None for every participant
+    destination_prefix: Option<GuidPrefix_t>,
+    // Source timestamp given by the last INFO_TS of the current message
+    timestamp: Option<Time_t>,
+    sent_messages: usize,
+}
+
+impl<'a> RTPSMessageGroup<'a> {
+    pub fn new(sender: &'a UdpSender, header: Header_t, destinations: LocatorList) -> Self {
+        RTPSMessageGroup {
+            sender,
+            header,
+            destinations,
+            buffer: Vec::new(),
+            destination_prefix: None,
+            timestamp: None,
+            sent_messages: 0,
+        }
+    }
+
+    // Messages sent so far
+    pub fn sent_messages(&self) -> usize {
+        self.sent_messages
+    }
+
+    /**
+     * @brief Send the following submessages to a single participant.
+     *
+     * @param guidPrefix Destination participant, None for every participant.
+     */
+    pub fn set_destination(&mut self, guidPrefix: Option<GuidPrefix_t>) {
+        if guidPrefix == self.destination_prefix {
+            return;
+        }
+        self.destination_prefix = guidPrefix;
+        // A new message starts with the INFO_DST of the destination
+        if !self.buffer.is_empty() {
+            let mut info_dst = Vec::new();
+            let prefix = guidPrefix.unwrap_or(GuidPrefix_t::c_GuidPrefix_Unknown);
+            InfoDestinationSubmessage::new(DEFAULT_ENDIANNESS, prefix).write(&mut info_dst);
+            if self.buffer.len() + info_dst.len() > MAX_MESSAGE_SIZE {
+                self.flush();
+            } else {
+                self.buffer.extend_from_slice(&info_dst);
+            }
+        }
+    }
+
+    // Octets to add before a submessage: message header, INFO_DST and INFO_TS
+    fn context(&self, timestamp: Option<Time_t>) -> Vec<u8> {
+        let mut context = Vec::new();
+        let new_message = self.buffer.is_empty();
+        if new_message {
+            self.header.write(&mut context);
+            if let Some(prefix) = self.destination_prefix {
+                InfoDestinationSubmessage::new(DEFAULT_ENDIANNESS, prefix).write(&mut context);
+            }
+        }
+        if timestamp.is_some() && (new_message || timestamp != self.timestamp) {
+            InfoTimestampSubmessage::new(DEFAULT_ENDIANNESS, timestamp).write(&mut context);
+        }
+        context
+    }
+
+    fn add(&mut self, timestamp: Option<Time_t>, submessage: &[u8]) -> Result<(), RtpsError> {
+        // Worst case, the submessage starts a new message after an INFO_DST and an INFO_TS
+        if Header_t::SIZE + 16 + 12 + submessage.len() > MAX_MESSAGE_SIZE {
+            return Err(RtpsError::new("Submessage does not fit in a message"));
+        }
+        let mut context = self.context(timestamp);
+        if self.buffer.len() + context.len() + submessage.len() > MAX_MESSAGE_SIZE {
+            self.flush();
+            context = self.context(timestamp);
+        }
+        self.buffer.extend_from_slice(&context);
+        self.buffer.extend_from_slice(submessage);
+        if timestamp.is_some() {
+            self.timestamp = timestamp;
+        }
+        Ok(())
+    }
+
+    /**
+     * @brief Add an encoded submessage that does not depend on a timestamp.
+     *
+     * @param submessage Submessage, header included.
+     * @return An error if the submessage is larger than a message.
+     */
+    pub fn add_submessage(&mut self, submessage: &[u8]) -> Result<(), RtpsError> {
+        self.add(None, submessage)
+    }
+
+    /**
+     * @brief Add a DATA with a change, preceded by its source timestamp.
+     *        Changes that are not alive are sent as key-only DATA. The inline QoS of the
+     *        change must be encoded with DEFAULT_ENDIANNESS.
+     *
+     * @param change Change to send.
+     * @param readerId Reader the change is sent to, c_EntityId_Unknown for every reader.
+     * @return An error if the change cannot be encoded or is larger than a message.
+     */
+    pub fn add_data(
+        &mut self,
+        change: &CacheChange_t,
+        readerId: EntityId_t,
+    ) -> Result<(), RtpsError> {
+        let mut payload = SerializedPayload_t::default();
+        payload.share(&change.serializedPayload);
+        let mut data = if change.kind.is_alive() {
+            DataSubmessage::new(
+                DEFAULT_ENDIANNESS,
+                readerId,
+                change.writerGUID.entityId,
+                change.sequenceNumber,
+                payload,
+            )
+        } else {
+            DataSubmessage::new_key_only(
+                DEFAULT_ENDIANNESS,
+                readerId,
+                change.writerGUID.entityId,
+                change.sequenceNumber,
+                change.kind,
+                &change.instanceHandle,
+                payload,
+            )?
+        };
+        if let Some(inlineQos) = &change.inlineQos {
+            match &mut data.inlineQos {
+                Some(parameters) => {
+                    for parameter in inlineQos.parameters() {
+                        parameters.push_raw(parameter.pid, parameter.value.clone())?;
+                    }
+                }
+                None => data.inlineQos = Some(inlineQos.clone()),
+            }
+        }
+        let mut submessage = Vec::new();
+        data.write(&mut submessage)?;
+        self.add(Some(change.sourceTimestamp), &submessage)
+    }
+
+    /**
+     * @brief Add a DATA_FRAG with fragments of an alive change, preceded by its source
+     *        timestamp.
+     *
+     * @param change Change to send.
+     * @param readerId Reader the change is sent to, c_EntityId_Unknown for every reader.
+     * @param fragmentSize Size of the fragments of the change.
+     * @param fragmentStartingNum First fragment sent, starting at 1.
+     * @param fragmentsInSubmessage Number of fragments sent.
+     * @return An error if the fragments are not part of the change or larger than a message.
+     */
+    pub fn add_data_frag(
+        &mut self,
+        change: &CacheChange_t,
+        readerId: EntityId_t,
+        fragmentSize: u16,
+        fragmentStartingNum: u32,
+        fragmentsInSubmessage: u16,
+    ) -> Result<(), RtpsError> {
+        let mut data_frag = DataFragSubmessage::new(
+            DEFAULT_ENDIANNESS,
+            readerId,
+            change.writerGUID.entityId,
+            change.sequenceNumber,
+            change.serializedPayload.data(),
+            fragmentSize,
+            fragmentStartingNum,
+            fragmentsInSubmessage,
+        )?;
+        data_frag.inlineQos = change.inlineQos.clone();
+        let mut submessage = Vec::new();
+        data_frag.write(&mut submessage)?;
+        self.add(Some(change.sourceTimestamp), &submessage)
+    }
+
+    /**
+     * @brief Add what is left of a change within a budget of payload bytes. An alive change
+     *        whose payload is larger than maxPayload is sent as DATA_FRAG, one fragment at a
+     *        time, so that it can be sent over several budgets. A key-only change can not be
+     *        fragmented, when larger than the budget of a period it takes all of it.
+     *
+     * @param change Change to send.
+     * @param readerId Reader the change is sent to, c_EntityId_Unknown for every reader.
+     * @param maxPayload Largest payload of a DATA, and size of the fragments.
+     * @param fragmentStartingNum First fragment left to send, starting at 1.
+     * @param budget Bytes of payload that may be sent, reduced by the bytes sent.
+     * @return The first fragment left to send when the budget ran out, None once the change
+     *         is completely sent. An error if the change cannot be encoded.
+     */
+    pub fn add_change(
+        &mut self,
+        change: &CacheChange_t,
+        readerId: EntityId_t,
+        maxPayload: usize,
+        fragmentStartingNum: u32,
+        budget: &mut SendBudget,
+    ) -> Result<Option<u32>, RtpsError> {
+        let length = change.serializedPayload.length();
+        if !change.kind.is_alive() || length <= maxPayload {
+            if !budget.try_consume_whole(length) {
+                return Ok(Some(1));
+            }
+            self.add_data(change, readerId)?;
+            return Ok(None);
+        }
+        let fragmentSize = std::cmp::min(maxPayload, u16::MAX as usize) as u16;
+        let fragments = length.div_ceil(fragmentSize as usize) as u32;
+        for fragment in std::cmp::max(fragmentStartingNum, 1)..=fragments {
+            let offset = (fragment as usize - 1) * fragmentSize as usize;
+            if !budget.try_consume(std::cmp::min(fragmentSize as usize, length - offset)) {
+                return Ok(Some(fragment));
+            }
+            self.add_data_frag(change, readerId, fragmentSize, fragment, 1)?;
+        }
+        Ok(None)
+    }
+
+    // Send the current message, if it has submessages
+    pub fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            self.sender.send_to_list(&self.buffer, &self.destinations);
+            self.sent_messages += 1;
+            self.buffer.clear();
+            self.timestamp = None;
+        }
+    }
+}
+
+impl Drop for RTPSMessageGroup<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtps::common::change_kind_t::ChangeKind_t;
+    use crate::rtps::common::guid::GUID_t;
+    use crate::rtps::common::locator::Locator_t;
+    use crate::rtps::common::protocol_version::c_ProtocolVersion;
+    use crate::rtps::common::sequence_number::SequenceNumber_t;
+    use crate::rtps::common::serialized_payload::RepresentationId_t;
+    use crate::rtps::common::vendor_id::c_VendorId_Unknown;
+    use crate::rtps::messages::message_receiver::*;
+    use crate::rtps::transport::udp_transport::open_input_channel;
+    use crate::rtps::utils::ip_locator::setIPv4;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Reader {
+        received: Mutex<Vec<(Option<Time_t>, SequenceNumber_t, usize)>>,
+        // Sequence number, first fragment and size of the fragments received
+        fragments: Mutex<Vec<(SequenceNumber_t, u32, usize)>>,
+    }
+
+    impl MessageReceiverListener for Reader {
+        fn on_data(&self, state: &ReceiverState, data: &DataSubmessage) {
+            let length = data.serializedPayload.as_ref().unwrap().length();
+            self.received
+                .lock()
+                .unwrap()
+                .push((state.timestamp, data.writerSN, length));
+        }
+
+        fn on_data_frag(&self, _state: &ReceiverState, data_frag: &DataFragSubmessage) {
+            self.fragments.lock().unwrap().push((
+                data_frag.writerSN,
+                data_frag.fragmentStartingNum,
+                data_frag.fragments.len(),
+            ));
+        }
+    }
+
+    fn change(sn: u64, seconds: i32, length: usize) -> CacheChange_t {
+        let mut change = CacheChange_t::new();
+        change.writerGUID = GUID_t::unknown();
+        change.sequenceNumber = SequenceNumber_t::from(sn);
+        change.sourceTimestamp = Time_t::new(seconds, 0);
+        change
+            .serializedPayload
+            .begin_write(RepresentationId_t::CDR_LE, 0);
+        change.serializedPayload.write_bytes(&vec![7; length]);
+        change
+    }
+
+    fn header() -> Header_t {
+        let mut prefix = GuidPrefix_t::unknown();
+        prefix.value[0] = 1;
+        Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, prefix)
+    }
+
+    fn input_channel() -> (std::net::UdpSocket, Locator_t) {
+        let mut locator = Locator_t::new_from_port(0);
+        setIPv4(&mut locator, "127.0.0.1").unwrap();
+        open_input_channel(&locator).unwrap()
+    }
+
+    // Receive messages and return the reader they were given to
+    fn receive(socket: &std::net::UdpSocket, bound: &Locator_t, messages: usize) -> Arc<Reader> {
+        let reader = Arc::new(Reader::default());
+        let endpoints = Arc::new(EndpointRegistry::new());
+        endpoints.add_reader(EntityId_t::c_EntityId_Unknown, reader.clone());
+        let receiver = MessageReceiver::new(header().guidPrefix, endpoints);
+        let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE];
+        for _ in 0..messages {
+            let (length, _) = socket.recv_from(&mut buffer).unwrap();
+            receiver.process_message(&buffer[..length], bound).unwrap();
+        }
+        reader
+    }
+
+    #[test]
+    fn message_group_test() {
+        let (socket, bound) = input_channel();
+        let sender = UdpSender::new().unwrap();
+        let header = header();
+        let prefix = header.guidPrefix;
+
+        let mut group = RTPSMessageGroup::new(&sender, header, LocatorList::from(vec![bound]));
+        group.set_destination(Some(prefix));
+        group
+            .add_data(&change(1, 10, 40_000), EntityId_t::c_EntityId_Unknown)
+            .unwrap();
+        group
+            .add_data(&change(2, 10, 4), EntityId_t::c_EntityId_Unknown)
+            .unwrap();
+        // Does not fit with the first change
+        group
+            .add_data(&change(3, 11, 40_000), EntityId_t::c_EntityId_Unknown)
+            .unwrap();
+        assert!(group
+            .add_data(
+                &change(4, 11, MAX_MESSAGE_SIZE),
+                EntityId_t::c_EntityId_Unknown
+            )
+            .is_err());
+        assert_eq!(1, group.sent_messages());
+        group.flush();
+        assert_eq!(2, group.sent_messages());
+
+        let reader = receive(&socket, &bound, 2);
+        let received = reader.received.lock().unwrap();
+        assert_eq!(
+            vec![
+                (Some(Time_t::new(10, 0)), SequenceNumber_t::from(1), 40_004),
+                (Some(Time_t::new(10, 0)), SequenceNumber_t::from(2), 8),
+                (Some(Time_t::new(11, 0)), SequenceNumber_t::from(3), 40_004),
+            ],
+            *received
+        );
+    }
+
+    #[test]
+    fn add_change_test() {
+        let (socket, bound) = input_channel();
+        let sender = UdpSender::new().unwrap();
+        let mut group = RTPSMessageGroup::new(&sender, header(), LocatorList::from(vec![bound]));
+        let unknown = EntityId_t::c_EntityId_Unknown;
+
+        // 40004 bytes sent as fragments of 16000 bytes
+        let large = change(1, 10, 40_000);
+        let mut budget = SendBudget::new(20_000);
+        assert_eq!(
+            Some(2),
+            group
+                .add_change(&large, unknown, 16_000, 1, &mut budget)
+                .unwrap()
+        );
+        assert_eq!(Some(4000), budget.remaining());
+        let mut budget = SendBudget::new(30_000);
+        assert_eq!(
+            None,
+            group
+                .add_change(&large, unknown, 16_000, 2, &mut budget)
+                .unwrap()
+        );
+        assert_eq!(Some(5996), budget.remaining());
+
+        let small = change(2, 10, 4);
+        let mut budget = SendBudget::new(4);
+        assert_eq!(
+            Some(1),
+            group
+                .add_change(&small, unknown, 16_000, 1, &mut budget)
+                .unwrap()
+        );
+        assert_eq!(
+            None,
+            group
+                .add_change(&small, unknown, 16_000, 1, &mut SendBudget::unlimited())
+                .unwrap()
+        );
+
+        // A key-only change larger than the budget of a period is sent with all of it
+        let mut disposed = change(3, 10, 20);
+        disposed.kind = ChangeKind_t::NOT_ALIVE_DISPOSED;
+        let mut budget = SendBudget::new(16);
+        assert_eq!(
+            Some(1),
+            group
+                .add_change(&disposed, unknown, 16, 1, &mut budget)
+                .unwrap()
+        );
+        let mut budget = SendBudget::new_period(16);
+        assert_eq!(
+            None,
+            group
+                .add_change(&disposed, unknown, 16, 1, &mut budget)
+                .unwrap()
+        );
+        assert_eq!(Some(0), budget.remaining());
+        group.flush();
+
+        let reader = receive(&socket, &bound, 1);
+        assert_eq!(
+            vec![
+                (SequenceNumber_t::from(1), 1, 16_000),
+                (SequenceNumber_t::from(1), 2, 16_000),
+                (SequenceNumber_t::from(1), 3, 8004),
+            ],
+            *reader.fragments.lock().unwrap()
+        );
+        assert_eq!(
+            vec![
+                (Some(Time_t::new(10, 0)), SequenceNumber_t::from(2), 8),
+                (Some(Time_t::new(10, 0)), SequenceNumber_t::from(3), 24),
+            ],
+            *reader.received.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+use crate::rtps::common::protocol_version::ProtocolVersion_t;
+use crate::rtps::common::vendor_id::VendorId_t;
+
+// First octets of every RTPS message
+pub const RTPS_PROTOCOL_ID: [u8; 4] = *b"RTPS";
+
+/**
+ * @brief Header of an RTPS message, as specified in the DDSI-RTPS 2.5 specification chapter 9.4.4.
+ *        Its fields are octet arrays, so it does not depend on endianness.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Header_t {
+    pub version: ProtocolVersion_t,
+    pub vendorId: VendorId_t,
+    pub guidPrefix: GuidPrefix_t,
+}
+
+impl Header_t {
+    pub const SIZE: usize = 20;
+
+    pub fn new(version: ProtocolVersion_t, vendorId: VendorId_t, guidPrefix: GuidPrefix_t) -> Self {
+        Header_t {
+            version,
+            vendorId,
+            guidPrefix,
+        }
+    }
+
+    /**
+     * @brief Decode a message header.
+     *
+     * @param data Data starting with the header.
+     * @return The header, an error if the data is not an RTPS message of a supported version.
+     */
+    pub fn read(data: &[u8]) -> Result<Self, RtpsError> {
+        if data.len() < Header_t::SIZE || data[..4] != RTPS_PROTOCOL_ID {
+            return Err(RtpsError::new("Not an RTPS message"));
+        }
+        let version = ProtocolVersion_t::new(data[4], data[5]);
+        if version.major != 2 {
+            return Err(RtpsError::new("Unsupported RTPS protocol version"));
+        }
+        let mut guidPrefix = GuidPrefix_t::unknown();
+        guidPrefix.value.copy_from_slice(&data[8..Header_t::SIZE]);
+        Ok(Header_t::new(version, [data[6], data[7]], guidPrefix))
+    }
+
+    /**
+     * @brief Encode the header.
+     *
+     * @param buffer Buffer where the header is appended.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&RTPS_PROTOCOL_ID);
+        buffer.push(self.version.major);
+        buffer.push(self.version.minor);
+        buffer.extend_from_slice(&self.vendorId);
+        buffer.extend_from_slice(&self.guidPrefix.value);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+use crate::rtps::common::locator::Locator_t;
+use crate::rtps::common::protocol_version::ProtocolVersion_t;
+use crate::rtps::common::time::Time_t;
+use crate::rtps::common::vendor_id::VendorId_t;
+use crate::rtps::messages::message_header::Header_t;
+use crate::rtps::messages::submessages::acknack::AckNackSubmessage;
+use crate::rtps::messages::submessages::data::DataSubmessage;
+use crate::rtps::messages::submessages::data_frag::DataFragSubmessage;
+use crate::rtps::messages::submessages::gap::GapSubmessage;
+use crate::rtps::messages::submessages::heartbeat::HeartbeatSubmessage;
+use crate::rtps::messages::submessages::info_dst::InfoDestinationSubmessage;
+use crate::rtps::messages::submessages::info_ts::InfoTimestampSubmessage;
+use crate::rtps::messages::submessages::nack_frag::NackFragSubmessage;
+use crate::rtps::messages::submessages::submessage_header::*;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/**
+ * @brief State of the receiver while the submessages of a message are interpreted,
+ *        as specified in the DDSI-RTPS 2.5 specification chapter 8.3.4.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReceiverState {
+    pub sourceVersion: ProtocolVersion_t,
+    pub sourceVendorId: VendorId_t,
+    pub sourceGuidPrefix: GuidPrefix_t,
+    pub destGuidPrefix: GuidPrefix_t,
+    /// Source timestamp given by the last INFO_TS, if any
+    pub timestamp: Option<Time_t>,
+    /// Address the message was received from
+    pub sourceLocator: Locator_t,
+}
+
+/**
+ * @brief Submessages handed to an endpoint by the message receiver.
+ *        The endpoint must not block, it is called from the receive threads.
+ */
+pub trait MessageReceiverListener: Send + Sync {
+    fn on_data(&self, _state: &ReceiverState, _data: &DataSubmessage) {}
+
+    fn on_data_frag(&self, _state: &ReceiverState, _data_frag: &DataFragSubmessage) {}
+
+    fn on_acknack(&self, _state: &ReceiverState, _acknack: &AckNackSubmessage) {}
+
+    fn on_nack_frag(&self, _state: &ReceiverState, _nack_frag: &NackFragSubmessage) {}
+
+    fn on_heartbeat(&self, _state: &ReceiverState, _heartbeat: &HeartbeatSubmessage) {}
+
+    fn on_gap(&self, _state: &ReceiverState, _gap: &GapSubmessage) {}
+
+    /**
+     * @brief Change handed over by a writer of the same process instead of a DATA, without
+     *        being serialized. The reader shares the payload of the change.
+     *
+     * @param state State of a message from the participant of the writer.
+     * @param change Change of the writer.
+     */
+    fn on_intraprocess_change(&self, _state: &ReceiverState, _change: &CacheChange_t) {}
+}
+
+/**
+ * @brief Endpoints of a participant, by entity id, the submessages are routed to.
+ */
+#[derive(Default)]
+pub struct EndpointRegistry {
+    writers: RwLock<HashMap<EntityId_t, Arc<dyn MessageReceiverListener>>>,
+    readers: RwLock<HashMap<EntityId_t, Arc<dyn MessageReceiverListener>>>,
+}
+
+impl EndpointRegistry {
+    pub fn new() -> Self {
+        EndpointRegistry::default()
+    }
+
+    pub fn add_writer(&self, entityId: EntityId_t, writer: Arc<dyn MessageReceiverListener>) {
+        self.writers.write().unwrap().insert(entityId, writer);
+    }
+
+    pub fn add_reader(&self, entityId: EntityId_t, reader: Arc<dyn MessageReceiverListener>) {
+        self.readers.write().unwrap().insert(entityId, reader);
+    }
+
+    pub fn remove_writer(&self, entityId: &EntityId_t) -> bool {
+        self.writers.write().unwrap().remove(entityId).is_some()
+    }
+
+    pub fn remove_reader(&self, entityId: &EntityId_t) -> bool {
+        self.readers.write().unwrap().remove(entityId).is_some()
+    }
+
+    pub fn writers_len(&self) -> usize {
+        self.writers.read().unwrap().len()
+    }
+
+    pub fn readers_len(&self) -> usize {
+        self.readers.read().unwrap().len()
+    }
+
+    pub fn writer(&self, writerId: &EntityId_t) -> Option<Arc<dyn MessageReceiverListener>> {
+        self.writers.read().unwrap().get(writerId).cloned()
+    }
+
+    pub fn reader(&self, readerId: &EntityId_t) -> Option<Arc<dyn MessageReceiverListener>> {
+        self.readers.read().unwrap().get(readerId).cloned()
+    }
+
+    // Readers a submessage is sent to, every reader for c_EntityId_Unknown
+    fn readers_for(&self, readerId: &EntityId_t) -> Vec<Arc<dyn MessageReceiverListener>> {
+        let readers = self.readers.read().unwrap();
+        if *readerId == EntityId_t::c_EntityId_Unknown {
+            readers.values().cloned().collect()
+        } else {
+            readers.get(readerId).into_iter().cloned().collect()
+        }
+    }
+}
+
+/**
+ * @brief Interpret the RTPS messages received by a participant and route their submessages
+ *        to its endpoints.
+ */
+pub struct MessageReceiver {
+    participantGuidPrefix: GuidPrefix_t,
+    endpoints: Arc<EndpointRegistry>,
+}
+
+impl MessageReceiver {
+    pub fn new(participantGuidPrefix: GuidPrefix_t, endpoints: Arc<EndpointRegistry>) -> Self {
+        MessageReceiver {
+            participantGuidPrefix,
+            endpoints,
+        }
+    }
+
+    /**
+     * @brief Process a received message.
+     *        Unknown submessages are skipped. A malformed submessage ends the processing of the
+     *        message, the submessages before it are kept.
+     *
+     * @param data Received message.
+     * @param sourceLocator Address the message was received from.
+     * @return An error if the message is not a valid RTPS message.
+     */
+    pub fn process_message(&self, data: &[u8], sourceLocator: &Locator_t) -> Result<(), RtpsError> {
+        let header = Header_t::read(data)?;
+        let mut state = ReceiverState {
+            sourceVersion: header.version,
+            sourceVendorId: header.vendorId,
+            sourceGuidPrefix: header.guidPrefix,
+            destGuidPrefix: self.participantGuidPrefix,
+            timestamp: None,
+            sourceLocator: *sourceLocator,
+        };
+
+        let mut pos = Header_t::SIZE;
+        while data.len() - pos >= SubmessageHeader_t::SIZE {
+            let submessage = SubmessageHeader_t::read(&data[pos..])?;
+            pos += SubmessageHeader_t::SIZE;
+            // A zero length means the submessage goes up to the end of the message
+            let length = match submessage.submessageLength as usize {
+                0 if submessage.submessageId != PAD && submessage.submessageId != INFO_TS => {
+                    data.len() - pos
+                }
+                length => length,
+            };
+            if length > data.len() - pos {
+                return Err(RtpsError::new("Submessage longer than the message"));
+            }
+            let body = &data[pos..pos + length];
+            pos += length;
+            self.process_submessage(&mut state, &submessage, body)?;
+        }
+        Ok(())
+    }
+
+    fn process_submessage(
+        &self,
+        state: &mut ReceiverState,
+        header: &SubmessageHeader_t,
+        body: &[u8],
+    ) -> Result<(), RtpsError> {
+        match header.submessageId {
+            INFO_TS => state.timestamp = InfoTimestampSubmessage::read(header, body)?.timestamp,
+            INFO_DST => {
+                let guidPrefix = InfoDestinationSubmessage::read(header, body)?.guidPrefix;
+                state.destGuidPrefix = if guidPrefix == GuidPrefix_t::c_GuidPrefix_Unknown {
+                    self.participantGuidPrefix
+                } else {
+                    guidPrefix
+                };
+            }
+            DATA => {
+                let data = DataSubmessage::read(header, body)?;
+                if self.is_for_this_participant(state) {
+                    for reader in self.endpoints.readers_for(&data.readerId) {
+                        reader.on_data(state, &data);
+                    }
+                }
+            }
+            DATA_FRAG => {
+                let data_frag = DataFragSubmessage::read(header, body)?;
+                if self.is_for_this_participant(state) {
+                    for reader in self.endpoints.readers_for(&data_frag.readerId) {
+                        reader.on_data_frag(state, &data_frag);
+                    }
+                }
+            }
+            ACKNACK => {
+                let acknack = AckNackSubmessage::read(header, body)?;
+                if self.is_for_this_participant(state) {
+                    if let Some(writer) = self.endpoints.writer(&acknack.writerId) {
+                        writer.on_acknack(state, &acknack);
+                    }
+                }
+            }
+            NACK_FRAG => {
+                let nack_frag = NackFragSubmessage::read(header, body)?;
+                if self.is_for_this_participant(state) {
+                    if let Some(writer) = self.endpoints.writer(&nack_frag.writerId) {
+                        writer.on_nack_frag(state, &nack_frag);
+                    }
+                }
+            }
+            HEARTBEAT => {
+                let heartbeat = HeartbeatSubmessage::read(header, body)?;
+                if self.is_for_this_participant(state) {
+                    for reader in self.endpoints.readers_for(&heartbeat.readerId) {
+                        reader.on_heartbeat(state, &heartbeat);
+                    }
+                }
+            }
+            GAP => {
+                let gap = GapSubmessage::read(header, body)?;
+                if self.is_for_this_participant(state) {
+                    for reader in self.endpoints.readers_for(&gap.readerId) {
+                        reader.on_gap(state, &gap);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn is_for_this_participant(&self, state: &ReceiverState) -> bool {
+        state.destGuidPrefix == self.participantGuidPrefix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdr::encoding::Endianness;
+    use crate::rtps::common::protocol_version::c_ProtocolVersion;
+    use crate::rtps::common::sequence_number::SequenceNumber_t;
+    use crate::rtps::common::serialized_payload::*;
+    use crate::rtps::common::vendor_id::c_VendorId_Unknown;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Reader {
+        received: Mutex<Vec<(ReceiverState, SequenceNumber_t)>>,
+    }
+
+    impl MessageReceiverListener for Reader {
+        fn on_data(&self, state: &ReceiverState, data: &DataSubmessage) {
+            self.received.lock().unwrap().push((*state, data.writerSN));
+        }
+    }
+
+    fn prefix(id: u8) -> GuidPrefix_t {
+        let mut prefix = GuidPrefix_t::unknown();
+        prefix.value[0] = id;
+        prefix
+    }
+
+    fn data(readerId: EntityId_t, sn: u64) -> DataSubmessage {
+        let mut payload = SerializedPayload_t::default();
+        payload.begin_write(RepresentationId_t::CDR_LE, 0);
+        payload.write_bytes(&[1, 2, 3, 4]);
+        DataSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            readerId,
+            EntityId_t::new_from_key_kind(1, EntityId_t::ENTITYKIND_USER_WRITER_NO_KEY),
+            SequenceNumber_t::from(sn),
+            payload,
+        )
+    }
+
+    #[test]
+    fn routing_test() {
+        let readerId = EntityId_t::new_from_key_kind(2, EntityId_t::ENTITYKIND_USER_READER_NO_KEY);
+        let otherId = EntityId_t::new_from_key_kind(3, EntityId_t::ENTITYKIND_USER_READER_NO_KEY);
+        let reader = Arc::new(Reader::default());
+        let other = Arc::new(Reader::default());
+        let endpoints = Arc::new(EndpointRegistry::new());
+        endpoints.add_reader(readerId, reader.clone());
+        endpoints.add_reader(otherId, other.clone());
+        let receiver = MessageReceiver::new(prefix(1), endpoints.clone());
+
+        let mut message = Vec::new();
+        Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, prefix(2)).write(&mut message);
+        InfoTimestampSubmessage::new(Endianness::BIG_ENDIANNESS, Some(Time_t::new(10, 0)))
+            .write(&mut message);
+        data(readerId, 1).write(&mut message).unwrap();
+        data(EntityId_t::c_EntityId_Unknown, 2)
+            .write(&mut message)
+            .unwrap();
+        // Sent to another participant
+        InfoDestinationSubmessage::new(Endianness::BIG_ENDIANNESS, prefix(3)).write(&mut message);
+        data(readerId, 3).write(&mut message).unwrap();
+        // Sent to every participant
+        InfoDestinationSubmessage::new(Endianness::BIG_ENDIANNESS, GuidPrefix_t::unknown())
+            .write(&mut message);
+        InfoTimestampSubmessage::new(Endianness::LITTLE_ENDIANNESS, None).write(&mut message);
+        data(readerId, 4).write(&mut message).unwrap();
+
+        let source = Locator_t::new_from_port(7411);
+        receiver.process_message(&message, &source).unwrap();
+
+        {
+            let received = reader.received.lock().unwrap();
+            let sns: Vec<u32> = received.iter().map(|(_, sn)| sn.low()).collect();
+            assert_eq!(vec![1, 2, 4], sns);
+            assert_eq!(prefix(2), received[0].0.sourceGuidPrefix);
+            assert_eq!(Some(Time_t::new(10, 0)), received[0].0.timestamp);
+            assert_eq!(None, received[2].0.timestamp);
+            assert_eq!(source, received[0].0.sourceLocator);
+        }
+        assert_eq!(1, other.received.lock().unwrap().len());
+
+        // Truncated message
+        let truncated = &message[..message.len() - 2];
+        assert!(receiver.process_message(truncated, &source).is_err());
+        assert!(receiver.process_message(&message[..10], &source).is_err());
+
+        assert!(endpoints.remove_reader(&otherId));
+        assert!(!endpoints.remove_reader(&otherId));
+        assert_eq!(1, endpoints.readers_len());
+    }
+}
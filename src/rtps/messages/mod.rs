@@ -1 +1,5 @@
+pub mod message_group;
+pub mod message_header;
+pub mod message_receiver;
+pub mod parameter_list;
 pub mod submessages;
@@ -0,0 +1,672 @@
+use crate::cdr::cdr_deserializer::*;
+use crate::cdr::cdr_serializer::*;
+use crate::cdr::encoding::{Endianness, PID_MUST_UNDERSTAND_FLAG};
+use crate::dds::core::policy::*;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::locator::Locator_t;
+use crate::rtps::common::serialized_payload::*;
+use crate::rtps::common::time::Duration_t;
+
+pub type ParameterId_t = u16;
+
+// Parameter ids, as specified in the DDSI-RTPS 2.5 specification tables 9.13 and 9.14
+pub const PID_PAD: ParameterId_t = 0x0000;
+pub const PID_SENTINEL: ParameterId_t = 0x0001;
+pub const PID_PARTICIPANT_LEASE_DURATION: ParameterId_t = 0x0002;
+pub const PID_TIME_BASED_FILTER: ParameterId_t = 0x0004;
+pub const PID_TOPIC_NAME: ParameterId_t = 0x0005;
+pub const PID_OWNERSHIP_STRENGTH: ParameterId_t = 0x0006;
+pub const PID_TYPE_NAME: ParameterId_t = 0x0007;
+pub const PID_DOMAIN_ID: ParameterId_t = 0x000f;
+pub const PID_PROTOCOL_VERSION: ParameterId_t = 0x0015;
+pub const PID_VENDORID: ParameterId_t = 0x0016;
+pub const PID_RELIABILITY: ParameterId_t = 0x001a;
+pub const PID_LIVELINESS: ParameterId_t = 0x001b;
+pub const PID_DURABILITY: ParameterId_t = 0x001d;
+pub const PID_DURABILITY_SERVICE: ParameterId_t = 0x001e;
+pub const PID_OWNERSHIP: ParameterId_t = 0x001f;
+pub const PID_PRESENTATION: ParameterId_t = 0x0021;
+pub const PID_DEADLINE: ParameterId_t = 0x0023;
+pub const PID_DESTINATION_ORDER: ParameterId_t = 0x0025;
+pub const PID_LATENCY_BUDGET: ParameterId_t = 0x0027;
+pub const PID_PARTITION: ParameterId_t = 0x0029;
+pub const PID_LIFESPAN: ParameterId_t = 0x002b;
+pub const PID_USER_DATA: ParameterId_t = 0x002c;
+pub const PID_GROUP_DATA: ParameterId_t = 0x002d;
+pub const PID_TOPIC_DATA: ParameterId_t = 0x002e;
+pub const PID_UNICAST_LOCATOR: ParameterId_t = 0x002f;
+pub const PID_MULTICAST_LOCATOR: ParameterId_t = 0x0030;
+pub const PID_DEFAULT_UNICAST_LOCATOR: ParameterId_t = 0x0031;
+pub const PID_METATRAFFIC_UNICAST_LOCATOR: ParameterId_t = 0x0032;
+pub const PID_METATRAFFIC_MULTICAST_LOCATOR: ParameterId_t = 0x0033;
+pub const PID_PARTICIPANT_MANUAL_LIVELINESS_COUNT: ParameterId_t = 0x0034;
+pub const PID_CONTENT_FILTER_PROPERTY: ParameterId_t = 0x0035;
+pub const PID_HISTORY: ParameterId_t = 0x0040;
+pub const PID_RESOURCE_LIMITS: ParameterId_t = 0x0041;
+pub const PID_EXPECTS_INLINE_QOS: ParameterId_t = 0x0043;
+pub const PID_DEFAULT_MULTICAST_LOCATOR: ParameterId_t = 0x0048;
+pub const PID_TRANSPORT_PRIORITY: ParameterId_t = 0x0049;
+pub const PID_PARTICIPANT_GUID: ParameterId_t = 0x0050;
+pub const PID_GROUP_GUID: ParameterId_t = 0x0052;
+pub const PID_CONTENT_FILTER_INFO: ParameterId_t = 0x0055;
+pub const PID_COHERENT_SET: ParameterId_t = 0x0056;
+pub const PID_DIRECTED_WRITE: ParameterId_t = 0x0057;
+pub const PID_BUILTIN_ENDPOINT_SET: ParameterId_t = 0x0058;
+pub const PID_PROPERTY_LIST: ParameterId_t = 0x0059;
+pub const PID_ENDPOINT_GUID: ParameterId_t = 0x005a;
+pub const PID_TYPE_MAX_SIZE_SERIALIZED: ParameterId_t = 0x0060;
+pub const PID_ORIGINAL_WRITER_INFO: ParameterId_t = 0x0061;
+pub const PID_ENTITY_NAME: ParameterId_t = 0x0062;
+pub const PID_GROUP_COHERENT_SET: ParameterId_t = 0x0063;
+pub const PID_GROUP_SEQ_NUM: ParameterId_t = 0x0064;
+pub const PID_WRITER_GROUP_INFO: ParameterId_t = 0x0065;
+pub const PID_SECURE_WRITER_GROUP_INFO: ParameterId_t = 0x0066;
+pub const PID_KEY_HASH: ParameterId_t = 0x0070;
+pub const PID_STATUS_INFO: ParameterId_t = 0x0071;
+pub const PID_DATA_REPRESENTATION: ParameterId_t = 0x0073;
+pub const PID_TYPE_CONSISTENCY_ENFORCEMENT: ParameterId_t = 0x0074;
+pub const PID_TYPE_INFORMATION: ParameterId_t = 0x0075;
+pub const PID_BUILTIN_ENDPOINT_QOS: ParameterId_t = 0x0077;
+pub const PID_DOMAIN_TAG: ParameterId_t = 0x4014;
+
+/// Parameter ids with this flag are defined by the vendor of the sender
+pub const PID_VENDOR_SPECIFIC_FLAG: ParameterId_t = 0x8000;
+
+// Parameter ids understood by this implementation
+const KNOWN_PIDS: &[ParameterId_t] = &[
+    PID_PAD,
+    PID_SENTINEL,
+    PID_PARTICIPANT_LEASE_DURATION,
+    PID_TIME_BASED_FILTER,
+    PID_TOPIC_NAME,
+    PID_OWNERSHIP_STRENGTH,
+    PID_TYPE_NAME,
+    PID_DOMAIN_ID,
+    PID_PROTOCOL_VERSION,
+    PID_VENDORID,
+    PID_RELIABILITY,
+    PID_LIVELINESS,
+    PID_DURABILITY,
+    PID_DURABILITY_SERVICE,
+    PID_OWNERSHIP,
+    PID_PRESENTATION,
+    PID_DEADLINE,
+    PID_DESTINATION_ORDER,
+    PID_LATENCY_BUDGET,
+    PID_PARTITION,
+    PID_LIFESPAN,
+    PID_USER_DATA,
+    PID_GROUP_DATA,
+    PID_TOPIC_DATA,
+    PID_UNICAST_LOCATOR,
+    PID_MULTICAST_LOCATOR,
+    PID_DEFAULT_UNICAST_LOCATOR,
+    PID_METATRAFFIC_UNICAST_LOCATOR,
+    PID_METATRAFFIC_MULTICAST_LOCATOR,
+    PID_PARTICIPANT_MANUAL_LIVELINESS_COUNT,
+    PID_CONTENT_FILTER_PROPERTY,
+    PID_HISTORY,
+    PID_RESOURCE_LIMITS,
+    PID_EXPECTS_INLINE_QOS,
+    PID_DEFAULT_MULTICAST_LOCATOR,
+    PID_TRANSPORT_PRIORITY,
+    PID_PARTICIPANT_GUID,
+    PID_GROUP_GUID,
+    PID_CONTENT_FILTER_INFO,
+    PID_COHERENT_SET,
+    PID_DIRECTED_WRITE,
+    PID_BUILTIN_ENDPOINT_SET,
+    PID_PROPERTY_LIST,
+    PID_ENDPOINT_GUID,
+    PID_TYPE_MAX_SIZE_SERIALIZED,
+    PID_ORIGINAL_WRITER_INFO,
+    PID_ENTITY_NAME,
+    PID_GROUP_COHERENT_SET,
+    PID_GROUP_SEQ_NUM,
+    PID_WRITER_GROUP_INFO,
+    PID_SECURE_WRITER_GROUP_INFO,
+    PID_KEY_HASH,
+    PID_STATUS_INFO,
+    PID_DATA_REPRESENTATION,
+    PID_TYPE_CONSISTENCY_ENFORCEMENT,
+    PID_TYPE_INFORMATION,
+    PID_BUILTIN_ENDPOINT_QOS,
+    PID_DOMAIN_TAG,
+];
+
+/**
+ * @brief Whether a parameter id is understood by this implementation.
+ *
+ * @param pid Parameter id.
+ * @return True if the parameter is known.
+ */
+pub fn is_known_pid(pid: ParameterId_t) -> bool {
+    // Vendor specific ids are only known by their vendor
+    (pid & PID_VENDOR_SPECIFIC_FLAG) == 0 && KNOWN_PIDS.contains(&pid)
+}
+
+/**
+ * @brief QoS policies that can be sent as a parameter.
+ */
+pub trait QosPolicyParameter: CdrSerialize + CdrDeserialize {
+    const PID: ParameterId_t;
+}
+
+macro_rules! qos_policy_parameter {
+    ($t:ty, $pid:expr) => {
+        impl QosPolicyParameter for $t {
+            const PID: ParameterId_t = $pid;
+        }
+    };
+}
+
+qos_policy_parameter!(DurabilityQosPolicy, PID_DURABILITY);
+qos_policy_parameter!(DeadlineQosPolicy, PID_DEADLINE);
+qos_policy_parameter!(LatencyBudgetQosPolicy, PID_LATENCY_BUDGET);
+qos_policy_parameter!(LivelinessQosPolicy, PID_LIVELINESS);
+qos_policy_parameter!(ReliabilityQosPolicy, PID_RELIABILITY);
+qos_policy_parameter!(DestinationOrderQosPolicy, PID_DESTINATION_ORDER);
+qos_policy_parameter!(HistoryQosPolicy, PID_HISTORY);
+qos_policy_parameter!(ResourceLimitsQosPolicy, PID_RESOURCE_LIMITS);
+qos_policy_parameter!(LifespanQosPolicy, PID_LIFESPAN);
+qos_policy_parameter!(OwnershipQosPolicy, PID_OWNERSHIP);
+qos_policy_parameter!(OwnershipStrengthQosPolicy, PID_OWNERSHIP_STRENGTH);
+qos_policy_parameter!(PresentationQosPolicy, PID_PRESENTATION);
+qos_policy_parameter!(PartitionQosPolicy, PID_PARTITION);
+qos_policy_parameter!(TimeBasedFilterQosPolicy, PID_TIME_BASED_FILTER);
+qos_policy_parameter!(TransportPriorityQosPolicy, PID_TRANSPORT_PRIORITY);
+qos_policy_parameter!(UserDataQosPolicy, PID_USER_DATA);
+qos_policy_parameter!(TopicDataQosPolicy, PID_TOPIC_DATA);
+qos_policy_parameter!(GroupDataQosPolicy, PID_GROUP_DATA);
+
+/**
+ * @brief Parameter of a parameter list, with its value as received.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Parameter_t {
+    pub pid: ParameterId_t,
+    /// Value, encoded with the endianness of the list, including its padding
+    pub value: Vec<u8>,
+}
+
+/**
+ * @brief List of parameters, used by the discovery data and the inline QoS.
+ *        Each parameter is a parameter id, a length and a value padded to 4 bytes. The
+ *        list ends with PID_SENTINEL. Values are CDR encoded with the endianness of the list.
+ *        Unknown parameters are kept as they are so they can be sent again.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParameterList {
+    endianness: Endianness,
+    parameters: Vec<Parameter_t>,
+}
+
+impl ParameterList {
+    pub fn new(endianness: Endianness) -> Self {
+        ParameterList {
+            endianness,
+            parameters: Vec::new(),
+        }
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn parameters(&self) -> &[Parameter_t] {
+        &self.parameters
+    }
+
+    pub fn len(&self) -> usize {
+        self.parameters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parameters.is_empty()
+    }
+
+    pub fn contains(&self, pid: ParameterId_t) -> bool {
+        self.parameters.iter().any(|p| p.pid == pid)
+    }
+
+    /**
+     * @brief Decode a parameter list.
+     *
+     * @param data Data starting with the first parameter.
+     * @param endianness Endianness of the list.
+     * @return The list and the number of bytes used by it, sentinel included.
+     *         An error if the list is malformed or has an unknown must understand parameter.
+     */
+    pub fn read(data: &[u8], endianness: Endianness) -> Result<(Self, usize), RtpsError> {
+        let mut list = ParameterList::new(endianness);
+        let read_u16 = |pos: usize| {
+            let bytes = [data[pos], data[pos + 1]];
+            match endianness {
+                Endianness::BIG_ENDIANNESS => u16::from_be_bytes(bytes),
+                Endianness::LITTLE_ENDIANNESS => u16::from_le_bytes(bytes),
+            }
+        };
+
+        let mut pos = 0;
+        loop {
+            if data.len() - pos < 4 {
+                return Err(RtpsError::new("Parameter list without sentinel"));
+            }
+            let pid = read_u16(pos);
+            let length = read_u16(pos + 2) as usize;
+            pos += 4;
+            if pid == PID_SENTINEL {
+                return Ok((list, pos));
+            }
+            if length > data.len() - pos {
+                return Err(RtpsError::new("Parameter bigger than the list"));
+            }
+            if (pid & PID_MUST_UNDERSTAND_FLAG) != 0 && !is_known_pid(pid) {
+                return Err(RtpsError::new("Unknown must understand parameter"));
+            }
+            if pid != PID_PAD {
+                list.push_raw(pid, data[pos..pos + length].to_vec())?;
+            }
+            pos += length;
+        }
+    }
+
+    /**
+     * @brief Decode a parameter list from a PL_CDR payload, such as discovery data.
+     *
+     * @param payload Payload holding the list.
+     * @return The list.
+     */
+    pub fn from_payload(payload: &SerializedPayload_t) -> Result<Self, RtpsError> {
+        let endianness = match payload.representation_id() {
+            Some(RepresentationId_t::PL_CDR_LE) => Endianness::LITTLE_ENDIANNESS,
+            Some(RepresentationId_t::PL_CDR_BE) => Endianness::BIG_ENDIANNESS,
+            _ => return Err(RtpsError::new("Not a parameter list representation")),
+        };
+        let data = &payload.data()[SerializedPayload_t::representation_header_size..];
+        ParameterList::read(data, endianness).map(|(list, _)| list)
+    }
+
+    // Size of the encoded list, sentinel included
+    pub fn serialized_size(&self) -> usize {
+        self.parameters
+            .iter()
+            .map(|p| 4 + p.value.len())
+            .sum::<usize>()
+            + 4
+    }
+
+    /**
+     * @brief Encode the list, ending it with PID_SENTINEL.
+     *
+     * @param buffer Buffer where the list is appended.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        let write_u16 = |buffer: &mut Vec<u8>, value: u16| match self.endianness {
+            Endianness::BIG_ENDIANNESS => buffer.extend_from_slice(&value.to_be_bytes()),
+            Endianness::LITTLE_ENDIANNESS => buffer.extend_from_slice(&value.to_le_bytes()),
+        };
+
+        buffer.reserve(self.serialized_size());
+        for parameter in &self.parameters {
+            write_u16(buffer, parameter.pid);
+            write_u16(buffer, parameter.value.len() as u16);
+            buffer.extend_from_slice(&parameter.value);
+        }
+        write_u16(buffer, PID_SENTINEL);
+        write_u16(buffer, 0);
+    }
+
+    /**
+     * @brief Encode the list on a PL_CDR payload.
+     *
+     * @param payload Payload where the list is written.
+     */
+    pub fn to_payload(&self, payload: &mut SerializedPayload_t) {
+        let representation = match self.endianness {
+            Endianness::BIG_ENDIANNESS => RepresentationId_t::PL_CDR_BE,
+            Endianness::LITTLE_ENDIANNESS => RepresentationId_t::PL_CDR_LE,
+        };
+        let mut buffer = Vec::new();
+        self.write(&mut buffer);
+        payload.begin_write(representation, 0);
+        payload.write_bytes(&buffer);
+    }
+
+    /**
+     * @brief Get the raw value of the first parameter with the given id.
+     *
+     * @param pid Parameter id.
+     * @return The value, None if the parameter is not in the list.
+     */
+    pub fn get_raw(&self, pid: ParameterId_t) -> Option<&[u8]> {
+        self.parameters
+            .iter()
+            .find(|p| p.pid == pid)
+            .map(|p| p.value.as_slice())
+    }
+
+    /**
+     * @brief Append a parameter with an already encoded value.
+     *        The value must use the endianness of the list, it is padded to 4 bytes.
+     *
+     * @param pid Parameter id.
+     * @param value Encoded value.
+     */
+    pub fn push_raw(&mut self, pid: ParameterId_t, mut value: Vec<u8>) -> Result<(), RtpsError> {
+        if padded(value.len()) > u16::MAX as usize {
+            return Err(RtpsError::new("Parameter value too big"));
+        }
+        value.resize(padded(value.len()), 0);
+        self.parameters.push(Parameter_t { pid, value });
+        Ok(())
+    }
+
+    // Remove every parameter with the given id
+    pub fn remove(&mut self, pid: ParameterId_t) {
+        self.parameters.retain(|p| p.pid != pid);
+    }
+
+    /**
+     * @brief Decode the value of the first parameter with the given id.
+     *
+     * @param pid Parameter id.
+     * @return The value, None if the parameter is not in the list.
+     */
+    pub fn get<T: CdrDeserialize>(&self, pid: ParameterId_t) -> Result<Option<T>, RtpsError> {
+        match self.get_raw(pid) {
+            Some(value) => self.decode(value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /**
+     * @brief Decode the values of every parameter with the given id, in list order.
+     *
+     * @param pid Parameter id.
+     * @return The values.
+     */
+    pub fn get_all<T: CdrDeserialize>(&self, pid: ParameterId_t) -> Result<Vec<T>, RtpsError> {
+        self.parameters
+            .iter()
+            .filter(|p| p.pid == pid)
+            .map(|p| self.decode(&p.value))
+            .collect()
+    }
+
+    /**
+     * @brief Set a parameter, replacing the parameters with the same id.
+     *
+     * @param pid Parameter id.
+     * @param value Parameter value.
+     */
+    pub fn set<T: CdrSerialize + ?Sized>(
+        &mut self,
+        pid: ParameterId_t,
+        value: &T,
+    ) -> Result<(), RtpsError> {
+        let value = self.encode(value)?;
+        self.remove(pid);
+        self.push_raw(pid, value)
+    }
+
+    /**
+     * @brief Append a parameter, keeping the parameters with the same id.
+     *
+     * @param pid Parameter id.
+     * @param value Parameter value.
+     */
+    pub fn add<T: CdrSerialize + ?Sized>(
+        &mut self,
+        pid: ParameterId_t,
+        value: &T,
+    ) -> Result<(), RtpsError> {
+        let value = self.encode(value)?;
+        self.push_raw(pid, value)
+    }
+
+    pub fn get_guid(&self, pid: ParameterId_t) -> Result<Option<GUID_t>, RtpsError> {
+        self.get(pid)
+    }
+
+    pub fn set_guid(&mut self, pid: ParameterId_t, guid: &GUID_t) -> Result<(), RtpsError> {
+        self.set(pid, guid)
+    }
+
+    pub fn get_locators(&self, pid: ParameterId_t) -> Result<Vec<Locator_t>, RtpsError> {
+        self.get_all(pid)
+    }
+
+    pub fn add_locator(
+        &mut self,
+        pid: ParameterId_t,
+        locator: &Locator_t,
+    ) -> Result<(), RtpsError> {
+        self.add(pid, locator)
+    }
+
+    pub fn get_duration(&self, pid: ParameterId_t) -> Result<Option<Duration_t>, RtpsError> {
+        self.get(pid)
+    }
+
+    pub fn set_duration(
+        &mut self,
+        pid: ParameterId_t,
+        duration: &Duration_t,
+    ) -> Result<(), RtpsError> {
+        self.set(pid, duration)
+    }
+
+    pub fn get_string(&self, pid: ParameterId_t) -> Result<Option<String>, RtpsError> {
+        self.get(pid)
+    }
+
+    pub fn set_string(&mut self, pid: ParameterId_t, value: &str) -> Result<(), RtpsError> {
+        self.set(pid, value)
+    }
+
+    pub fn get_properties(&self) -> Result<Vec<Property_t>, RtpsError> {
+        Ok(self.get(PID_PROPERTY_LIST)?.unwrap_or_default())
+    }
+
+    pub fn set_properties(&mut self, properties: &[Property_t]) -> Result<(), RtpsError> {
+        self.set(PID_PROPERTY_LIST, &properties.to_vec())
+    }
+
+    pub fn get_qos<T: QosPolicyParameter>(&self) -> Result<Option<T>, RtpsError> {
+        self.get(T::PID)
+    }
+
+    pub fn set_qos<T: QosPolicyParameter>(&mut self, policy: &T) -> Result<(), RtpsError> {
+        self.set(T::PID, policy)
+    }
+
+    // CDR representation of the parameter values
+    fn representation(&self) -> RepresentationId_t {
+        match self.endianness {
+            Endianness::BIG_ENDIANNESS => RepresentationId_t::CDR_BE,
+            Endianness::LITTLE_ENDIANNESS => RepresentationId_t::CDR_LE,
+        }
+    }
+
+    fn decode<T: CdrDeserialize>(&self, value: &[u8]) -> Result<T, RtpsError> {
+        let mut de = CdrDeserializer::from_bytes(value, self.representation())?;
+        de.deserialize()
+    }
+
+    fn encode<T: CdrSerialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, RtpsError> {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, self.representation())?;
+        ser.serialize(value)?;
+        let size = ser.serialized_size();
+        Ok(payload.data()[SerializedPayload_t::representation_header_size..][..size].to_vec())
+    }
+}
+
+fn padded(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtps::common::locator::LOCATOR_KIND_UDPv4;
+
+    fn locator(port: u32) -> Locator_t {
+        let mut address = [0; 16];
+        address[12..].copy_from_slice(&[192, 168, 1, 10]);
+        Locator_t::new(LOCATOR_KIND_UDPv4, port, address)
+    }
+
+    #[test]
+    fn golden_bytes_test() {
+        let mut list = ParameterList::new(Endianness::LITTLE_ENDIANNESS);
+        list.set_string(PID_TOPIC_NAME, "Square").unwrap();
+        list.set_qos(&ReliabilityQosPolicy {
+            kind: ReliabilityQosPolicyKind::RELIABLE_RELIABILITY_QOS,
+            max_blocking_time: Duration_t::new(1, 0),
+        })
+        .unwrap();
+
+        let mut buffer = Vec::new();
+        list.write(&mut buffer);
+        #[rustfmt::skip]
+        assert_eq!(
+            vec![
+                0x05, 0x00, 0x0c, 0x00,
+                0x07, 0x00, 0x00, 0x00, b'S', b'q', b'u', b'a', b'r', b'e', 0x00, 0x00,
+                0x1a, 0x00, 0x0c, 0x00,
+                0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x01, 0x00, 0x00, 0x00,
+            ],
+            buffer
+        );
+        assert_eq!(buffer.len(), list.serialized_size());
+
+        let (decoded, size) = ParameterList::read(&buffer, Endianness::LITTLE_ENDIANNESS).unwrap();
+        assert_eq!(buffer.len(), size);
+        assert_eq!(
+            Some("Square".to_string()),
+            decoded.get_string(PID_TOPIC_NAME).unwrap()
+        );
+    }
+
+    #[test]
+    fn typed_accessors_test() {
+        for endianness in [Endianness::BIG_ENDIANNESS, Endianness::LITTLE_ENDIANNESS] {
+            let mut guid = GUID_t::unknown();
+            guid.guidPrefix.value = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+            guid.entityId.value = [0, 0, 1, 0xc1];
+            let properties = vec![Property_t {
+                name: "fastdds.physical_data.host".to_string(),
+                value: "host".to_string(),
+            }];
+            let history = HistoryQosPolicy {
+                kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+                depth: 10,
+            };
+
+            let mut list = ParameterList::new(endianness);
+            list.set_guid(PID_PARTICIPANT_GUID, &guid).unwrap();
+            list.add_locator(PID_METATRAFFIC_UNICAST_LOCATOR, &locator(7410))
+                .unwrap();
+            list.add_locator(PID_METATRAFFIC_UNICAST_LOCATOR, &locator(7412))
+                .unwrap();
+            list.set_duration(PID_PARTICIPANT_LEASE_DURATION, &Duration_t::new(20, 0))
+                .unwrap();
+            list.set_properties(&properties).unwrap();
+            list.set_qos(&history).unwrap();
+            list.set_qos(&PartitionQosPolicy {
+                names: vec!["a".to_string(), "b*".to_string()],
+            })
+            .unwrap();
+
+            let mut payload = SerializedPayload_t::default();
+            list.to_payload(&mut payload);
+            let decoded = ParameterList::from_payload(&payload).unwrap();
+            assert_eq!(list, decoded);
+
+            assert_eq!(Some(guid), decoded.get_guid(PID_PARTICIPANT_GUID).unwrap());
+            assert_eq!(
+                vec![locator(7410), locator(7412)],
+                decoded
+                    .get_locators(PID_METATRAFFIC_UNICAST_LOCATOR)
+                    .unwrap()
+            );
+            assert_eq!(
+                Some(Duration_t::new(20, 0)),
+                decoded
+                    .get_duration(PID_PARTICIPANT_LEASE_DURATION)
+                    .unwrap()
+            );
+            assert_eq!(properties, decoded.get_properties().unwrap());
+            assert_eq!(Some(history), decoded.get_qos().unwrap());
+            assert_eq!(None, decoded.get_qos::<DeadlineQosPolicy>().unwrap());
+            assert_eq!(
+                2,
+                decoded
+                    .get_qos::<PartitionQosPolicy>()
+                    .unwrap()
+                    .unwrap()
+                    .names
+                    .len()
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_parameters_test() {
+        #[rustfmt::skip]
+        let data = [
+            // Vendor specific parameter
+            0x80, 0x00, 0x00, 0x04, 0xaa, 0xbb, 0xcc, 0xdd,
+            // Padding
+            0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+            // Unknown parameter
+            0x00, 0x7f, 0x00, 0x04, 0x01, 0x02, 0x03, 0x04,
+            0x00, 0x01, 0x00, 0x00,
+            // Data after the list
+            0xff, 0xff,
+        ];
+        let (list, size) = ParameterList::read(&data, Endianness::BIG_ENDIANNESS).unwrap();
+        assert_eq!(28, size);
+        assert_eq!(2, list.len());
+        assert_eq!(Some(&[0xaa, 0xbb, 0xcc, 0xdd][..]), list.get_raw(0x8000));
+
+        // Unknown parameters are sent again
+        let mut buffer = Vec::new();
+        list.write(&mut buffer);
+        assert_eq!(&data[..8], &buffer[..8]);
+        assert_eq!(&data[16..28], &buffer[8..]);
+
+        // Must understand parameters
+        let mut data = data;
+        data[0] = 0xc0;
+        assert!(ParameterList::read(&data, Endianness::BIG_ENDIANNESS).is_err());
+        data[0] = 0x80;
+        data[16] = 0x40;
+        assert!(ParameterList::read(&data, Endianness::BIG_ENDIANNESS).is_err());
+        data[17] = 0x14;
+        assert!(ParameterList::read(&data, Endianness::BIG_ENDIANNESS).is_ok());
+    }
+
+    #[test]
+    fn error_test() {
+        // No sentinel
+        let data = [0x05, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert!(ParameterList::read(&data, Endianness::LITTLE_ENDIANNESS).is_err());
+        // Value longer than the list
+        let data = [0x05, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert!(ParameterList::read(&data, Endianness::LITTLE_ENDIANNESS).is_err());
+
+        let mut list = ParameterList::new(Endianness::LITTLE_ENDIANNESS);
+        assert!(list.push_raw(PID_USER_DATA, vec![0; 0x10000]).is_err());
+        list.push_raw(PID_TOPIC_NAME, vec![0xff; 8]).unwrap();
+        assert!(list.get_string(PID_TOPIC_NAME).is_err());
+
+        let mut payload = SerializedPayload_t::default();
+        payload.begin_write(RepresentationId_t::CDR_LE, 0);
+        assert!(ParameterList::from_payload(&payload).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::sequence_number_set::SequenceNumberSet_t;
+use crate::rtps::messages::submessages::submessage_header::*;
+
+// Flag telling the writer does not have to answer with a HEARTBEAT
+pub const FLAG_FINAL: u8 = 0x02;
+
+/**
+ * @brief ACKNACK submessage, sent by a reader to acknowledge the changes before the base of
+ *        readerSNState and request the ones in the set, as specified in the DDSI-RTPS 2.5
+ *        specification chapter 9.4.5.2.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AckNackSubmessage {
+    pub endianness: Endianness,
+    pub readerId: EntityId_t,
+    pub writerId: EntityId_t,
+    pub readerSNState: SequenceNumberSet_t,
+    /// Incremented with every ACKNACK, to detect duplicates
+    pub count: u32,
+    pub finalFlag: bool,
+}
+
+impl AckNackSubmessage {
+    pub fn new(
+        endianness: Endianness,
+        readerId: EntityId_t,
+        writerId: EntityId_t,
+        readerSNState: SequenceNumberSet_t,
+        count: u32,
+        finalFlag: bool,
+    ) -> Self {
+        AckNackSubmessage {
+            endianness,
+            readerId,
+            writerId,
+            readerSNState,
+            count,
+            finalFlag,
+        }
+    }
+
+    /**
+     * @brief Encode the submessage, header included.
+     *
+     * @param buffer Buffer where the submessage is appended.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.endianness == Endianness::LITTLE_ENDIANNESS {
+            flags |= FLAG_ENDIANNESS;
+        }
+        if self.finalFlag {
+            flags |= FLAG_FINAL;
+        }
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.readerId.value);
+        body.extend_from_slice(&self.writerId.value);
+        self.readerSNState.write(&mut body, self.endianness);
+        write_u32(&mut body, self.endianness, self.count);
+        SubmessageHeader_t::new(ACKNACK, flags, body.len() as u16).write(buffer);
+        buffer.extend_from_slice(&body);
+    }
+
+    /**
+     * @brief Decode the body of an ACKNACK submessage.
+     *
+     * @param header Header of the submessage.
+     * @param body Octets following the header, up to the next header.
+     * @return The submessage, an error if it is malformed.
+     */
+    pub fn read(header: &SubmessageHeader_t, body: &[u8]) -> Result<Self, RtpsError> {
+        let endianness = header.endianness();
+        if body.len() < 8 {
+            return Err(RtpsError::new("ACKNACK submessage too short"));
+        }
+        let (readerSNState, length) = SequenceNumberSet_t::read(&body[8..], endianness)?;
+        if body.len() < 8 + length + 4 {
+            return Err(RtpsError::new("ACKNACK submessage too short"));
+        }
+        Ok(AckNackSubmessage::new(
+            endianness,
+            EntityId_t {
+                value: [body[0], body[1], body[2], body[3]],
+            },
+            EntityId_t {
+                value: [body[4], body[5], body[6], body[7]],
+            },
+            readerSNState,
+            read_u32(&body[8 + length..], endianness),
+            (header.flags & FLAG_FINAL) != 0,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtps::common::sequence_number::SequenceNumber_t;
+
+    #[test]
+    fn round_trip_test() {
+        let mut set = SequenceNumberSet_t::new(SequenceNumber_t::from(3));
+        set.add(SequenceNumber_t::from(5));
+        let acknack = AckNackSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            EntityId_t::new_from_key_kind(1, EntityId_t::ENTITYKIND_USER_READER_WITH_KEY),
+            EntityId_t::new_from_key_kind(2, EntityId_t::ENTITYKIND_USER_WRITER_WITH_KEY),
+            set,
+            7,
+            true,
+        );
+        let mut buffer = Vec::new();
+        acknack.write(&mut buffer);
+        assert_eq!(4 + 8 + 16 + 4, buffer.len());
+        let header = SubmessageHeader_t::read(&buffer).unwrap();
+        assert_eq!(ACKNACK, header.submessageId);
+        assert_eq!(
+            acknack,
+            AckNackSubmessage::read(&header, &buffer[SubmessageHeader_t::SIZE..]).unwrap()
+        );
+        assert!(AckNackSubmessage::read(&header, &buffer[4..buffer.len() - 2]).is_err());
+    }
+}
@@ -0,0 +1,546 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::change_kind_t::ChangeKind_t;
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+use crate::rtps::common::instance_handle::InstanceHandle_t;
+use crate::rtps::common::payload_pool::PayloadPool;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::common::serialized_payload::SerializedPayload_t;
+use crate::rtps::common::time::Time_t;
+use crate::rtps::messages::parameter_list::*;
+use crate::rtps::messages::submessages::submessage_header::*;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use std::convert::TryInto;
+
+// Flags of the DATA submessage, as specified in the DDSI-RTPS 2.5 specification chapter 9.4.5.3
+pub const FLAG_INLINE_QOS: u8 = 0x02;
+pub const FLAG_DATA: u8 = 0x04;
+pub const FLAG_KEY: u8 = 0x08;
+pub const FLAG_NON_STANDARD_PAYLOAD: u8 = 0x10;
+
+// Octets from the end of octetsToInlineQos to the inline QoS: readerId, writerId and writerSN
+const OCTETS_TO_INLINE_QOS: u16 = 16;
+
+/**
+ * @brief Kind of a change notified by a DATA or a DATA_FRAG. PID_STATUS_INFO gives the kind
+ *        when it is present, otherwise the change is alive if it carries data and disposed if
+ *        it only carries the key.
+ *
+ * @param inlineQos Inline QoS of the submessage.
+ * @param hasPayload Whether the submessage carries a payload.
+ * @param keyFlag Whether the payload is only the key.
+ * @return The kind, an error if the submessage carries neither data nor status info.
+ */
+pub fn change_kind(
+    inlineQos: Option<&ParameterList>,
+    hasPayload: bool,
+    keyFlag: bool,
+) -> Result<ChangeKind_t, RtpsError> {
+    match inlineQos.and_then(|qos| qos.get_raw(PID_STATUS_INFO)) {
+        Some(value) => {
+            let value: [u8; 4] = value
+                .try_into()
+                .map_err(|_| RtpsError::new("Wrong status info length"))?;
+            Ok(ChangeKind_t::from_status_info(&value))
+        }
+        None if !hasPayload => Err(RtpsError::new("DATA without payload nor status info")),
+        None if keyFlag => Ok(ChangeKind_t::NOT_ALIVE_DISPOSED),
+        None => Ok(ChangeKind_t::ALIVE),
+    }
+}
+
+// Instance sent on PID_KEY_HASH, None if the inline QoS does not have it
+pub fn key_hash(inlineQos: Option<&ParameterList>) -> Result<Option<InstanceHandle_t>, RtpsError> {
+    let value = match inlineQos.and_then(|qos| qos.get_raw(PID_KEY_HASH)) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let mut handle = InstanceHandle_t::new();
+    if value.len() != InstanceHandle_t::SIZE {
+        return Err(RtpsError::new("Wrong key hash length"));
+    }
+    handle.value.copy_from_slice(value);
+    Ok(Some(handle))
+}
+
+/**
+ * @brief DATA submessage, notifying a change of a data object.
+ *        The payload is either the serialized data (D flag) or only its key (K flag).
+ */
+#[derive(Debug, PartialEq)]
+pub struct DataSubmessage {
+    pub endianness: Endianness,
+    pub readerId: EntityId_t,
+    pub writerId: EntityId_t,
+    pub writerSN: SequenceNumber_t,
+    pub inlineQos: Option<ParameterList>,
+    pub serializedPayload: Option<SerializedPayload_t>,
+    /// The payload holds only the key of the data
+    pub keyFlag: bool,
+}
+
+impl DataSubmessage {
+    /**
+     * @brief Create a DATA submessage with a new value of a data object.
+     *
+     * @param endianness Endianness of the submessage.
+     * @param readerId Reader the change is sent to, c_EntityId_Unknown for every reader.
+     * @param writerId Writer of the change.
+     * @param writerSN Sequence number of the change.
+     * @param payload Serialized data.
+     * @return The submessage.
+     */
+    pub fn new(
+        endianness: Endianness,
+        readerId: EntityId_t,
+        writerId: EntityId_t,
+        writerSN: SequenceNumber_t,
+        payload: SerializedPayload_t,
+    ) -> Self {
+        DataSubmessage {
+            endianness,
+            readerId,
+            writerId,
+            writerSN,
+            inlineQos: None,
+            serializedPayload: Some(payload),
+            keyFlag: false,
+        }
+    }
+
+    /**
+     * @brief Create a key-only DATA submessage notifying a dispose or an unregister.
+     *        The kind of the change is sent on PID_STATUS_INFO and the instance on PID_KEY_HASH.
+     *
+     * @param endianness Endianness of the submessage.
+     * @param readerId Reader the change is sent to, c_EntityId_Unknown for every reader.
+     * @param writerId Writer of the change.
+     * @param writerSN Sequence number of the change.
+     * @param kind Kind of the change, it must not be ALIVE.
+     * @param keyHash Instance of the change.
+     * @param key Serialized key of the instance.
+     * @return The submessage.
+     */
+    pub fn new_key_only(
+        endianness: Endianness,
+        readerId: EntityId_t,
+        writerId: EntityId_t,
+        writerSN: SequenceNumber_t,
+        kind: ChangeKind_t,
+        keyHash: &InstanceHandle_t,
+        key: SerializedPayload_t,
+    ) -> Result<Self, RtpsError> {
+        if kind.is_alive() {
+            return Err(RtpsError::new(
+                "Key-only DATA can not notify an alive change",
+            ));
+        }
+        let mut inlineQos = ParameterList::new(endianness);
+        inlineQos.push_raw(PID_KEY_HASH, keyHash.value.to_vec())?;
+        inlineQos.push_raw(PID_STATUS_INFO, kind.to_status_info().to_vec())?;
+        Ok(DataSubmessage {
+            endianness,
+            readerId,
+            writerId,
+            writerSN,
+            inlineQos: Some(inlineQos),
+            serializedPayload: Some(key),
+            keyFlag: true,
+        })
+    }
+
+    // Flags of the submessage header
+    pub fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.endianness == Endianness::LITTLE_ENDIANNESS {
+            flags |= FLAG_ENDIANNESS;
+        }
+        if self.inlineQos.is_some() {
+            flags |= FLAG_INLINE_QOS;
+        }
+        if self.serializedPayload.is_some() {
+            flags |= if self.keyFlag { FLAG_KEY } else { FLAG_DATA };
+        }
+        flags
+    }
+
+    // Kind of the notified change, an error if the submessage carries neither data nor status info
+    pub fn change_kind(&self) -> Result<ChangeKind_t, RtpsError> {
+        change_kind(
+            self.inlineQos.as_ref(),
+            self.serializedPayload.is_some(),
+            self.keyFlag,
+        )
+    }
+
+    /**
+     * @brief Instance of the change, sent on PID_KEY_HASH.
+     *
+     * @return The instance handle, None if the inline QoS does not have it.
+     */
+    pub fn key_hash(&self) -> Result<Option<InstanceHandle_t>, RtpsError> {
+        key_hash(self.inlineQos.as_ref())
+    }
+
+    /**
+     * @brief Create the change notified by the submessage, as received by a reader.
+     *        The payload is copied to a buffer of the pool, if any, otherwise the received
+     *        buffer is shared.
+     *
+     * @param writerGuidPrefix Participant of the writer.
+     * @param sourceTimestamp Timestamp given by the last INFO_TS, if any.
+     * @param payloadPool Pool of the reader, if any.
+     * @return The change, an error if its kind or instance cannot be found.
+     */
+    pub fn to_cache_change(
+        &self,
+        writerGuidPrefix: GuidPrefix_t,
+        sourceTimestamp: Option<Time_t>,
+        payloadPool: Option<&PayloadPool>,
+    ) -> Result<CacheChange_t, RtpsError> {
+        let mut change = CacheChange_t::new();
+        change.kind = self.change_kind()?;
+        change.writerGUID = GUID_t {
+            guidPrefix: writerGuidPrefix,
+            entityId: self.writerId,
+        };
+        change.sequenceNumber = self.writerSN;
+        change.instanceHandle = self.key_hash()?.unwrap_or_else(InstanceHandle_t::new);
+        change.inlineQos = self.inlineQos.clone();
+        change.sourceTimestamp = sourceTimestamp.unwrap_or(Time_t::c_TimeZero);
+        if let Some(payload) = &self.serializedPayload {
+            match payloadPool {
+                Some(pool) if pool.get_payload(payload.length(), &mut change.serializedPayload) => {
+                    change.serializedPayload.copy(payload, true);
+                }
+                // Shared when there is no pool or it is exhausted
+                _ => change.serializedPayload.share(payload),
+            }
+            change.serializedPayload.begin_read();
+        }
+        Ok(change)
+    }
+
+    /**
+     * @brief Encode the submessage, header included.
+     *        The payload is written as it is, it must already be padded to 4 bytes.
+     *
+     * @param buffer Buffer where the submessage is appended.
+     * @return An error if the submessage does not fit in a single DATA.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) -> Result<(), RtpsError> {
+        if let Some(qos) = &self.inlineQos {
+            if qos.endianness() != self.endianness {
+                return Err(RtpsError::new(
+                    "Inline QoS endianness differs from the DATA",
+                ));
+            }
+        }
+        let length = 4
+            + OCTETS_TO_INLINE_QOS as usize
+            + self
+                .inlineQos
+                .as_ref()
+                .map_or(0, |qos| qos.serialized_size())
+            + self.serializedPayload.as_ref().map_or(0, |p| p.length());
+        if length > u16::MAX as usize {
+            return Err(RtpsError::new("DATA submessage too big"));
+        }
+
+        let write_u16 = |buffer: &mut Vec<u8>, value: u16| match self.endianness {
+            Endianness::BIG_ENDIANNESS => buffer.extend_from_slice(&value.to_be_bytes()),
+            Endianness::LITTLE_ENDIANNESS => buffer.extend_from_slice(&value.to_le_bytes()),
+        };
+        let write_u32 = |buffer: &mut Vec<u8>, value: u32| match self.endianness {
+            Endianness::BIG_ENDIANNESS => buffer.extend_from_slice(&value.to_be_bytes()),
+            Endianness::LITTLE_ENDIANNESS => buffer.extend_from_slice(&value.to_le_bytes()),
+        };
+
+        buffer.reserve(SubmessageHeader_t::SIZE + length);
+        SubmessageHeader_t::new(DATA, self.flags(), length as u16).write(buffer);
+        // extraFlags
+        write_u16(buffer, 0);
+        write_u16(buffer, OCTETS_TO_INLINE_QOS);
+        buffer.extend_from_slice(&self.readerId.value);
+        buffer.extend_from_slice(&self.writerId.value);
+        write_u32(buffer, self.writerSN.high() as u32);
+        write_u32(buffer, self.writerSN.low());
+        if let Some(qos) = &self.inlineQos {
+            qos.write(buffer);
+        }
+        if let Some(payload) = &self.serializedPayload {
+            buffer.extend_from_slice(payload.data());
+        }
+        Ok(())
+    }
+
+    /**
+     * @brief Decode the body of a DATA submessage.
+     *
+     * @param header Header of the submessage.
+     * @param body Octets following the header, up to the next header.
+     * @return The submessage, an error if it is malformed.
+     */
+    pub fn read(header: &SubmessageHeader_t, body: &[u8]) -> Result<Self, RtpsError> {
+        if header.submessageId != DATA {
+            return Err(RtpsError::new("Not a DATA submessage"));
+        }
+        if (header.flags & FLAG_DATA) != 0 && (header.flags & FLAG_KEY) != 0 {
+            return Err(RtpsError::new("DATA with both data and key flags"));
+        }
+        if body.len() < 4 + OCTETS_TO_INLINE_QOS as usize {
+            return Err(RtpsError::new("DATA submessage too short"));
+        }
+
+        let endianness = header.endianness();
+        let read_u16 = |pos: usize| {
+            let bytes = [body[pos], body[pos + 1]];
+            match endianness {
+                Endianness::BIG_ENDIANNESS => u16::from_be_bytes(bytes),
+                Endianness::LITTLE_ENDIANNESS => u16::from_le_bytes(bytes),
+            }
+        };
+        let read_u32 = |pos: usize| {
+            let bytes = [body[pos], body[pos + 1], body[pos + 2], body[pos + 3]];
+            match endianness {
+                Endianness::BIG_ENDIANNESS => u32::from_be_bytes(bytes),
+                Endianness::LITTLE_ENDIANNESS => u32::from_le_bytes(bytes),
+            }
+        };
+
+        let octetsToInlineQos = read_u16(2);
+        let mut readerId = EntityId_t::default();
+        readerId.value.copy_from_slice(&body[4..8]);
+        let mut writerId = EntityId_t::default();
+        writerId.value.copy_from_slice(&body[8..12]);
+        let writerSN = SequenceNumber_t::new(read_u32(12) as i32, read_u32(16));
+
+        // Newer versions of the protocol may add fields before the inline QoS
+        let mut pos = 4 + octetsToInlineQos as usize;
+        if octetsToInlineQos < OCTETS_TO_INLINE_QOS || pos > body.len() {
+            return Err(RtpsError::new("Wrong octetsToInlineQos"));
+        }
+
+        let mut inlineQos = None;
+        if (header.flags & FLAG_INLINE_QOS) != 0 {
+            let (qos, size) = ParameterList::read(&body[pos..], endianness)?;
+            inlineQos = Some(qos);
+            pos += size;
+        }
+
+        let mut serializedPayload = None;
+        if (header.flags & (FLAG_DATA | FLAG_KEY)) != 0 {
+            let data = &body[pos..];
+            let mut payload = SerializedPayload_t::new(data.len());
+            payload.write_bytes(data);
+            if !payload.begin_read() {
+                return Err(RtpsError::new("DATA payload without representation header"));
+            }
+            serializedPayload = Some(payload);
+        }
+
+        Ok(DataSubmessage {
+            endianness,
+            readerId,
+            writerId,
+            writerSN,
+            inlineQos,
+            serializedPayload,
+            keyFlag: (header.flags & FLAG_KEY) != 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdr::cdr_serializer::*;
+    use crate::rtps::common::change_kind_t::STATUS_INFO_FILTERED_FLAG;
+    use crate::rtps::common::payload_pool::PoolConfig;
+    use crate::rtps::common::serialized_payload::RepresentationId_t;
+
+    fn key_payload() -> SerializedPayload_t {
+        let mut payload = SerializedPayload_t::default();
+        let mut ser = CdrSerializer::new(&mut payload, RepresentationId_t::CDR_LE).unwrap();
+        ser.serialize(&7u32).unwrap();
+        ser.finish().unwrap();
+        payload
+    }
+
+    fn round_trip(data: &DataSubmessage) -> DataSubmessage {
+        let mut buffer = Vec::new();
+        data.write(&mut buffer).unwrap();
+        let header = SubmessageHeader_t::read(&buffer).unwrap();
+        assert_eq!(
+            buffer.len() - SubmessageHeader_t::SIZE,
+            header.submessageLength as usize
+        );
+        DataSubmessage::read(&header, &buffer[SubmessageHeader_t::SIZE..]).unwrap()
+    }
+
+    #[test]
+    fn golden_bytes_test() {
+        let data = DataSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            EntityId_t::c_EntityId_SPDPWriter,
+            SequenceNumber_t::new(0, 3),
+            key_payload(),
+        );
+        let mut buffer = Vec::new();
+        data.write(&mut buffer).unwrap();
+        #[rustfmt::skip]
+        assert_eq!(
+            vec![
+                0x15, 0x05, 0x1c, 0x00,
+                0x00, 0x00, 0x10, 0x00,
+                0x00, 0x00, 0x00, 0x00,
+                0x00, 0x01, 0x00, 0xc2,
+                0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+                0x00, 0x01, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00,
+            ],
+            buffer
+        );
+        let decoded = round_trip(&data);
+        assert_eq!(data, decoded);
+        assert_eq!(ChangeKind_t::ALIVE, decoded.change_kind().unwrap());
+        assert_eq!(None, decoded.key_hash().unwrap());
+    }
+
+    #[test]
+    fn key_only_test() {
+        let mut handle = InstanceHandle_t::new();
+        handle.value[3] = 7;
+        for endianness in [Endianness::BIG_ENDIANNESS, Endianness::LITTLE_ENDIANNESS] {
+            for kind in [
+                ChangeKind_t::NOT_ALIVE_DISPOSED,
+                ChangeKind_t::NOT_ALIVE_UNREGISTERED,
+                ChangeKind_t::NOT_ALIVE_DISPOSED_UNREGISTERED,
+            ] {
+                let data = DataSubmessage::new_key_only(
+                    endianness,
+                    EntityId_t::c_EntityId_Unknown,
+                    EntityId_t::c_EntityId_SEDPPubWriter,
+                    SequenceNumber_t::new(1, 2),
+                    kind,
+                    &handle,
+                    key_payload(),
+                )
+                .unwrap();
+                assert_eq!(FLAG_INLINE_QOS | FLAG_KEY, data.flags() & !FLAG_ENDIANNESS);
+
+                let decoded = round_trip(&data);
+                assert_eq!(data, decoded);
+                assert!(decoded.keyFlag);
+                assert_eq!(kind, decoded.change_kind().unwrap());
+                assert_eq!(Some(handle), decoded.key_hash().unwrap());
+            }
+        }
+
+        assert!(DataSubmessage::new_key_only(
+            Endianness::BIG_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            EntityId_t::c_EntityId_SEDPPubWriter,
+            SequenceNumber_t::new(0, 1),
+            ChangeKind_t::ALIVE,
+            &handle,
+            key_payload(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn to_cache_change_test() {
+        let data = round_trip(&DataSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            EntityId_t::c_EntityId_SPDPWriter,
+            SequenceNumber_t::new(0, 3),
+            key_payload(),
+        ));
+        let mut prefix = GuidPrefix_t::unknown();
+        prefix.value[0] = 1;
+        let change = data
+            .to_cache_change(prefix, Some(Time_t::new(1, 0)), None)
+            .unwrap();
+        assert_eq!(SequenceNumber_t::new(0, 3), change.sequenceNumber);
+        assert_eq!(Time_t::new(1, 0), change.sourceTimestamp);
+        assert!(change
+            .serializedPayload
+            .shares_buffer_with(data.serializedPayload.as_ref().unwrap()));
+
+        // The reader pool gets a copy, its buffer goes back with the change
+        let pool = PayloadPool::new(PoolConfig::default());
+        let change = data.to_cache_change(prefix, None, Some(&pool)).unwrap();
+        assert_eq!(1, pool.outstanding());
+        assert_eq!(
+            data.serializedPayload.as_ref().unwrap(),
+            &change.serializedPayload
+        );
+        drop(change);
+        assert_eq!(0, pool.outstanding());
+        assert_eq!(1, pool.free_count());
+    }
+
+    #[test]
+    fn change_kind_test() {
+        let mut data = DataSubmessage::new(
+            Endianness::BIG_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            EntityId_t::c_EntityId_SEDPPubWriter,
+            SequenceNumber_t::new(0, 1),
+            key_payload(),
+        );
+
+        // Key without status info
+        data.keyFlag = true;
+        assert_eq!(
+            ChangeKind_t::NOT_ALIVE_DISPOSED,
+            data.change_kind().unwrap()
+        );
+
+        // Filtered changes are still alive
+        let mut qos = ParameterList::new(Endianness::BIG_ENDIANNESS);
+        qos.push_raw(PID_STATUS_INFO, vec![0, 0, 0, STATUS_INFO_FILTERED_FLAG])
+            .unwrap();
+        data.inlineQos = Some(qos);
+        data.keyFlag = false;
+        assert_eq!(
+            ChangeKind_t::ALIVE,
+            round_trip(&data).change_kind().unwrap()
+        );
+
+        data.inlineQos = None;
+        data.serializedPayload = None;
+        assert!(data.change_kind().is_err());
+    }
+
+    #[test]
+    fn error_test() {
+        let data = DataSubmessage::new(
+            Endianness::BIG_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            EntityId_t::c_EntityId_SEDPPubWriter,
+            SequenceNumber_t::new(0, 1),
+            key_payload(),
+        );
+        let mut buffer = Vec::new();
+        data.write(&mut buffer).unwrap();
+        let mut header = SubmessageHeader_t::read(&buffer).unwrap();
+        let body = &buffer[SubmessageHeader_t::SIZE..];
+
+        assert!(DataSubmessage::read(&header, &body[..12]).is_err());
+        header.flags |= FLAG_KEY;
+        assert!(DataSubmessage::read(&header, body).is_err());
+        header.flags &= !FLAG_KEY;
+        header.submessageId = DATA_FRAG;
+        assert!(DataSubmessage::read(&header, body).is_err());
+
+        // Inline QoS with a different endianness
+        let mut data = data;
+        data.inlineQos = Some(ParameterList::new(Endianness::LITTLE_ENDIANNESS));
+        assert!(data.write(&mut Vec::new()).is_err());
+    }
+}
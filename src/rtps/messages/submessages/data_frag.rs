@@ -0,0 +1,433 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::change_kind_t::ChangeKind_t;
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+use crate::rtps::common::instance_handle::InstanceHandle_t;
+use crate::rtps::common::payload_pool::PayloadPool;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::common::serialized_payload::PayloadData;
+use crate::rtps::common::time::Time_t;
+use crate::rtps::messages::parameter_list::ParameterList;
+use crate::rtps::messages::submessages::data;
+use crate::rtps::messages::submessages::submessage_header::*;
+use crate::rtps::structure::cache_change::CacheChange_t;
+
+// Flags of the DATA_FRAG submessage, as specified in the DDSI-RTPS 2.5 specification chapter 9.4.5.4
+pub const FLAG_INLINE_QOS: u8 = 0x02;
+pub const FLAG_KEY: u8 = 0x04;
+pub const FLAG_NON_STANDARD_PAYLOAD: u8 = 0x08;
+
+// Octets from the end of octetsToInlineQos to the inline QoS: readerId, writerId, writerSN,
+// fragmentStartingNum, fragmentsInSubmessage, fragmentSize and sampleSize
+const OCTETS_TO_INLINE_QOS: u16 = 28;
+
+/**
+ * @brief DATA_FRAG submessage, carrying consecutive fragments of a serialized payload too
+ *        large for a single DATA. The fragments cover the whole payload, representation
+ *        header included, and are numbered from 1.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataFragSubmessage {
+    pub endianness: Endianness,
+    pub readerId: EntityId_t,
+    pub writerId: EntityId_t,
+    pub writerSN: SequenceNumber_t,
+    pub fragmentStartingNum: u32,
+    pub fragmentsInSubmessage: u16,
+    pub fragmentSize: u16,
+    /// Size of the whole payload
+    pub sampleSize: u32,
+    pub inlineQos: Option<ParameterList>,
+    /// Data of the fragments, without padding
+    pub fragments: Vec<u8>,
+    /// The payload holds only the key of the data
+    pub keyFlag: bool,
+}
+
+impl DataFragSubmessage {
+    /**
+     * @brief Create a DATA_FRAG with fragments of a payload.
+     *
+     * @param endianness Endianness of the submessage.
+     * @param readerId Reader the change is sent to, c_EntityId_Unknown for every reader.
+     * @param writerId Writer of the change.
+     * @param writerSN Sequence number of the change.
+     * @param payload Whole serialized payload, representation header included.
+     * @param fragmentSize Size of the fragments.
+     * @param fragmentStartingNum First fragment of the submessage, starting at 1.
+     * @param fragmentsInSubmessage Number of fragments of the submessage.
+     * @return The submessage, an error if the fragments are not part of the payload.
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        endianness: Endianness,
+        readerId: EntityId_t,
+        writerId: EntityId_t,
+        writerSN: SequenceNumber_t,
+        payload: &[u8],
+        fragmentSize: u16,
+        fragmentStartingNum: u32,
+        fragmentsInSubmessage: u16,
+    ) -> Result<Self, RtpsError> {
+        if fragmentSize == 0 || fragmentStartingNum == 0 || fragmentsInSubmessage == 0 {
+            return Err(RtpsError::new("Empty fragment range"));
+        }
+        let offset = (fragmentStartingNum as usize - 1) * fragmentSize as usize;
+        if offset >= payload.len() {
+            return Err(RtpsError::new("Fragment out of the payload"));
+        }
+        let end = std::cmp::min(
+            payload.len(),
+            offset + fragmentsInSubmessage as usize * fragmentSize as usize,
+        );
+        Ok(DataFragSubmessage {
+            endianness,
+            readerId,
+            writerId,
+            writerSN,
+            fragmentStartingNum,
+            fragmentsInSubmessage: (end - offset).div_ceil(fragmentSize as usize) as u16,
+            fragmentSize,
+            sampleSize: payload.len() as u32,
+            inlineQos: None,
+            fragments: payload[offset..end].to_vec(),
+            keyFlag: false,
+        })
+    }
+
+    // Number of fragments of the whole payload
+    pub fn fragment_count(&self) -> u32 {
+        self.sampleSize.div_ceil(self.fragmentSize as u32)
+    }
+
+    // Flags of the submessage header
+    pub fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.endianness == Endianness::LITTLE_ENDIANNESS {
+            flags |= FLAG_ENDIANNESS;
+        }
+        if self.inlineQos.is_some() {
+            flags |= FLAG_INLINE_QOS;
+        }
+        if self.keyFlag {
+            flags |= FLAG_KEY;
+        }
+        flags
+    }
+
+    // Kind of the notified change, see data::change_kind
+    pub fn change_kind(&self) -> Result<ChangeKind_t, RtpsError> {
+        data::change_kind(self.inlineQos.as_ref(), true, self.keyFlag)
+    }
+
+    // Instance of the change, None if the inline QoS does not have it
+    pub fn key_hash(&self) -> Result<Option<InstanceHandle_t>, RtpsError> {
+        data::key_hash(self.inlineQos.as_ref())
+    }
+
+    /**
+     * @brief Create the change the fragments are reassembled into, with every fragment
+     *        missing.
+     *
+     * @param writerGuidPrefix Participant of the writer.
+     * @param sourceTimestamp Timestamp given by the last INFO_TS, if any.
+     * @param maxSampleSize Largest sample the reader accepts.
+     * @param payloadPool Pool the payload is taken from, if any.
+     * @return The change, an error if its kind or instance cannot be found or the sample is
+     *         larger than maxSampleSize.
+     */
+    pub fn to_cache_change(
+        &self,
+        writerGuidPrefix: GuidPrefix_t,
+        sourceTimestamp: Option<Time_t>,
+        maxSampleSize: usize,
+        payloadPool: Option<&PayloadPool>,
+    ) -> Result<CacheChange_t, RtpsError> {
+        // The sample size comes from the wire, it is checked before allocating anything
+        if self.sampleSize as usize > maxSampleSize {
+            return Err(RtpsError::new("DATA_FRAG sample too big"));
+        }
+        let mut change = CacheChange_t::new();
+        change.kind = self.change_kind()?;
+        change.writerGUID = GUID_t {
+            guidPrefix: writerGuidPrefix,
+            entityId: self.writerId,
+        };
+        change.sequenceNumber = self.writerSN;
+        change.instanceHandle = self.key_hash()?.unwrap_or_else(InstanceHandle_t::new);
+        change.inlineQos = self.inlineQos.clone();
+        change.sourceTimestamp = sourceTimestamp.unwrap_or(Time_t::c_TimeZero);
+        let size = self.sampleSize as usize;
+        let pooled =
+            payloadPool.is_some_and(|pool| pool.get_payload(size, &mut change.serializedPayload));
+        if !pooled {
+            // Zeroed by the allocator, the memory is only touched as the fragments are written
+            change
+                .serializedPayload
+                .set_buffer(PayloadData::from_vec(vec![0; size]));
+        }
+        change.serializedPayload.set_length(size);
+        change.setFragmentSize(self.fragmentSize, true);
+        Ok(change)
+    }
+
+    /**
+     * @brief Encode the submessage, header included. The fragments are padded to 4 bytes.
+     *
+     * @param buffer Buffer where the submessage is appended.
+     * @return An error if the submessage is too big.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) -> Result<(), RtpsError> {
+        if let Some(qos) = &self.inlineQos {
+            if qos.endianness() != self.endianness {
+                return Err(RtpsError::new(
+                    "Inline QoS endianness differs from the DATA_FRAG",
+                ));
+            }
+        }
+        let padding = (4 - self.fragments.len() % 4) % 4;
+        let length = 4
+            + OCTETS_TO_INLINE_QOS as usize
+            + self
+                .inlineQos
+                .as_ref()
+                .map_or(0, |qos| qos.serialized_size())
+            + self.fragments.len()
+            + padding;
+        if length > u16::MAX as usize {
+            return Err(RtpsError::new("DATA_FRAG submessage too big"));
+        }
+
+        buffer.reserve(SubmessageHeader_t::SIZE + length);
+        SubmessageHeader_t::new(DATA_FRAG, self.flags(), length as u16).write(buffer);
+        // extraFlags
+        write_u16(buffer, self.endianness, 0);
+        write_u16(buffer, self.endianness, OCTETS_TO_INLINE_QOS);
+        buffer.extend_from_slice(&self.readerId.value);
+        buffer.extend_from_slice(&self.writerId.value);
+        write_sequence_number(buffer, self.endianness, self.writerSN);
+        write_u32(buffer, self.endianness, self.fragmentStartingNum);
+        write_u16(buffer, self.endianness, self.fragmentsInSubmessage);
+        write_u16(buffer, self.endianness, self.fragmentSize);
+        write_u32(buffer, self.endianness, self.sampleSize);
+        if let Some(qos) = &self.inlineQos {
+            qos.write(buffer);
+        }
+        buffer.extend_from_slice(&self.fragments);
+        buffer.resize(buffer.len() + padding, 0);
+        Ok(())
+    }
+
+    /**
+     * @brief Decode the body of a DATA_FRAG submessage.
+     *
+     * @param header Header of the submessage.
+     * @param body Octets following the header, up to the next header.
+     * @return The submessage, an error if it is malformed.
+     */
+    pub fn read(header: &SubmessageHeader_t, body: &[u8]) -> Result<Self, RtpsError> {
+        if header.submessageId != DATA_FRAG {
+            return Err(RtpsError::new("Not a DATA_FRAG submessage"));
+        }
+        if body.len() < 4 + OCTETS_TO_INLINE_QOS as usize {
+            return Err(RtpsError::new("DATA_FRAG submessage too short"));
+        }
+        let endianness = header.endianness();
+        let octetsToInlineQos = read_u16(&body[2..], endianness);
+        let mut readerId = EntityId_t::default();
+        readerId.value.copy_from_slice(&body[4..8]);
+        let mut writerId = EntityId_t::default();
+        writerId.value.copy_from_slice(&body[8..12]);
+        let writerSN = read_sequence_number(&body[12..], endianness);
+        let fragmentStartingNum = read_u32(&body[20..], endianness);
+        let fragmentsInSubmessage = read_u16(&body[24..], endianness);
+        let fragmentSize = read_u16(&body[26..], endianness);
+        let sampleSize = read_u32(&body[28..], endianness);
+        if fragmentSize == 0 || fragmentStartingNum == 0 || fragmentsInSubmessage == 0 {
+            return Err(RtpsError::new("Empty DATA_FRAG"));
+        }
+
+        // Newer versions of the protocol may add fields before the inline QoS
+        let mut pos = 4 + octetsToInlineQos as usize;
+        if octetsToInlineQos < OCTETS_TO_INLINE_QOS || pos > body.len() {
+            return Err(RtpsError::new("Wrong octetsToInlineQos"));
+        }
+        let mut inlineQos = None;
+        if (header.flags & FLAG_INLINE_QOS) != 0 {
+            let (qos, size) = ParameterList::read(&body[pos..], endianness)?;
+            inlineQos = Some(qos);
+            pos += size;
+        }
+
+        // The last fragment of the payload may be shorter, and followed by padding
+        let offset = (fragmentStartingNum as u64 - 1) * fragmentSize as u64;
+        let end = std::cmp::min(
+            sampleSize as u64,
+            offset + fragmentsInSubmessage as u64 * fragmentSize as u64,
+        );
+        if offset >= end || (end - offset) as usize > body.len() - pos {
+            return Err(RtpsError::new("DATA_FRAG fragments out of the submessage"));
+        }
+
+        Ok(DataFragSubmessage {
+            endianness,
+            readerId,
+            writerId,
+            writerSN,
+            fragmentStartingNum,
+            fragmentsInSubmessage,
+            fragmentSize,
+            sampleSize,
+            inlineQos,
+            fragments: body[pos..pos + (end - offset) as usize].to_vec(),
+            keyFlag: (header.flags & FLAG_KEY) != 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtps::common::payload_pool::{PayloadPoolMode, PoolConfig};
+    use crate::rtps::messages::parameter_list::PID_STATUS_INFO;
+    use crate::rtps::structure::endpoint::DEFAULT_MAX_SAMPLE_SIZE;
+
+    #[test]
+    fn round_trip_test() {
+        let payload: Vec<u8> = (0..250).map(|n| n as u8).collect();
+        let mut frags = Vec::new();
+        for start in [1, 3] {
+            let mut frag = DataFragSubmessage::new(
+                Endianness::BIG_ENDIANNESS,
+                EntityId_t::c_EntityId_Unknown,
+                EntityId_t::c_EntityId_SEDPPubWriter,
+                SequenceNumber_t::from(5),
+                &payload,
+                100,
+                start,
+                2,
+            )
+            .unwrap();
+            frag.inlineQos = Some(ParameterList::new(Endianness::BIG_ENDIANNESS));
+            let mut buffer = Vec::new();
+            frag.write(&mut buffer).unwrap();
+            assert_eq!(0, buffer.len() % 4);
+            let header = SubmessageHeader_t::read(&buffer).unwrap();
+            let decoded =
+                DataFragSubmessage::read(&header, &buffer[SubmessageHeader_t::SIZE..]).unwrap();
+            assert_eq!(frag, decoded);
+            frags.push(decoded);
+        }
+        assert_eq!(1, frags[1].fragmentsInSubmessage);
+        assert_eq!(3, frags[1].fragment_count());
+        assert!(DataFragSubmessage::new(
+            Endianness::BIG_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            EntityId_t::c_EntityId_SEDPPubWriter,
+            SequenceNumber_t::from(5),
+            &payload,
+            100,
+            4,
+            1,
+        )
+        .is_err());
+
+        // Reassembly
+        let mut prefix = GuidPrefix_t::unknown();
+        prefix.value[0] = 1;
+        let mut change = frags[1]
+            .to_cache_change(
+                prefix,
+                Some(Time_t::new(1, 0)),
+                DEFAULT_MAX_SAMPLE_SIZE,
+                None,
+            )
+            .unwrap();
+        assert_eq!(ChangeKind_t::ALIVE, change.kind);
+        assert_eq!(prefix, change.writerGUID.guidPrefix);
+        assert_eq!(Time_t::new(1, 0), change.sourceTimestamp);
+        for frag in frags.iter().rev() {
+            assert!(!change.is_fully_assembled());
+            assert!(change.add_fragments(
+                &frag.fragments,
+                frag.fragmentStartingNum,
+                frag.fragmentsInSubmessage as u32
+            ));
+        }
+        assert!(change.is_fully_assembled());
+        assert_eq!(&payload[..], change.serializedPayload.data());
+        assert!(frags[1]
+            .to_cache_change(prefix, None, payload.len() - 1, None)
+            .is_err());
+
+        // Reassembly in a buffer of the reader pool
+        let pool = PayloadPool::new(PoolConfig {
+            mode: PayloadPoolMode::SIZE_CLASS,
+            ..PoolConfig::default()
+        });
+        let mut pooled = frags[0]
+            .to_cache_change(prefix, None, DEFAULT_MAX_SAMPLE_SIZE, Some(&pool))
+            .unwrap();
+        for frag in frags.iter() {
+            pooled.add_fragments(
+                &frag.fragments,
+                frag.fragmentStartingNum,
+                frag.fragmentsInSubmessage as u32,
+            );
+        }
+        assert!(pooled.is_fully_assembled());
+        assert_eq!(&payload[..], pooled.serializedPayload.data());
+        assert_eq!(1, pool.outstanding());
+        drop(pooled);
+        assert_eq!(0, pool.outstanding());
+
+        // Key-only fragments of a dispose
+        let mut frag = frags.remove(0);
+        frag.keyFlag = true;
+        assert_eq!(
+            ChangeKind_t::NOT_ALIVE_DISPOSED,
+            frag.to_cache_change(prefix, None, DEFAULT_MAX_SAMPLE_SIZE, None)
+                .unwrap()
+                .kind
+        );
+        let mut qos = ParameterList::new(Endianness::BIG_ENDIANNESS);
+        qos.push_raw(PID_STATUS_INFO, vec![0, 0, 0, 2]).unwrap();
+        frag.inlineQos = Some(qos);
+        assert_eq!(
+            ChangeKind_t::NOT_ALIVE_UNREGISTERED,
+            frag.change_kind().unwrap()
+        );
+    }
+
+    #[test]
+    fn error_test() {
+        let payload = vec![0_u8; 10];
+        let frag = DataFragSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            EntityId_t::c_EntityId_SEDPPubWriter,
+            SequenceNumber_t::from(1),
+            &payload,
+            8,
+            2,
+            1,
+        )
+        .unwrap();
+        let mut buffer = Vec::new();
+        frag.write(&mut buffer).unwrap();
+        let mut header = SubmessageHeader_t::read(&buffer).unwrap();
+        let body = &buffer[SubmessageHeader_t::SIZE..];
+        assert_eq!(
+            vec![0, 0],
+            DataFragSubmessage::read(&header, body).unwrap().fragments
+        );
+
+        assert!(DataFragSubmessage::read(&header, &body[..20]).is_err());
+        // Fragments missing from the submessage
+        assert!(DataFragSubmessage::read(&header, &body[..32]).is_err());
+        header.submessageId = DATA;
+        assert!(DataFragSubmessage::read(&header, body).is_err());
+    }
+}
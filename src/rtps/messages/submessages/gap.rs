@@ -0,0 +1,122 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::common::sequence_number_set::SequenceNumberSet_t;
+use crate::rtps::messages::submessages::submessage_header::*;
+
+/**
+ * @brief GAP submessage, telling a reader that changes are not relevant to it, as specified
+ *        in the DDSI-RTPS 2.5 specification chapter 9.4.5.5.
+ *        The irrelevant changes are the ones from gapStart to the base of gapList, excluded,
+ *        and the ones in gapList.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GapSubmessage {
+    pub endianness: Endianness,
+    pub readerId: EntityId_t,
+    pub writerId: EntityId_t,
+    pub gapStart: SequenceNumber_t,
+    pub gapList: SequenceNumberSet_t,
+}
+
+impl GapSubmessage {
+    pub fn new(
+        endianness: Endianness,
+        readerId: EntityId_t,
+        writerId: EntityId_t,
+        gapStart: SequenceNumber_t,
+        gapList: SequenceNumberSet_t,
+    ) -> Self {
+        GapSubmessage {
+            endianness,
+            readerId,
+            writerId,
+            gapStart,
+            gapList,
+        }
+    }
+
+    // Whether a change is notified as irrelevant
+    pub fn contains(&self, sequenceNumber: SequenceNumber_t) -> bool {
+        (sequenceNumber >= self.gapStart && sequenceNumber < self.gapList.base())
+            || self.gapList.is_set(sequenceNumber)
+    }
+
+    /**
+     * @brief Encode the submessage, header included.
+     *
+     * @param buffer Buffer where the submessage is appended.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.endianness == Endianness::LITTLE_ENDIANNESS {
+            flags |= FLAG_ENDIANNESS;
+        }
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.readerId.value);
+        body.extend_from_slice(&self.writerId.value);
+        write_sequence_number(&mut body, self.endianness, self.gapStart);
+        self.gapList.write(&mut body, self.endianness);
+        SubmessageHeader_t::new(GAP, flags, body.len() as u16).write(buffer);
+        buffer.extend_from_slice(&body);
+    }
+
+    /**
+     * @brief Decode the body of a GAP submessage.
+     *
+     * @param header Header of the submessage.
+     * @param body Octets following the header, up to the next header.
+     * @return The submessage, an error if it is malformed.
+     */
+    pub fn read(header: &SubmessageHeader_t, body: &[u8]) -> Result<Self, RtpsError> {
+        let endianness = header.endianness();
+        if body.len() < 16 {
+            return Err(RtpsError::new("GAP submessage too short"));
+        }
+        let (gapList, _) = SequenceNumberSet_t::read(&body[16..], endianness)?;
+        Ok(GapSubmessage::new(
+            endianness,
+            EntityId_t {
+                value: [body[0], body[1], body[2], body[3]],
+            },
+            EntityId_t {
+                value: [body[4], body[5], body[6], body[7]],
+            },
+            read_sequence_number(&body[8..], endianness),
+            gapList,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_test() {
+        let mut gapList = SequenceNumberSet_t::new(SequenceNumber_t::from(5));
+        gapList.add(SequenceNumber_t::from(7));
+        let gap = GapSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            EntityId_t::c_EntityId_SEDPPubWriter,
+            SequenceNumber_t::from(2),
+            gapList,
+        );
+        let irrelevant: Vec<u64> = (1..10)
+            .filter(|sn| gap.contains(SequenceNumber_t::from(*sn)))
+            .collect();
+        assert_eq!(vec![2, 3, 4, 7], irrelevant);
+
+        let mut buffer = Vec::new();
+        gap.write(&mut buffer);
+        let header = SubmessageHeader_t::read(&buffer).unwrap();
+        assert_eq!(GAP, header.submessageId);
+        assert_eq!(
+            gap,
+            GapSubmessage::read(&header, &buffer[SubmessageHeader_t::SIZE..]).unwrap()
+        );
+        assert!(GapSubmessage::read(&header, &buffer[4..20]).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::messages::submessages::submessage_header::*;
+
+// Flag telling the reader does not have to answer with an ACKNACK
+pub const FLAG_FINAL: u8 = 0x02;
+// Flag telling the heartbeat asserts the liveliness of the writer
+pub const FLAG_LIVELINESS: u8 = 0x04;
+
+/**
+ * @brief HEARTBEAT submessage, giving the range of changes available in the writer, as
+ *        specified in the DDSI-RTPS 2.5 specification chapter 9.4.5.6.
+ *        An empty writer sends firstSN = lastSN + 1.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HeartbeatSubmessage {
+    pub endianness: Endianness,
+    pub readerId: EntityId_t,
+    pub writerId: EntityId_t,
+    pub firstSN: SequenceNumber_t,
+    pub lastSN: SequenceNumber_t,
+    /// Incremented with every HEARTBEAT, to detect duplicates
+    pub count: u32,
+    pub finalFlag: bool,
+    pub livelinessFlag: bool,
+}
+
+impl HeartbeatSubmessage {
+    pub fn new(
+        endianness: Endianness,
+        readerId: EntityId_t,
+        writerId: EntityId_t,
+        firstSN: SequenceNumber_t,
+        lastSN: SequenceNumber_t,
+        count: u32,
+    ) -> Self {
+        HeartbeatSubmessage {
+            endianness,
+            readerId,
+            writerId,
+            firstSN,
+            lastSN,
+            count,
+            finalFlag: false,
+            livelinessFlag: false,
+        }
+    }
+
+    /**
+     * @brief Encode the submessage, header included.
+     *
+     * @param buffer Buffer where the submessage is appended.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.endianness == Endianness::LITTLE_ENDIANNESS {
+            flags |= FLAG_ENDIANNESS;
+        }
+        if self.finalFlag {
+            flags |= FLAG_FINAL;
+        }
+        if self.livelinessFlag {
+            flags |= FLAG_LIVELINESS;
+        }
+        SubmessageHeader_t::new(HEARTBEAT, flags, 28).write(buffer);
+        buffer.extend_from_slice(&self.readerId.value);
+        buffer.extend_from_slice(&self.writerId.value);
+        write_sequence_number(buffer, self.endianness, self.firstSN);
+        write_sequence_number(buffer, self.endianness, self.lastSN);
+        write_u32(buffer, self.endianness, self.count);
+    }
+
+    /**
+     * @brief Decode the body of a HEARTBEAT submessage.
+     *
+     * @param header Header of the submessage.
+     * @param body Octets following the header, up to the next header.
+     * @return The submessage, an error if it is malformed.
+     */
+    pub fn read(header: &SubmessageHeader_t, body: &[u8]) -> Result<Self, RtpsError> {
+        let endianness = header.endianness();
+        if body.len() < 28 {
+            return Err(RtpsError::new("HEARTBEAT submessage too short"));
+        }
+        let mut heartbeat = HeartbeatSubmessage::new(
+            endianness,
+            EntityId_t {
+                value: [body[0], body[1], body[2], body[3]],
+            },
+            EntityId_t {
+                value: [body[4], body[5], body[6], body[7]],
+            },
+            read_sequence_number(&body[8..], endianness),
+            read_sequence_number(&body[16..], endianness),
+            read_u32(&body[24..], endianness),
+        );
+        heartbeat.finalFlag = (header.flags & FLAG_FINAL) != 0;
+        heartbeat.livelinessFlag = (header.flags & FLAG_LIVELINESS) != 0;
+        Ok(heartbeat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_test() {
+        let mut heartbeat = HeartbeatSubmessage::new(
+            Endianness::BIG_ENDIANNESS,
+            EntityId_t::c_EntityId_Unknown,
+            EntityId_t::c_EntityId_SPDPWriter,
+            SequenceNumber_t::from(1),
+            SequenceNumber_t::new(1, 2),
+            3,
+        );
+        heartbeat.finalFlag = true;
+        let mut buffer = Vec::new();
+        heartbeat.write(&mut buffer);
+        assert_eq!(
+            vec![
+                HEARTBEAT, FLAG_FINAL, 0, 28, 0, 0, 0, 0, 0, 1, 0, 0xc2, 0, 0, 0, 0, 0, 0, 0, 1, 0,
+                0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3
+            ],
+            buffer
+        );
+        let header = SubmessageHeader_t::read(&buffer).unwrap();
+        assert_eq!(
+            heartbeat,
+            HeartbeatSubmessage::read(&header, &buffer[SubmessageHeader_t::SIZE..]).unwrap()
+        );
+        assert!(HeartbeatSubmessage::read(&header, &buffer[4..30]).is_err());
+    }
+}
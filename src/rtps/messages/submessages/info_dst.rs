@@ -0,0 +1,59 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+use crate::rtps::messages::submessages::submessage_header::*;
+
+/**
+ * @brief INFO_DST submessage, giving the participant the following submessages are sent to,
+ *        as specified in the DDSI-RTPS 2.5 specification chapter 9.4.5.7.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InfoDestinationSubmessage {
+    pub endianness: Endianness,
+    /// Destination participant, c_GuidPrefix_Unknown for every participant
+    pub guidPrefix: GuidPrefix_t,
+}
+
+impl InfoDestinationSubmessage {
+    pub fn new(endianness: Endianness, guidPrefix: GuidPrefix_t) -> Self {
+        InfoDestinationSubmessage {
+            endianness,
+            guidPrefix,
+        }
+    }
+
+    /**
+     * @brief Encode the submessage, header included.
+     *
+     * @param buffer Buffer where the submessage is appended.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.endianness == Endianness::LITTLE_ENDIANNESS {
+            flags |= FLAG_ENDIANNESS;
+        }
+        SubmessageHeader_t::new(INFO_DST, flags, GuidPrefix_t::SIZE as u16).write(buffer);
+        buffer.extend_from_slice(&self.guidPrefix.value);
+    }
+
+    /**
+     * @brief Decode the body of an INFO_DST submessage.
+     *
+     * @param header Header of the submessage.
+     * @param body Octets following the header, up to the next header.
+     * @return The submessage, an error if it is malformed.
+     */
+    pub fn read(header: &SubmessageHeader_t, body: &[u8]) -> Result<Self, RtpsError> {
+        if body.len() < GuidPrefix_t::SIZE {
+            return Err(RtpsError::new("INFO_DST submessage too short"));
+        }
+        let mut guidPrefix = GuidPrefix_t::unknown();
+        guidPrefix
+            .value
+            .copy_from_slice(&body[..GuidPrefix_t::SIZE]);
+        Ok(InfoDestinationSubmessage::new(
+            header.endianness(),
+            guidPrefix,
+        ))
+    }
+}
@@ -0,0 +1,83 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::time::Time_t;
+use crate::rtps::messages::submessages::submessage_header::*;
+
+// Flag telling the timestamp is not sent and the following submessages have none
+pub const FLAG_INVALIDATE: u8 = 0x02;
+
+/**
+ * @brief INFO_TS submessage, giving the source timestamp of the following submessages,
+ *        as specified in the DDSI-RTPS 2.5 specification chapter 9.4.5.9.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InfoTimestampSubmessage {
+    pub endianness: Endianness,
+    /// Source timestamp, None to invalidate the timestamp
+    pub timestamp: Option<Time_t>,
+}
+
+impl InfoTimestampSubmessage {
+    pub fn new(endianness: Endianness, timestamp: Option<Time_t>) -> Self {
+        InfoTimestampSubmessage {
+            endianness,
+            timestamp,
+        }
+    }
+
+    /**
+     * @brief Encode the submessage, header included.
+     *
+     * @param buffer Buffer where the submessage is appended.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.endianness == Endianness::LITTLE_ENDIANNESS {
+            flags |= FLAG_ENDIANNESS;
+        }
+        match self.timestamp {
+            None => SubmessageHeader_t::new(INFO_TS, flags | FLAG_INVALIDATE, 0).write(buffer),
+            Some(timestamp) => {
+                SubmessageHeader_t::new(INFO_TS, flags, 8).write(buffer);
+                match self.endianness {
+                    Endianness::BIG_ENDIANNESS => {
+                        buffer.extend_from_slice(&timestamp.seconds.to_be_bytes());
+                        buffer.extend_from_slice(&timestamp.fraction().to_be_bytes());
+                    }
+                    Endianness::LITTLE_ENDIANNESS => {
+                        buffer.extend_from_slice(&timestamp.seconds.to_le_bytes());
+                        buffer.extend_from_slice(&timestamp.fraction().to_le_bytes());
+                    }
+                }
+            }
+        }
+    }
+
+    /**
+     * @brief Decode the body of an INFO_TS submessage.
+     *
+     * @param header Header of the submessage.
+     * @param body Octets following the header, up to the next header.
+     * @return The submessage, an error if it is malformed.
+     */
+    pub fn read(header: &SubmessageHeader_t, body: &[u8]) -> Result<Self, RtpsError> {
+        let endianness = header.endianness();
+        if (header.flags & FLAG_INVALIDATE) != 0 {
+            return Ok(InfoTimestampSubmessage::new(endianness, None));
+        }
+        if body.len() < 8 {
+            return Err(RtpsError::new("INFO_TS submessage too short"));
+        }
+        let seconds = [body[0], body[1], body[2], body[3]];
+        let fraction = [body[4], body[5], body[6], body[7]];
+        let timestamp = match endianness {
+            Endianness::BIG_ENDIANNESS => {
+                Time_t::from_fraction(i32::from_be_bytes(seconds), u32::from_be_bytes(fraction))
+            }
+            Endianness::LITTLE_ENDIANNESS => {
+                Time_t::from_fraction(i32::from_le_bytes(seconds), u32::from_le_bytes(fraction))
+            }
+        };
+        Ok(InfoTimestampSubmessage::new(endianness, Some(timestamp)))
+    }
+}
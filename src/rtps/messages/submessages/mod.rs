@@ -1 +1,9 @@
-
+pub mod acknack;
+pub mod data;
+pub mod data_frag;
+pub mod gap;
+pub mod heartbeat;
+pub mod info_dst;
+pub mod info_ts;
+pub mod nack_frag;
+pub mod submessage_header;
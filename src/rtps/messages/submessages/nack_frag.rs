@@ -0,0 +1,121 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::fragment_number_set::FragmentNumberSet_t;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::messages::submessages::submessage_header::*;
+
+/**
+ * @brief NACK_FRAG submessage, sent by a reader to request the fragments of a change it is
+ *        missing, as specified in the DDSI-RTPS 2.5 specification chapter 9.4.5.10.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NackFragSubmessage {
+    pub endianness: Endianness,
+    pub readerId: EntityId_t,
+    pub writerId: EntityId_t,
+    pub writerSN: SequenceNumber_t,
+    pub fragmentNumberState: FragmentNumberSet_t,
+    /// Incremented with every NACK_FRAG, to detect duplicates
+    pub count: u32,
+}
+
+impl NackFragSubmessage {
+    pub fn new(
+        endianness: Endianness,
+        readerId: EntityId_t,
+        writerId: EntityId_t,
+        writerSN: SequenceNumber_t,
+        fragmentNumberState: FragmentNumberSet_t,
+        count: u32,
+    ) -> Self {
+        NackFragSubmessage {
+            endianness,
+            readerId,
+            writerId,
+            writerSN,
+            fragmentNumberState,
+            count,
+        }
+    }
+
+    /**
+     * @brief Encode the submessage, header included.
+     *
+     * @param buffer Buffer where the submessage is appended.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.endianness == Endianness::LITTLE_ENDIANNESS {
+            flags |= FLAG_ENDIANNESS;
+        }
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.readerId.value);
+        body.extend_from_slice(&self.writerId.value);
+        write_sequence_number(&mut body, self.endianness, self.writerSN);
+        self.fragmentNumberState.write(&mut body, self.endianness);
+        write_u32(&mut body, self.endianness, self.count);
+        SubmessageHeader_t::new(NACK_FRAG, flags, body.len() as u16).write(buffer);
+        buffer.extend_from_slice(&body);
+    }
+
+    /**
+     * @brief Decode the body of a NACK_FRAG submessage.
+     *
+     * @param header Header of the submessage.
+     * @param body Octets following the header, up to the next header.
+     * @return The submessage, an error if it is malformed.
+     */
+    pub fn read(header: &SubmessageHeader_t, body: &[u8]) -> Result<Self, RtpsError> {
+        let endianness = header.endianness();
+        if body.len() < 16 {
+            return Err(RtpsError::new("NACK_FRAG submessage too short"));
+        }
+        let (fragmentNumberState, length) = FragmentNumberSet_t::read(&body[16..], endianness)?;
+        if body.len() < 16 + length + 4 {
+            return Err(RtpsError::new("NACK_FRAG submessage too short"));
+        }
+        Ok(NackFragSubmessage::new(
+            endianness,
+            EntityId_t {
+                value: [body[0], body[1], body[2], body[3]],
+            },
+            EntityId_t {
+                value: [body[4], body[5], body[6], body[7]],
+            },
+            read_sequence_number(&body[8..], endianness),
+            fragmentNumberState,
+            read_u32(&body[16 + length..], endianness),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_test() {
+        let mut set = FragmentNumberSet_t::new(2);
+        set.add(2);
+        set.add(4);
+        let nack_frag = NackFragSubmessage::new(
+            Endianness::BIG_ENDIANNESS,
+            EntityId_t::new_from_key_kind(1, EntityId_t::ENTITYKIND_USER_READER_WITH_KEY),
+            EntityId_t::new_from_key_kind(2, EntityId_t::ENTITYKIND_USER_WRITER_WITH_KEY),
+            SequenceNumber_t::from(9),
+            set,
+            3,
+        );
+        let mut buffer = Vec::new();
+        nack_frag.write(&mut buffer);
+        assert_eq!(4 + 16 + 12 + 4, buffer.len());
+        let header = SubmessageHeader_t::read(&buffer).unwrap();
+        assert_eq!(NACK_FRAG, header.submessageId);
+        assert_eq!(
+            nack_frag,
+            NackFragSubmessage::read(&header, &buffer[SubmessageHeader_t::SIZE..]).unwrap()
+        );
+        assert!(NackFragSubmessage::read(&header, &buffer[4..30]).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::cdr::encoding::Endianness;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+
+// Submessage kinds, as specified in the DDSI-RTPS 2.5 specification chapter 9.4.5.1.1
+pub const PAD: u8 = 0x01;
+pub const ACKNACK: u8 = 0x06;
+pub const HEARTBEAT: u8 = 0x07;
+pub const GAP: u8 = 0x08;
+pub const INFO_TS: u8 = 0x09;
+pub const INFO_SRC: u8 = 0x0c;
+pub const INFO_REPLY_IP4: u8 = 0x0d;
+pub const INFO_DST: u8 = 0x0e;
+pub const INFO_REPLY: u8 = 0x0f;
+pub const NACK_FRAG: u8 = 0x12;
+pub const HEARTBEAT_FRAG: u8 = 0x13;
+pub const DATA: u8 = 0x15;
+pub const DATA_FRAG: u8 = 0x16;
+
+// Flag telling the endianness of the submessage, common to every submessage kind
+pub const FLAG_ENDIANNESS: u8 = 0x01;
+
+/**
+ * @brief Header of a submessage: kind, flags and number of octets up to the next header.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SubmessageHeader_t {
+    pub submessageId: u8,
+    pub flags: u8,
+    pub submessageLength: u16,
+}
+
+impl SubmessageHeader_t {
+    pub const SIZE: usize = 4;
+
+    pub fn new(submessageId: u8, flags: u8, submessageLength: u16) -> Self {
+        SubmessageHeader_t {
+            submessageId,
+            flags,
+            submessageLength,
+        }
+    }
+
+    // Endianness of the submessage, given by the E flag
+    pub fn endianness(&self) -> Endianness {
+        if (self.flags & FLAG_ENDIANNESS) != 0 {
+            Endianness::LITTLE_ENDIANNESS
+        } else {
+            Endianness::BIG_ENDIANNESS
+        }
+    }
+
+    /**
+     * @brief Decode a submessage header.
+     *
+     * @param data Data starting with the header.
+     * @return The header, an error if there are not enough bytes.
+     */
+    pub fn read(data: &[u8]) -> Result<Self, RtpsError> {
+        if data.len() < SubmessageHeader_t::SIZE {
+            return Err(RtpsError::new("Submessage header too short"));
+        }
+        let mut header = SubmessageHeader_t::new(data[0], data[1], 0);
+        let length = [data[2], data[3]];
+        header.submessageLength = match header.endianness() {
+            Endianness::BIG_ENDIANNESS => u16::from_be_bytes(length),
+            Endianness::LITTLE_ENDIANNESS => u16::from_le_bytes(length),
+        };
+        Ok(header)
+    }
+
+    /**
+     * @brief Encode the header with the endianness given by its flags.
+     *
+     * @param buffer Buffer where the header is appended.
+     */
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.submessageId);
+        buffer.push(self.flags);
+        match self.endianness() {
+            Endianness::BIG_ENDIANNESS => {
+                buffer.extend_from_slice(&self.submessageLength.to_be_bytes())
+            }
+            Endianness::LITTLE_ENDIANNESS => {
+                buffer.extend_from_slice(&self.submessageLength.to_le_bytes())
+            }
+        }
+    }
+}
+
+/*
+ * Encoding of the fields of the submessages, with the endianness of the submessage.
+ * The read functions expect the caller to have checked the length of the data.
+ */
+
+pub fn write_u16(buffer: &mut Vec<u8>, endianness: Endianness, value: u16) {
+    match endianness {
+        Endianness::BIG_ENDIANNESS => buffer.extend_from_slice(&value.to_be_bytes()),
+        Endianness::LITTLE_ENDIANNESS => buffer.extend_from_slice(&value.to_le_bytes()),
+    }
+}
+
+pub fn write_u32(buffer: &mut Vec<u8>, endianness: Endianness, value: u32) {
+    match endianness {
+        Endianness::BIG_ENDIANNESS => buffer.extend_from_slice(&value.to_be_bytes()),
+        Endianness::LITTLE_ENDIANNESS => buffer.extend_from_slice(&value.to_le_bytes()),
+    }
+}
+
+pub fn write_sequence_number(
+    buffer: &mut Vec<u8>,
+    endianness: Endianness,
+    sequenceNumber: SequenceNumber_t,
+) {
+    write_u32(buffer, endianness, sequenceNumber.high() as u32);
+    write_u32(buffer, endianness, sequenceNumber.low());
+}
+
+pub fn read_u16(data: &[u8], endianness: Endianness) -> u16 {
+    let bytes = [data[0], data[1]];
+    match endianness {
+        Endianness::BIG_ENDIANNESS => u16::from_be_bytes(bytes),
+        Endianness::LITTLE_ENDIANNESS => u16::from_le_bytes(bytes),
+    }
+}
+
+pub fn read_u32(data: &[u8], endianness: Endianness) -> u32 {
+    let bytes = [data[0], data[1], data[2], data[3]];
+    match endianness {
+        Endianness::BIG_ENDIANNESS => u32::from_be_bytes(bytes),
+        Endianness::LITTLE_ENDIANNESS => u32::from_le_bytes(bytes),
+    }
+}
+
+pub fn read_sequence_number(data: &[u8], endianness: Endianness) -> SequenceNumber_t {
+    SequenceNumber_t::new(
+        read_u32(data, endianness) as i32,
+        read_u32(&data[4..], endianness),
+    )
+}
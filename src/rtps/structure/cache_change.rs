@@ -1 +1,247 @@
+use crate::rtps::common::change_kind_t::ChangeKind_t;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::instance_handle::InstanceHandle_t;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::common::serialized_payload::SerializedPayload_t;
+use crate::rtps::common::time::Time_t;
+use crate::rtps::messages::parameter_list::ParameterList;
 
+/**
+ * @brief Change of a data object, as stored by the histories of the writers and readers.
+ */
+#[derive(Debug)]
+pub struct CacheChange_t {
+    /// Kind of change, default value ALIVE.
+    pub kind: ChangeKind_t,
+    /// GUID_t of the writer that generated this change.
+    pub writerGUID: GUID_t,
+    /// Handle of the data associated with this change.
+    pub instanceHandle: InstanceHandle_t,
+    /// SequenceNumber of the change
+    pub sequenceNumber: SequenceNumber_t,
+    /// Serialized Payload associated with the change.
+    pub serializedPayload: SerializedPayload_t,
+    /// Inline QoS received or to be sent with the change.
+    pub inlineQos: Option<ParameterList>,
+    /// Time at which the writer created the change.
+    pub sourceTimestamp: Time_t,
+    /// Time at which the reader received the change.
+    pub receptionTimestamp: Time_t,
+    /// Whether the application has read the change.
+    pub isRead: bool,
+    // Size of the fragments, 0 if the change is not fragmented
+    fragment_size: u16,
+    // Number of fragments of the payload
+    fragment_count: u32,
+    // Received fragments, bit n of word n / 32 for fragment n + 1
+    received_fragments: Vec<u32>,
+}
+
+impl Default for CacheChange_t {
+    fn default() -> Self {
+        CacheChange_t {
+            kind: ChangeKind_t::ALIVE,
+            writerGUID: GUID_t::unknown(),
+            instanceHandle: InstanceHandle_t::new(),
+            sequenceNumber: SequenceNumber_t::new(0, 0),
+            serializedPayload: SerializedPayload_t::default(),
+            inlineQos: None,
+            sourceTimestamp: Time_t::c_TimeZero,
+            receptionTimestamp: Time_t::c_TimeZero,
+            isRead: false,
+            fragment_size: 0,
+            fragment_count: 0,
+            received_fragments: Vec::new(),
+        }
+    }
+}
+
+impl CacheChange_t {
+    pub fn new() -> Self {
+        CacheChange_t::default()
+    }
+
+    /**
+     * @brief Copy the information of another change, including its payload.
+     *
+     * @param ch_ptr Change to copy.
+     * @return True if correct.
+     */
+    pub fn copy(&mut self, ch_ptr: &CacheChange_t) -> bool {
+        self.kind = ch_ptr.kind;
+        self.writerGUID = ch_ptr.writerGUID;
+        self.instanceHandle = ch_ptr.instanceHandle;
+        self.sequenceNumber = ch_ptr.sequenceNumber;
+        self.inlineQos = ch_ptr.inlineQos.clone();
+        self.sourceTimestamp = ch_ptr.sourceTimestamp;
+        self.receptionTimestamp = ch_ptr.receptionTimestamp;
+        self.isRead = ch_ptr.isRead;
+        self.fragment_size = ch_ptr.fragment_size;
+        self.fragment_count = ch_ptr.fragment_count;
+        self.received_fragments = ch_ptr.received_fragments.clone();
+        self.serializedPayload
+            .copy(&ch_ptr.serializedPayload, false)
+    }
+
+    /**
+     * @brief Copy the information of another change, sharing the buffer of its payload
+     *        instead of copying it. The payload is ready to be read.
+     *
+     * @param ch_ptr Change to share.
+     */
+    pub fn share(&mut self, ch_ptr: &CacheChange_t) {
+        self.kind = ch_ptr.kind;
+        self.writerGUID = ch_ptr.writerGUID;
+        self.instanceHandle = ch_ptr.instanceHandle;
+        self.sequenceNumber = ch_ptr.sequenceNumber;
+        self.inlineQos = ch_ptr.inlineQos.clone();
+        self.sourceTimestamp = ch_ptr.sourceTimestamp;
+        self.serializedPayload.share(&ch_ptr.serializedPayload);
+        self.serializedPayload.begin_read();
+    }
+
+    pub fn getFragmentSize(&self) -> u16 {
+        self.fragment_size
+    }
+
+    pub fn getFragmentCount(&self) -> u32 {
+        self.fragment_count
+    }
+
+    /**
+     * @brief Set the fragment size of the change and compute its number of fragments.
+     *        The length of the payload must already be set.
+     *
+     * @param fragment_size Size of the fragments, 0 if the change is not fragmented.
+     * @param create_fragment_list Whether to track the received fragments, used by the readers
+     *                             reassembling the payload. Every fragment starts missing.
+     */
+    pub fn setFragmentSize(&mut self, fragment_size: u16, create_fragment_list: bool) {
+        self.fragment_size = fragment_size;
+        self.fragment_count = 0;
+        self.received_fragments.clear();
+        if fragment_size == 0 {
+            return;
+        }
+
+        let length = self.serializedPayload.length();
+        self.fragment_count = length.div_ceil(fragment_size as usize) as u32;
+        if create_fragment_list {
+            self.received_fragments = vec![0; (self.fragment_count as usize).div_ceil(32)];
+        }
+    }
+
+    // Whether every fragment of the payload has been received
+    pub fn is_fully_assembled(&self) -> bool {
+        if self.fragment_size == 0 || self.received_fragments.is_empty() {
+            return true;
+        }
+        (1..=self.fragment_count).all(|n| self.is_fragment_received(n))
+    }
+
+    /**
+     * @brief Whether a fragment has been received.
+     *
+     * @param fragment_number Fragment number, starting at 1.
+     * @return True if the fragment is in the payload.
+     */
+    pub fn is_fragment_received(&self, fragment_number: u32) -> bool {
+        if fragment_number == 0 || fragment_number > self.fragment_count {
+            return false;
+        }
+        if self.received_fragments.is_empty() {
+            return true;
+        }
+        let index = (fragment_number - 1) as usize;
+        (self.received_fragments[index / 32] & (1 << (index % 32))) != 0
+    }
+
+    // Fragments not received yet, in increasing order
+    pub fn get_missing_fragments(&self) -> Vec<u32> {
+        (1..=self.fragment_count)
+            .filter(|n| !self.is_fragment_received(*n))
+            .collect()
+    }
+
+    /**
+     * @brief Copy received fragments to the payload and mark them as received.
+     *
+     * @param incoming_data Data of the fragments.
+     * @param fragment_starting_num Number of the first fragment, starting at 1.
+     * @param fragments_in_submessage Number of fragments in incoming_data.
+     * @return False if the fragments are not part of the payload or their size is wrong.
+     */
+    pub fn add_fragments(
+        &mut self,
+        incoming_data: &[u8],
+        fragment_starting_num: u32,
+        fragments_in_submessage: u32,
+    ) -> bool {
+        let last_fragment = match fragment_starting_num.checked_add(fragments_in_submessage) {
+            Some(end) if fragment_starting_num > 0 && end > fragment_starting_num => end - 1,
+            _ => return false,
+        };
+        if self.fragment_size == 0 || last_fragment > self.fragment_count {
+            return false;
+        }
+
+        let length = self.serializedPayload.length();
+        let offset = (fragment_starting_num as usize - 1) * self.fragment_size as usize;
+        let end = std::cmp::min(
+            length,
+            offset + fragments_in_submessage as usize * self.fragment_size as usize,
+        );
+        // Only the last fragment of the payload may be shorter
+        if incoming_data.len() != end - offset {
+            return false;
+        }
+
+        self.serializedPayload.data_mut()[offset..end].copy_from_slice(incoming_data);
+        if !self.received_fragments.is_empty() {
+            for n in fragment_starting_num..=last_fragment {
+                let index = (n - 1) as usize;
+                self.received_fragments[index / 32] |= 1 << (index % 32);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragments_test() {
+        let data: Vec<u8> = (0..250).map(|n| n as u8).collect();
+
+        let mut change = CacheChange_t::new();
+        change.serializedPayload.reserve(data.len());
+        assert!(change.serializedPayload.set_length(data.len()));
+        change.setFragmentSize(100, true);
+        assert_eq!(3, change.getFragmentCount());
+        assert_eq!(vec![1, 2, 3], change.get_missing_fragments());
+        assert!(!change.is_fully_assembled());
+
+        assert!(change.add_fragments(&data[200..], 3, 1));
+        assert_eq!(vec![1, 2], change.get_missing_fragments());
+        // Wrong size, unknown fragment numbers
+        assert!(!change.add_fragments(&data[..99], 1, 1));
+        assert!(!change.add_fragments(&data[..100], 0, 1));
+        assert!(!change.add_fragments(&data[..100], 4, 1));
+        assert!(!change.add_fragments(&data[..], 1, 0));
+
+        assert!(change.add_fragments(&data[..200], 1, 2));
+        assert!(change.is_fully_assembled());
+        assert!(change.get_missing_fragments().is_empty());
+        assert_eq!(&data[..], change.serializedPayload.data());
+
+        // The writer does not track fragments
+        let mut copy = CacheChange_t::new();
+        assert!(copy.copy(&change));
+        copy.setFragmentSize(64, false);
+        assert_eq!(4, copy.getFragmentCount());
+        assert!(copy.is_fully_assembled());
+        assert_eq!(change.serializedPayload, copy.serializedPayload);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::dds::core::policy::DurabilityQosPolicyKind_t;
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::locator_list::LocatorList;
+use crate::rtps::common::payload_pool::PayloadPool;
+use std::sync::Arc;
+
+/// Largest sample a reader reassembles from DATA_FRAG by default
+pub const DEFAULT_MAX_SAMPLE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Whether the endpoint is a writer or a reader
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EndpointKind_t {
+    WRITER,
+    READER,
+}
+
+/// Whether the data of the topic has a key, as specified in the DDSI-RTPS 2.5 specification chapter 8.2.1.2
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TopicKind_t {
+    NO_KEY = 1,
+    WITH_KEY = 2,
+}
+
+impl TopicKind_t {
+    // Entity kind of the user writers of the topic
+    pub fn writer_entity_kind(&self) -> u8 {
+        match self {
+            TopicKind_t::NO_KEY => EntityId_t::ENTITYKIND_USER_WRITER_NO_KEY,
+            TopicKind_t::WITH_KEY => EntityId_t::ENTITYKIND_USER_WRITER_WITH_KEY,
+        }
+    }
+
+    // Entity kind of the user readers of the topic
+    pub fn reader_entity_kind(&self) -> u8 {
+        match self {
+            TopicKind_t::NO_KEY => EntityId_t::ENTITYKIND_USER_READER_NO_KEY,
+            TopicKind_t::WITH_KEY => EntityId_t::ENTITYKIND_USER_READER_WITH_KEY,
+        }
+    }
+
+    /**
+     * @brief Get the topic kind of an endpoint from its entity id, builtin or not.
+     *
+     * @param entityId Entity id of the endpoint.
+     * @return The topic kind, None if the entity is not a writer or a reader.
+     */
+    pub fn from_entity_id(entityId: &EntityId_t) -> Option<TopicKind_t> {
+        match entityId.entity_kind() & 0x3f {
+            0x02 | 0x07 => Some(TopicKind_t::WITH_KEY),
+            0x03 | 0x04 => Some(TopicKind_t::NO_KEY),
+            _ => None,
+        }
+    }
+}
+
+/// Reliability of the communication between endpoints
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ReliabilityKind_t {
+    BEST_EFFORT = 1,
+    RELIABLE = 2,
+}
+
+/**
+ * @brief Attributes of a local endpoint.
+ */
+#[derive(Clone, Debug)]
+pub struct EndpointAttributes {
+    pub endpointKind: EndpointKind_t,
+    pub topicKind: TopicKind_t,
+    pub reliabilityKind: ReliabilityKind_t,
+    pub durabilityKind: DurabilityQosPolicyKind_t,
+    /// Locators the endpoint receives unicast messages on, the participant ones if empty
+    pub unicastLocatorList: LocatorList,
+    /// Locators the endpoint receives multicast messages on, the participant ones if empty
+    pub multicastLocatorList: LocatorList,
+    /// Largest sample a reader reassembles from fragments, the larger ones are dropped
+    pub maxSampleSize: usize,
+    /// Pool the payloads of the changes are taken from, plain allocations if None
+    pub payloadPool: Option<Arc<PayloadPool>>,
+}
+
+impl EndpointAttributes {
+    pub fn new(
+        endpointKind: EndpointKind_t,
+        topicKind: TopicKind_t,
+        reliabilityKind: ReliabilityKind_t,
+    ) -> Self {
+        EndpointAttributes {
+            endpointKind,
+            topicKind,
+            reliabilityKind,
+            durabilityKind: DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS,
+            unicastLocatorList: LocatorList::new(),
+            multicastLocatorList: LocatorList::new(),
+            maxSampleSize: DEFAULT_MAX_SAMPLE_SIZE,
+            payloadPool: None,
+        }
+    }
+}
+
+/**
+ * @brief Remote endpoint matched with a local endpoint, as announced by discovery.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteEndpointAttributes {
+    pub guid: GUID_t,
+    pub reliabilityKind: ReliabilityKind_t,
+    pub durabilityKind: DurabilityQosPolicyKind_t,
+    pub unicastLocatorList: LocatorList,
+    pub multicastLocatorList: LocatorList,
+    /// Whether a remote reader expects the inline QoS with every DATA
+    pub expectsInlineQos: bool,
+}
+
+impl RemoteEndpointAttributes {
+    pub fn new(guid: GUID_t, reliabilityKind: ReliabilityKind_t) -> Self {
+        RemoteEndpointAttributes {
+            guid,
+            reliabilityKind,
+            durabilityKind: DurabilityQosPolicyKind_t::VOLATILE_DURABILITY_QOS,
+            unicastLocatorList: LocatorList::new(),
+            multicastLocatorList: LocatorList::new(),
+            expectsInlineQos: false,
+        }
+    }
+}
+
+/**
+ * @brief RTPS endpoint, as specified in the DDSI-RTPS 2.5 specification chapter 8.2.6.
+ */
+pub trait Endpoint: Send + Sync {
+    fn getGuid(&self) -> &GUID_t;
+
+    fn getAttributes(&self) -> &EndpointAttributes;
+
+    fn topicKind(&self) -> TopicKind_t {
+        self.getAttributes().topicKind
+    }
+
+    fn reliabilityLevel(&self) -> ReliabilityKind_t {
+        self.getAttributes().reliabilityKind
+    }
+
+    fn unicastLocatorList(&self) -> &LocatorList {
+        &self.getAttributes().unicastLocatorList
+    }
+
+    fn multicastLocatorList(&self) -> &LocatorList {
+        &self.getAttributes().multicastLocatorList
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topic_kind_test() {
+        for topicKind in [TopicKind_t::NO_KEY, TopicKind_t::WITH_KEY] {
+            let writer = EntityId_t::new_from_key_kind(1, topicKind.writer_entity_kind());
+            let reader = EntityId_t::new_from_key_kind(1, topicKind.reader_entity_kind());
+            assert!(writer.is_writer());
+            assert!(reader.is_reader());
+            assert_eq!(Some(topicKind), TopicKind_t::from_entity_id(&writer));
+            assert_eq!(Some(topicKind), TopicKind_t::from_entity_id(&reader));
+        }
+        assert_eq!(
+            Some(TopicKind_t::WITH_KEY),
+            TopicKind_t::from_entity_id(&EntityId_t::c_EntityId_SPDPWriter)
+        );
+        assert_eq!(
+            None,
+            TopicKind_t::from_entity_id(&EntityId_t::c_EntityId_RTPSParticipant)
+        );
+    }
+}
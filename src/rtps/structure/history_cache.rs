@@ -0,0 +1,463 @@
+use crate::dds::core::policy::*;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::instance_handle::InstanceHandle_t;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+
+// Changes are ordered by sequence number, the writer GUID tells apart changes of several writers
+type ChangeKey = (SequenceNumber_t, GUID_t);
+
+/**
+ * @brief Notifications of a history cache, used by the writers and readers to learn about
+ *        the changes added and removed by other entities.
+ *        They are called after the owner of the history has released its lock, so they may
+ *        use the history and the entities locking it.
+ */
+pub trait HistoryCacheListener: Send + Sync {
+    fn on_change_added(&self, _change: &CacheChange_t) {}
+
+    fn on_change_removed(&self, _change: &CacheChange_t) {}
+}
+
+/**
+ * @brief History of the changes of a writer or a reader, as specified in the DDSI-RTPS 2.5
+ *        specification chapter 8.2.2.
+ *        Changes are kept in sequence number order and indexed by instance, so the depth of
+ *        KEEP_LAST histories is enforced without going through the whole history.
+ *        Resource limits with negative values are unlimited.
+ */
+pub struct HistoryCache {
+    history: HistoryQosPolicy,
+    resource_limits: ResourceLimitsQosPolicy,
+    changes: BTreeMap<ChangeKey, CacheChange_t>,
+    // Changes of each instance, oldest first
+    instances: HashMap<InstanceHandle_t, VecDeque<ChangeKey>>,
+    listeners: Vec<Arc<dyn HistoryCacheListener>>,
+    // Notifications not yet given to the listeners, with whether the change was added
+    pending: Vec<(bool, CacheChange_t)>,
+}
+
+/**
+ * @brief Notifications taken from a history cache, to give to its listeners once the history
+ *        is no longer locked.
+ */
+#[must_use]
+pub struct HistoryNotifications {
+    listeners: Vec<Arc<dyn HistoryCacheListener>>,
+    changes: Vec<(bool, CacheChange_t)>,
+}
+
+impl HistoryNotifications {
+    pub fn notify(self) {
+        for (added, change) in &self.changes {
+            for listener in &self.listeners {
+                if *added {
+                    listener.on_change_added(change);
+                } else {
+                    listener.on_change_removed(change);
+                }
+            }
+        }
+    }
+}
+
+impl HistoryCache {
+    pub fn new(history: HistoryQosPolicy, resource_limits: ResourceLimitsQosPolicy) -> Self {
+        HistoryCache {
+            history,
+            resource_limits,
+            changes: BTreeMap::new(),
+            instances: HashMap::new(),
+            listeners: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn history(&self) -> &HistoryQosPolicy {
+        &self.history
+    }
+
+    pub fn resource_limits(&self) -> &ResourceLimitsQosPolicy {
+        &self.resource_limits
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /**
+     * @brief Add a change to the history.
+     *        On KEEP_LAST histories the oldest change of the instance is removed when the
+     *        instance already has depth changes, or max_samples_per_instance if lower.
+     *
+     * @param change Change to add.
+     * @return The change removed to make room for the new one, if any. An error if the change
+     *         is already in the history or a resource limit is reached.
+     */
+    pub fn add_change(
+        &mut self,
+        change: CacheChange_t,
+    ) -> Result<Option<CacheChange_t>, RtpsError> {
+        let key = (change.sequenceNumber, change.writerGUID);
+        if self.changes.contains_key(&key) {
+            return Err(RtpsError::new("Change already in the history"));
+        }
+
+        let instance_len = self.instance_len(&change.instanceHandle);
+        if instance_len == 0
+            && !is_unlimited(self.resource_limits.max_instances)
+            && self.instances.len() >= self.resource_limits.max_instances as usize
+        {
+            return Err(RtpsError::new("Maximum number of instances reached"));
+        }
+
+        let mut replaced = None;
+        match self.history.kind {
+            HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS => {
+                let mut depth = self.history.depth;
+                let max = self.resource_limits.max_samples_per_instance;
+                if !is_unlimited(max) {
+                    depth = std::cmp::min(depth, max);
+                }
+                if instance_len >= std::cmp::max(depth, 1) as usize {
+                    let oldest = self.instances[&change.instanceHandle][0];
+                    replaced = self.remove_change(&oldest.1, oldest.0);
+                }
+            }
+            HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS => {
+                let max = self.resource_limits.max_samples_per_instance;
+                if !is_unlimited(max) && instance_len >= max as usize {
+                    return Err(RtpsError::new(
+                        "Maximum number of samples per instance reached",
+                    ));
+                }
+            }
+        }
+
+        let max = self.resource_limits.max_samples;
+        if !is_unlimited(max) && self.changes.len() >= max as usize {
+            return Err(RtpsError::new("Maximum number of samples reached"));
+        }
+
+        self.instances
+            .entry(change.instanceHandle)
+            .or_default()
+            .push_back(key);
+        self.notify_later(true, &change);
+        self.changes.insert(key, change);
+        Ok(replaced)
+    }
+
+    /**
+     * @brief Remove a change from the history.
+     *
+     * @param writerGUID Writer of the change.
+     * @param sequenceNumber Sequence number of the change.
+     * @return The removed change, None if it is not in the history.
+     */
+    pub fn remove_change(
+        &mut self,
+        writerGUID: &GUID_t,
+        sequenceNumber: SequenceNumber_t,
+    ) -> Option<CacheChange_t> {
+        let key = (sequenceNumber, *writerGUID);
+        let change = self.changes.remove(&key)?;
+        if let Some(keys) = self.instances.get_mut(&change.instanceHandle) {
+            // The oldest change is the usual one, removed in constant time
+            if keys.front() == Some(&key) {
+                keys.pop_front();
+            } else {
+                keys.retain(|k| *k != key);
+            }
+            if keys.is_empty() {
+                self.instances.remove(&change.instanceHandle);
+            }
+        }
+        self.notify_later(false, &change);
+        Some(change)
+    }
+
+    // The payload is shared with the notification, not copied
+    fn notify_later(&mut self, added: bool, change: &CacheChange_t) {
+        if !self.listeners.is_empty() {
+            let mut notified = CacheChange_t::new();
+            notified.share(change);
+            self.pending.push((added, notified));
+        }
+    }
+
+    /**
+     * @brief Take the notifications of the changes added and removed since the last call.
+     *        The owner of the history calls notify on them after releasing its lock.
+     *
+     * @return The notifications, with the listeners to give them to.
+     */
+    pub fn take_notifications(&mut self) -> HistoryNotifications {
+        HistoryNotifications {
+            listeners: self.listeners.clone(),
+            changes: std::mem::take(&mut self.pending),
+        }
+    }
+
+    pub fn get_change(
+        &self,
+        writerGUID: &GUID_t,
+        sequenceNumber: SequenceNumber_t,
+    ) -> Option<&CacheChange_t> {
+        self.changes.get(&(sequenceNumber, *writerGUID))
+    }
+
+    pub fn get_change_mut(
+        &mut self,
+        writerGUID: &GUID_t,
+        sequenceNumber: SequenceNumber_t,
+    ) -> Option<&mut CacheChange_t> {
+        self.changes.get_mut(&(sequenceNumber, *writerGUID))
+    }
+
+    pub fn get_min_change(&self) -> Option<&CacheChange_t> {
+        self.changes.values().next()
+    }
+
+    pub fn get_max_change(&self) -> Option<&CacheChange_t> {
+        self.changes.values().next_back()
+    }
+
+    pub fn get_seq_num_min(&self) -> Option<SequenceNumber_t> {
+        self.changes.keys().next().map(|key| key.0)
+    }
+
+    pub fn get_seq_num_max(&self) -> Option<SequenceNumber_t> {
+        self.changes.keys().next_back().map(|key| key.0)
+    }
+
+    // Changes in sequence number order
+    pub fn changes(&self) -> impl DoubleEndedIterator<Item = &CacheChange_t> {
+        self.changes.values()
+    }
+
+    // Number of changes of an instance
+    pub fn instance_len(&self, instanceHandle: &InstanceHandle_t) -> usize {
+        self.instances
+            .get(instanceHandle)
+            .map_or(0, |keys| keys.len())
+    }
+
+    // Number of instances with changes in the history
+    pub fn instances_len(&self) -> usize {
+        self.instances.len()
+    }
+
+    // Changes of an instance, oldest first
+    pub fn instance_changes<'a>(
+        &'a self,
+        instanceHandle: &InstanceHandle_t,
+    ) -> impl Iterator<Item = &'a CacheChange_t> + 'a {
+        self.instances
+            .get(instanceHandle)
+            .into_iter()
+            .flatten()
+            .filter_map(move |key| self.changes.get(key))
+    }
+
+    pub fn add_listener(&mut self, listener: Arc<dyn HistoryCacheListener>) {
+        self.listeners.push(listener);
+    }
+
+    pub fn remove_listener(&mut self, listener: &Arc<dyn HistoryCacheListener>) {
+        self.listeners.retain(|l| !Arc::ptr_eq(l, listener));
+    }
+}
+
+fn is_unlimited(limit: i32) -> bool {
+    limit < 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        added: Mutex<Vec<SequenceNumber_t>>,
+        removed: Mutex<Vec<SequenceNumber_t>>,
+    }
+
+    impl HistoryCacheListener for Recorder {
+        fn on_change_added(&self, change: &CacheChange_t) {
+            self.added.lock().unwrap().push(change.sequenceNumber);
+        }
+
+        fn on_change_removed(&self, change: &CacheChange_t) {
+            self.removed.lock().unwrap().push(change.sequenceNumber);
+        }
+    }
+
+    fn unlimited() -> ResourceLimitsQosPolicy {
+        ResourceLimitsQosPolicy {
+            max_samples: -1,
+            max_instances: -1,
+            max_samples_per_instance: -1,
+        }
+    }
+
+    fn change(writer: u8, sn: u64, instance: u8) -> CacheChange_t {
+        let mut change = CacheChange_t::new();
+        change.writerGUID.guidPrefix.value[0] = writer;
+        change.sequenceNumber = SequenceNumber_t::from(sn);
+        change.instanceHandle.value[0] = instance;
+        change
+    }
+
+    fn handle(instance: u8) -> InstanceHandle_t {
+        let mut handle = InstanceHandle_t::new();
+        handle.value[0] = instance;
+        handle
+    }
+
+    #[test]
+    fn ordering_test() {
+        let history = HistoryQosPolicy {
+            kind: HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+            depth: 1,
+        };
+        let mut cache = HistoryCache::new(history, unlimited());
+        assert_eq!(None, cache.get_seq_num_min());
+
+        for (writer, sn) in [(1, 5), (2, 3), (1, 7), (2, 4)] {
+            assert!(cache.add_change(change(writer, sn, 0)).unwrap().is_none());
+        }
+        assert!(cache.add_change(change(1, 5, 0)).is_err());
+        assert_eq!(4, cache.len());
+        assert_eq!(Some(SequenceNumber_t::from(3)), cache.get_seq_num_min());
+        assert_eq!(Some(SequenceNumber_t::from(7)), cache.get_seq_num_max());
+        let order: Vec<u32> = cache.changes().map(|c| c.sequenceNumber.low()).collect();
+        assert_eq!(vec![3, 4, 5, 7], order);
+
+        let writer = change(2, 0, 0).writerGUID;
+        assert!(cache
+            .get_change(&writer, SequenceNumber_t::from(4))
+            .is_some());
+        assert!(cache
+            .get_change(&writer, SequenceNumber_t::from(5))
+            .is_none());
+        assert!(cache
+            .remove_change(&writer, SequenceNumber_t::from(3))
+            .is_some());
+        assert!(cache
+            .remove_change(&writer, SequenceNumber_t::from(3))
+            .is_none());
+        assert_eq!(Some(SequenceNumber_t::from(4)), cache.get_seq_num_min());
+        assert_eq!(3, cache.instance_len(&handle(0)));
+    }
+
+    #[test]
+    fn keep_last_test() {
+        let history = HistoryQosPolicy {
+            kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+            depth: 2,
+        };
+        let mut cache = HistoryCache::new(history, unlimited());
+        let recorder = Arc::new(Recorder::default());
+        let listener: Arc<dyn HistoryCacheListener> = recorder.clone();
+        cache.add_listener(listener.clone());
+
+        cache.add_change(change(1, 1, 1)).unwrap();
+        cache.add_change(change(1, 2, 2)).unwrap();
+        cache.add_change(change(1, 3, 1)).unwrap();
+        let replaced = cache.add_change(change(1, 4, 1)).unwrap().unwrap();
+        assert_eq!(SequenceNumber_t::from(1), replaced.sequenceNumber);
+        // The listeners are only called once the notifications are taken
+        assert!(recorder.added.lock().unwrap().is_empty());
+        cache.take_notifications().notify();
+        assert_eq!(2, cache.instance_len(&handle(1)));
+        assert_eq!(1, cache.instance_len(&handle(2)));
+        assert_eq!(2, cache.instances_len());
+        let instance: Vec<SequenceNumber_t> = cache
+            .instance_changes(&handle(1))
+            .map(|c| c.sequenceNumber)
+            .collect();
+        assert_eq!(
+            vec![SequenceNumber_t::from(3), SequenceNumber_t::from(4)],
+            instance
+        );
+
+        assert_eq!(4, recorder.added.lock().unwrap().len());
+        assert_eq!(
+            vec![SequenceNumber_t::from(1)],
+            *recorder.removed.lock().unwrap()
+        );
+
+        assert_eq!(4, recorder.added.lock().unwrap().len());
+
+        cache.remove_listener(&listener);
+        cache.add_change(change(1, 5, 2)).unwrap();
+        cache.take_notifications().notify();
+        assert_eq!(4, recorder.added.lock().unwrap().len());
+
+        // The depth is limited by max_samples_per_instance
+        let history = HistoryQosPolicy {
+            kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+            depth: 3,
+        };
+        let limits = ResourceLimitsQosPolicy {
+            max_samples_per_instance: 2,
+            ..unlimited()
+        };
+        let mut cache = HistoryCache::new(history, limits);
+        cache.add_change(change(1, 1, 1)).unwrap();
+        cache.add_change(change(1, 2, 1)).unwrap();
+        let replaced = cache.add_change(change(1, 3, 1)).unwrap().unwrap();
+        assert_eq!(SequenceNumber_t::from(1), replaced.sequenceNumber);
+        assert_eq!(2, cache.instance_len(&handle(1)));
+
+        // Removing a change other than the oldest keeps the instance in order
+        let writer = change(1, 0, 0).writerGUID;
+        cache.add_change(change(1, 4, 2)).unwrap();
+        cache.add_change(change(1, 5, 2)).unwrap();
+        cache.remove_change(&writer, SequenceNumber_t::from(5));
+        cache.add_change(change(1, 6, 2)).unwrap();
+        let instance: Vec<SequenceNumber_t> = cache
+            .instance_changes(&handle(2))
+            .map(|c| c.sequenceNumber)
+            .collect();
+        assert_eq!(
+            vec![SequenceNumber_t::from(4), SequenceNumber_t::from(6)],
+            instance
+        );
+    }
+
+    #[test]
+    fn resource_limits_test() {
+        let history = HistoryQosPolicy {
+            kind: HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+            depth: 1,
+        };
+        let limits = ResourceLimitsQosPolicy {
+            max_samples: 3,
+            max_instances: 2,
+            max_samples_per_instance: 2,
+        };
+        let mut cache = HistoryCache::new(history, limits);
+        cache.add_change(change(1, 1, 1)).unwrap();
+        cache.add_change(change(1, 2, 1)).unwrap();
+        assert!(cache.add_change(change(1, 3, 1)).is_err());
+        cache.add_change(change(1, 3, 2)).unwrap();
+        assert!(cache.add_change(change(1, 4, 3)).is_err());
+        assert!(cache.add_change(change(1, 4, 2)).is_err());
+
+        // Removing the last change of an instance frees it
+        let writer = change(1, 0, 0).writerGUID;
+        cache.remove_change(&writer, SequenceNumber_t::from(3));
+        assert_eq!(1, cache.instances_len());
+        cache.add_change(change(1, 4, 3)).unwrap();
+    }
+}
@@ -0,0 +1,415 @@
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+use crate::rtps::common::locator::Locator_t;
+use crate::rtps::common::protocol_version::c_ProtocolVersion;
+use crate::rtps::common::time::Time_t;
+use crate::rtps::common::vendor_id::c_VendorId_Unknown;
+use crate::rtps::messages::message_receiver::*;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, Weak};
+
+lazy_static! {
+    // Endpoints of the participants of this process, by GUID prefix
+    static ref LOCAL_PARTICIPANTS: RwLock<HashMap<GuidPrefix_t, Weak<EndpointRegistry>>> =
+        RwLock::new(HashMap::new());
+}
+
+/**
+ * @brief Let the endpoints of the same process reach the endpoints of a participant
+ *        directly, instead of through the network.
+ *
+ * @param guidPrefix GUID prefix of the participant.
+ * @param endpoints Endpoints of the participant, kept as a weak reference.
+ */
+pub fn register_participant(guidPrefix: GuidPrefix_t, endpoints: &Arc<EndpointRegistry>) {
+    LOCAL_PARTICIPANTS
+        .write()
+        .unwrap()
+        .insert(guidPrefix, Arc::downgrade(endpoints));
+}
+
+// Stop reaching the endpoints of a participant directly, false if they were not registered
+pub fn unregister_participant(
+    guidPrefix: &GuidPrefix_t,
+    endpoints: &Arc<EndpointRegistry>,
+) -> bool {
+    let mut participants = LOCAL_PARTICIPANTS.write().unwrap();
+    // Another participant may have been registered with the same prefix since
+    match participants.get(guidPrefix) {
+        Some(registered) if registered.ptr_eq(&Arc::downgrade(endpoints)) => {
+            participants.remove(guidPrefix);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn local_endpoints(local: &GUID_t, remote: &GUID_t) -> Option<Arc<EndpointRegistry>> {
+    if !local.is_on_same_process_as(remote) {
+        return None;
+    }
+    LOCAL_PARTICIPANTS
+        .read()
+        .unwrap()
+        .get(&remote.guidPrefix)
+        .and_then(Weak::upgrade)
+}
+
+/**
+ * @brief Find a matched reader in the same process as a writer.
+ *
+ * @param writer GUID of the writer.
+ * @param reader GUID of the matched reader.
+ * @return The reader, None if it is not in a participant of this process.
+ */
+pub fn find_local_reader(
+    writer: &GUID_t,
+    reader: &GUID_t,
+) -> Option<Weak<dyn MessageReceiverListener>> {
+    local_endpoints(writer, reader)?
+        .reader(&reader.entityId)
+        .map(|reader| Arc::downgrade(&reader))
+}
+
+/**
+ * @brief Find a matched writer in the same process as a reader.
+ *
+ * @param reader GUID of the reader.
+ * @param writer GUID of the matched writer.
+ * @return The writer, None if it is not in a participant of this process.
+ */
+pub fn find_local_writer(
+    reader: &GUID_t,
+    writer: &GUID_t,
+) -> Option<Weak<dyn MessageReceiverListener>> {
+    local_endpoints(reader, writer)?
+        .writer(&writer.entityId)
+        .map(|writer| Arc::downgrade(&writer))
+}
+
+/**
+ * @brief State of the receiver of the submessages handed over directly between two
+ *        endpoints of this process.
+ *
+ * @param source Participant the submessages come from.
+ * @param destination Participant they are handed to.
+ * @param timestamp Source timestamp of the submessages, if any.
+ * @return The state, as if the submessages were in a message of the source participant.
+ */
+pub fn intraprocess_state(
+    source: GuidPrefix_t,
+    destination: GuidPrefix_t,
+    timestamp: Option<Time_t>,
+) -> ReceiverState {
+    ReceiverState {
+        sourceVersion: c_ProtocolVersion,
+        sourceVendorId: c_VendorId_Unknown,
+        sourceGuidPrefix: source,
+        destGuidPrefix: destination,
+        timestamp,
+        sourceLocator: Locator_t::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dds::core::policy::*;
+    use crate::rtps::behavior::stateful_reader::{ReaderTimes, StatefulReader};
+    use crate::rtps::behavior::stateful_writer::{StatefulWriter, WriterTimes};
+    use crate::rtps::behavior::timed_event::{Clock, ResourceEvent, VirtualClock};
+    use crate::rtps::common::change_kind_t::ChangeKind_t;
+    use crate::rtps::common::entity_id_t::EntityId_t;
+    use crate::rtps::common::instance_handle::InstanceHandle_t;
+    use crate::rtps::common::sequence_number::SequenceNumber_t;
+    use crate::rtps::common::serialized_payload::RepresentationId_t;
+    use crate::rtps::messages::message_header::Header_t;
+    use crate::rtps::structure::endpoint::*;
+    use crate::rtps::structure::reader_history::ReaderHistory;
+    use crate::rtps::structure::rtps_reader::{RTPSReader, ReaderListener};
+    use crate::rtps::structure::rtps_writer::RTPSWriter;
+    use crate::rtps::structure::writer_history::WriterHistory;
+    use crate::rtps::transport::udp_transport::UdpSender;
+    use std::time::{Duration, Instant};
+
+    struct Endpoint;
+
+    impl MessageReceiverListener for Endpoint {}
+
+    // Prefix of a participant of the process of the tests
+    fn prefix(id: u8) -> GuidPrefix_t {
+        let mut prefix = GuidPrefix_t::unknown();
+        prefix.value[..8].copy_from_slice(&[0x1d; 8]);
+        prefix.value[11] = id;
+        prefix
+    }
+
+    fn guid(id: u8, entityKind: u8) -> GUID_t {
+        GUID_t {
+            guidPrefix: prefix(id),
+            entityId: EntityId_t::new_from_key_kind(1, entityKind),
+        }
+    }
+
+    #[test]
+    fn registry_test() {
+        let writerGuid = guid(1, TopicKind_t::NO_KEY.writer_entity_kind());
+        let readerGuid = guid(2, TopicKind_t::NO_KEY.reader_entity_kind());
+        let endpoints = Arc::new(EndpointRegistry::new());
+        endpoints.add_writer(writerGuid.entityId, Arc::new(Endpoint));
+        assert!(find_local_writer(&readerGuid, &writerGuid).is_none());
+
+        register_participant(writerGuid.guidPrefix, &endpoints);
+        assert!(find_local_writer(&readerGuid, &writerGuid).is_some());
+        // Only the endpoints of the participant, in the same process
+        assert!(find_local_reader(&readerGuid, &writerGuid).is_none());
+        let mut remote = readerGuid;
+        remote.guidPrefix.value[0] = 0x2e;
+        assert!(find_local_writer(&remote, &writerGuid).is_none());
+
+        // Another participant with the same prefix does not unregister it
+        let other = Arc::new(EndpointRegistry::new());
+        assert!(!unregister_participant(&writerGuid.guidPrefix, &other));
+        assert!(unregister_participant(&writerGuid.guidPrefix, &endpoints));
+        assert!(find_local_writer(&readerGuid, &writerGuid).is_none());
+    }
+
+    fn writer(guid: GUID_t) -> Arc<StatefulWriter> {
+        let attributes = EndpointAttributes::new(
+            EndpointKind_t::WRITER,
+            TopicKind_t::NO_KEY,
+            ReliabilityKind_t::RELIABLE,
+        );
+        let history = WriterHistory::new(
+            guid,
+            HistoryQosPolicy {
+                kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+                depth: 4,
+            },
+            ResourceLimitsQosPolicy {
+                max_samples: 4,
+                max_instances: -1,
+                max_samples_per_instance: -1,
+            },
+            &ReliabilityQosPolicy {
+                kind: ReliabilityQosPolicyKind::RELIABLE_RELIABILITY_QOS,
+                max_blocking_time: Time_t::c_TimeZero,
+            },
+        );
+        let writer = StatefulWriter::new(
+            guid,
+            attributes,
+            history,
+            Arc::new(UdpSender::new().unwrap()),
+            Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, guid.guidPrefix),
+            WriterTimes::default(),
+        )
+        .unwrap();
+        Arc::new(writer)
+    }
+
+    fn reader(guid: GUID_t) -> Arc<StatefulReader> {
+        let history = ReaderHistory::new(
+            HistoryQosPolicy {
+                kind: HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+                depth: 1,
+            },
+            ResourceLimitsQosPolicy {
+                max_samples: -1,
+                max_instances: -1,
+                max_samples_per_instance: -1,
+            },
+        );
+        let reader = StatefulReader::new(
+            guid,
+            EndpointAttributes::new(
+                EndpointKind_t::READER,
+                TopicKind_t::NO_KEY,
+                ReliabilityKind_t::RELIABLE,
+            ),
+            history,
+            Arc::new(UdpSender::new().unwrap()),
+            Header_t::new(c_ProtocolVersion, c_VendorId_Unknown, guid.guidPrefix),
+            ReaderTimes {
+                heartbeatResponseDelay: Time_t::new(0, 10_000_000),
+            },
+        )
+        .unwrap();
+        Arc::new(reader)
+    }
+
+    fn write(writer: &StatefulWriter) -> SequenceNumber_t {
+        let mut change = writer.new_change(ChangeKind_t::ALIVE, InstanceHandle_t::new());
+        change
+            .serializedPayload
+            .begin_write(RepresentationId_t::CDR_LE, 0);
+        change.serializedPayload.write_bytes(&[1, 2, 3, 4]);
+        writer.add_change(change).unwrap()
+    }
+
+    #[test]
+    fn reliable_delivery_test() {
+        let writerGuid = guid(3, TopicKind_t::NO_KEY.writer_entity_kind());
+        let readerGuid = guid(4, TopicKind_t::NO_KEY.reader_entity_kind());
+        let writer = writer(writerGuid);
+        let reader = reader(readerGuid);
+        let writer_endpoints = Arc::new(EndpointRegistry::new());
+        writer_endpoints.add_writer(writerGuid.entityId, writer.clone());
+        register_participant(writerGuid.guidPrefix, &writer_endpoints);
+        let reader_endpoints = Arc::new(EndpointRegistry::new());
+        reader_endpoints.add_reader(readerGuid.entityId, reader.clone());
+        register_participant(readerGuid.guidPrefix, &reader_endpoints);
+
+        // No locator: every submessage is handed over directly
+        assert!(writer.matched_reader_add(&RemoteEndpointAttributes::new(
+            readerGuid,
+            ReliabilityKind_t::RELIABLE
+        )));
+        assert!(reader.matched_writer_add(&RemoteEndpointAttributes::new(
+            writerGuid,
+            ReliabilityKind_t::RELIABLE
+        )));
+        assert_eq!(
+            Some(true),
+            writer
+                .matched_reader_lookup(&readerGuid)
+                .map(|proxy| proxy.is_local())
+        );
+
+        let first = write(&writer);
+        let second = write(&writer);
+        {
+            let history = reader.history().lock().unwrap();
+            let received: Vec<SequenceNumber_t> = history
+                .cache()
+                .changes()
+                .map(|change| change.sequenceNumber)
+                .collect();
+            assert_eq!(vec![first, second], received);
+            // The reader shares the payload of the writer
+            let change = history.cache().changes().next().unwrap();
+            assert!(writer
+                .history()
+                .with_change(first, |sent| change
+                    .serializedPayload
+                    .shares_buffer_with(&sent.serializedPayload))
+                .unwrap());
+        }
+
+        // The HEARTBEAT handed over with the changes is answered, and the VOLATILE writer
+        // removes the acknowledged changes
+        assert!(!writer.is_acked_by_all(second));
+        let deadline = reader.next_acknack().unwrap();
+        assert!(deadline <= Instant::now() + Duration::from_millis(10));
+        assert_eq!(1, reader.send_acknacks(deadline));
+        assert!(writer.is_acked_by_all(second));
+        assert!(writer.history().is_empty());
+
+        unregister_participant(&writerGuid.guidPrefix, &writer_endpoints);
+        unregister_participant(&readerGuid.guidPrefix, &reader_endpoints);
+    }
+
+    #[test]
+    fn timed_events_test() {
+        let clock = Arc::new(VirtualClock::new());
+        let service = ResourceEvent::new(clock.clone());
+        let writerGuid = guid(7, TopicKind_t::NO_KEY.writer_entity_kind());
+        let readerGuid = guid(8, TopicKind_t::NO_KEY.reader_entity_kind());
+        let writer = writer(writerGuid);
+        let reader = reader(readerGuid);
+        writer.set_timed_events(&service);
+        reader.set_timed_events(&service);
+        let writer_endpoints = Arc::new(EndpointRegistry::new());
+        writer_endpoints.add_writer(writerGuid.entityId, writer.clone());
+        register_participant(writerGuid.guidPrefix, &writer_endpoints);
+        let reader_endpoints = Arc::new(EndpointRegistry::new());
+        reader_endpoints.add_reader(readerGuid.entityId, reader.clone());
+        register_participant(readerGuid.guidPrefix, &reader_endpoints);
+        assert!(writer.matched_reader_add(&RemoteEndpointAttributes::new(
+            readerGuid,
+            ReliabilityKind_t::RELIABLE
+        )));
+        assert!(reader.matched_writer_add(&RemoteEndpointAttributes::new(
+            writerGuid,
+            ReliabilityKind_t::RELIABLE
+        )));
+
+        // The HEARTBEAT handed over with the change is answered once heartbeatResponseDelay
+        // has elapsed, which stops the periodic HEARTBEAT of the writer
+        let sequenceNumber = write(&writer);
+        assert!(!writer.is_acked_by_all(sequenceNumber));
+        assert_eq!(
+            Some(clock.now() + Duration::from_millis(10)),
+            service.next_deadline()
+        );
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(1, service.run_due());
+        assert!(writer.is_acked_by_all(sequenceNumber));
+        assert_eq!(None, service.next_deadline());
+
+        unregister_participant(&writerGuid.guidPrefix, &writer_endpoints);
+        unregister_participant(&readerGuid.guidPrefix, &reader_endpoints);
+    }
+
+    // Writes a change back to the writer when the first change is received
+    struct Echo {
+        writer: Weak<StatefulWriter>,
+    }
+
+    impl ReaderListener for Echo {
+        fn on_new_cache_change_added(
+            &self,
+            _reader: &GUID_t,
+            _writerGUID: &GUID_t,
+            sequenceNumber: SequenceNumber_t,
+        ) {
+            if sequenceNumber == SequenceNumber_t::from(1) {
+                if let Some(writer) = self.writer.upgrade() {
+                    write(&writer);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn listener_write_test() {
+        let writerGuid = guid(5, TopicKind_t::NO_KEY.writer_entity_kind());
+        let readerGuid = guid(6, TopicKind_t::NO_KEY.reader_entity_kind());
+        let writer = writer(writerGuid);
+        let reader = reader(readerGuid);
+        reader.setListener(Some(Arc::new(Echo {
+            writer: Arc::downgrade(&writer),
+        })));
+        let writer_endpoints = Arc::new(EndpointRegistry::new());
+        writer_endpoints.add_writer(writerGuid.entityId, writer.clone());
+        register_participant(writerGuid.guidPrefix, &writer_endpoints);
+        let reader_endpoints = Arc::new(EndpointRegistry::new());
+        reader_endpoints.add_reader(readerGuid.entityId, reader.clone());
+        register_participant(readerGuid.guidPrefix, &reader_endpoints);
+        assert!(writer.matched_reader_add(&RemoteEndpointAttributes::new(
+            readerGuid,
+            ReliabilityKind_t::RELIABLE
+        )));
+        assert!(reader.matched_writer_add(&RemoteEndpointAttributes::new(
+            writerGuid,
+            ReliabilityKind_t::RELIABLE
+        )));
+
+        // Handed over once the writer is unlocked, the listener writes to it right away
+        write(&writer);
+        let history = reader.history().lock().unwrap();
+        let received: Vec<SequenceNumber_t> = history
+            .cache()
+            .changes()
+            .map(|change| change.sequenceNumber)
+            .collect();
+        assert_eq!(
+            vec![SequenceNumber_t::from(1), SequenceNumber_t::from(2)],
+            received
+        );
+        drop(history);
+
+        unregister_participant(&writerGuid.guidPrefix, &writer_endpoints);
+        unregister_participant(&readerGuid.guidPrefix, &reader_endpoints);
+    }
+}
@@ -1 +1,9 @@
 pub mod cache_change;
+pub mod endpoint;
+pub mod history_cache;
+pub mod intraprocess;
+pub mod participant;
+pub mod reader_history;
+pub mod rtps_reader;
+pub mod rtps_writer;
+pub mod writer_history;
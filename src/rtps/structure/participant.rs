@@ -0,0 +1,424 @@
+use crate::rtps::common::entity_id_t::EntityId_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::guid_prefix_t::GuidPrefix_t;
+use crate::rtps::common::locator::Locator_t;
+use crate::rtps::common::locator_list::LocatorList;
+use crate::rtps::common::protocol_version::*;
+use crate::rtps::common::vendor_id::*;
+use crate::rtps::messages::message_header::Header_t;
+use crate::rtps::messages::message_receiver::*;
+use crate::rtps::structure::endpoint::TopicKind_t;
+use crate::rtps::structure::intraprocess;
+use crate::rtps::structure::rtps_reader::RTPSReader;
+use crate::rtps::structure::rtps_writer::RTPSWriter;
+use crate::rtps::transport::udp_transport::*;
+use crate::rtps::utils::ip_locator::fromSocketAddr;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/**
+ * @brief Attributes of an RTPSParticipant.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct RTPSParticipantAttributes {
+    /// Locators the participant receives unicast messages on. An empty list binds an
+    /// ephemeral UDPv4 port on every interface.
+    pub defaultUnicastLocatorList: LocatorList,
+    /// Multicast groups the participant joins
+    pub defaultMulticastLocatorList: LocatorList,
+    pub vendorId: VendorId_t,
+}
+
+/**
+ * @brief RTPS participant, as specified in the DDSI-RTPS 2.5 specification chapter 8.2.4.
+ *        It creates the RTPS writers and readers of the participant, gives them their entity
+ *        ids, and owns the threads receiving the messages sent to its locators, which are
+ *        routed to its endpoints. The threads are stopped when the participant is dropped.
+ *        The writers and readers of the same process matched with its endpoints exchange
+ *        their changes with them directly instead of through the network.
+ */
+pub struct RTPSParticipant {
+    guid: GUID_t,
+    protocolVersion: ProtocolVersion_t,
+    vendorId: VendorId_t,
+    defaultUnicastLocatorList: LocatorList,
+    defaultMulticastLocatorList: LocatorList,
+    // Key of the last user entity created
+    last_entity_key: AtomicU32,
+    endpoints: Arc<EndpointRegistry>,
+    sender: Arc<UdpSender>,
+    running: Arc<AtomicBool>,
+    receive_threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl RTPSParticipant {
+    /**
+     * @brief Create a participant and start receiving on its locators.
+     *
+     * @param guidPrefix GUID prefix of the participant.
+     * @param attributes Attributes of the participant.
+     * @return The participant, an error if one of its locators cannot be opened.
+     *         Locators with port 0 are updated with the port they are bound to.
+     */
+    pub fn new(
+        guidPrefix: GuidPrefix_t,
+        attributes: RTPSParticipantAttributes,
+    ) -> Result<Self, RtpsError> {
+        let mut unicast = attributes.defaultUnicastLocatorList;
+        if unicast.is_empty() {
+            unicast.push_back(Locator_t::new_from_port(0));
+        }
+
+        let mut sockets = Vec::new();
+        let mut defaultUnicastLocatorList = LocatorList::new();
+        for locator in &unicast {
+            let (socket, bound) = open_input_channel(locator)?;
+            defaultUnicastLocatorList.push_back(bound);
+            sockets.push(socket);
+        }
+        let mut defaultMulticastLocatorList = LocatorList::new();
+        for locator in &attributes.defaultMulticastLocatorList {
+            let (socket, bound) = open_input_channel(locator)?;
+            defaultMulticastLocatorList.push_back(bound);
+            sockets.push(socket);
+        }
+
+        let participant = RTPSParticipant {
+            guid: GUID_t {
+                guidPrefix,
+                entityId: EntityId_t::c_EntityId_RTPSParticipant,
+            },
+            protocolVersion: c_ProtocolVersion,
+            vendorId: attributes.vendorId,
+            defaultUnicastLocatorList,
+            defaultMulticastLocatorList,
+            last_entity_key: AtomicU32::new(0),
+            endpoints: Arc::new(EndpointRegistry::new()),
+            sender: Arc::new(UdpSender::new()?),
+            running: Arc::new(AtomicBool::new(true)),
+            receive_threads: Mutex::new(Vec::new()),
+        };
+        for socket in sockets {
+            participant.spawn_receive_thread(socket)?;
+        }
+        intraprocess::register_participant(guidPrefix, &participant.endpoints);
+        Ok(participant)
+    }
+
+    fn spawn_receive_thread(&self, socket: UdpSocket) -> Result<(), RtpsError> {
+        let receiver = MessageReceiver::new(self.guid.guidPrefix, self.endpoints.clone());
+        let running = self.running.clone();
+        let handle = thread::Builder::new()
+            .name("rtps-receive".to_string())
+            .spawn(move || {
+                let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE];
+                while running.load(Ordering::Relaxed) {
+                    // Timeouts let the thread check whether it must stop
+                    if let Ok((length, source)) = socket.recv_from(&mut buffer) {
+                        let mut sourceLocator = Locator_t::default();
+                        fromSocketAddr(true, &source, &mut sourceLocator);
+                        // Invalid messages are dropped
+                        let _ = receiver.process_message(&buffer[..length], &sourceLocator);
+                    }
+                }
+            })?;
+        self.receive_threads.lock().unwrap().push(handle);
+        Ok(())
+    }
+
+    pub fn getGuid(&self) -> &GUID_t {
+        &self.guid
+    }
+
+    pub fn protocolVersion(&self) -> ProtocolVersion_t {
+        self.protocolVersion
+    }
+
+    pub fn vendorId(&self) -> VendorId_t {
+        self.vendorId
+    }
+
+    pub fn defaultUnicastLocatorList(&self) -> &LocatorList {
+        &self.defaultUnicastLocatorList
+    }
+
+    pub fn defaultMulticastLocatorList(&self) -> &LocatorList {
+        &self.defaultMulticastLocatorList
+    }
+
+    // Sockets shared by the writers of the participant
+    pub fn sender(&self) -> &Arc<UdpSender> {
+        &self.sender
+    }
+
+    // Header of the messages sent by the participant
+    pub fn message_header(&self) -> Header_t {
+        Header_t::new(self.protocolVersion, self.vendorId, self.guid.guidPrefix)
+    }
+
+    /**
+     * @brief Get the entity id of a new user entity.
+     *
+     * @param kind Entity kind, one of the EntityId_t::ENTITYKIND_USER_* constants.
+     * @return A new entity id, unique in the participant.
+     */
+    pub fn next_entity_id(&self, kind: u8) -> EntityId_t {
+        let key = self.last_entity_key.fetch_add(1, Ordering::Relaxed) + 1;
+        EntityId_t::new_from_key_kind(key, kind)
+    }
+
+    /**
+     * @brief Create a writer of the participant. Its entity id has the kind of the user writers
+     *        of the topic kind. The submessages sent to the writer are routed to it until it is
+     *        deleted.
+     *
+     * @param topicKind Whether the topic of the writer has a key.
+     * @param build Function building the writer from its GUID.
+     * @return The writer, the error of build if it fails.
+     */
+    pub fn create_writer<W, F>(&self, topicKind: TopicKind_t, build: F) -> Result<Arc<W>, RtpsError>
+    where
+        W: RTPSWriter + 'static,
+        F: FnOnce(GUID_t) -> Result<W, RtpsError>,
+    {
+        let entityId = self.next_entity_id(topicKind.writer_entity_kind());
+        let writer = Arc::new(build(self.endpoint_guid(entityId))?);
+        if writer.topicKind() != topicKind {
+            return Err(RtpsError::new(
+                "Topic kind of the writer does not match its entity id",
+            ));
+        }
+        self.endpoints.add_writer(entityId, writer.clone());
+        Ok(writer)
+    }
+
+    /**
+     * @brief Create a reader of the participant. Its entity id has the kind of the user readers
+     *        of the topic kind. The submessages sent to the reader are routed to it until it is
+     *        deleted.
+     *
+     * @param topicKind Whether the topic of the reader has a key.
+     * @param build Function building the reader from its GUID.
+     * @return The reader, the error of build if it fails.
+     */
+    pub fn create_reader<R, F>(&self, topicKind: TopicKind_t, build: F) -> Result<Arc<R>, RtpsError>
+    where
+        R: RTPSReader + 'static,
+        F: FnOnce(GUID_t) -> Result<R, RtpsError>,
+    {
+        let entityId = self.next_entity_id(topicKind.reader_entity_kind());
+        let reader = Arc::new(build(self.endpoint_guid(entityId))?);
+        if reader.topicKind() != topicKind {
+            return Err(RtpsError::new(
+                "Topic kind of the reader does not match its entity id",
+            ));
+        }
+        self.endpoints.add_reader(entityId, reader.clone());
+        Ok(reader)
+    }
+
+    // Stop routing submessages to a writer, false if it is not a writer of the participant
+    pub fn delete_writer(&self, guid: &GUID_t) -> bool {
+        guid.guidPrefix == self.guid.guidPrefix && self.endpoints.remove_writer(&guid.entityId)
+    }
+
+    // Stop routing submessages to a reader, false if it is not a reader of the participant
+    pub fn delete_reader(&self, guid: &GUID_t) -> bool {
+        guid.guidPrefix == self.guid.guidPrefix && self.endpoints.remove_reader(&guid.entityId)
+    }
+
+    pub fn writers_len(&self) -> usize {
+        self.endpoints.writers_len()
+    }
+
+    pub fn readers_len(&self) -> usize {
+        self.endpoints.readers_len()
+    }
+
+    fn endpoint_guid(&self, entityId: EntityId_t) -> GUID_t {
+        GUID_t {
+            guidPrefix: self.guid.guidPrefix,
+            entityId,
+        }
+    }
+}
+
+impl Drop for RTPSParticipant {
+    fn drop(&mut self) {
+        intraprocess::unregister_participant(&self.guid.guidPrefix, &self.endpoints);
+        self.running.store(false, Ordering::Relaxed);
+        for handle in self.receive_threads.lock().unwrap().drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdr::encoding::Endianness;
+    use crate::dds::core::policy::*;
+    use crate::rtps::common::sequence_number::SequenceNumber_t;
+    use crate::rtps::common::serialized_payload::*;
+    use crate::rtps::messages::submessages::data::DataSubmessage;
+    use crate::rtps::structure::endpoint::*;
+    use crate::rtps::structure::reader_history::ReaderHistory;
+    use crate::rtps::structure::rtps_reader::ReaderListener;
+    use crate::rtps::utils::ip_locator::setIPv4;
+    use std::sync::mpsc::{channel, Sender};
+    use std::time::Duration;
+
+    struct Reader {
+        guid: GUID_t,
+        attributes: EndpointAttributes,
+        history: Mutex<ReaderHistory>,
+        received: Mutex<Sender<(GuidPrefix_t, SequenceNumber_t)>>,
+    }
+
+    impl Reader {
+        fn new(
+            guid: GUID_t,
+            topicKind: TopicKind_t,
+            received: Sender<(GuidPrefix_t, SequenceNumber_t)>,
+        ) -> Self {
+            Reader {
+                guid,
+                attributes: EndpointAttributes::new(
+                    EndpointKind_t::READER,
+                    topicKind,
+                    ReliabilityKind_t::BEST_EFFORT,
+                ),
+                history: Mutex::new(ReaderHistory::new(
+                    HistoryQosPolicy {
+                        kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+                        depth: 1,
+                    },
+                    ResourceLimitsQosPolicy {
+                        max_samples: -1,
+                        max_instances: -1,
+                        max_samples_per_instance: -1,
+                    },
+                )),
+                received: Mutex::new(received),
+            }
+        }
+    }
+
+    impl Endpoint for Reader {
+        fn getGuid(&self) -> &GUID_t {
+            &self.guid
+        }
+
+        fn getAttributes(&self) -> &EndpointAttributes {
+            &self.attributes
+        }
+    }
+
+    impl MessageReceiverListener for Reader {
+        fn on_data(&self, state: &ReceiverState, data: &DataSubmessage) {
+            let _ = self
+                .received
+                .lock()
+                .unwrap()
+                .send((state.sourceGuidPrefix, data.writerSN));
+        }
+    }
+
+    impl RTPSReader for Reader {
+        fn history(&self) -> &Mutex<ReaderHistory> {
+            &self.history
+        }
+
+        fn getListener(&self) -> Option<Arc<dyn ReaderListener>> {
+            None
+        }
+
+        fn setListener(&self, _listener: Option<Arc<dyn ReaderListener>>) {}
+
+        fn matched_writer_add(&self, _writer: &RemoteEndpointAttributes) -> bool {
+            true
+        }
+
+        fn matched_writer_remove(&self, _writerGuid: &GUID_t) -> bool {
+            true
+        }
+
+        fn matched_writer_is_matched(&self, _writerGuid: &GUID_t) -> bool {
+            true
+        }
+    }
+
+    fn new_participant(id: u8) -> RTPSParticipant {
+        let mut prefix = GuidPrefix_t::unknown();
+        prefix.value[0] = id;
+        let mut locator = Locator_t::new_from_port(0);
+        setIPv4(&mut locator, "127.0.0.1").unwrap();
+        RTPSParticipant::new(
+            prefix,
+            RTPSParticipantAttributes {
+                defaultUnicastLocatorList: LocatorList::from(vec![locator]),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn endpoints_test() {
+        let participant = new_participant(1);
+        let (tx, rx) = channel();
+        let reader = participant
+            .create_reader(TopicKind_t::WITH_KEY, |guid| {
+                Ok(Reader::new(guid, TopicKind_t::WITH_KEY, tx))
+            })
+            .unwrap();
+        assert_eq!(participant.getGuid().guidPrefix, reader.guid.guidPrefix);
+        assert_eq!(
+            Some(TopicKind_t::WITH_KEY),
+            TopicKind_t::from_entity_id(&reader.guid.entityId)
+        );
+        assert!(reader.guid.entityId.is_reader());
+        // Entity kind not matching the topic kind of the reader
+        assert!(participant
+            .create_reader(TopicKind_t::NO_KEY, |guid| {
+                Ok(Reader::new(guid, TopicKind_t::WITH_KEY, channel().0))
+            })
+            .is_err());
+        assert_eq!(1, participant.readers_len());
+        let locator = *participant
+            .defaultUnicastLocatorList()
+            .iter()
+            .next()
+            .unwrap();
+        assert_ne!(0, locator.port);
+
+        // Message sent by another participant
+        let other = new_participant(2);
+        let mut payload = SerializedPayload_t::default();
+        payload.begin_write(RepresentationId_t::CDR_LE, 0);
+        payload.write_bytes(&[1, 2, 3, 4]);
+        let mut message = Vec::new();
+        other.message_header().write(&mut message);
+        DataSubmessage::new(
+            Endianness::LITTLE_ENDIANNESS,
+            reader.guid.entityId,
+            other.next_entity_id(TopicKind_t::WITH_KEY.writer_entity_kind()),
+            SequenceNumber_t::from(1),
+            payload,
+        )
+        .write(&mut message)
+        .unwrap();
+        assert!(other.sender().send(&message, &locator));
+
+        let (prefix, sn) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(other.getGuid().guidPrefix, prefix);
+        assert_eq!(SequenceNumber_t::from(1), sn);
+
+        assert!(participant.delete_reader(&reader.guid));
+        assert!(!participant.delete_reader(&reader.guid));
+        assert_eq!(0, participant.readers_len());
+    }
+}
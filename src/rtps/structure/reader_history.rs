@@ -0,0 +1,668 @@
+use crate::dds::core::policy::*;
+use crate::dds::subscription::sample_info::*;
+use crate::rtps::common::change_kind_t::ChangeKind_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::instance_handle::InstanceHandle_t;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::common::serialized_payload::SerializedPayload_t;
+use crate::rtps::common::time::Time_t;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use crate::rtps::structure::history_cache::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+// Value of max_samples returning every matching sample
+pub const LENGTH_UNLIMITED: i32 = -1;
+
+type ChangeKey = (SequenceNumber_t, GUID_t);
+
+#[derive(Clone, Debug)]
+struct InstanceInfo {
+    instance_state: InstanceStateKind,
+    view_state: ViewStateKind,
+    disposed_generation_count: i32,
+    no_writers_generation_count: i32,
+    // Writers that have written the instance and not unregistered it
+    writers: BTreeSet<GUID_t>,
+}
+
+impl InstanceInfo {
+    fn new() -> Self {
+        InstanceInfo {
+            instance_state: InstanceStateKind::ALIVE_INSTANCE_STATE,
+            view_state: ViewStateKind::NEW_VIEW_STATE,
+            disposed_generation_count: 0,
+            no_writers_generation_count: 0,
+            writers: BTreeSet::new(),
+        }
+    }
+
+    fn generation(&self) -> i32 {
+        self.disposed_generation_count + self.no_writers_generation_count
+    }
+
+    // Instance state after receiving a change from a writer
+    fn apply(&mut self, kind: ChangeKind_t, writer: &GUID_t) {
+        match kind {
+            ChangeKind_t::ALIVE => {
+                match self.instance_state {
+                    InstanceStateKind::ALIVE_INSTANCE_STATE => {}
+                    InstanceStateKind::NOT_ALIVE_DISPOSED_INSTANCE_STATE => {
+                        self.disposed_generation_count += 1;
+                        self.view_state = ViewStateKind::NEW_VIEW_STATE;
+                    }
+                    InstanceStateKind::NOT_ALIVE_NO_WRITERS_INSTANCE_STATE => {
+                        self.no_writers_generation_count += 1;
+                        self.view_state = ViewStateKind::NEW_VIEW_STATE;
+                    }
+                }
+                self.instance_state = InstanceStateKind::ALIVE_INSTANCE_STATE;
+                self.writers.insert(*writer);
+            }
+            ChangeKind_t::NOT_ALIVE_DISPOSED => {
+                self.instance_state = InstanceStateKind::NOT_ALIVE_DISPOSED_INSTANCE_STATE;
+            }
+            ChangeKind_t::NOT_ALIVE_UNREGISTERED => self.remove_writer(writer),
+            ChangeKind_t::NOT_ALIVE_DISPOSED_UNREGISTERED => {
+                self.instance_state = InstanceStateKind::NOT_ALIVE_DISPOSED_INSTANCE_STATE;
+                self.remove_writer(writer);
+            }
+        }
+    }
+
+    fn remove_writer(&mut self, writer: &GUID_t) {
+        self.writers.remove(writer);
+        if self.writers.is_empty() && self.instance_state == InstanceStateKind::ALIVE_INSTANCE_STATE
+        {
+            self.instance_state = InstanceStateKind::NOT_ALIVE_NO_WRITERS_INSTANCE_STATE;
+        }
+    }
+}
+
+/**
+ * @brief Sample returned by read and take.
+ */
+#[derive(Debug)]
+pub struct Sample {
+    pub info: SampleInfo,
+    /// Serialized data, empty if the sample has no valid data
+    pub data: SerializedPayload_t,
+}
+
+/**
+ * @brief History of a reader. Besides the changes it keeps the DDS state of the samples and
+ *        instances: the sample state (READ/NOT_READ), the view state (NEW/NOT_NEW) and the
+ *        instance state (ALIVE/NOT_ALIVE_DISPOSED/NOT_ALIVE_NO_WRITERS) with its generation
+ *        counts, as specified in the DDS 1.4 specification chapter 2.2.2.5.1.
+ *        Changes that are not ALIVE are kept as samples without valid data, so the application
+ *        learns about the new instance state.
+ *        Samples are returned in reception order, whatever the writers they come from.
+ */
+pub struct ReaderHistory {
+    cache: HistoryCache,
+    instances: HashMap<InstanceHandle_t, InstanceInfo>,
+    // Reception number and generation counts of the instance when each change was received
+    received: HashMap<ChangeKey, (u64, (i32, i32))>,
+    // Changes by reception number
+    reception_order: BTreeMap<u64, ChangeKey>,
+    last_reception: u64,
+}
+
+impl ReaderHistory {
+    pub fn new(history: HistoryQosPolicy, resource_limits: ResourceLimitsQosPolicy) -> Self {
+        ReaderHistory {
+            cache: HistoryCache::new(history, resource_limits),
+            instances: HashMap::new(),
+            received: HashMap::new(),
+            reception_order: BTreeMap::new(),
+            last_reception: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    // Read access to the changes of the history
+    pub fn cache(&self) -> &HistoryCache {
+        &self.cache
+    }
+
+    /**
+     * @brief Add a change received from a writer and update the state of its instance.
+     *
+     * @param change Received change.
+     * @return An error if the change is already in the history or a resource limit is reached,
+     *         the state of the instance is not changed then.
+     */
+    pub fn received_change(&mut self, mut change: CacheChange_t) -> Result<(), RtpsError> {
+        let mut instance = self
+            .instances
+            .get(&change.instanceHandle)
+            .cloned()
+            .unwrap_or_else(InstanceInfo::new);
+        instance.apply(change.kind, &change.writerGUID);
+
+        let key = (change.sequenceNumber, change.writerGUID);
+        let handle = change.instanceHandle;
+        change.isRead = false;
+        if change.receptionTimestamp == Time_t::c_TimeZero {
+            change.receptionTimestamp = Time_t::now();
+        }
+        if let Some(replaced) = self.cache.add_change(change)? {
+            self.forget(&(replaced.sequenceNumber, replaced.writerGUID));
+        }
+        self.last_reception += 1;
+        self.reception_order.insert(self.last_reception, key);
+        self.received.insert(
+            key,
+            (
+                self.last_reception,
+                (
+                    instance.disposed_generation_count,
+                    instance.no_writers_generation_count,
+                ),
+            ),
+        );
+        self.instances.insert(handle, instance);
+        Ok(())
+    }
+
+    // Forget the reception of a change removed from the history
+    fn forget(&mut self, key: &ChangeKey) {
+        if let Some((reception, _)) = self.received.remove(key) {
+            self.reception_order.remove(&reception);
+        }
+    }
+
+    /**
+     * @brief A writer is no longer alive or matched: the instances it was writing lose it, and
+     *        become NOT_ALIVE_NO_WRITERS when no other writer is left.
+     *
+     * @param writerGUID Writer that is gone.
+     */
+    pub fn writer_removed(&mut self, writerGUID: &GUID_t) {
+        for instance in self.instances.values_mut() {
+            if instance.writers.contains(writerGUID) {
+                instance.remove_writer(writerGUID);
+            }
+        }
+    }
+
+    pub fn instance_state(&self, instanceHandle: &InstanceHandle_t) -> Option<InstanceStateKind> {
+        self.instances
+            .get(instanceHandle)
+            .map(|instance| instance.instance_state)
+    }
+
+    pub fn view_state(&self, instanceHandle: &InstanceHandle_t) -> Option<ViewStateKind> {
+        self.instances
+            .get(instanceHandle)
+            .map(|instance| instance.view_state)
+    }
+
+    // Disposed and no writers generation counts of an instance
+    pub fn generation_counts(&self, instanceHandle: &InstanceHandle_t) -> Option<(i32, i32)> {
+        self.instances.get(instanceHandle).map(|instance| {
+            (
+                instance.disposed_generation_count,
+                instance.no_writers_generation_count,
+            )
+        })
+    }
+
+    /**
+     * @brief Get samples, leaving them in the history marked as read.
+     *
+     * @param max_samples Maximum number of samples, LENGTH_UNLIMITED for every sample.
+     * @param sample_states Sample states of the samples to return.
+     * @param view_states View states of the instances to return.
+     * @param instance_states Instance states of the instances to return.
+     * @return The samples in reception order. Their data shares the buffers of the history.
+     */
+    pub fn read(
+        &mut self,
+        max_samples: i32,
+        sample_states: SampleStateMask,
+        view_states: ViewStateMask,
+        instance_states: InstanceStateMask,
+    ) -> Vec<Sample> {
+        self.get_samples(
+            max_samples,
+            sample_states,
+            view_states,
+            instance_states,
+            false,
+        )
+    }
+
+    /**
+     * @brief Get samples, removing them from the history.
+     *
+     * @param max_samples Maximum number of samples, LENGTH_UNLIMITED for every sample.
+     * @param sample_states Sample states of the samples to return.
+     * @param view_states View states of the instances to return.
+     * @param instance_states Instance states of the instances to return.
+     * @return The samples in reception order.
+     */
+    pub fn take(
+        &mut self,
+        max_samples: i32,
+        sample_states: SampleStateMask,
+        view_states: ViewStateMask,
+        instance_states: InstanceStateMask,
+    ) -> Vec<Sample> {
+        self.get_samples(
+            max_samples,
+            sample_states,
+            view_states,
+            instance_states,
+            true,
+        )
+    }
+
+    fn get_samples(
+        &mut self,
+        max_samples: i32,
+        sample_states: SampleStateMask,
+        view_states: ViewStateMask,
+        instance_states: InstanceStateMask,
+        take: bool,
+    ) -> Vec<Sample> {
+        let instances = &self.instances;
+        let cache = &self.cache;
+        let selected: Vec<(ChangeKey, InstanceHandle_t)> = self
+            .reception_order
+            .values()
+            .filter_map(|key| cache.get_change(&key.1, key.0))
+            .filter(|change| {
+                let instance = &instances[&change.instanceHandle];
+                let sample_state = if change.isRead {
+                    SampleStateKind::READ_SAMPLE_STATE
+                } else {
+                    SampleStateKind::NOT_READ_SAMPLE_STATE
+                };
+                (sample_state as u32 & sample_states) != 0
+                    && (instance.view_state as u32 & view_states) != 0
+                    && (instance.instance_state as u32 & instance_states) != 0
+            })
+            .take(if max_samples < 0 {
+                usize::MAX
+            } else {
+                max_samples as usize
+            })
+            .map(|change| {
+                (
+                    (change.sequenceNumber, change.writerGUID),
+                    change.instanceHandle,
+                )
+            })
+            .collect();
+
+        let generation = |key: &ChangeKey| {
+            let (disposed, no_writers) = self.received[key].1;
+            disposed + no_writers
+        };
+        // Generation of the most recent sample of each instance in the collection
+        let mut most_recent: HashMap<InstanceHandle_t, i32> = HashMap::new();
+        for (key, handle) in &selected {
+            most_recent.insert(*handle, generation(key));
+        }
+
+        let mut infos = Vec::with_capacity(selected.len());
+        for (index, (key, handle)) in selected.iter().enumerate() {
+            let change = self.cache.get_change(&key.1, key.0).unwrap();
+            let instance = &self.instances[handle];
+            let (disposed, no_writers) = self.received[key].1;
+            let mut publication_handle = InstanceHandle_t::new();
+            publication_handle.value[..12].copy_from_slice(&change.writerGUID.guidPrefix.value);
+            publication_handle.value[12..].copy_from_slice(&change.writerGUID.entityId.value);
+            infos.push(SampleInfo {
+                sample_state: if change.isRead {
+                    SampleStateKind::READ_SAMPLE_STATE
+                } else {
+                    SampleStateKind::NOT_READ_SAMPLE_STATE
+                },
+                view_state: instance.view_state,
+                instance_state: instance.instance_state,
+                disposed_generation_count: disposed,
+                no_writers_generation_count: no_writers,
+                sample_rank: selected[index + 1..]
+                    .iter()
+                    .filter(|(_, h)| h == handle)
+                    .count() as i32,
+                generation_rank: most_recent[handle] - (disposed + no_writers),
+                absolute_generation_rank: instance.generation() - (disposed + no_writers),
+                source_timestamp: change.sourceTimestamp,
+                instance_handle: *handle,
+                publication_handle,
+                valid_data: change.kind.is_alive(),
+            });
+        }
+
+        let mut samples = Vec::with_capacity(selected.len());
+        for ((key, handle), info) in selected.into_iter().zip(infos) {
+            let data = if take {
+                self.forget(&key);
+                let mut change = self.cache.remove_change(&key.1, key.0).unwrap();
+                std::mem::take(&mut change.serializedPayload)
+            } else {
+                let change = self.cache.get_change_mut(&key.1, key.0).unwrap();
+                change.isRead = true;
+                let mut data = SerializedPayload_t::default();
+                data.share(&change.serializedPayload);
+                data
+            };
+            if let Some(instance) = self.instances.get_mut(&handle) {
+                instance.view_state = ViewStateKind::NOT_NEW_VIEW_STATE;
+            }
+            samples.push(Sample { info, data });
+        }
+
+        if take {
+            // Forget the instances that can not change any more
+            let cache = &self.cache;
+            self.instances.retain(|handle, instance| {
+                instance.instance_state == InstanceStateKind::ALIVE_INSTANCE_STATE
+                    || !instance.writers.is_empty()
+                    || cache.instance_len(handle) > 0
+            });
+        }
+        samples
+    }
+
+    // Notifications of the changes added and taken, to give once the history is unlocked
+    pub fn take_notifications(&mut self) -> HistoryNotifications {
+        self.cache.take_notifications()
+    }
+
+    pub fn add_listener(&mut self, listener: Arc<dyn HistoryCacheListener>) {
+        self.cache.add_listener(listener);
+    }
+
+    pub fn remove_listener(&mut self, listener: &Arc<dyn HistoryCacheListener>) {
+        self.cache.remove_listener(listener);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> ReaderHistory {
+        ReaderHistory::new(
+            HistoryQosPolicy {
+                kind: HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+                depth: 1,
+            },
+            ResourceLimitsQosPolicy {
+                max_samples: -1,
+                max_instances: -1,
+                max_samples_per_instance: -1,
+            },
+        )
+    }
+
+    fn writer(id: u8) -> GUID_t {
+        let mut guid = GUID_t::unknown();
+        guid.guidPrefix.value[0] = id;
+        guid
+    }
+
+    fn change(writer_id: u8, sn: u64, kind: ChangeKind_t) -> CacheChange_t {
+        let mut change = CacheChange_t::new();
+        change.kind = kind;
+        change.writerGUID = writer(writer_id);
+        change.sequenceNumber = SequenceNumber_t::from(sn);
+        change.instanceHandle.value[0] = 1;
+        // Tells the samples apart
+        change.sourceTimestamp = Time_t::new(sn as i32, 0);
+        change
+    }
+
+    fn handle() -> InstanceHandle_t {
+        change(1, 1, ChangeKind_t::ALIVE).instanceHandle
+    }
+
+    #[test]
+    fn sample_and_view_states_test() {
+        let mut history = history();
+        history
+            .received_change(change(1, 1, ChangeKind_t::ALIVE))
+            .unwrap();
+
+        let samples = history.read(
+            LENGTH_UNLIMITED,
+            ANY_SAMPLE_STATE,
+            ANY_VIEW_STATE,
+            ANY_INSTANCE_STATE,
+        );
+        assert_eq!(1, samples.len());
+        let info = samples[0].info;
+        assert_eq!(SampleStateKind::NOT_READ_SAMPLE_STATE, info.sample_state);
+        assert_eq!(ViewStateKind::NEW_VIEW_STATE, info.view_state);
+        assert_eq!(InstanceStateKind::ALIVE_INSTANCE_STATE, info.instance_state);
+        assert_eq!(
+            writer(1).guidPrefix.value[0],
+            info.publication_handle.value[0]
+        );
+        assert!(info.valid_data);
+
+        // Already read samples are filtered out by the masks
+        let not_read = SampleStateKind::NOT_READ_SAMPLE_STATE as u32;
+        assert!(history
+            .read(
+                LENGTH_UNLIMITED,
+                not_read,
+                ANY_VIEW_STATE,
+                ANY_INSTANCE_STATE
+            )
+            .is_empty());
+        let samples = history.read(1, ANY_SAMPLE_STATE, ANY_VIEW_STATE, ANY_INSTANCE_STATE);
+        assert_eq!(
+            SampleStateKind::READ_SAMPLE_STATE,
+            samples[0].info.sample_state
+        );
+        assert_eq!(
+            ViewStateKind::NOT_NEW_VIEW_STATE,
+            samples[0].info.view_state
+        );
+
+        history
+            .received_change(change(1, 2, ChangeKind_t::ALIVE))
+            .unwrap();
+        let samples = history.take(
+            LENGTH_UNLIMITED,
+            not_read,
+            ANY_VIEW_STATE,
+            ANY_INSTANCE_STATE,
+        );
+        assert_eq!(1, samples.len());
+        assert_eq!(
+            SequenceNumber_t::from(1),
+            history.cache().get_seq_num_min().unwrap()
+        );
+        assert_eq!(1, history.len());
+    }
+
+    #[test]
+    fn instance_states_test() {
+        let mut history = history();
+        history
+            .received_change(change(1, 1, ChangeKind_t::ALIVE))
+            .unwrap();
+        history
+            .received_change(change(2, 1, ChangeKind_t::ALIVE))
+            .unwrap();
+        history
+            .received_change(change(1, 2, ChangeKind_t::NOT_ALIVE_DISPOSED))
+            .unwrap();
+        assert_eq!(
+            Some(InstanceStateKind::NOT_ALIVE_DISPOSED_INSTANCE_STATE),
+            history.instance_state(&handle())
+        );
+        let alive = InstanceStateKind::ALIVE_INSTANCE_STATE as u32;
+        assert!(history
+            .read(LENGTH_UNLIMITED, ANY_SAMPLE_STATE, ANY_VIEW_STATE, alive)
+            .is_empty());
+        let samples = history.read(
+            LENGTH_UNLIMITED,
+            ANY_SAMPLE_STATE,
+            ANY_VIEW_STATE,
+            NOT_ALIVE_INSTANCE_STATE,
+        );
+        assert_eq!(3, samples.len());
+        assert!(!samples[2].info.valid_data);
+
+        // The instance is alive again
+        history
+            .received_change(change(2, 2, ChangeKind_t::ALIVE))
+            .unwrap();
+        assert_eq!(Some((1, 0)), history.generation_counts(&handle()));
+        assert_eq!(
+            Some(ViewStateKind::NEW_VIEW_STATE),
+            history.view_state(&handle())
+        );
+        let samples = history.read(
+            LENGTH_UNLIMITED,
+            ANY_SAMPLE_STATE,
+            ANY_VIEW_STATE,
+            ANY_INSTANCE_STATE,
+        );
+        let ranks: Vec<(i32, i32, i32)> = samples
+            .iter()
+            .map(|s| {
+                (
+                    s.info.sample_rank,
+                    s.info.generation_rank,
+                    s.info.absolute_generation_rank,
+                )
+            })
+            .collect();
+        assert_eq!(vec![(3, 1, 1), (2, 1, 1), (1, 1, 1), (0, 0, 0)], ranks);
+
+        // Unregistered by every writer
+        history
+            .received_change(change(2, 3, ChangeKind_t::NOT_ALIVE_UNREGISTERED))
+            .unwrap();
+        assert_eq!(
+            Some(InstanceStateKind::ALIVE_INSTANCE_STATE),
+            history.instance_state(&handle())
+        );
+        history.writer_removed(&writer(1));
+        assert_eq!(
+            Some(InstanceStateKind::NOT_ALIVE_NO_WRITERS_INSTANCE_STATE),
+            history.instance_state(&handle())
+        );
+        history
+            .received_change(change(1, 3, ChangeKind_t::ALIVE))
+            .unwrap();
+        assert_eq!(Some((1, 1)), history.generation_counts(&handle()));
+
+        // The instance is forgotten once it has no samples nor writers
+        history
+            .received_change(change(1, 4, ChangeKind_t::NOT_ALIVE_DISPOSED_UNREGISTERED))
+            .unwrap();
+        history.take(
+            LENGTH_UNLIMITED,
+            ANY_SAMPLE_STATE,
+            ANY_VIEW_STATE,
+            ANY_INSTANCE_STATE,
+        );
+        assert!(history.is_empty());
+        assert_eq!(None, history.instance_state(&handle()));
+    }
+
+    // Writer and sequence number of the samples, given by their source timestamp
+    fn received(samples: &[Sample]) -> Vec<(u8, i32)> {
+        samples
+            .iter()
+            .map(|s| {
+                (
+                    s.info.publication_handle.value[0],
+                    s.info.source_timestamp.seconds,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reception_order_test() {
+        let mut history = history();
+        for (writer_id, sn) in [(1, 5), (2, 3), (1, 6), (2, 4)] {
+            history
+                .received_change(change(writer_id, sn, ChangeKind_t::ALIVE))
+                .unwrap();
+        }
+
+        // By reception, not by the sequence numbers of each writer
+        let samples = history.read(
+            LENGTH_UNLIMITED,
+            ANY_SAMPLE_STATE,
+            ANY_VIEW_STATE,
+            ANY_INSTANCE_STATE,
+        );
+        assert_eq!(vec![(1, 5), (2, 3), (1, 6), (2, 4)], received(&samples));
+        let ranks: Vec<i32> = samples.iter().map(|s| s.info.sample_rank).collect();
+        assert_eq!(vec![3, 2, 1, 0], ranks);
+
+        let samples = history.take(2, ANY_SAMPLE_STATE, ANY_VIEW_STATE, ANY_INSTANCE_STATE);
+        assert_eq!(vec![(1, 5), (2, 3)], received(&samples));
+        history
+            .received_change(change(2, 1, ChangeKind_t::ALIVE))
+            .unwrap();
+        let samples = history.take(
+            LENGTH_UNLIMITED,
+            ANY_SAMPLE_STATE,
+            ANY_VIEW_STATE,
+            ANY_INSTANCE_STATE,
+        );
+        assert_eq!(vec![(1, 6), (2, 4), (2, 1)], received(&samples));
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn keep_last_test() {
+        let mut history = ReaderHistory::new(
+            HistoryQosPolicy {
+                kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+                depth: 1,
+            },
+            ResourceLimitsQosPolicy {
+                max_samples: 2,
+                max_instances: -1,
+                max_samples_per_instance: -1,
+            },
+        );
+        let mut other = change(2, 1, ChangeKind_t::ALIVE);
+        other.instanceHandle.value[0] = 2;
+        history
+            .received_change(change(1, 1, ChangeKind_t::ALIVE))
+            .unwrap();
+        history.received_change(other).unwrap();
+        // Replaces the first sample of its instance
+        history
+            .received_change(change(1, 2, ChangeKind_t::ALIVE))
+            .unwrap();
+        let samples = history.read(
+            LENGTH_UNLIMITED,
+            ANY_SAMPLE_STATE,
+            ANY_VIEW_STATE,
+            ANY_INSTANCE_STATE,
+        );
+        assert_eq!(vec![(2, 1), (1, 2)], received(&samples));
+
+        // A rejected change does not create its instance
+        let mut rejected = change(3, 1, ChangeKind_t::NOT_ALIVE_DISPOSED);
+        rejected.instanceHandle.value[0] = 3;
+        let rejected_handle = rejected.instanceHandle;
+        assert!(history.received_change(rejected).is_err());
+        assert_eq!(None, history.instance_state(&rejected_handle));
+        assert_eq!(2, history.len());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::messages::message_receiver::MessageReceiverListener;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use crate::rtps::structure::endpoint::*;
+use crate::rtps::structure::reader_history::ReaderHistory;
+use std::sync::{Arc, Mutex};
+
+/**
+ * @brief Notifications of an RTPS reader. They are called from the receive threads, without
+ *        the history locked.
+ */
+pub trait ReaderListener: Send + Sync {
+    /**
+     * @brief A change was added to the history of the reader.
+     *
+     * @param reader GUID of the reader.
+     * @param writerGUID Writer of the change.
+     * @param sequenceNumber Sequence number of the change.
+     */
+    fn on_new_cache_change_added(
+        &self,
+        _reader: &GUID_t,
+        _writerGUID: &GUID_t,
+        _sequenceNumber: SequenceNumber_t,
+    ) {
+    }
+
+    // Changes of a writer will never be received
+    fn on_sample_lost(&self, _reader: &GUID_t, _writerGUID: &GUID_t, _count: i32) {}
+
+    // A writer was matched or unmatched
+    fn on_reader_matched(&self, _reader: &GUID_t, _writerGUID: &GUID_t, _matched: bool) {}
+}
+
+/**
+ * @brief RTPS reader, as specified in the DDSI-RTPS 2.5 specification chapter 8.4.10.
+ *        The stateless and stateful behaviors implement it, so the DDS layer uses either
+ *        through this trait. The submessages sent to the reader, such as DATA, are received
+ *        through MessageReceiverListener.
+ */
+pub trait RTPSReader: Endpoint + MessageReceiverListener {
+    fn history(&self) -> &Mutex<ReaderHistory>;
+
+    fn getListener(&self) -> Option<Arc<dyn ReaderListener>>;
+
+    fn setListener(&self, listener: Option<Arc<dyn ReaderListener>>);
+
+    // Whether the writers must send the inline QoS with every DATA
+    fn expectsInlineQos(&self) -> bool {
+        false
+    }
+
+    /**
+     * @brief Start receiving the changes of a remote writer.
+     *
+     * @param writer Matched writer.
+     * @return False if the writer was already matched or is not compatible.
+     */
+    fn matched_writer_add(&self, writer: &RemoteEndpointAttributes) -> bool;
+
+    // Stop receiving the changes of a remote writer, false if it was not matched
+    fn matched_writer_remove(&self, writerGuid: &GUID_t) -> bool;
+
+    fn matched_writer_is_matched(&self, writerGuid: &GUID_t) -> bool;
+
+    /**
+     * @brief Add a received change to the history and notify the listener.
+     *
+     * @param change Change to deliver.
+     * @return The error of the history if the change cannot be added.
+     */
+    fn deliver_change(&self, change: CacheChange_t) -> Result<(), RtpsError> {
+        let writerGUID = change.writerGUID;
+        let sequenceNumber = change.sequenceNumber;
+        // The listeners of the history are called once it is unlocked
+        let (received, notifications) = {
+            let mut history = self.history().lock().unwrap();
+            let received = history.received_change(change);
+            (received, history.take_notifications())
+        };
+        notifications.notify();
+        received?;
+        if let Some(listener) = self.getListener() {
+            listener.on_new_cache_change_added(self.getGuid(), &writerGUID, sequenceNumber);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use crate::rtps::common::change_kind_t::ChangeKind_t;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::instance_handle::InstanceHandle_t;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::messages::message_receiver::MessageReceiverListener;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use crate::rtps::structure::endpoint::*;
+use crate::rtps::structure::writer_history::WriterHistory;
+
+/**
+ * @brief RTPS writer, as specified in the DDSI-RTPS 2.5 specification chapter 8.4.7.
+ *        The stateless and stateful behaviors implement it, so the DDS layer uses either
+ *        through this trait. The submessages sent to the writer, such as ACKNACK, are received
+ *        through MessageReceiverListener.
+ */
+pub trait RTPSWriter: Endpoint + MessageReceiverListener {
+    fn history(&self) -> &WriterHistory;
+
+    /**
+     * @brief Create a change of this writer. Its payload is filled by the caller before adding
+     *        it with add_change. With a payload pool, the payload starts with a buffer of the
+     *        pool, which goes back to it once the change is dropped.
+     *
+     * @param kind Kind of change.
+     * @param handle Instance of the change.
+     * @return The change, without sequence number.
+     */
+    fn new_change(&self, kind: ChangeKind_t, handle: InstanceHandle_t) -> CacheChange_t {
+        let mut change = CacheChange_t::new();
+        change.kind = kind;
+        change.writerGUID = *self.getGuid();
+        change.instanceHandle = handle;
+        if let Some(pool) = &self.getAttributes().payloadPool {
+            // An exhausted pool leaves the payload to plain allocations
+            pool.get_payload(
+                pool.config().payload_initial_size,
+                &mut change.serializedPayload,
+            );
+        }
+        change
+    }
+
+    /**
+     * @brief Add a change to the history and send it to the matched readers.
+     *        A full KEEP_ALL history makes room by removing its oldest change once it is
+     *        acknowledged by every reliable reader.
+     *
+     * @param change Change created by new_change.
+     * @return The sequence number of the change, the error of the history if it is full.
+     */
+    fn add_change(&self, change: CacheChange_t) -> Result<SequenceNumber_t, RtpsError> {
+        let sequenceNumber = self
+            .history()
+            .add_change_removing(change, |sn| self.is_acked_by_all(sn))?;
+        self.unsent_change_added_to_history(sequenceNumber);
+        Ok(sequenceNumber)
+    }
+
+    // Called when a change is added to the history, to send it
+    fn unsent_change_added_to_history(&self, sequenceNumber: SequenceNumber_t);
+
+    /**
+     * @brief Start sending the changes to a remote reader.
+     *
+     * @param reader Matched reader.
+     * @return False if the reader was already matched or is not compatible.
+     */
+    fn matched_reader_add(&self, reader: &RemoteEndpointAttributes) -> bool;
+
+    // Stop sending changes to a remote reader, false if it was not matched
+    fn matched_reader_remove(&self, readerGuid: &GUID_t) -> bool;
+
+    fn matched_reader_is_matched(&self, readerGuid: &GUID_t) -> bool;
+
+    // Whether every matched reliable reader has acknowledged a change
+    fn is_acked_by_all(&self, sequenceNumber: SequenceNumber_t) -> bool;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dds::core::policy::*;
+    use crate::rtps::common::entity_id_t::EntityId_t;
+    use crate::rtps::common::payload_pool::{PayloadPool, PoolConfig};
+    use crate::rtps::common::serialized_payload::RepresentationId_t;
+    use crate::rtps::common::time::Time_t;
+    use std::collections::BTreeSet;
+    use std::sync::{Arc, Mutex};
+
+    struct TestWriter {
+        guid: GUID_t,
+        attributes: EndpointAttributes,
+        history: WriterHistory,
+        unsent: Mutex<Vec<SequenceNumber_t>>,
+        readers: Mutex<BTreeSet<GUID_t>>,
+    }
+
+    impl Endpoint for TestWriter {
+        fn getGuid(&self) -> &GUID_t {
+            &self.guid
+        }
+
+        fn getAttributes(&self) -> &EndpointAttributes {
+            &self.attributes
+        }
+    }
+
+    impl MessageReceiverListener for TestWriter {}
+
+    impl RTPSWriter for TestWriter {
+        fn history(&self) -> &WriterHistory {
+            &self.history
+        }
+
+        fn unsent_change_added_to_history(&self, sequenceNumber: SequenceNumber_t) {
+            self.unsent.lock().unwrap().push(sequenceNumber);
+        }
+
+        fn matched_reader_add(&self, reader: &RemoteEndpointAttributes) -> bool {
+            self.readers.lock().unwrap().insert(reader.guid)
+        }
+
+        fn matched_reader_remove(&self, readerGuid: &GUID_t) -> bool {
+            self.readers.lock().unwrap().remove(readerGuid)
+        }
+
+        fn matched_reader_is_matched(&self, readerGuid: &GUID_t) -> bool {
+            self.readers.lock().unwrap().contains(readerGuid)
+        }
+
+        fn is_acked_by_all(&self, _sequenceNumber: SequenceNumber_t) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn new_change_test() {
+        let mut guid = GUID_t::unknown();
+        guid.entityId =
+            EntityId_t::new_from_key_kind(1, TopicKind_t::WITH_KEY.writer_entity_kind());
+        let test_writer = TestWriter {
+            guid,
+            attributes: EndpointAttributes::new(
+                EndpointKind_t::WRITER,
+                TopicKind_t::WITH_KEY,
+                ReliabilityKind_t::BEST_EFFORT,
+            ),
+            history: WriterHistory::new(
+                guid,
+                HistoryQosPolicy {
+                    kind: HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+                    depth: 1,
+                },
+                ResourceLimitsQosPolicy {
+                    max_samples: -1,
+                    max_instances: -1,
+                    max_samples_per_instance: -1,
+                },
+                &ReliabilityQosPolicy {
+                    kind: ReliabilityQosPolicyKind::BEST_EFFORT_RELIABILITY_QOS,
+                    max_blocking_time: Time_t::c_TimeZero,
+                },
+            ),
+            unsent: Mutex::new(Vec::new()),
+            readers: Mutex::new(BTreeSet::new()),
+        };
+        let writer: &dyn RTPSWriter = &test_writer;
+        assert_eq!(TopicKind_t::WITH_KEY, writer.topicKind());
+        assert_eq!(ReliabilityKind_t::BEST_EFFORT, writer.reliabilityLevel());
+
+        let mut handle = InstanceHandle_t::new();
+        handle.value[0] = 1;
+        let change = writer.new_change(ChangeKind_t::NOT_ALIVE_DISPOSED, handle);
+        assert_eq!(guid, change.writerGUID);
+        assert_eq!(handle, change.instanceHandle);
+        let sn = writer.add_change(change).unwrap();
+        assert_eq!(SequenceNumber_t::from(1), sn);
+        assert_eq!(vec![sn], *test_writer.unsent.lock().unwrap());
+        assert_eq!(
+            Some(ChangeKind_t::NOT_ALIVE_DISPOSED),
+            writer.history().with_change(sn, |c| c.kind)
+        );
+
+        let reader = RemoteEndpointAttributes::new(GUID_t::unknown(), ReliabilityKind_t::RELIABLE);
+        assert!(writer.matched_reader_add(&reader));
+        assert!(!writer.matched_reader_add(&reader));
+        assert!(writer.matched_reader_is_matched(&reader.guid));
+        assert!(writer.matched_reader_remove(&reader.guid));
+
+        // The changes take their payload from the pool and give it back when removed
+        let pool = Arc::new(PayloadPool::new(PoolConfig::default()));
+        let mut attributes = test_writer.attributes.clone();
+        attributes.payloadPool = Some(pool.clone());
+        let pooled_writer = TestWriter {
+            attributes,
+            ..test_writer
+        };
+        let mut change = pooled_writer.new_change(ChangeKind_t::ALIVE, handle);
+        assert_eq!(512, change.serializedPayload.max_size());
+        change
+            .serializedPayload
+            .begin_write(RepresentationId_t::CDR_LE, 0);
+        change.serializedPayload.write_bytes(&[1, 2, 3, 4]);
+        let sn = pooled_writer.add_change(change).unwrap();
+        assert_eq!(1, pool.outstanding());
+        assert!(pooled_writer.history().remove_change(sn).is_some());
+        assert_eq!(0, pool.outstanding());
+        assert_eq!(1, pool.free_count());
+    }
+}
@@ -0,0 +1,488 @@
+use crate::dds::core::policy::*;
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::guid::GUID_t;
+use crate::rtps::common::sequence_number::SequenceNumber_t;
+use crate::rtps::common::time::Time_t;
+use crate::rtps::structure::cache_change::CacheChange_t;
+use crate::rtps::structure::history_cache::*;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Instant;
+
+struct WriterHistoryState {
+    cache: HistoryCache,
+    last_sequence_number: SequenceNumber_t,
+    // Incremented when readers acknowledge changes
+    acknowledgements: u64,
+}
+
+/**
+ * @brief History of a writer. It assigns the sequence numbers of the changes and enforces the
+ *        HISTORY and RESOURCE_LIMITS policies.
+ *        KEEP_LAST histories replace the oldest change of the instance, or the oldest change
+ *        of the history when max_samples is reached. KEEP_ALL histories block the writer up to
+ *        the max_blocking_time of the RELIABILITY policy until changes are removed, or until
+ *        the oldest change is no longer needed by the writer, usually once every reliable
+ *        reader has acknowledged it.
+ *        The history is shared between the application and the RTPS writer, its listeners are
+ *        called once the history is unlocked.
+ */
+pub struct WriterHistory {
+    writerGUID: GUID_t,
+    max_blocking_time: Time_t,
+    state: Mutex<WriterHistoryState>,
+    // Signaled when changes are removed
+    space_available: Condvar,
+}
+
+impl WriterHistory {
+    pub fn new(
+        writerGUID: GUID_t,
+        history: HistoryQosPolicy,
+        resource_limits: ResourceLimitsQosPolicy,
+        reliability: &ReliabilityQosPolicy,
+    ) -> Self {
+        WriterHistory {
+            writerGUID,
+            max_blocking_time: reliability.max_blocking_time,
+            state: Mutex::new(WriterHistoryState {
+                cache: HistoryCache::new(history, resource_limits),
+                last_sequence_number: SequenceNumber_t::new(0, 0),
+                acknowledgements: 0,
+            }),
+            space_available: Condvar::new(),
+        }
+    }
+
+    pub fn writerGUID(&self) -> &GUID_t {
+        &self.writerGUID
+    }
+
+    // Sequence number of the last change added, 0 if none
+    pub fn last_sequence_number(&self) -> SequenceNumber_t {
+        self.state.lock().unwrap().last_sequence_number
+    }
+
+    /**
+     * @brief Add a change written by the application.
+     *        The change gets the next sequence number and the writer GUID. Its source timestamp
+     *        is set to the current time if it has none.
+     *
+     * @param change Change to add.
+     * @return The sequence number of the change. An error if max_instances is reached, or if
+     *         a KEEP_ALL history is still full after max_blocking_time.
+     */
+    pub fn add_change(&self, change: CacheChange_t) -> Result<SequenceNumber_t, RtpsError> {
+        self.add_change_removing(change, |_| false)
+    }
+
+    /**
+     * @brief Add a change written by the application, making room in a full KEEP_ALL history
+     *        by removing its oldest change, or the oldest change of the instance, once the
+     *        writer no longer needs it.
+     *
+     * @param change Change to add.
+     * @param is_removable Whether the writer no longer needs a change, called without the
+     *        history locked. Asked again when changes_acknowledged is called.
+     * @return The sequence number of the change. An error if max_instances is reached, or if
+     *         a KEEP_ALL history is still full after max_blocking_time.
+     */
+    pub fn add_change_removing(
+        &self,
+        mut change: CacheChange_t,
+        is_removable: impl Fn(SequenceNumber_t) -> bool,
+    ) -> Result<SequenceNumber_t, RtpsError> {
+        let deadline = self
+            .max_blocking_time
+            .to_duration()
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let cache = &state.cache;
+            let limits = cache.resource_limits().clone();
+            let instance_len = cache.instance_len(&change.instanceHandle);
+            if instance_len == 0
+                && limits.max_instances >= 0
+                && cache.instances_len() >= limits.max_instances as usize
+            {
+                return self.unlock(
+                    state,
+                    Err(RtpsError::new("Maximum number of instances reached")),
+                );
+            }
+
+            let oldest = match cache.history().kind {
+                HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS => None,
+                HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS => {
+                    if limits.max_samples >= 0 && cache.len() >= limits.max_samples as usize {
+                        cache.get_seq_num_min()
+                    } else if limits.max_samples_per_instance >= 0
+                        && instance_len >= limits.max_samples_per_instance as usize
+                    {
+                        cache
+                            .instance_changes(&change.instanceHandle)
+                            .next()
+                            .map(|oldest| oldest.sequenceNumber)
+                    } else {
+                        None
+                    }
+                }
+            };
+            let oldest = match oldest {
+                Some(oldest) => oldest,
+                None => break,
+            };
+
+            // The writer may lock itself to answer, the history must not be locked meanwhile
+            let acknowledgements = state.acknowledgements;
+            drop(state);
+            let removable = is_removable(oldest);
+            state = self.state.lock().unwrap();
+            if removable {
+                state.cache.remove_change(&self.writerGUID, oldest);
+                continue;
+            }
+            if state.acknowledgements != acknowledgements {
+                continue;
+            }
+
+            state = match deadline {
+                None => self.space_available.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return self.unlock(
+                            state,
+                            Err(RtpsError::new("Timeout waiting for space in the history")),
+                        );
+                    }
+                    self.space_available
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0
+                }
+            };
+        }
+
+        // KEEP_LAST: make room by removing the oldest change of the history
+        let cache = &mut state.cache;
+        let max_samples = cache.resource_limits().max_samples;
+        let depth = std::cmp::max(cache.history().depth, 1) as usize;
+        if max_samples >= 0
+            && cache.len() >= max_samples as usize
+            && cache.instance_len(&change.instanceHandle) < depth
+        {
+            if let Some(sn) = cache.get_seq_num_min() {
+                cache.remove_change(&self.writerGUID, sn);
+            }
+        }
+
+        let sequence_number = state.last_sequence_number + 1;
+        change.sequenceNumber = sequence_number;
+        change.writerGUID = self.writerGUID;
+        if change.sourceTimestamp == Time_t::c_TimeZero {
+            change.sourceTimestamp = Time_t::now();
+        }
+        let added = state.cache.add_change(change).map(|_| sequence_number);
+        if added.is_ok() {
+            state.last_sequence_number = sequence_number;
+        }
+        self.unlock(state, added)
+    }
+
+    // Release the history, then call its listeners with the changes added and removed
+    fn unlock<R>(&self, mut state: MutexGuard<WriterHistoryState>, result: R) -> R {
+        let notifications = state.cache.take_notifications();
+        drop(state);
+        notifications.notify();
+        result
+    }
+
+    /**
+     * @brief Remove a change, waking up the writes waiting for space.
+     *
+     * @param sequenceNumber Sequence number of the change.
+     * @return The removed change, None if it is not in the history.
+     */
+    pub fn remove_change(&self, sequenceNumber: SequenceNumber_t) -> Option<CacheChange_t> {
+        let mut state = self.state.lock().unwrap();
+        let removed = state.cache.remove_change(&self.writerGUID, sequenceNumber);
+        if removed.is_some() {
+            self.space_available.notify_all();
+        }
+        self.unlock(state, removed)
+    }
+
+    // Wake up the writes waiting for the changes of a full history to be acknowledged
+    pub fn changes_acknowledged(&self) {
+        self.state.lock().unwrap().acknowledgements += 1;
+        self.space_available.notify_all();
+    }
+
+    // Remove the oldest change of the history
+    pub fn remove_min_change(&self) -> Option<CacheChange_t> {
+        let sn = self.get_seq_num_min()?;
+        self.remove_change(sn)
+    }
+
+    /**
+     * @brief Remove every change up to a sequence number, such as the changes acknowledged by
+     *        every reliable reader.
+     *
+     * @param sequenceNumber Last sequence number to remove, included.
+     * @return The number of removed changes.
+     */
+    pub fn remove_changes_up_to(&self, sequenceNumber: SequenceNumber_t) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let mut removed = 0;
+        while let Some(sn) = state.cache.get_seq_num_min() {
+            if sn > sequenceNumber {
+                break;
+            }
+            state.cache.remove_change(&self.writerGUID, sn);
+            removed += 1;
+        }
+        if removed > 0 {
+            self.space_available.notify_all();
+        }
+        self.unlock(state, removed)
+    }
+
+    pub fn get_seq_num_min(&self) -> Option<SequenceNumber_t> {
+        self.state.lock().unwrap().cache.get_seq_num_min()
+    }
+
+    pub fn get_seq_num_max(&self) -> Option<SequenceNumber_t> {
+        self.state.lock().unwrap().cache.get_seq_num_max()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * @brief Access a change of the history.
+     *
+     * @param sequenceNumber Sequence number of the change.
+     * @param f Function called with the change, while the history is locked.
+     * @return The result of f, None if the change is not in the history.
+     */
+    pub fn with_change<R>(
+        &self,
+        sequenceNumber: SequenceNumber_t,
+        f: impl FnOnce(&CacheChange_t) -> R,
+    ) -> Option<R> {
+        let state = self.state.lock().unwrap();
+        state
+            .cache
+            .get_change(&self.writerGUID, sequenceNumber)
+            .map(f)
+    }
+
+    // Read access to the whole history, while it is locked
+    pub fn with_cache<R>(&self, f: impl FnOnce(&HistoryCache) -> R) -> R {
+        f(&self.state.lock().unwrap().cache)
+    }
+
+    pub fn add_listener(&self, listener: Arc<dyn HistoryCacheListener>) {
+        self.state.lock().unwrap().cache.add_listener(listener);
+    }
+
+    pub fn remove_listener(&self, listener: &Arc<dyn HistoryCacheListener>) {
+        self.state.lock().unwrap().cache.remove_listener(listener);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtps::common::instance_handle::InstanceHandle_t;
+    use std::thread;
+    use std::time::Duration;
+
+    fn history(
+        kind: HistoryQosPolicyKind,
+        depth: i32,
+        max_samples: i32,
+        max_blocking_time: Time_t,
+    ) -> WriterHistory {
+        WriterHistory::new(
+            GUID_t::unknown(),
+            HistoryQosPolicy { kind, depth },
+            ResourceLimitsQosPolicy {
+                max_samples,
+                max_instances: 2,
+                max_samples_per_instance: -1,
+            },
+            &ReliabilityQosPolicy {
+                kind: ReliabilityQosPolicyKind::RELIABLE_RELIABILITY_QOS,
+                max_blocking_time,
+            },
+        )
+    }
+
+    fn change(instance: u8) -> CacheChange_t {
+        let mut change = CacheChange_t::new();
+        change.instanceHandle = InstanceHandle_t::new();
+        change.instanceHandle.value[0] = instance;
+        change
+    }
+
+    #[test]
+    fn keep_last_test() {
+        let history = history(
+            HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+            2,
+            3,
+            Time_t::c_TimeZero,
+        );
+        for n in 1..=3 {
+            assert_eq!(
+                SequenceNumber_t::from(n),
+                history.add_change(change(1)).unwrap()
+            );
+        }
+        // Depth of the instance
+        assert_eq!(2, history.len());
+        assert_eq!(Some(SequenceNumber_t::from(2)), history.get_seq_num_min());
+
+        history.add_change(change(2)).unwrap();
+        // max_samples
+        history.add_change(change(2)).unwrap();
+        assert_eq!(3, history.len());
+        assert_eq!(Some(SequenceNumber_t::from(3)), history.get_seq_num_min());
+        // max_instances
+        assert!(history.add_change(change(3)).is_err());
+        assert_eq!(SequenceNumber_t::from(5), history.last_sequence_number());
+
+        let timestamp = history
+            .with_change(SequenceNumber_t::from(5), |c| c.sourceTimestamp)
+            .unwrap();
+        assert!(timestamp > Time_t::c_TimeZero);
+    }
+
+    #[test]
+    fn keep_all_timeout_test() {
+        let history = history(
+            HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+            1,
+            2,
+            Time_t::new(0, 20_000_000),
+        );
+        history.add_change(change(1)).unwrap();
+        history.add_change(change(1)).unwrap();
+        let start = Instant::now();
+        assert!(history.add_change(change(1)).is_err());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(2, history.len());
+
+        assert_eq!(1, history.remove_changes_up_to(SequenceNumber_t::from(1)));
+        assert_eq!(
+            SequenceNumber_t::from(3),
+            history.add_change(change(1)).unwrap()
+        );
+    }
+
+    #[test]
+    fn keep_all_blocking_test() {
+        let history = Arc::new(history(
+            HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+            1,
+            1,
+            Time_t::c_TimeInfinite,
+        ));
+        history.add_change(change(1)).unwrap();
+
+        // The change is acknowledged by another thread
+        let acknowledger = {
+            let history = history.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                history.remove_min_change().unwrap();
+            })
+        };
+        assert_eq!(
+            SequenceNumber_t::from(2),
+            history.add_change(change(1)).unwrap()
+        );
+        acknowledger.join().unwrap();
+        assert_eq!(1, history.len());
+    }
+
+    #[test]
+    fn keep_all_acknowledged_test() {
+        let history = Arc::new(history(
+            HistoryQosPolicyKind::KEEP_ALL_HISTORY_QOS,
+            1,
+            2,
+            Time_t::c_TimeInfinite,
+        ));
+        history.add_change(change(1)).unwrap();
+        history.add_change(change(1)).unwrap();
+
+        // The oldest change is removed once the writer no longer needs it
+        let acked = Arc::new(Mutex::new(SequenceNumber_t::new(0, 0)));
+        let acknowledger = {
+            let history = history.clone();
+            let acked = acked.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                *acked.lock().unwrap() = SequenceNumber_t::from(1);
+                history.changes_acknowledged();
+            })
+        };
+        let is_removable = |sn| sn <= *acked.lock().unwrap();
+        assert_eq!(
+            SequenceNumber_t::from(3),
+            history
+                .add_change_removing(change(1), is_removable)
+                .unwrap()
+        );
+        acknowledger.join().unwrap();
+        assert_eq!(2, history.len());
+        assert_eq!(Some(SequenceNumber_t::from(2)), history.get_seq_num_min());
+    }
+
+    // Listener reading the history it is notified by
+    struct LenRecorder {
+        history: Mutex<Option<Arc<WriterHistory>>>,
+        lens: Mutex<Vec<usize>>,
+    }
+
+    impl HistoryCacheListener for LenRecorder {
+        fn on_change_added(&self, _change: &CacheChange_t) {
+            let history = self.history.lock().unwrap().clone().unwrap();
+            self.lens.lock().unwrap().push(history.len());
+        }
+
+        fn on_change_removed(&self, change: &CacheChange_t) {
+            self.on_change_added(change);
+        }
+    }
+
+    #[test]
+    fn listener_test() {
+        let history = Arc::new(history(
+            HistoryQosPolicyKind::KEEP_LAST_HISTORY_QOS,
+            1,
+            -1,
+            Time_t::c_TimeZero,
+        ));
+        let recorder = Arc::new(LenRecorder {
+            history: Mutex::new(Some(history.clone())),
+            lens: Mutex::new(Vec::new()),
+        });
+        history.add_listener(recorder.clone());
+
+        // The listener is called once the history is unlocked, so it can use it
+        history.add_change(change(1)).unwrap();
+        history.add_change(change(1)).unwrap();
+        history.remove_min_change().unwrap();
+        assert_eq!(vec![1, 1, 1, 0], *recorder.lens.lock().unwrap());
+        recorder.history.lock().unwrap().take();
+    }
+}
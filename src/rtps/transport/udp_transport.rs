@@ -0,0 +1,158 @@
+use crate::rtps::common::error::RtpsError;
+use crate::rtps::common::locator::*;
+use crate::rtps::common::locator_list::LocatorList;
+use crate::rtps::utils::ip_locator::*;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+// Largest UDP datagram
+pub const MAX_MESSAGE_SIZE: usize = 65500;
+
+// Time a receive thread waits for a message before checking whether it must stop
+pub const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/**
+ * @brief Check whether a locator is an IP multicast address.
+ */
+pub fn isMulticast(locator: &Locator_t) -> bool {
+    match toSocketAddr(locator) {
+        Some(address) => address.ip().is_multicast(),
+        None => false,
+    }
+}
+
+/**
+ * @brief Sockets used by the writers of a participant to send messages.
+ *        A message sent to a locator of an unsupported kind is dropped.
+ */
+pub struct UdpSender {
+    socket_v4: UdpSocket,
+    socket_v6: Option<UdpSocket>,
+}
+
+impl UdpSender {
+    pub fn new() -> Result<Self, RtpsError> {
+        let socket_v4 = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket_v4.set_multicast_loop_v4(true)?;
+        // Hosts without IPv6 only send to IPv4 locators
+        let socket_v6 = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0)).ok();
+        Ok(UdpSender {
+            socket_v4,
+            socket_v6,
+        })
+    }
+
+    /**
+     * @brief Send a message to a locator.
+     *
+     * @param message Message to send.
+     * @param locator Destination of the message.
+     * @return False if the locator is not a UDP locator or the message could not be sent.
+     */
+    pub fn send(&self, message: &[u8], locator: &Locator_t) -> bool {
+        if locator.kind != LOCATOR_KIND_UDPv4 && locator.kind != LOCATOR_KIND_UDPv6 {
+            return false;
+        }
+        let address = match toSocketAddr(locator) {
+            Some(address) => address,
+            None => return false,
+        };
+        let socket = match address {
+            SocketAddr::V4(_) => &self.socket_v4,
+            SocketAddr::V6(_) => match &self.socket_v6 {
+                Some(socket) => socket,
+                None => return false,
+            },
+        };
+        socket.send_to(message, address).is_ok()
+    }
+
+    /**
+     * @brief Send a message to every locator of a list.
+     *
+     * @param message Message to send.
+     * @param locators Destinations of the message.
+     * @return The number of locators the message was sent to.
+     */
+    pub fn send_to_list(&self, message: &[u8], locators: &LocatorList) -> usize {
+        locators
+            .iter()
+            .filter(|locator| self.send(message, locator))
+            .count()
+    }
+}
+
+/**
+ * @brief Open a socket receiving the messages sent to a locator.
+ *        Multicast sockets share their port with the other participants of the host.
+ *        Reads time out after RECEIVE_TIMEOUT.
+ *
+ * @param locator Locator to listen on. Port 0 binds an ephemeral port.
+ * @return The socket and the locator it is bound to, with the actual port.
+ */
+pub fn open_input_channel(locator: &Locator_t) -> Result<(UdpSocket, Locator_t), RtpsError> {
+    if locator.kind != LOCATOR_KIND_UDPv4 && locator.kind != LOCATOR_KIND_UDPv6 {
+        return Err(RtpsError::new("Only UDP locators are supported"));
+    }
+    let address = toSocketAddr(locator).ok_or_else(|| RtpsError::new("Invalid locator"))?;
+    let domain = match address {
+        SocketAddr::V4(_) => Domain::IPV4,
+        SocketAddr::V6(_) => Domain::IPV6,
+    };
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+
+    match address.ip() {
+        IpAddr::V4(ip) if ip.is_multicast() => {
+            socket.set_reuse_address(true)?;
+            socket.bind(&SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), address.port()).into())?;
+            socket.join_multicast_v4(&ip, &Ipv4Addr::UNSPECIFIED)?;
+        }
+        IpAddr::V6(ip) if ip.is_multicast() => {
+            socket.set_only_v6(true)?;
+            socket.set_reuse_address(true)?;
+            socket.bind(&SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), address.port()).into())?;
+            socket.join_multicast_v6(&ip, locator.scope_id)?;
+        }
+        _ => {
+            if domain == Domain::IPV6 {
+                socket.set_only_v6(true)?;
+            }
+            socket.bind(&address.into())?;
+        }
+    }
+
+    let socket: UdpSocket = socket.into();
+    socket.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+    let mut bound = *locator;
+    bound.port = socket.local_addr()?.port() as u32;
+    Ok((socket, bound))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_receive_test() {
+        let mut locator = Locator_t::new_from_port(0);
+        setIPv4(&mut locator, "127.0.0.1").unwrap();
+        let (socket, bound) = open_input_channel(&locator).unwrap();
+        assert_ne!(0, bound.port);
+        assert!(!isMulticast(&bound));
+
+        let sender = UdpSender::new().unwrap();
+        let mut shm = Locator_t::new_from_kind_port(LOCATOR_KIND_SHM, bound.port);
+        shm.address = bound.address;
+        let locators = LocatorList::from(vec![bound, shm]);
+        assert_eq!(1, sender.send_to_list(b"RTPS", &locators));
+
+        let mut buffer = [0_u8; MAX_MESSAGE_SIZE];
+        let (length, _) = socket.recv_from(&mut buffer).unwrap();
+        assert_eq!(b"RTPS", &buffer[..length]);
+
+        let mut multicast = Locator_t::new_from_port(7400);
+        setIPv4(&mut multicast, "239.255.0.1").unwrap();
+        assert!(isMulticast(&multicast));
+    }
+}
@@ -1,9 +1,11 @@
+use crate::base::net::interface::*;
 use crate::rtps::common::locator::*;
 use crate::LOCATOR_ADDRESS_INVALID;
 use regex::Regex;
 use std::net::AddrParseError;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
+use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::str::FromStr;
 use substring::Substring;
 
@@ -98,6 +100,14 @@ pub fn toIPv6string(locator: &Locator_t) -> String {
         i += 2;
     }
 
+    // Zone index, using the interface name when it can be resolved
+    if locator.scope_id != 0 {
+        match interface_name(locator.scope_id) {
+            Some(name) => ss += &format!("%{}", name),
+            None => ss += &format!("%{}", locator.scope_id),
+        }
+    }
+
     return ss;
 }
 
@@ -200,15 +210,55 @@ pub fn setIPv4(locator: &mut Locator_t, address: &str) -> Result<(), AddrParseEr
     }
 }
 
+/**
+ * @brief Set the IPv6 address of a locator.
+ *        The address may carry a zone index as in 'fe80::1%eth0' or 'fe80::1%2'.
+ *        The zone is only kept for link-local addresses, as it has no meaning for
+ *        any other scope.
+ *
+ * @param locator Locator to be modified.
+ * @param address IPv6 address, optionally followed by '%' and an interface name or index.
+ */
 pub fn setIPv6(locator: &mut Locator_t, address: &str) -> Result<(), AddrParseError> {
-    let addr = Ipv6Addr::from_str(address);
-    match addr {
-        Ok(addr) => {
-            locator.address = addr.octets();
-            Ok(())
+    let (ip, zone) = match address.find('%') {
+        Some(p) => (
+            address.substring(0, p),
+            Some(address.substring(p + 1, address.len())),
+        ),
+        None => (address, None),
+    };
+
+    let addr = Ipv6Addr::from_str(ip)?;
+    let scope_id = match zone {
+        Some(zone) => {
+            // Interface names are translated to their index, which is what the sockets use
+            let zone = match interface_index(zone) {
+                Some(index) => index.to_string(),
+                None => zone.to_string(),
+            };
+            let scoped = SocketAddrV6::from_str(&format!("[{}%{}]:0", ip, zone))?;
+            scoped.scope_id()
         }
-        Err(e) => return Err(e),
-    }
+        None => 0,
+    };
+
+    locator.address = addr.octets();
+    locator.scope_id = if isLinkLocal(locator) { scope_id } else { 0 };
+    Ok(())
+}
+
+/**
+ * @brief Check whether the IPv6 address of a locator is link-local.
+ *        Both link-local unicast (fe80::/10) and interface/link-local multicast
+ *        (ff01::/16, ff02::/16) addresses need a zone index to be usable.
+ *
+ * @param locator Locator to be checked.
+ * @return true if the address is an IPv6 link-local address.
+ */
+pub fn isLinkLocal(locator: &Locator_t) -> bool {
+    let unicast = locator.address[0] == 0xfe && (locator.address[1] & 0xc0) == 0x80;
+    let multicast = locator.address[0] == 0xff && (locator.address[1] & 0x0f) <= 0x02;
+    unicast || multicast
 }
 
 // Factory
@@ -221,6 +271,7 @@ pub fn createLocator(
     locator.kind = kindin;
     locator.port = portin;
     LOCATOR_ADDRESS_INVALID!(locator.address);
+    locator.scope_id = 0;
 
     match kindin {
         LOCATOR_KIND_TCPv4 | LOCATOR_KIND_UDPv4 => {
@@ -235,6 +286,260 @@ pub fn createLocator(
     }
 }
 
+/**
+ * @brief Set the logical port of a TCP locator.
+ *        The logical port is stored on the 16 most significant bits of the locator port.
+ *
+ * @param locator Locator to be modified.
+ * @param port New logical port.
+ * @return true if the locator is a TCP locator.
+ */
+pub fn setLogicalPort(locator: &mut Locator_t, port: u16) -> bool {
+    if locator.kind != LOCATOR_KIND_TCPv4 && locator.kind != LOCATOR_KIND_TCPv6 {
+        return false;
+    }
+    locator.port = ((port as u32) << 16) | (locator.port & 0x0000_ffff);
+    true
+}
+
+/**
+ * @brief Getter for the logical port of a TCP locator.
+ *
+ * @param locator Locator to be checked.
+ * @return Logical port.
+ */
+pub fn getLogicalPort(locator: &Locator_t) -> u16 {
+    (locator.port >> 16) as u16
+}
+
+/**
+ * @brief Set the physical port of a TCP locator.
+ *        The physical port is stored on the 16 least significant bits of the locator port.
+ *
+ * @param locator Locator to be modified.
+ * @param port New physical port.
+ * @return true if the locator is a TCP locator.
+ */
+pub fn setPhysicalPort(locator: &mut Locator_t, port: u16) -> bool {
+    if locator.kind != LOCATOR_KIND_TCPv4 && locator.kind != LOCATOR_KIND_TCPv6 {
+        return false;
+    }
+    locator.port = (locator.port & 0xffff_0000) | port as u32;
+    true
+}
+
+/**
+ * @brief Getter for the physical port of a TCP locator.
+ *
+ * @param locator Locator to be checked.
+ * @return Physical port.
+ */
+pub fn getPhysicalPort(locator: &Locator_t) -> u16 {
+    (locator.port & 0x0000_ffff) as u16
+}
+
+/**
+ * @brief Set the WAN address of a TCPv4 locator.
+ *        The WAN address is stored on bytes 8 to 11 of the locator address.
+ *
+ * @param locator Locator to be modified.
+ * @param address WAN IPv4 address.
+ * @return true if the locator is TCPv4 and the address was valid.
+ */
+pub fn setWan(locator: &mut Locator_t, address: &str) -> bool {
+    if locator.kind != LOCATOR_KIND_TCPv4 {
+        return false;
+    }
+    match Ipv4Addr::from_str(address) {
+        Ok(addr) => {
+            locator.address[8..12].copy_from_slice(&addr.octets());
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/**
+ * @brief Getter for the WAN address of a TCPv4 locator.
+ *
+ * @param locator Locator to be checked.
+ * @return WAN address octets.
+ */
+pub fn getWan(locator: &Locator_t) -> [u8; 4] {
+    [
+        locator.address[8],
+        locator.address[9],
+        locator.address[10],
+        locator.address[11],
+    ]
+}
+
+/**
+ * @brief Check whether a TCPv4 locator has a WAN address.
+ *
+ * @param locator Locator to be checked.
+ * @return true if the locator is TCPv4 and the WAN address is not 0.
+ */
+pub fn hasWan(locator: &Locator_t) -> bool {
+    locator.kind == LOCATOR_KIND_TCPv4 && getWan(locator) != [0_u8; 4]
+}
+
+/**
+ * @brief Getter for the WAN address of a TCPv4 locator as string.
+ *
+ * @param locator Locator to be checked.
+ * @return WAN address in dotted notation.
+ */
+pub fn toWanstring(locator: &Locator_t) -> String {
+    let wan = getWan(locator);
+    format!("{}.{}.{}.{}", wan[0], wan[1], wan[2], wan[3])
+}
+
+/**
+ * @brief Set the LAN id of a TCPv4 locator.
+ *        The LAN id is stored on bytes 0 to 7 of the locator address and is written
+ *        as eight dot separated octets, i.e. "0.0.0.0.0.0.0.1".
+ *
+ * @param locator Locator to be modified.
+ * @param lan_id LAN id string.
+ * @return true if the locator is TCPv4 and the LAN id was valid.
+ */
+pub fn setLanID(locator: &mut Locator_t, lan_id: &str) -> bool {
+    if locator.kind != LOCATOR_KIND_TCPv4 {
+        return false;
+    }
+    let mut octets = [0_u8; 8];
+    let mut count = 0;
+    for field in lan_id.split('.') {
+        if count == octets.len() {
+            return false;
+        }
+        match field.parse::<u8>() {
+            Ok(v) => octets[count] = v,
+            Err(_) => return false,
+        }
+        count += 1;
+    }
+    if count != octets.len() {
+        return false;
+    }
+
+    locator.address[0..8].copy_from_slice(&octets);
+    true
+}
+
+/**
+ * @brief Getter for the LAN id of a TCPv4 locator.
+ *
+ * @param locator Locator to be checked.
+ * @return LAN id octets.
+ */
+pub fn getLanID(locator: &Locator_t) -> [u8; 8] {
+    let mut lan_id = [0_u8; 8];
+    lan_id.copy_from_slice(&locator.address[0..8]);
+    lan_id
+}
+
+/**
+ * @brief Check whether a TCPv4 locator has a LAN id.
+ *
+ * @param locator Locator to be checked.
+ * @return true if the locator is TCPv4 and the LAN id is not 0.
+ */
+pub fn hasLanID(locator: &Locator_t) -> bool {
+    locator.kind == LOCATOR_KIND_TCPv4 && getLanID(locator) != [0_u8; 8]
+}
+
+/**
+ * @brief Getter for the LAN id of a TCPv4 locator as string.
+ *
+ * @param locator Locator to be checked.
+ * @return LAN id as eight dot separated octets.
+ */
+pub fn toLanIDstring(locator: &Locator_t) -> String {
+    getLanID(locator)
+        .iter()
+        .map(|o| o.to_string())
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+/**
+ * @brief Convert a UDP or TCP locator to a socket address.
+ *        IPv6 link-local locators keep their zone index as the socket scope id.
+ *
+ * @param locator Locator to be converted.
+ * @return Socket address, or None if the locator is not an IP locator or its port does not fit.
+ *         TCP locators use their physical port.
+ */
+pub fn toSocketAddr(locator: &Locator_t) -> Option<SocketAddr> {
+    // TCP locators carry the logical port on the upper half of the port
+    let port = if locator.kind == LOCATOR_KIND_TCPv4 || locator.kind == LOCATOR_KIND_TCPv6 {
+        getPhysicalPort(locator)
+    } else if locator.port > u16::MAX as u32 {
+        return None;
+    } else {
+        locator.port as u16
+    };
+
+    match locator.kind {
+        LOCATOR_KIND_UDPv4 | LOCATOR_KIND_TCPv4 => {
+            let ip = Ipv4Addr::new(
+                locator.address[12],
+                locator.address[13],
+                locator.address[14],
+                locator.address[15],
+            );
+            Some(SocketAddr::V4(SocketAddrV4::new(ip, port)))
+        }
+        LOCATOR_KIND_UDPv6 | LOCATOR_KIND_TCPv6 => {
+            let ip = Ipv6Addr::from(locator.address);
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                ip,
+                port,
+                0,
+                locator.scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+/**
+ * @brief Fill a locator from a socket address.
+ *
+ * @param udp true to create a UDP locator, false to create a TCP locator.
+ * @param address Socket address to be converted.
+ * @param locator Locator where the result is saved.
+ */
+pub fn fromSocketAddr(udp: bool, address: &SocketAddr, locator: &mut Locator_t) {
+    locator.port = address.port() as u32;
+    LOCATOR_ADDRESS_INVALID!(locator.address);
+    locator.scope_id = 0;
+
+    match address {
+        SocketAddr::V4(v4) => {
+            locator.kind = if udp {
+                LOCATOR_KIND_UDPv4
+            } else {
+                LOCATOR_KIND_TCPv4
+            };
+            locator.address[12..16].copy_from_slice(&v4.ip().octets());
+        }
+        SocketAddr::V6(v6) => {
+            locator.kind = if udp {
+                LOCATOR_KIND_UDPv6
+            } else {
+                LOCATOR_KIND_TCPv6
+            };
+            locator.address = v6.ip().octets();
+            if isLinkLocal(locator) {
+                locator.scope_id = v6.scope_id();
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -261,4 +566,149 @@ mod tests {
 
         assert_eq!(false, IPv6isCorrect("fe80:"));
     }
+
+    #[test]
+    fn IPv6_scope_test() {
+        assert!(IPv6isCorrect("fe80::1%eth0"));
+
+        let mut locator = Locator_t::new_from_kind_port(LOCATOR_KIND_UDPv6, 7400);
+        setIPv6(&mut locator, "fe80::1%7").unwrap();
+        assert_eq!(7, locator.scope_id);
+        assert_eq!(0xfe, locator.address[0]);
+        assert_eq!(0x80, locator.address[1]);
+        assert_eq!(1, locator.address[15]);
+        match interface_name(7) {
+            Some(name) => assert_eq!(format!("fe80::1%{}", name), toIPv6string(&locator)),
+            None => assert_eq!("fe80::1%7", toIPv6string(&locator)),
+        }
+
+        // Link-local multicast keeps the zone too
+        setIPv6(&mut locator, "ff02::1%3").unwrap();
+        assert_eq!(3, locator.scope_id);
+
+        // Zone is dropped for global addresses
+        setIPv6(&mut locator, "2001:db8::1%7").unwrap();
+        assert_eq!(0, locator.scope_id);
+        assert_eq!("2001:db8::1", toIPv6string(&locator));
+
+        // Without zone
+        setIPv6(&mut locator, "fe80::1").unwrap();
+        assert_eq!(0, locator.scope_id);
+        assert_eq!("fe80::1", toIPv6string(&locator));
+
+        // Unknown interface name
+        assert!(setIPv6(&mut locator, "fe80::1%no-such-interface0").is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn IPv6_interface_name_scope_test() {
+        let index = interface_index("lo").unwrap();
+        let mut locator = Locator_t::new_from_kind_port(LOCATOR_KIND_UDPv6, 7400);
+        setIPv6(&mut locator, "fe80::1%lo").unwrap();
+        assert_eq!(index, locator.scope_id);
+        assert_eq!("fe80::1%lo", toIPv6string(&locator));
+    }
+
+    #[test]
+    fn socket_addr_test() {
+        let mut locator = Locator_t::new_from_kind_port(LOCATOR_KIND_UDPv6, 7400);
+        setIPv6(&mut locator, "fe80::1%5").unwrap();
+        let address = toSocketAddr(&locator).unwrap();
+        match address {
+            SocketAddr::V6(v6) => {
+                assert_eq!(5, v6.scope_id());
+                assert_eq!(7400, v6.port());
+            }
+            _ => panic!("Expected an IPv6 socket address"),
+        }
+
+        let mut back = Locator_t::default();
+        fromSocketAddr(true, &address, &mut back);
+        assert_eq!(locator, back);
+
+        let mut v4 = Locator_t::new_from_kind_port(LOCATOR_KIND_TCPv4, 7410);
+        setIPv4(&mut v4, "192.168.1.2").unwrap();
+        let address = toSocketAddr(&v4).unwrap();
+        assert_eq!("192.168.1.2:7410", address.to_string());
+        fromSocketAddr(false, &address, &mut back);
+        assert_eq!(v4, back);
+
+        let shm = Locator_t::new_from_kind_port(LOCATOR_KIND_SHM, 1);
+        assert_eq!(None, toSocketAddr(&shm));
+    }
+
+    #[test]
+    fn tcp_ports_test() {
+        let mut locator = Locator_t::new_from_kind_port(LOCATOR_KIND_TCPv4, 5100);
+        assert_eq!(5100, getPhysicalPort(&locator));
+        assert_eq!(0, getLogicalPort(&locator));
+
+        assert!(setLogicalPort(&mut locator, 7410));
+        assert_eq!(5100, getPhysicalPort(&locator));
+        assert_eq!(7410, getLogicalPort(&locator));
+        assert_eq!((7410_u32 << 16) | 5100, locator.port);
+
+        assert!(setPhysicalPort(&mut locator, 5200));
+        assert_eq!(5200, getPhysicalPort(&locator));
+        assert_eq!(7410, getLogicalPort(&locator));
+
+        locator.kind = LOCATOR_KIND_TCPv6;
+        assert!(setLogicalPort(&mut locator, u16::MAX));
+        assert_eq!(u16::MAX, getLogicalPort(&locator));
+
+        let mut udp = Locator_t::new_from_port(7400);
+        assert!(!setLogicalPort(&mut udp, 1));
+        assert!(!setPhysicalPort(&mut udp, 1));
+        assert_eq!(7400, udp.port);
+
+        // Sockets only see the physical port
+        setIPv4(&mut locator, "127.0.0.1").unwrap();
+        locator.kind = LOCATOR_KIND_TCPv4;
+        assert_eq!(
+            "127.0.0.1:5200",
+            toSocketAddr(&locator).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn tcp_wan_lan_id_test() {
+        let mut locator = Locator_t::new_from_kind_port(LOCATOR_KIND_TCPv4, 5100);
+        assert!(!hasWan(&locator));
+        assert!(!hasLanID(&locator));
+
+        setIPv4(&mut locator, "192.168.1.2").unwrap();
+        assert!(setWan(&mut locator, "80.1.2.3"));
+        assert!(hasWan(&locator));
+        assert_eq!([80, 1, 2, 3], getWan(&locator));
+        assert_eq!("80.1.2.3", toWanstring(&locator));
+        assert_eq!("192.168.1.2", toIPv4string(&locator));
+        assert!(!setWan(&mut locator, "80.1.2"));
+
+        assert!(setLanID(&mut locator, "0.0.0.0.0.0.1.2"));
+        assert!(hasLanID(&locator));
+        assert_eq!([0, 0, 0, 0, 0, 0, 1, 2], getLanID(&locator));
+        assert_eq!("0.0.0.0.0.0.1.2", toLanIDstring(&locator));
+        assert!(!setLanID(&mut locator, "0.0.0.1"));
+        assert!(!setLanID(&mut locator, "0.0.0.0.0.0.0.0.1"));
+        assert!(!setLanID(&mut locator, "0.0.0.0.0.0.0.256"));
+        assert_eq!("0.0.0.0.0.0.1.2", toLanIDstring(&locator));
+
+        // WAN and LAN id do not change the LAN address
+        assert_eq!("192.168.1.2", toIPv4string(&locator));
+
+        locator.kind = LOCATOR_KIND_UDPv4;
+        assert!(!hasWan(&locator));
+        assert!(!hasLanID(&locator));
+
+        // Only TCPv4 locators have a WAN address and a LAN id
+        let mut udp = Locator_t::new_from_kind_port(LOCATOR_KIND_UDPv4, 5100);
+        setIPv4(&mut udp, "192.168.1.2").unwrap();
+        assert!(!setWan(&mut udp, "80.1.2.3"));
+        assert!(!setLanID(&mut udp, "0.0.0.0.0.0.1.2"));
+        assert_eq!(
+            Locator_t::new_from_kind_port(LOCATOR_KIND_UDPv4, 5100).address[..12],
+            udp.address[..12]
+        );
+    }
 }
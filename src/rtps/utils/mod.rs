@@ -1,2 +1,3 @@
 pub mod fixed_size_bitmap;
 pub mod ip_locator;
+pub mod shm_locator;
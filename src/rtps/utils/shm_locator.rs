@@ -0,0 +1,254 @@
+use crate::base::net::host::host_id;
+use crate::rtps::common::locator::*;
+use crate::LOCATOR_ADDRESS_INVALID;
+use substring::Substring;
+
+/*
+ * Shared memory locators keep their identity on the address bytes:
+ *
+ *  address[0]      'U' for unicast, 'M' for multicast
+ *  address[1..3]   Host identifier, big endian (unicast only)
+ *  address[4..8]   Segment identifier, big endian (0 when not specified)
+ *
+ * The port is kept on the locator port, as for the other kinds.
+ */
+
+/// Unicast shared memory locator mark
+pub const SHM_UNICAST: u8 = b'U';
+/// Multicast shared memory locator mark
+pub const SHM_MULTICAST: u8 = b'M';
+
+/**
+ * @brief Factory for shared memory locators.
+ *        Unicast locators are tagged with the identifier of the local host.
+ *
+ * @param port Port of the locator.
+ * @param multicast true to create a multicast locator.
+ * @param locator Locator where the result is saved.
+ */
+pub fn createSHMLocator(port: u32, multicast: bool, locator: &mut Locator_t) {
+    locator.kind = LOCATOR_KIND_SHM;
+    locator.port = port;
+    LOCATOR_ADDRESS_INVALID!(locator.address);
+    locator.scope_id = 0;
+
+    if multicast {
+        locator.address[0] = SHM_MULTICAST;
+    } else {
+        locator.address[0] = SHM_UNICAST;
+        setSHMHostId(locator, host_id());
+    }
+}
+
+/**
+ * @brief Check whether a shared memory locator is multicast.
+ *
+ * @param locator Locator to be checked.
+ * @return true if the locator is a multicast SHM locator.
+ */
+pub fn isSHMMulticast(locator: &Locator_t) -> bool {
+    locator.kind == LOCATOR_KIND_SHM && locator.address[0] == SHM_MULTICAST
+}
+
+/**
+ * @brief Check whether a shared memory locator is unicast.
+ *
+ * @param locator Locator to be checked.
+ * @return true if the locator is a unicast SHM locator.
+ */
+pub fn isSHMUnicast(locator: &Locator_t) -> bool {
+    locator.kind == LOCATOR_KIND_SHM && locator.address[0] == SHM_UNICAST
+}
+
+/**
+ * @brief Set the host identifier of a shared memory locator.
+ *
+ * @param locator Locator to be modified.
+ * @param host Host identifier.
+ */
+pub fn setSHMHostId(locator: &mut Locator_t, host: u16) {
+    locator.address[1..3].copy_from_slice(&host.to_be_bytes());
+}
+
+/**
+ * @brief Getter for the host identifier of a shared memory locator.
+ *
+ * @param locator Locator to be checked.
+ * @return Host identifier.
+ */
+pub fn getSHMHostId(locator: &Locator_t) -> u16 {
+    u16::from_be_bytes([locator.address[1], locator.address[2]])
+}
+
+/**
+ * @brief Set the segment identifier of a shared memory locator.
+ *
+ * @param locator Locator to be modified.
+ * @param segment Segment identifier, 0 when not specified.
+ */
+pub fn setSHMSegmentId(locator: &mut Locator_t, segment: u32) {
+    locator.address[4..8].copy_from_slice(&segment.to_be_bytes());
+}
+
+/**
+ * @brief Getter for the segment identifier of a shared memory locator.
+ *
+ * @param locator Locator to be checked.
+ * @return Segment identifier, 0 when not specified.
+ */
+pub fn getSHMSegmentId(locator: &Locator_t) -> u32 {
+    u32::from_be_bytes([
+        locator.address[4],
+        locator.address[5],
+        locator.address[6],
+        locator.address[7],
+    ])
+}
+
+/**
+ * @brief Check whether a locator is a shared memory locator reachable from this host.
+ *        Multicast locators are always local, unicast ones must carry the local host id.
+ *
+ * @param locator Locator to be checked.
+ * @return true if the locator is SHM and can be reached from the local host.
+ */
+pub fn isSHMAndFromThisHost(locator: &Locator_t) -> bool {
+    isSHMMulticast(locator) || (isSHMUnicast(locator) && getSHMHostId(locator) == host_id())
+}
+
+/**
+ * @brief Getter for the address of a shared memory locator as string.
+ *        The format is U@host[#segment] for unicast locators, M[#segment] for multicast
+ *        locators and _ for any other address. Host and segment ids are hexadecimal.
+ *
+ * @param locator Locator to be converted.
+ * @return Address string.
+ */
+pub fn toSHMstring(locator: &Locator_t) -> String {
+    let mut ss = if isSHMUnicast(locator) {
+        format!("U@{:04x}", getSHMHostId(locator))
+    } else if isSHMMulticast(locator) {
+        "M".to_string()
+    } else {
+        return "_".to_string();
+    };
+
+    let segment = getSHMSegmentId(locator);
+    if segment != 0 {
+        ss += &format!("#{:08x}", segment);
+    }
+    ss
+}
+
+/**
+ * @brief Set the address of a shared memory locator from its string form.
+ *
+ * @param locator Locator to be modified.
+ * @param address Address string, as returned by toSHMstring.
+ * @return true if the address could be parsed.
+ */
+pub fn setSHMAddress(locator: &mut Locator_t, address: &str) -> bool {
+    LOCATOR_ADDRESS_INVALID!(locator.address);
+    if address == "_" {
+        return true;
+    }
+
+    let (head, segment) = match address.find('#') {
+        Some(p) => (
+            address.substring(0, p),
+            Some(address.substring(p + 1, address.len())),
+        ),
+        None => (address, None),
+    };
+
+    if head == "M" {
+        locator.address[0] = SHM_MULTICAST;
+    } else if let Some(host) = head.strip_prefix("U@") {
+        match u16::from_str_radix(host, 16) {
+            Ok(host) => {
+                locator.address[0] = SHM_UNICAST;
+                setSHMHostId(locator, host);
+            }
+            Err(_) => return false,
+        }
+    } else {
+        return false;
+    }
+
+    if let Some(segment) = segment {
+        match u32::from_str_radix(segment, 16) {
+            Ok(segment) => setSHMSegmentId(locator, segment),
+            Err(_) => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_test() {
+        let mut locator = Locator_t::default();
+        createSHMLocator(7400, false, &mut locator);
+        assert_eq!(LOCATOR_KIND_SHM, locator.kind);
+        assert_eq!(7400, locator.port);
+        assert!(isSHMUnicast(&locator));
+        assert!(!isSHMMulticast(&locator));
+        assert_eq!(host_id(), getSHMHostId(&locator));
+        assert_eq!(0, getSHMSegmentId(&locator));
+
+        createSHMLocator(7401, true, &mut locator);
+        assert!(isSHMMulticast(&locator));
+        assert!(!isSHMUnicast(&locator));
+        assert_eq!(0, getSHMHostId(&locator));
+    }
+
+    #[test]
+    fn this_host_test() {
+        let mut locator = Locator_t::default();
+        createSHMLocator(7400, false, &mut locator);
+        assert!(isSHMAndFromThisHost(&locator));
+
+        setSHMHostId(&mut locator, host_id().wrapping_add(1));
+        assert!(!isSHMAndFromThisHost(&locator));
+
+        createSHMLocator(7400, true, &mut locator);
+        assert!(isSHMAndFromThisHost(&locator));
+
+        locator = Locator_t::new_from_port(7400);
+        assert!(!isSHMAndFromThisHost(&locator));
+
+        locator = Locator_t::new_from_kind_port(LOCATOR_KIND_SHM, 7400);
+        assert!(!isSHMAndFromThisHost(&locator));
+    }
+
+    #[test]
+    fn string_test() {
+        let mut locator = Locator_t::default();
+        createSHMLocator(7400, false, &mut locator);
+        setSHMHostId(&mut locator, 0x1a2b);
+        assert_eq!("U@1a2b", toSHMstring(&locator));
+
+        setSHMSegmentId(&mut locator, 42);
+        assert_eq!("U@1a2b#0000002a", toSHMstring(&locator));
+
+        let mut other = Locator_t::new_from_kind_port(LOCATOR_KIND_SHM, 7400);
+        assert!(setSHMAddress(&mut other, "U@1a2b#0000002a"));
+        assert_eq!(locator, other);
+
+        createSHMLocator(7400, true, &mut locator);
+        assert_eq!("M", toSHMstring(&locator));
+        assert!(setSHMAddress(&mut other, "M"));
+        assert_eq!(locator, other);
+
+        assert!(setSHMAddress(&mut other, "_"));
+        assert_eq!("_", toSHMstring(&other));
+
+        assert!(!setSHMAddress(&mut other, "U"));
+        assert!(!setSHMAddress(&mut other, "U@xyz"));
+        assert!(!setSHMAddress(&mut other, "M#segment"));
+        assert!(!setSHMAddress(&mut other, "X"));
+    }
+}